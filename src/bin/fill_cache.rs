@@ -1,6 +1,7 @@
 use std::process::Command;
 use std::{thread, time};
 
+#[allow(clippy::zombie_processes)]
 fn main() {
     println!("Started filling");
 
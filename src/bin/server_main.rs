@@ -6,14 +6,18 @@ use std::time::Duration;
 pub async fn main() -> Result<()> {
     env_logger::init();
     let port = std::env::var("PORT").unwrap_or("6379".to_string());
+    let databases = 16;
     let sample = 10;
     let threshold = 0.5;
     let frequency = Duration::from_millis(100);
+    let admin_password = std::env::var("ADMIN_PASSWORD").ok();
     run_server(
         format!("0.0.0.0:{}", port).as_str(),
+        databases,
         sample,
         threshold,
         frequency,
+        admin_password,
     )
     .await;
     Ok(())
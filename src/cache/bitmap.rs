@@ -0,0 +1,599 @@
+use crate::cache::entry::Entry;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+
+/// Bitmaps are limited to 512MB like in Redis.
+const MAX_BITS: u64 = 1 << 32;
+
+/// Parse a bit offset, which must address a bit within the maximum bitmap size.
+pub fn parse_offset(s: &str) -> Result<u64> {
+    s.parse::<u64>()
+        .ok()
+        .filter(|offset| *offset < MAX_BITS)
+        .ok_or_else(|| Error::msg("bit offset is not an integer or out of range"))
+}
+
+/// The unit of the range of BITCOUNT and BITPOS.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Unit {
+    #[default]
+    Byte,
+    Bit,
+}
+
+impl Unit {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "byte" => Ok(Unit::Byte),
+            "bit" => Ok(Unit::Bit),
+            _ => Err(Error::msg("syntax error")),
+        }
+    }
+}
+
+/// An inclusive range of BITCOUNT or BITPOS, negative indexes counting from the end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitRange {
+    pub start: i64,
+    pub end: Option<i64>,
+    pub unit: Unit,
+}
+
+impl BitRange {
+    /// Resolve the range into inclusive bit positions within `bytes` bytes.
+    fn resolve(&self, bytes: usize) -> Option<(u64, u64)> {
+        let len = match self.unit {
+            Unit::Byte => bytes as i64,
+            Unit::Bit => bytes as i64 * 8,
+        };
+        let resolve = |index: i64| {
+            if index < 0 {
+                (len + index).max(0)
+            } else {
+                index
+            }
+        };
+        let start = resolve(self.start);
+        let end = resolve(self.end.unwrap_or(-1)).min(len - 1);
+        if len == 0 || start > end {
+            return None;
+        }
+        match self.unit {
+            Unit::Byte => Some((start as u64 * 8, end as u64 * 8 + 7)),
+            Unit::Bit => Some((start as u64, end as u64)),
+        }
+    }
+}
+
+/// The operation of BITOP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitOperation {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+impl BitOperation {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "and" => Ok(BitOperation::And),
+            "or" => Ok(BitOperation::Or),
+            "xor" => Ok(BitOperation::Xor),
+            "not" => Ok(BitOperation::Not),
+            _ => Err(Error::msg("syntax error")),
+        }
+    }
+}
+
+/// An integer type of BITFIELD, like `i8` or `u4`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldType {
+    pub signed: bool,
+    pub bits: u8,
+}
+
+impl FieldType {
+    pub fn parse(s: &str) -> Result<Self> {
+        let error = || {
+            Error::msg(
+                "Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.",
+            )
+        };
+        let signed = match s.chars().next() {
+            Some('i') | Some('I') => true,
+            Some('u') | Some('U') => false,
+            _ => return Err(error()),
+        };
+        let bits = s[1..].parse::<u8>().map_err(|_| error())?;
+        let max = if signed { 64 } else { 63 };
+        if bits == 0 || bits > max {
+            return Err(error());
+        }
+        Ok(FieldType { signed, bits })
+    }
+
+    /// Parse the offset of a field of this type, `#N` addressing the Nth field of this type.
+    pub fn parse_offset(&self, s: &str) -> Result<u64> {
+        let offset = match s.strip_prefix('#') {
+            Some(index) => index
+                .parse::<u64>()
+                .ok()
+                .and_then(|index| index.checked_mul(self.bits as u64)),
+            None => s.parse::<u64>().ok(),
+        };
+        offset
+            .filter(|offset| offset + self.bits as u64 <= MAX_BITS)
+            .ok_or_else(|| Error::msg("bit offset is not an integer or out of range"))
+    }
+
+    fn min(&self) -> i128 {
+        if self.signed {
+            -(1 << (self.bits - 1))
+        } else {
+            0
+        }
+    }
+
+    fn max(&self) -> i128 {
+        if self.signed {
+            (1 << (self.bits - 1)) - 1
+        } else {
+            (1 << self.bits) - 1
+        }
+    }
+
+    /// Fit `value` into this type, or `None` if it overflows and `overflow` is `Fail`.
+    fn fit(&self, value: i128, overflow: Overflow) -> Option<i64> {
+        let (min, max) = (self.min(), self.max());
+        if (min..=max).contains(&value) {
+            return Some(value as i64);
+        }
+        match overflow {
+            Overflow::Wrap => Some(((value - min).rem_euclid(max - min + 1) + min) as i64),
+            Overflow::Sat => Some(value.clamp(min, max) as i64),
+            Overflow::Fail => None,
+        }
+    }
+}
+
+/// How BITFIELD handles values that don't fit their type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    #[default]
+    Wrap,
+    Sat,
+    Fail,
+}
+
+impl Overflow {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "wrap" => Ok(Overflow::Wrap),
+            "sat" => Ok(Overflow::Sat),
+            "fail" => Ok(Overflow::Fail),
+            _ => Err(Error::msg("Invalid OVERFLOW type specified")),
+        }
+    }
+}
+
+/// A subcommand of BITFIELD.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldOp {
+    Get(FieldType, u64),
+    Set(FieldType, u64, i64),
+    IncrBy(FieldType, u64, i64),
+    /// Change the overflow handling of the following subcommands.
+    Overflow(Overflow),
+}
+
+impl FieldOp {
+    fn is_write(&self) -> bool {
+        matches!(self, FieldOp::Set(..) | FieldOp::IncrBy(..))
+    }
+}
+
+fn get_bit(bytes: &[u8], offset: u64) -> bool {
+    match bytes.get((offset / 8) as usize) {
+        Some(byte) => byte & (0x80 >> (offset % 8)) != 0,
+        None => false,
+    }
+}
+
+/// Set a bit, growing `bytes` with zeroes as needed. Returns the previous bit.
+fn set_bit(bytes: &mut Vec<u8>, offset: u64, bit: bool) -> bool {
+    let index = (offset / 8) as usize;
+    if index >= bytes.len() {
+        bytes.resize(index + 1, 0);
+    }
+    let mask = 0x80 >> (offset % 8);
+    let previous = bytes[index] & mask != 0;
+    if bit {
+        bytes[index] |= mask;
+    } else {
+        bytes[index] &= !mask;
+    }
+    previous
+}
+
+fn get_field(bytes: &[u8], field: FieldType, offset: u64) -> i64 {
+    let mut value = 0u64;
+    for i in 0..field.bits as u64 {
+        value = (value << 1) | get_bit(bytes, offset + i) as u64;
+    }
+    if field.signed && field.bits < 64 && value >> (field.bits - 1) != 0 {
+        // sign extend
+        value |= u64::MAX << field.bits;
+    }
+    value as i64
+}
+
+fn set_field(bytes: &mut Vec<u8>, field: FieldType, offset: u64, value: i64) {
+    let bits = field.bits as u64;
+    for i in 0..bits {
+        set_bit(bytes, offset + i, (value as u64 >> (bits - 1 - i)) & 1 != 0);
+    }
+}
+
+/// Run BITFIELD subcommands against `bytes`, returning a reply per subcommand other than
+/// OVERFLOW, `None` when a write failed on overflow.
+fn run_fields(bytes: &mut Vec<u8>, ops: &[FieldOp]) -> Vec<Option<i64>> {
+    let mut overflow = Overflow::default();
+    let mut replies = Vec::with_capacity(ops.len());
+    for op in ops {
+        match *op {
+            FieldOp::Get(field, offset) => replies.push(Some(get_field(bytes, field, offset))),
+            FieldOp::Set(field, offset, value) => {
+                let previous = get_field(bytes, field, offset);
+                let reply = field.fit(value as i128, overflow).map(|value| {
+                    set_field(bytes, field, offset, value);
+                    previous
+                });
+                replies.push(reply);
+            }
+            FieldOp::IncrBy(field, offset, increment) => {
+                let value = get_field(bytes, field, offset) as i128 + increment as i128;
+                let reply = field.fit(value, overflow).inspect(|value| {
+                    set_field(bytes, field, offset, *value);
+                });
+                replies.push(reply);
+            }
+            FieldOp::Overflow(mode) => overflow = mode,
+        }
+    }
+    replies
+}
+
+/// Find the first bit set to `bit` between the inclusive bit positions `start` and `end`.
+fn find_bit(bytes: &[u8], bit: bool, start: u64, end: u64) -> Option<u64> {
+    let skip = if bit { 0x00 } else { 0xff };
+    let mut offset = start;
+    while offset <= end {
+        // skip whole bytes without a match
+        if offset.is_multiple_of(8) && offset + 7 <= end && bytes[(offset / 8) as usize] == skip {
+            offset += 8;
+            continue;
+        }
+        if get_bit(bytes, offset) == bit {
+            return Some(offset);
+        }
+        offset += 1;
+    }
+    None
+}
+
+impl Cache {
+    /// Set or clear the bit at `offset` of the string at `key`, returning the previous bit.
+    pub async fn setbit(&self, key: String, offset: u64, bit: bool) -> Result<bool> {
+        let previous = self.write_object(
+            &key,
+            || Some(Vec::new().into()),
+            |object| Ok(set_bit(object.as_bytes_mut()?, offset, bit)),
+        )?;
+        Ok(previous.unwrap_or(false))
+    }
+
+    pub async fn getbit(&self, key: String, offset: u64) -> Result<bool> {
+        let bit = self.read_object(&key, |object| Ok(get_bit(object.as_bytes()?, offset)))?;
+        Ok(bit.unwrap_or(false))
+    }
+
+    /// Count the set bits of the string at `key`, within `range` if given.
+    pub async fn bitcount(&self, key: String, range: Option<BitRange>) -> Result<u64> {
+        let count = self.read_object(&key, |object| {
+            let bytes = object.as_bytes()?;
+            Ok(match range {
+                None => bytes.iter().map(|byte| byte.count_ones() as u64).sum(),
+                Some(range) => match range.resolve(bytes.len()) {
+                    Some((start, end)) => (start..=end)
+                        .filter(|offset| get_bit(bytes, *offset))
+                        .count() as u64,
+                    None => 0,
+                },
+            })
+        })?;
+        Ok(count.unwrap_or(0))
+    }
+
+    /// Find the position of the first bit set to `bit` in the string at `key`, or -1.
+    pub async fn bitpos(&self, key: String, bit: bool, range: Option<BitRange>) -> Result<i64> {
+        let position = self.read_object(&key, |object| {
+            let bytes = object.as_bytes()?;
+            let range = range.unwrap_or(BitRange {
+                start: 0,
+                end: None,
+                unit: Unit::Byte,
+            });
+            let found = range
+                .resolve(bytes.len())
+                .and_then(|(start, end)| find_bit(bytes, bit, start, end));
+            Ok(match found {
+                Some(position) => position as i64,
+                // without an explicit end, a string is padded with clear bits on the right
+                None if !bit && range.end.is_none() && !bytes.is_empty() => bytes.len() as i64 * 8,
+                None => -1,
+            })
+        })?;
+        // a missing key is an empty string, made of clear bits only
+        Ok(position.unwrap_or(if bit { -1 } else { 0 }))
+    }
+
+    /// Store the bitwise operation of the strings at `keys` in `destination`, returning its
+    /// length in bytes. Missing keys are zero-filled strings.
+    pub async fn bitop(
+        &self,
+        operation: BitOperation,
+        destination: String,
+        keys: Vec<String>,
+    ) -> Result<usize> {
+        if operation == BitOperation::Not && keys.len() != 1 {
+            return Err(Error::msg(
+                "BITOP NOT must be called with a single source key.",
+            ));
+        }
+
+        let mut store = self.store.write().unwrap();
+        for key in &keys {
+            self.evict_if_expired(&mut store, key);
+        }
+
+        let mut sources = Vec::with_capacity(keys.len());
+        for key in &keys {
+            let bytes = match store.get(key) {
+                Some(entry) => entry.value().as_bytes()?.as_slice(),
+                None => &[],
+            };
+            sources.push(bytes);
+        }
+
+        let len = sources.iter().map(|bytes| bytes.len()).max().unwrap_or(0);
+        let byte = |bytes: &[u8], i: usize| bytes.get(i).copied().unwrap_or(0);
+        let result: Vec<u8> = (0..len)
+            .map(|i| {
+                let mut bytes = sources.iter().map(|bytes| byte(bytes, i));
+                let first = bytes.next().unwrap_or(0);
+                match operation {
+                    BitOperation::And => bytes.fold(first, |acc, byte| acc & byte),
+                    BitOperation::Or => bytes.fold(first, |acc, byte| acc | byte),
+                    BitOperation::Xor => bytes.fold(first, |acc, byte| acc ^ byte),
+                    BitOperation::Not => !first,
+                }
+            })
+            .collect();
+
+        if result.is_empty() {
+            self.remove_entry(&mut store, &destination);
+        } else {
+            self.admit(&mut store, &destination)?;
+            let entry = Entry::new(result, self.default_expiry());
+            self.charge(destination.len() + entry.value().memory_usage());
+            self.place(&mut store, destination, entry);
+        }
+        Ok(len)
+    }
+
+    /// Run BITFIELD subcommands against the string at `key` atomically. The string is only
+    /// created when a subcommand writes to it.
+    pub async fn bitfield(&self, key: String, ops: Vec<FieldOp>) -> Result<Vec<Option<i64>>> {
+        if !ops.iter().any(FieldOp::is_write) {
+            let replies = self.read_object(&key, |object| {
+                let mut bytes = object.as_bytes()?.clone();
+                Ok(run_fields(&mut bytes, &ops))
+            })?;
+            return Ok(replies.unwrap_or_else(|| run_fields(&mut Vec::new(), &ops)));
+        }
+
+        let replies = self.write_object(
+            &key,
+            || Some(Vec::new().into()),
+            |object| Ok(run_fields(object.as_bytes_mut()?, &ops)),
+        )?;
+        Ok(replies.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(s: &str) -> FieldType {
+        FieldType::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_field_type() {
+        assert_eq!(
+            field("i8"),
+            FieldType {
+                signed: true,
+                bits: 8
+            }
+        );
+        assert_eq!(
+            field("u63"),
+            FieldType {
+                signed: false,
+                bits: 63
+            }
+        );
+        assert!(FieldType::parse("u64").is_err());
+        assert!(FieldType::parse("i0").is_err());
+        assert!(FieldType::parse("x8").is_err());
+        assert_eq!(field("u4").parse_offset("#3").unwrap(), 12);
+        assert!(field("u8").parse_offset(&MAX_BITS.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_fit() {
+        let u2 = field("u2");
+        assert_eq!(u2.fit(5, Overflow::Wrap), Some(1));
+        assert_eq!(u2.fit(5, Overflow::Sat), Some(3));
+        assert_eq!(u2.fit(-1, Overflow::Sat), Some(0));
+        assert_eq!(u2.fit(5, Overflow::Fail), None);
+
+        let i8 = field("i8");
+        assert_eq!(i8.fit(128, Overflow::Wrap), Some(-128));
+        assert_eq!(i8.fit(-129, Overflow::Wrap), Some(127));
+        assert_eq!(i8.fit(300, Overflow::Sat), Some(127));
+        assert_eq!(
+            field("i64").fit(i64::MAX as i128 + 1, Overflow::Wrap),
+            Some(i64::MIN)
+        );
+    }
+
+    #[test]
+    fn test_fields() {
+        let mut bytes = Vec::new();
+        let ops = [
+            FieldOp::Set(field("i5"), 100, 10),
+            FieldOp::Get(field("u4"), 0),
+            FieldOp::IncrBy(field("i5"), 100, 6),
+            FieldOp::Overflow(Overflow::Fail),
+            FieldOp::IncrBy(field("u2"), 102, 4),
+            FieldOp::Get(field("i5"), 100),
+        ];
+        let replies = run_fields(&mut bytes, &ops);
+        assert_eq!(replies, vec![Some(0), Some(0), Some(-16), None, Some(-16)]);
+        assert_eq!(bytes.len(), 14);
+    }
+
+    #[test]
+    fn test_bit_range() {
+        let range = |start, end, unit| BitRange { start, end, unit };
+        assert_eq!(range(0, None, Unit::Byte).resolve(2), Some((0, 15)));
+        assert_eq!(range(-1, Some(-1), Unit::Byte).resolve(2), Some((8, 15)));
+        assert_eq!(range(5, Some(9), Unit::Bit).resolve(1), Some((5, 7)));
+        assert_eq!(range(2, Some(1), Unit::Byte).resolve(4), None);
+        assert_eq!(range(0, None, Unit::Byte).resolve(0), None);
+    }
+
+    #[tokio::test]
+    async fn test_setbit_getbit() -> Result<()> {
+        let cache = Cache::default();
+        let key = "bits".to_string();
+        assert!(!cache.setbit(key.clone(), 7, true).await?);
+        assert!(cache.setbit(key.clone(), 7, true).await?);
+        assert!(cache.getbit(key.clone(), 7).await?);
+        assert!(!cache.getbit(key.clone(), 100).await?);
+        assert_eq!(cache.get(key.clone()).await, Some("\u{1}".to_string()));
+
+        cache.set("word".to_string(), "a".to_string()).await?;
+        // 'a' is 0b01100001
+        assert!(cache.getbit("word".to_string(), 1).await?);
+        assert!(!cache.getbit("word".to_string(), 0).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bitcount_bitpos() -> Result<()> {
+        let cache = Cache::default();
+        cache.set("key".to_string(), "foobar".to_string()).await?;
+        assert_eq!(cache.bitcount("key".to_string(), None).await?, 26);
+        let range = BitRange {
+            start: 1,
+            end: Some(1),
+            unit: Unit::Byte,
+        };
+        assert_eq!(cache.bitcount("key".to_string(), Some(range)).await?, 6);
+        let range = BitRange {
+            start: 5,
+            end: Some(30),
+            unit: Unit::Bit,
+        };
+        assert_eq!(cache.bitcount("key".to_string(), Some(range)).await?, 17);
+
+        cache.set("ones".to_string(), "\u{7f}".to_string()).await?;
+        assert_eq!(cache.bitpos("ones".to_string(), true, None).await?, 1);
+        assert_eq!(cache.bitpos("ones".to_string(), false, None).await?, 0);
+        let range = BitRange {
+            start: 1,
+            end: None,
+            unit: Unit::Bit,
+        };
+        assert_eq!(
+            cache.bitpos("ones".to_string(), false, Some(range)).await?,
+            8
+        );
+        let range = BitRange {
+            start: 1,
+            end: Some(-1),
+            unit: Unit::Bit,
+        };
+        assert_eq!(
+            cache.bitpos("ones".to_string(), false, Some(range)).await?,
+            -1
+        );
+        assert_eq!(cache.bitpos("missing".to_string(), false, None).await?, 0);
+        assert_eq!(cache.bitpos("missing".to_string(), true, None).await?, -1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bitop() -> Result<()> {
+        let cache = Cache::default();
+        cache.setbit("a".to_string(), 0, true).await?;
+        cache.setbit("a".to_string(), 9, true).await?;
+        cache.setbit("b".to_string(), 0, true).await?;
+
+        let keys = vec!["a".to_string(), "b".to_string()];
+        let len = cache
+            .bitop(BitOperation::And, "and".to_string(), keys.clone())
+            .await?;
+        assert_eq!(len, 2);
+        assert_eq!(cache.bitcount("and".to_string(), None).await?, 1);
+
+        cache
+            .bitop(BitOperation::Xor, "xor".to_string(), keys)
+            .await?;
+        assert_eq!(cache.bitpos("xor".to_string(), true, None).await?, 9);
+
+        cache
+            .bitop(BitOperation::Not, "not".to_string(), vec!["b".to_string()])
+            .await?;
+        assert_eq!(cache.bitcount("not".to_string(), None).await?, 7);
+
+        let result = cache
+            .bitop(BitOperation::Not, "not".to_string(), vec![])
+            .await;
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bitfield() -> Result<()> {
+        let cache = Cache::default();
+        let ops = vec![FieldOp::Get(field("u8"), 0)];
+        assert_eq!(cache.bitfield("key".to_string(), ops).await?, vec![Some(0)]);
+        assert!(!cache.exists("key".to_string()).await);
+
+        let ops = vec![
+            FieldOp::IncrBy(field("u8"), 0, 255),
+            FieldOp::Overflow(Overflow::Sat),
+            FieldOp::IncrBy(field("u8"), 0, 10),
+        ];
+        let replies = cache.bitfield("key".to_string(), ops).await?;
+        assert_eq!(replies, vec![Some(255), Some(255)]);
+        assert_eq!(cache.bitcount("key".to_string(), None).await?, 8);
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// A client parked on one or more keys.
+#[derive(Debug, Default)]
+struct Waiter {
+    id: u64,
+    notify: Notify,
+    /// Keys that woke this waiter since it last tried to serve itself.
+    ready: Mutex<Vec<String>>,
+}
+
+/// The clients blocked on each key, in the order they blocked.
+///
+/// A write that makes a key ready wakes only the client at the head of its queue. That client
+/// either serves itself and passes the wakeup on to the next one, or goes back to the head of
+/// the queue, so clients are served first come, first served.
+#[derive(Debug, Default)]
+pub struct Waiters {
+    queues: Mutex<HashMap<String, VecDeque<Arc<Waiter>>>>,
+    next_id: AtomicU64,
+}
+
+impl Waiters {
+    /// Wake the oldest client blocked on `key`, if any.
+    pub fn wake(&self, key: &str) {
+        let waiter = {
+            let mut queues = self.queues.lock().unwrap();
+            let waiter = match queues.get_mut(key) {
+                Some(queue) => queue.pop_front(),
+                None => return,
+            };
+            if queues.get(key).map(VecDeque::is_empty).unwrap_or(false) {
+                queues.remove(key);
+            }
+            waiter
+        };
+
+        if let Some(waiter) = waiter {
+            log::debug!("waking client {} blocked on key {}", waiter.id, key);
+            waiter.ready.lock().unwrap().push(key.to_owned());
+            waiter.notify.notify_one();
+        }
+    }
+
+    /// Queue a new client at the tail of the queues of `keys`.
+    fn park(&self, keys: &[String]) -> Parked<'_> {
+        let waiter = Arc::new(Waiter {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            ..Waiter::default()
+        });
+        let mut queues = self.queues.lock().unwrap();
+        for key in keys {
+            queues
+                .entry(key.clone())
+                .or_default()
+                .push_back(waiter.clone());
+        }
+        Parked {
+            waiters: self,
+            waiter,
+            keys: keys.to_vec(),
+        }
+    }
+}
+
+/// A parked client, removed from every queue when dropped.
+struct Parked<'a> {
+    waiters: &'a Waiters,
+    waiter: Arc<Waiter>,
+    keys: Vec<String>,
+}
+
+impl Parked<'_> {
+    /// Go back to the head of the queues of the keys that woke this client without serving it.
+    fn requeue(&self) {
+        let ready: Vec<String> = self.waiter.ready.lock().unwrap().drain(..).collect();
+        let mut queues = self.waiters.queues.lock().unwrap();
+        for key in ready {
+            queues
+                .entry(key)
+                .or_default()
+                .push_front(self.waiter.clone());
+        }
+    }
+}
+
+impl Drop for Parked<'_> {
+    fn drop(&mut self) {
+        {
+            let mut queues = self.waiters.queues.lock().unwrap();
+            for key in &self.keys {
+                if let Some(queue) = queues.get_mut(key) {
+                    queue.retain(|waiter| waiter.id != self.waiter.id);
+                    if queue.is_empty() {
+                        queues.remove(key);
+                    }
+                }
+            }
+        }
+
+        // pass on the wakeups this client received, the data may be left for the next one
+        let ready: Vec<String> = self.waiter.ready.lock().unwrap().drain(..).collect();
+        for key in ready {
+            self.waiters.wake(&key);
+        }
+    }
+}
+
+impl Cache {
+    /// Run `attempt` until it produces a value, blocking on `keys` in between. Returns `None`
+    /// once `timeout` elapses, a `None` timeout blocks forever, and fails right away if it
+    /// ends later than the clock can hold.
+    pub async fn block_on<T, F, Fut>(
+        &self,
+        keys: &[String],
+        timeout: Option<Duration>,
+        mut attempt: F,
+    ) -> Result<Option<T>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Option<T>>>,
+    {
+        let deadline = match timeout {
+            Some(timeout) => match tokio::time::Instant::now().checked_add(timeout) {
+                Some(deadline) => Some(deadline),
+                None => return Err(Error::msg("timeout is out of range")),
+            },
+            None => None,
+        };
+        // park before the first attempt so that no write in between goes unnoticed
+        let parked = self.blocked.park(keys);
+
+        loop {
+            // transactions are held back during an attempt, but not while blocked
+            let gate = self.enter().await;
+            if let Some(value) = attempt().await? {
+                return Ok(Some(value));
+            }
+            drop(gate);
+            parked.requeue();
+
+            let notified = parked.waiter.notify.notified();
+            match deadline {
+                Some(deadline) => {
+                    if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                        return Ok(None);
+                    }
+                }
+                None => notified.await,
+            }
+        }
+    }
+
+    /// Wake the oldest client blocked on `key`, after a write that may have made it ready.
+    pub(crate) fn wake_blocked(&self, key: &str) {
+        self.blocked.wake(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::list::End;
+    use crate::cache::Cache;
+    use anyhow::Result;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_block_on_times_out() -> Result<()> {
+        let cache = Cache::default();
+        let keys = vec!["list".to_string()];
+        let popped = cache
+            .block_on(&keys, Some(Duration::from_millis(20)), || {
+                cache.pop("list".to_string(), End::Left, 1)
+            })
+            .await?;
+        assert_eq!(popped, None);
+        assert!(cache.blocked.queues.lock().unwrap().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_block_on_wakes_in_order() -> Result<()> {
+        let cache = Arc::new(Cache::default());
+        let mut clients = Vec::new();
+        for _ in 0..3 {
+            let cache = cache.clone();
+            clients.push(tokio::spawn(async move {
+                let keys = vec!["list".to_string()];
+                cache
+                    .block_on(&keys, None, || cache.pop("list".to_string(), End::Left, 1))
+                    .await
+            }));
+            // let each client block before the next one
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let values = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        cache
+            .push("list".to_string(), End::Right, values, true)
+            .await?;
+
+        let mut served = Vec::new();
+        for client in clients {
+            served.push(client.await??.unwrap());
+        }
+        assert_eq!(served, vec![vec!["a"], vec!["b"], vec!["c"]]);
+        Ok(())
+    }
+}
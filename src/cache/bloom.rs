@@ -0,0 +1,361 @@
+use crate::cache::hyperloglog::murmur_hash64a;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use std::f64::consts::LN_2;
+use std::mem;
+
+/// The defaults of filters created by BF.ADD, matching RedisBloom.
+pub const DEFAULT_ERROR_RATE: f64 = 0.01;
+pub const DEFAULT_CAPACITY: usize = 100;
+pub const DEFAULT_EXPANSION: u32 = 2;
+/// Each new layer gets a tighter error rate so that the compound rate stays bounded by the
+/// requested one.
+const TIGHTENING_RATIO: f64 = 0.5;
+/// The most items a filter may be reserved for.
+pub const MAX_CAPACITY: usize = 1 << 27;
+/// The most bytes a filter or sketch may allocate at once, so that sizes too large are
+/// refused rather than aborting the server.
+const MAX_ALLOCATION: usize = 1 << 29;
+
+fn too_large() -> Error {
+    Error::msg("the requested size is too large")
+}
+
+/// Allocate `len` copies of `value`, failing past `MAX_ALLOCATION` bytes or if memory runs out.
+pub(crate) fn allocate<T: Clone>(len: usize, value: T) -> Result<Vec<T>> {
+    len.checked_mul(mem::size_of::<T>())
+        .filter(|bytes| *bytes <= MAX_ALLOCATION)
+        .ok_or_else(too_large)?;
+    let mut items = Vec::new();
+    items.try_reserve_exact(len).map_err(|_| too_large())?;
+    items.resize(len, value);
+    Ok(items)
+}
+
+/// The two hashes of `item`, combined into as many independent hashes as needed with
+/// `first + i * second`.
+pub(crate) fn hash_pair(item: &[u8]) -> (u64, u64) {
+    let first = murmur_hash64a(item, 0xc6a4a7935bd1e995);
+    let second = murmur_hash64a(item, 0x5bd1e9955bd1e995);
+    // an odd step never cycles back early on a power of two
+    (first, second | 1)
+}
+
+/// A fixed size Bloom filter.
+#[derive(Clone, Debug, PartialEq)]
+struct Layer {
+    bits: Vec<u64>,
+    size: u64,
+    hashes: u32,
+    capacity: usize,
+    count: usize,
+}
+
+impl Layer {
+    /// Size a layer holding `capacity` items with a false positive rate of `error_rate`,
+    /// failing if it would be too large.
+    fn new(capacity: usize, error_rate: f64) -> Result<Self> {
+        let size = (-(capacity as f64) * error_rate.ln() / (LN_2 * LN_2))
+            .ceil()
+            .max(64.0);
+        if size > (MAX_ALLOCATION * 8) as f64 {
+            return Err(too_large());
+        }
+        let size = size as u64;
+        let hashes = (-error_rate.log2()).ceil().max(1.0) as u32;
+        Ok(Layer {
+            bits: allocate(size.div_ceil(64) as usize, 0)?,
+            size,
+            hashes,
+            capacity,
+            count: 0,
+        })
+    }
+
+    fn positions(&self, (first, second): (u64, u64)) -> impl Iterator<Item = usize> + '_ {
+        (0..self.hashes as u64)
+            .map(move |i| (first.wrapping_add(i.wrapping_mul(second)) % self.size) as usize)
+    }
+
+    fn contains(&self, hash: (u64, u64)) -> bool {
+        self.positions(hash)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    fn insert(&mut self, hash: (u64, u64)) {
+        let positions = self.positions(hash).collect::<Vec<_>>();
+        for bit in positions {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+        self.count += 1;
+    }
+
+    fn is_full(&self) -> bool {
+        self.count >= self.capacity
+    }
+}
+
+/// A scalable Bloom filter, stacking layers of growing capacity as items are added so the
+/// false positive rate holds without knowing the number of items upfront.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BloomFilter {
+    error_rate: f64,
+    /// The capacity growth factor of new layers, `None` for a filter that never scales.
+    expansion: Option<u32>,
+    layers: Vec<Layer>,
+}
+
+impl Default for BloomFilter {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_ERROR_RATE,
+            DEFAULT_CAPACITY,
+            Some(DEFAULT_EXPANSION),
+        )
+        .expect("the default filter is small")
+    }
+}
+
+impl BloomFilter {
+    /// Create a filter for `capacity` items, failing if it would be too large.
+    pub fn new(error_rate: f64, capacity: usize, expansion: Option<u32>) -> Result<Self> {
+        Ok(BloomFilter {
+            error_rate,
+            expansion,
+            layers: vec![Layer::new(capacity, error_rate * TIGHTENING_RATIO)?],
+        })
+    }
+
+    /// Add `item`, returning whether it wasn't in the filter yet. Fails once a non scaling
+    /// filter is full, or once a new layer would be too large.
+    pub fn add(&mut self, item: &[u8]) -> Result<bool> {
+        let hash = hash_pair(item);
+        if self.layers.iter().any(|layer| layer.contains(hash)) {
+            return Ok(false);
+        }
+
+        let last = self.layers.last().unwrap();
+        if last.is_full() {
+            let expansion = self
+                .expansion
+                .ok_or_else(|| Error::msg("non scaling filter is full"))?;
+            let capacity = last.capacity.saturating_mul(expansion as usize);
+            let error_rate = self.error_rate * TIGHTENING_RATIO.powi(self.layers.len() as i32 + 1);
+            self.layers.push(Layer::new(capacity, error_rate)?);
+        }
+        self.layers.last_mut().unwrap().insert(hash);
+        Ok(true)
+    }
+
+    /// Retrieve whether `item` may have been added. False positives happen at the error
+    /// rate of the filter, false negatives never do.
+    pub fn contains(&self, item: &[u8]) -> bool {
+        let hash = hash_pair(item);
+        self.layers.iter().any(|layer| layer.contains(hash))
+    }
+
+    /// Retrieve the number of items added.
+    pub fn len(&self) -> usize {
+        self.layers.iter().map(|layer| layer.count).sum()
+    }
+
+    /// Retrieve the number of items the filter holds before scaling or filling up.
+    pub fn capacity(&self) -> usize {
+        self.layers.iter().map(|layer| layer.capacity).sum()
+    }
+
+    /// Describe the filter for BF.INFO.
+    pub fn info(&self) -> BloomInfo {
+        BloomInfo {
+            capacity: self.capacity(),
+            size: self.layers.iter().map(|layer| layer.bits.len() * 8).sum(),
+            filters: self.layers.len(),
+            items: self.len(),
+            expansion: self.expansion,
+        }
+    }
+
+    /// Retrieve an estimate of the memory used by the bit arrays of every layer, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.layers
+            .iter()
+            .map(|layer| layer.bits.len() * std::mem::size_of::<u64>())
+            .sum()
+    }
+}
+
+/// The properties of a Bloom filter reported by BF.INFO.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BloomInfo {
+    pub capacity: usize,
+    /// The memory used by the bits of every layer, in bytes.
+    pub size: usize,
+    pub filters: usize,
+    pub items: usize,
+    pub expansion: Option<u32>,
+}
+
+impl Cache {
+    /// Create an empty Bloom filter at `key`, failing if the key exists.
+    pub async fn bf_reserve(
+        &self,
+        key: String,
+        error_rate: f64,
+        capacity: usize,
+        expansion: Option<u32>,
+    ) -> Result<()> {
+        let filter = BloomFilter::new(error_rate, capacity, expansion)?;
+        self.create_object(key, filter.into())
+    }
+
+    /// Add `items` to the filter at `key`, creating it with the defaults if needed. Returns
+    /// for each item whether it was new, or why it couldn't be added.
+    pub async fn bf_add(&self, key: String, items: Vec<String>) -> Result<Vec<Result<bool>>> {
+        let added = self.write_object(
+            &key,
+            || Some(BloomFilter::default().into()),
+            |object| {
+                let filter = object.as_bloom_filter_mut()?;
+                Ok(items
+                    .iter()
+                    .map(|item| filter.add(item.as_bytes()))
+                    .collect())
+            },
+        )?;
+        Ok(added.unwrap_or_default())
+    }
+
+    /// Retrieve whether each of `items` may be in the filter at `key`.
+    pub async fn bf_exists(&self, key: String, items: Vec<String>) -> Result<Vec<bool>> {
+        let found = self.read_object(&key, |object| {
+            let filter = object.as_bloom_filter()?;
+            Ok(items
+                .iter()
+                .map(|item| filter.contains(item.as_bytes()))
+                .collect())
+        })?;
+        Ok(found.unwrap_or_else(|| vec![false; items.len()]))
+    }
+
+    /// Describe the filter at `key`, failing if it doesn't exist.
+    pub async fn bf_info(&self, key: String) -> Result<BloomInfo> {
+        self.read_object(&key, |object| Ok(object.as_bloom_filter()?.info()))?
+            .ok_or_else(|| Error::msg("not found"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::expiry::Expiry;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_no_false_negatives() {
+        let mut filter = BloomFilter::new(0.01, 1000, Some(2)).unwrap();
+        for n in 0..1000 {
+            filter.add(n.to_string().as_bytes()).unwrap();
+        }
+        assert!((0..1000).all(|n| filter.contains(n.to_string().as_bytes())));
+        assert!(!filter.add(b"7").unwrap());
+        // items colliding with earlier ones aren't counted
+        assert!(filter.len() > 990 && filter.len() <= 1000);
+    }
+
+    #[test]
+    fn test_error_rate() {
+        let mut filter = BloomFilter::new(0.01, 10_000, Some(2)).unwrap();
+        for n in 0..10_000 {
+            filter.add(n.to_string().as_bytes()).unwrap();
+        }
+        let false_positives = (10_000..110_000)
+            .filter(|n: &i32| filter.contains(n.to_string().as_bytes()))
+            .count();
+        assert!(
+            false_positives < 1000,
+            "{} false positives",
+            false_positives
+        );
+    }
+
+    #[test]
+    fn test_scaling() {
+        let mut filter = BloomFilter::new(0.01, 10, Some(2)).unwrap();
+        for n in 0..100 {
+            filter.add(n.to_string().as_bytes()).unwrap();
+        }
+        assert!(filter.layers.len() > 1);
+        assert!(filter.capacity() >= 100);
+        assert!((0..100).all(|n| filter.contains(n.to_string().as_bytes())));
+
+        let mut fixed = BloomFilter::new(0.01, 2, None).unwrap();
+        fixed.add(b"a").unwrap();
+        fixed.add(b"b").unwrap();
+        assert!(fixed.add(b"c").is_err());
+        assert!(!fixed.add(b"a").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_bf_commands() -> Result<()> {
+        let cache = Cache::default();
+        cache
+            .bf_reserve("users".to_string(), 0.001, 100, None)
+            .await?;
+        assert!(cache
+            .bf_reserve("users".to_string(), 0.001, 100, None)
+            .await
+            .is_err());
+
+        let added = cache
+            .bf_add(
+                "users".to_string(),
+                vec!["alice".to_string(), "alice".to_string()],
+            )
+            .await?;
+        assert_eq!(
+            added
+                .into_iter()
+                .map(|added| added.unwrap())
+                .collect::<Vec<_>>(),
+            vec![true, false]
+        );
+        let found = cache
+            .bf_exists(
+                "users".to_string(),
+                vec!["alice".to_string(), "bob".to_string()],
+            )
+            .await?;
+        assert_eq!(found, vec![true, false]);
+        let found = cache
+            .bf_exists("missing".to_string(), vec!["alice".to_string()])
+            .await?;
+        assert_eq!(found, vec![false]);
+
+        let info = cache.bf_info("users".to_string()).await?;
+        assert_eq!((info.capacity, info.items, info.filters), (100, 1, 1));
+        assert!(cache.bf_info("missing".to_string()).await.is_err());
+
+        cache.set("string".to_string(), "value".to_string()).await?;
+        assert!(cache
+            .bf_add("string".to_string(), vec!["alice".to_string()])
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_filter_expires() -> Result<()> {
+        let cache = Cache::default();
+        cache
+            .bf_add("seen".to_string(), vec!["alice".to_string()])
+            .await?;
+        let expiry = Expiry::new(Instant::now() - Duration::from_secs(1));
+        assert!(cache.expire("seen".to_string(), expiry).await);
+
+        let found = cache
+            .bf_exists("seen".to_string(), vec!["alice".to_string()])
+            .await?;
+        assert_eq!(found, vec![false]);
+        Ok(())
+    }
+}
@@ -0,0 +1,302 @@
+use crate::cache::bloom::{allocate, hash_pair};
+use crate::cache::entry::Entry;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+
+/// The largest dimensions of a sketch, whose counters must also fit in the largest allocation.
+pub const MAX_WIDTH: usize = 1 << 24;
+pub const MAX_DEPTH: usize = 64;
+
+/// A Count-Min sketch, estimating the frequency of items with a table of counters. Items
+/// colliding in every row make the estimates overshoot, they never undershoot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CountMinSketch {
+    width: usize,
+    depth: usize,
+    counters: Vec<u64>,
+    /// The sum of every increment.
+    total: u64,
+}
+
+impl CountMinSketch {
+    /// Create a sketch of `width` by `depth` counters, failing if it would be too large.
+    pub fn new(width: usize, depth: usize) -> Result<Self> {
+        if width > MAX_WIDTH || depth > MAX_DEPTH {
+            return Err(Error::msg(format!(
+                "width must be at most {} and depth at most {}",
+                MAX_WIDTH, MAX_DEPTH
+            )));
+        }
+        let cells = width
+            .checked_mul(depth)
+            .ok_or_else(|| Error::msg("the requested size is too large"))?;
+        Ok(CountMinSketch {
+            width,
+            depth,
+            counters: allocate(cells, 0)?,
+            total: 0,
+        })
+    }
+
+    /// Size a sketch overestimating by at most `error` times the total count, with a
+    /// `probability` of exceeding that bound. Fails if it would be too large.
+    pub fn with_error(error: f64, probability: f64) -> Result<Self> {
+        let width = (2.0 / error).ceil().min(usize::MAX as f64) as usize;
+        let depth = (probability.ln() / 0.5f64.ln()).ceil().max(1.0) as usize;
+        Self::new(width, depth)
+    }
+
+    /// Retrieve the width and depth of the table of counters.
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.depth)
+    }
+
+    fn cells(&self, item: &[u8]) -> impl Iterator<Item = usize> {
+        let (first, second) = hash_pair(item);
+        let width = self.width;
+        (0..self.depth as u64).map(move |row| {
+            let column = first.wrapping_add(row.wrapping_mul(second)) % width as u64;
+            row as usize * width + column as usize
+        })
+    }
+
+    /// Increment the count of `item`, returning its new estimate.
+    pub fn increment(&mut self, item: &[u8], by: u64) -> u64 {
+        let cells = self.cells(item).collect::<Vec<_>>();
+        for &cell in &cells {
+            self.counters[cell] = self.counters[cell].saturating_add(by);
+        }
+        self.total = self.total.saturating_add(by);
+        cells
+            .into_iter()
+            .map(|cell| self.counters[cell])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Estimate the count of `item`.
+    pub fn count(&self, item: &[u8]) -> u64 {
+        self.cells(item)
+            .map(|cell| self.counters[cell])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Add the counters of `other` multiplied by `weight`. Both sketches must have the same
+    /// dimensions.
+    pub fn merge(&mut self, other: &CountMinSketch, weight: u64) -> Result<()> {
+        if self.dimensions() != other.dimensions() {
+            return Err(Error::msg("width and depth of the sketches must match"));
+        }
+        for (counter, other) in self.counters.iter_mut().zip(&other.counters) {
+            *counter = counter.saturating_add(other.saturating_mul(weight));
+        }
+        self.total = self
+            .total
+            .saturating_add(other.total.saturating_mul(weight));
+        Ok(())
+    }
+
+    /// Retrieve the sum of every increment.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Retrieve an estimate of the memory used by the counters, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.counters.len() * std::mem::size_of::<u64>()
+    }
+}
+
+impl Cache {
+    /// Create an empty sketch at `key`, failing if the key exists.
+    pub async fn cms_init(&self, key: String, sketch: CountMinSketch) -> Result<()> {
+        self.create_object(key, sketch.into())
+    }
+
+    /// Increment the counts of `items` in the sketch at `key`, returning their new
+    /// estimates. Fails if the key doesn't exist.
+    pub async fn cms_incrby(&self, key: String, items: Vec<(String, u64)>) -> Result<Vec<u64>> {
+        self.write_object(
+            &key,
+            || None,
+            |object| {
+                let sketch = object.as_count_min_sketch_mut()?;
+                Ok(items
+                    .iter()
+                    .map(|(item, by)| sketch.increment(item.as_bytes(), *by))
+                    .collect())
+            },
+        )?
+        .ok_or_else(|| Error::msg("key does not exist"))
+    }
+
+    /// Estimate the counts of `items` in the sketch at `key`. Fails if the key doesn't
+    /// exist.
+    pub async fn cms_query(&self, key: String, items: Vec<String>) -> Result<Vec<u64>> {
+        self.read_object(&key, |object| {
+            let sketch = object.as_count_min_sketch()?;
+            Ok(items
+                .iter()
+                .map(|item| sketch.count(item.as_bytes()))
+                .collect())
+        })?
+        .ok_or_else(|| Error::msg("key does not exist"))
+    }
+
+    /// Retrieve the width, depth and total count of the sketch at `key`.
+    pub async fn cms_info(&self, key: String) -> Result<(usize, usize, u64)> {
+        self.read_object(&key, |object| {
+            let sketch = object.as_count_min_sketch()?;
+            let (width, depth) = sketch.dimensions();
+            Ok((width, depth, sketch.total()))
+        })?
+        .ok_or_else(|| Error::msg("key does not exist"))
+    }
+
+    /// Store the weighted sum of the sketches at `sources` in `destination`, creating it
+    /// with their dimensions if needed. An existing destination keeps its expiry.
+    pub async fn cms_merge(&self, destination: String, sources: Vec<(String, u64)>) -> Result<()> {
+        let mut store = self.store.write().unwrap();
+        self.evict_if_expired(&mut store, &destination);
+        for (key, _) in &sources {
+            self.evict_if_expired(&mut store, key);
+        }
+
+        let mut merged: Option<CountMinSketch> = None;
+        for (key, weight) in &sources {
+            let sketch = store
+                .get(key)
+                .ok_or_else(|| Error::msg("key does not exist"))?
+                .value()
+                .as_count_min_sketch()?;
+            let merged = match &mut merged {
+                Some(merged) => merged,
+                None => {
+                    let (width, depth) = sketch.dimensions();
+                    merged.insert(CountMinSketch::new(width, depth)?)
+                }
+            };
+            merged.merge(sketch, *weight)?;
+        }
+        let merged = match merged {
+            Some(merged) => merged,
+            None => return Err(Error::msg("at least one source is required")),
+        };
+
+        self.admit(&mut store, &destination)?;
+        let before = self.footprint(&store, &destination);
+        match store.get_mut(&destination) {
+            Some(entry) => {
+                let target = entry.value_mut().as_count_min_sketch_mut()?;
+                if target.dimensions() != merged.dimensions() {
+                    return Err(Error::msg("width and depth of the sketches must match"));
+                }
+                *target = merged;
+            }
+            None => {
+                let entry = Entry::new(merged, self.default_expiry());
+                store.insert(destination.clone(), entry);
+            }
+        }
+        self.charge(self.footprint(&store, &destination).saturating_sub(before));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::quota::Quota;
+
+    #[test]
+    fn test_never_undercounts() {
+        let mut sketch = CountMinSketch::with_error(0.01, 0.01).unwrap();
+        assert_eq!(sketch.dimensions(), (200, 7));
+        for n in 0..1000u64 {
+            sketch.increment(n.to_string().as_bytes(), n % 10 + 1);
+        }
+        for n in 0..1000u64 {
+            let exact = n % 10 + 1;
+            let count = sketch.count(n.to_string().as_bytes());
+            // overestimates stay within the error bound with high probability
+            assert!((exact..=exact + sketch.total() / 50).contains(&count));
+        }
+        assert!(sketch.count(b"missing") <= sketch.total() / 50);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut first = CountMinSketch::new(100, 5).unwrap();
+        first.increment(b"a", 3);
+        let mut second = CountMinSketch::new(100, 5).unwrap();
+        second.increment(b"a", 2);
+        second.increment(b"b", 1);
+
+        first.merge(&second, 2).unwrap();
+        assert_eq!(first.count(b"a"), 7);
+        assert_eq!(first.count(b"b"), 2);
+        assert_eq!(first.total(), 9);
+        assert!(first
+            .merge(&CountMinSketch::new(10, 5).unwrap(), 1)
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cms_commands() -> Result<()> {
+        let cache = Cache::default();
+        let item = |item: &str, by| (item.to_string(), by);
+        assert!(cache
+            .cms_incrby("hour".to_string(), vec![item("a", 1)])
+            .await
+            .is_err());
+
+        cache
+            .cms_init("hour".to_string(), CountMinSketch::new(100, 5).unwrap())
+            .await?;
+        cache
+            .cms_init("day".to_string(), CountMinSketch::new(100, 5).unwrap())
+            .await?;
+        let counts = cache
+            .cms_incrby("hour".to_string(), vec![item("a", 2), item("a", 3)])
+            .await?;
+        assert_eq!(counts, vec![2, 5]);
+        cache
+            .cms_incrby("day".to_string(), vec![item("a", 10)])
+            .await?;
+
+        let sources = vec![("hour".to_string(), 1), ("day".to_string(), 1)];
+        cache.cms_merge("total".to_string(), sources).await?;
+        let counts = cache
+            .cms_query("total".to_string(), vec!["a".to_string(), "b".to_string()])
+            .await?;
+        assert_eq!(counts, vec![15, 0]);
+        assert_eq!(cache.cms_info("total".to_string()).await?, (100, 5, 15));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_over_quota() -> Result<()> {
+        let cache = Cache::default();
+        for key in ["hour", "total"] {
+            cache
+                .cms_init(key.to_string(), CountMinSketch::new(100, 5).unwrap())
+                .await?;
+        }
+        cache
+            .set_quota(Quota {
+                max_memory: Some(1),
+                ..Quota::default()
+            })
+            .await;
+
+        // an existing destination is replaced, which may grow it
+        let sources = vec![("hour".to_string(), 1)];
+        let error = cache
+            .cms_merge("total".to_string(), sources)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("OOM"));
+        Ok(())
+    }
+}
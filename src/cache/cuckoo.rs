@@ -0,0 +1,413 @@
+use crate::cache::bloom::allocate;
+use crate::cache::hyperloglog::murmur_hash64a;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use rand::Rng;
+use std::mem;
+
+/// The defaults of filters created by CF.ADD, matching RedisBloom.
+pub const DEFAULT_CAPACITY: usize = 1024;
+pub const DEFAULT_BUCKET_SIZE: usize = 2;
+pub const DEFAULT_MAX_ITERATIONS: usize = 20;
+pub const DEFAULT_EXPANSION: usize = 1;
+/// The most items a filter may be reserved for, and the largest bucket size.
+pub const MAX_CAPACITY: usize = 1 << 27;
+pub const MAX_BUCKET_SIZE: usize = 255;
+
+/// An empty slot, fingerprints are never zero.
+const EMPTY: u8 = 0;
+
+/// Hash `item` into its fingerprint and the hash its primary bucket is derived from.
+fn fingerprint(item: &[u8]) -> (u8, u64) {
+    let hash = murmur_hash64a(item, 0xadc83b19);
+    let fingerprint = ((hash >> 32) % 255 + 1) as u8;
+    (fingerprint, hash)
+}
+
+/// A table of buckets, each holding up to `bucket_size` fingerprints.
+#[derive(Clone, Debug, PartialEq)]
+struct Table {
+    slots: Vec<u8>,
+    bucket_size: usize,
+    /// The number of buckets minus one, the number of buckets being a power of two.
+    mask: u64,
+}
+
+impl Table {
+    /// Size a table for `capacity` fingerprints, failing if it would be too large.
+    fn new(capacity: usize, bucket_size: usize) -> Result<Self> {
+        let buckets = capacity
+            .div_ceil(bucket_size)
+            .checked_next_power_of_two()
+            .ok_or_else(|| Error::msg("the requested size is too large"))?;
+        let slots = buckets
+            .checked_mul(bucket_size)
+            .ok_or_else(|| Error::msg("the requested size is too large"))?;
+        Ok(Table {
+            slots: allocate(slots, EMPTY)?,
+            bucket_size,
+            mask: buckets as u64 - 1,
+        })
+    }
+
+    /// Retrieve the two candidate buckets of a fingerprint. Either can be derived from the
+    /// other and the fingerprint, so entries can move without knowing their item.
+    fn buckets(&self, fingerprint: u8, hash: u64) -> (usize, usize) {
+        let first = hash & self.mask;
+        (first as usize, self.alternate(fingerprint, first as usize))
+    }
+
+    fn alternate(&self, fingerprint: u8, bucket: usize) -> usize {
+        let offset = murmur_hash64a(&[fingerprint], 0);
+        ((bucket as u64 ^ offset) & self.mask) as usize
+    }
+
+    fn bucket(&self, bucket: usize) -> &[u8] {
+        &self.slots[bucket * self.bucket_size..(bucket + 1) * self.bucket_size]
+    }
+
+    fn bucket_mut(&mut self, bucket: usize) -> &mut [u8] {
+        &mut self.slots[bucket * self.bucket_size..(bucket + 1) * self.bucket_size]
+    }
+
+    /// Store `fingerprint` in a free slot of `bucket`, returning whether there was one.
+    fn store(&mut self, bucket: usize, fingerprint: u8) -> bool {
+        match self
+            .bucket_mut(bucket)
+            .iter_mut()
+            .find(|slot| **slot == EMPTY)
+        {
+            Some(slot) => {
+                *slot = fingerprint;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Store `fingerprint` without moving other entries.
+    fn insert_free(&mut self, fingerprint: u8, hash: u64) -> bool {
+        let (first, second) = self.buckets(fingerprint, hash);
+        self.store(first, fingerprint) || self.store(second, fingerprint)
+    }
+
+    /// Store `fingerprint` by kicking entries to their alternate bucket, at most
+    /// `max_iterations` times. The table is left unchanged when no room could be made.
+    fn insert_kicking(&mut self, fingerprint: u8, hash: u64, max_iterations: usize) -> bool {
+        if self.insert_free(fingerprint, hash) {
+            return true;
+        }
+
+        let mut rng = rand::thread_rng();
+        let mut bucket = self.buckets(fingerprint, hash).0;
+        let mut homeless = fingerprint;
+        let mut kicks = Vec::with_capacity(max_iterations);
+        for _ in 0..max_iterations {
+            let slot = bucket * self.bucket_size + rng.gen_range(0..self.bucket_size);
+            mem::swap(&mut self.slots[slot], &mut homeless);
+            kicks.push(slot);
+
+            bucket = self.alternate(homeless, bucket);
+            if self.store(bucket, homeless) {
+                return true;
+            }
+        }
+
+        // undo the kicks so every entry is back in one of its buckets
+        for slot in kicks.into_iter().rev() {
+            mem::swap(&mut self.slots[slot], &mut homeless);
+        }
+        false
+    }
+
+    fn count(&self, fingerprint: u8, hash: u64) -> usize {
+        let (first, second) = self.buckets(fingerprint, hash);
+        let matches = |bucket| {
+            self.bucket(bucket)
+                .iter()
+                .filter(|slot| **slot == fingerprint)
+                .count()
+        };
+        if first == second {
+            matches(first)
+        } else {
+            matches(first) + matches(second)
+        }
+    }
+
+    fn remove(&mut self, fingerprint: u8, hash: u64) -> bool {
+        let (first, second) = self.buckets(fingerprint, hash);
+        for bucket in [first, second] {
+            if let Some(slot) = self
+                .bucket_mut(bucket)
+                .iter_mut()
+                .find(|slot| **slot == fingerprint)
+            {
+                *slot = EMPTY;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// The CF.RESERVE options.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CuckooOptions {
+    pub capacity: usize,
+    /// Number of fingerprints per bucket, more lowering the false positive rate and
+    /// raising the load the filter reaches before filling up.
+    pub bucket_size: usize,
+    /// Number of entries moved around looking for room before the filter expands.
+    pub max_iterations: usize,
+    /// The capacity growth factor of new tables, zero for a filter that never expands.
+    pub expansion: usize,
+}
+
+impl Default for CuckooOptions {
+    fn default() -> Self {
+        CuckooOptions {
+            capacity: DEFAULT_CAPACITY,
+            bucket_size: DEFAULT_BUCKET_SIZE,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            expansion: DEFAULT_EXPANSION,
+        }
+    }
+}
+
+/// A cuckoo filter, storing short fingerprints of the items so that unlike Bloom filters
+/// items can be deleted. Filled up filters expand by stacking new tables.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CuckooFilter {
+    options: CuckooOptions,
+    tables: Vec<Table>,
+}
+
+impl Default for CuckooFilter {
+    fn default() -> Self {
+        Self::new(CuckooOptions::default()).expect("the default filter is small")
+    }
+}
+
+impl CuckooFilter {
+    /// Create a filter as configured by `options`, failing if it would be too large.
+    pub fn new(options: CuckooOptions) -> Result<Self> {
+        Ok(CuckooFilter {
+            options,
+            tables: vec![Table::new(options.capacity, options.bucket_size)?],
+        })
+    }
+
+    /// Add `item`, even if it may already be in the filter. Fails once a non expanding
+    /// filter is full, or once a new table would be too large.
+    pub fn add(&mut self, item: &[u8]) -> Result<()> {
+        let (fingerprint, hash) = fingerprint(item);
+        let inserted = self
+            .tables
+            .iter_mut()
+            .any(|table| table.insert_free(fingerprint, hash))
+            || self.tables.last_mut().unwrap().insert_kicking(
+                fingerprint,
+                hash,
+                self.options.max_iterations,
+            );
+
+        if !inserted {
+            if self.options.expansion == 0 {
+                return Err(Error::msg("filter is full"));
+            }
+            let capacity = (self.options.expansion)
+                .checked_pow(self.tables.len() as u32)
+                .and_then(|growth| growth.checked_mul(self.options.capacity))
+                .ok_or_else(|| Error::msg("the requested size is too large"))?;
+            let mut table = Table::new(capacity, self.options.bucket_size)?;
+            table.insert_free(fingerprint, hash);
+            self.tables.push(table);
+        }
+        Ok(())
+    }
+
+    /// Add `item` unless it may already be in the filter, returning whether it was added.
+    pub fn add_new(&mut self, item: &[u8]) -> Result<bool> {
+        if self.contains(item) {
+            return Ok(false);
+        }
+        self.add(item).map(|()| true)
+    }
+
+    /// Retrieve whether `item` may have been added.
+    pub fn contains(&self, item: &[u8]) -> bool {
+        self.count(item) > 0
+    }
+
+    /// Retrieve how many times `item` may have been added.
+    pub fn count(&self, item: &[u8]) -> usize {
+        let (fingerprint, hash) = fingerprint(item);
+        self.tables
+            .iter()
+            .map(|table| table.count(fingerprint, hash))
+            .sum()
+    }
+
+    /// Remove one occurrence of `item`, returning whether it was found. Deleting an item
+    /// that was never added may remove another one sharing its fingerprint.
+    pub fn remove(&mut self, item: &[u8]) -> bool {
+        let (fingerprint, hash) = fingerprint(item);
+        self.tables
+            .iter_mut()
+            .rev()
+            .any(|table| table.remove(fingerprint, hash))
+    }
+
+    /// Retrieve an estimate of the memory used by the tables, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.tables.iter().map(|table| table.slots.len()).sum()
+    }
+}
+
+impl Cache {
+    /// Create an empty cuckoo filter at `key`, failing if the key exists.
+    pub async fn cf_reserve(&self, key: String, options: CuckooOptions) -> Result<()> {
+        self.create_object(key, CuckooFilter::new(options)?.into())
+    }
+
+    /// Add `item` to the filter at `key`, creating it with the defaults if needed. With `nx`
+    /// the item is only added if it isn't in the filter yet. Returns whether it was added.
+    pub async fn cf_add(&self, key: String, item: String, nx: bool) -> Result<bool> {
+        let added = self.write_object(
+            &key,
+            || Some(CuckooFilter::default().into()),
+            |object| {
+                let filter = object.as_cuckoo_filter_mut()?;
+                if nx {
+                    filter.add_new(item.as_bytes())
+                } else {
+                    filter.add(item.as_bytes()).map(|()| true)
+                }
+            },
+        )?;
+        Ok(added.unwrap_or(false))
+    }
+
+    /// Retrieve whether each of `items` may be in the filter at `key`.
+    pub async fn cf_exists(&self, key: String, items: Vec<String>) -> Result<Vec<bool>> {
+        let found = self.read_object(&key, |object| {
+            let filter = object.as_cuckoo_filter()?;
+            Ok(items
+                .iter()
+                .map(|item| filter.contains(item.as_bytes()))
+                .collect())
+        })?;
+        Ok(found.unwrap_or_else(|| vec![false; items.len()]))
+    }
+
+    /// Retrieve how many times `item` may have been added to the filter at `key`.
+    pub async fn cf_count(&self, key: String, item: String) -> Result<usize> {
+        let count = self.read_object(&key, |object| {
+            Ok(object.as_cuckoo_filter()?.count(item.as_bytes()))
+        })?;
+        Ok(count.unwrap_or(0))
+    }
+
+    /// Remove one occurrence of `item` from the filter at `key`, returning whether it was
+    /// found. Fails if the key doesn't exist.
+    pub async fn cf_del(&self, key: String, item: String) -> Result<bool> {
+        self.shrink_object(&key, |object| {
+            Ok(object.as_cuckoo_filter_mut()?.remove(item.as_bytes()))
+        })?
+        .ok_or_else(|| Error::msg("not found"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(capacity: usize, expansion: usize) -> CuckooOptions {
+        CuckooOptions {
+            capacity,
+            expansion,
+            ..CuckooOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_alternate_buckets() {
+        let table = Table::new(1024, 2).unwrap();
+        for n in 0..100u32 {
+            let (fingerprint, hash) = fingerprint(n.to_string().as_bytes());
+            let (first, second) = table.buckets(fingerprint, hash);
+            assert_eq!(table.alternate(fingerprint, second), first);
+        }
+    }
+
+    #[test]
+    fn test_add_remove() {
+        let mut filter = CuckooFilter::new(options(1000, 1)).unwrap();
+        for n in 0..1000 {
+            filter.add(n.to_string().as_bytes()).unwrap();
+        }
+        assert!((0..1000).all(|n| filter.contains(n.to_string().as_bytes())));
+
+        for n in 0..500 {
+            assert!(filter.remove(n.to_string().as_bytes()));
+        }
+        assert!((500..1000).all(|n| filter.contains(n.to_string().as_bytes())));
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let mut filter = CuckooFilter::default();
+        filter.add(b"a").unwrap();
+        filter.add(b"a").unwrap();
+        assert!(!filter.add_new(b"a").unwrap());
+        assert_eq!(filter.count(b"a"), 2);
+        assert!(filter.remove(b"a"));
+        assert_eq!(filter.count(b"a"), 1);
+    }
+
+    #[test]
+    fn test_expansion() {
+        let mut filter = CuckooFilter::new(options(8, 2)).unwrap();
+        for n in 0..100 {
+            filter.add(n.to_string().as_bytes()).unwrap();
+        }
+        assert!(filter.tables.len() > 1);
+        assert!((0..100).all(|n| filter.contains(n.to_string().as_bytes())));
+
+        let mut fixed = CuckooFilter::new(options(64, 0)).unwrap();
+        let added = (0..100)
+            .take_while(|n: &i32| fixed.add(n.to_string().as_bytes()).is_ok())
+            .count();
+        assert!((32..=64).contains(&added));
+        assert!((0..added as i32).all(|n| fixed.contains(n.to_string().as_bytes())));
+    }
+
+    #[tokio::test]
+    async fn test_cf_commands() -> Result<()> {
+        let cache = Cache::default();
+        cache
+            .cf_reserve("emails".to_string(), options(100, 1))
+            .await?;
+        assert!(cache
+            .cf_reserve("emails".to_string(), options(100, 1))
+            .await
+            .is_err());
+
+        let email = || "alice@example.com".to_string();
+        assert!(cache.cf_add("emails".to_string(), email(), false).await?);
+        assert!(!cache.cf_add("emails".to_string(), email(), true).await?);
+        assert_eq!(cache.cf_count("emails".to_string(), email()).await?, 1);
+        let found = cache
+            .cf_exists("emails".to_string(), vec![email(), "bob".to_string()])
+            .await?;
+        assert_eq!(found, vec![true, false]);
+
+        assert!(cache.cf_del("emails".to_string(), email()).await?);
+        assert!(!cache.cf_del("emails".to_string(), email()).await?);
+        assert!(cache.cf_del("missing".to_string(), email()).await.is_err());
+        // an emptied filter keeps its key
+        assert!(cache.exists("emails".to_string()).await);
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use crate::cache::notifications::Notifications;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use std::sync::{Arc, RwLock};
+
+/// The numbered databases of a server, shared by every connection.
+///
+/// Each database is a whole `Cache` with its own indexes and expiry sampling. Cloning only
+/// clones a handle, and connections resolve their selected database on every command so that
+/// a swap is seen by the next command. Clients already blocked on a key keep waiting on the
+/// database they blocked in.
+#[derive(Clone, Debug)]
+pub struct Databases {
+    caches: Arc<RwLock<Vec<Arc<Cache>>>>,
+}
+
+impl Databases {
+    pub fn new(count: usize, sample: usize, threshold: f64) -> Self {
+        let caches = (0..count.max(1))
+            .map(|_| Arc::new(Cache::new(sample, threshold)))
+            .collect();
+        Databases {
+            caches: Arc::new(RwLock::new(caches)),
+        }
+    }
+
+    /// Publish the events of every database through `notifications`, numbered by their index.
+    pub fn notify_through(&self, notifications: &Notifications) {
+        for (db, cache) in self.caches.read().unwrap().iter().enumerate() {
+            cache.notify_through(notifications.clone(), db);
+        }
+    }
+
+    /// Retrieve the number of databases.
+    pub fn count(&self) -> usize {
+        self.caches.read().unwrap().len()
+    }
+
+    /// Retrieve the database numbered `index`.
+    pub fn get(&self, index: usize) -> Result<Arc<Cache>> {
+        let caches = self.caches.read().unwrap();
+        caches.get(index).cloned().ok_or_else(out_of_range)
+    }
+
+    /// Retrieve every database, in order.
+    pub fn all(&self) -> Vec<Arc<Cache>> {
+        self.caches.read().unwrap().clone()
+    }
+
+    /// Swap the databases numbered `first` and `second` at once, for every connection.
+    pub fn swap(&self, first: usize, second: usize) -> Result<()> {
+        let mut caches = self.caches.write().unwrap();
+        if first >= caches.len() || second >= caches.len() {
+            return Err(out_of_range());
+        }
+        caches.swap(first, second);
+        for db in [first, second] {
+            caches[db].renumber(db);
+            caches[db].next_epoch();
+        }
+        log::debug!("swapped databases {} and {}", first, second);
+        Ok(())
+    }
+}
+
+/// A single database holding `cache`.
+impl From<Arc<Cache>> for Databases {
+    fn from(cache: Arc<Cache>) -> Self {
+        Databases {
+            caches: Arc::new(RwLock::new(vec![cache])),
+        }
+    }
+}
+
+pub fn out_of_range() -> Error {
+    Error::msg("DB index is out of range")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_swap() -> Result<()> {
+        let databases = Databases::new(3, 25, 0.25);
+        assert_eq!(databases.count(), 3);
+        databases
+            .get(0)?
+            .set("key".to_string(), "zero".to_string())
+            .await?;
+        let handle = databases.clone();
+
+        handle.swap(0, 2)?;
+        assert!(databases.get(0)?.is_empty().await);
+        assert_eq!(
+            databases.get(2)?.get("key".to_string()).await,
+            Some("zero".to_string())
+        );
+        assert!(databases.swap(0, 3).is_err());
+        assert!(databases.get(3).is_err());
+        Ok(())
+    }
+}
@@ -1,15 +1,34 @@
 use crate::cache::expiry::Expiry;
+use crate::cache::object::Object;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-#[derive(Eq, PartialEq, Clone, Debug)]
+/// The last version given to an entry, shared by every keyspace so that a key removed and
+/// created again never gets a version it had before.
+static VERSION: AtomicU64 = AtomicU64::new(0);
+
+fn next_version() -> u64 {
+    VERSION.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+#[derive(PartialEq, Clone, Debug)]
 pub struct Entry {
-    value: String,
+    value: Object,
     expiration: Expiry,
+    /// Changes with every write to the value or the expiration, for WATCH.
+    version: u64,
 }
 
 impl Entry {
     /// Create a new cache entry from a value and expiration.
-    pub fn new(value: String, expiration: Expiry) -> Self {
-        Self { value, expiration }
+    pub fn new<V>(value: V, expiration: Expiry) -> Self
+    where
+        V: Into<Object>,
+    {
+        Self {
+            value: value.into(),
+            expiration,
+            version: next_version(),
+        }
     }
 
     /// Retrieve the internal expiration.
@@ -17,15 +36,32 @@ impl Entry {
         &self.expiration
     }
 
+    /// Replace the internal expiration.
+    pub fn set_expiration(&mut self, expiration: Expiry) {
+        self.expiration = expiration;
+        self.touch();
+    }
+
     /// Retrieve the internal value.
-    pub fn value(&self) -> &String {
+    pub fn value(&self) -> &Object {
         &self.value
     }
 
-    /// Retrieve the mutable internal value.
-    pub fn value_mut(&mut self) -> &mut String {
+    /// Retrieve the mutable internal value, counting as a write.
+    pub fn value_mut(&mut self) -> &mut Object {
+        self.touch();
         &mut self.value
     }
+
+    /// Retrieve the version, which changes with every write.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Give the entry a new version, as when written or placed under another key.
+    pub fn touch(&mut self) {
+        self.version = next_version();
+    }
 }
 
 #[cfg(test)]
@@ -58,7 +94,7 @@ mod tests {
 
         // Test value_mut setter and getter
         let new_value = String::from("new_value");
-        *entry.value_mut() = new_value.clone();
+        *entry.value_mut() = new_value.clone().into();
         assert_eq!(entry.value(), &new_value);
 
         // Test expiration setter and getter
@@ -68,6 +104,23 @@ mod tests {
         assert_eq!(entry.expiration(), &expiry);
     }
 
+    #[test]
+    fn test_writes_change_the_version() {
+        let mut entry = Entry::new(String::from("test"), Expiry::none());
+        let version = entry.version();
+        assert_eq!(entry.clone().version(), version);
+
+        entry.value_mut();
+        assert_ne!(entry.version(), version);
+        let version = entry.version();
+        entry.set_expiration(Expiry::none());
+        assert_ne!(entry.version(), version);
+        assert_ne!(
+            Entry::new(String::from("test"), Expiry::none()).version(),
+            entry.version()
+        );
+    }
+
     #[test]
     fn test_entry_is_expired() {
         let value = String::from("test");
@@ -1,3 +1,4 @@
+use anyhow::{Error, Result};
 use std::time::{Duration, Instant};
 
 #[derive(Eq, PartialEq, Clone, Debug)]
@@ -15,6 +16,14 @@ impl Expiry {
         }
     }
 
+    /// Create an expiration `duration` from now, failing if it's later than the clock can tell.
+    pub fn after(duration: Duration) -> Result<Self> {
+        match Instant::now().checked_add(duration) {
+            Some(instant) => Ok(Self::new(instant)),
+            None => Err(Error::msg("invalid expire time")),
+        }
+    }
+
     /// Create an empty expiration (i.e. no expiration).
     pub fn none() -> Self {
         Self { instant: None }
@@ -46,31 +55,37 @@ impl From<Instant> for Expiry {
     }
 }
 
-// Automatic conversation from `u64`.
-impl From<u64> for Expiry {
-    fn from(millis: u64) -> Self {
-        Duration::from_millis(millis).into()
+// Conversion from milliseconds.
+impl TryFrom<u64> for Expiry {
+    type Error = Error;
+
+    fn try_from(millis: u64) -> Result<Self> {
+        Self::after(Duration::from_millis(millis))
     }
 }
 
-// Automatic conversation from `u64`.
-impl From<(u64, &String)> for Expiry {
-    fn from(expiry: (u64, &String)) -> Self {
+// Conversion from an amount in the format of SET.
+impl TryFrom<(u64, &String)> for Expiry {
+    type Error = Error;
+
+    fn try_from(expiry: (u64, &String)) -> Result<Self> {
         let amount = expiry.0;
         let format = expiry.1;
         let expiry_type = format.to_ascii_lowercase().as_str().into();
         match expiry_type {
-            ExpiryFormat::PX => Duration::from_millis(amount).into(),
-            ExpiryFormat::EX => Duration::from_secs(amount).into(),
-            _ => Self { instant: None },
+            ExpiryFormat::PX => Self::after(Duration::from_millis(amount)),
+            ExpiryFormat::EX => Self::after(Duration::from_secs(amount)),
+            _ => Ok(Self { instant: None }),
         }
     }
 }
 
-// Automatic conversation from `Duration`.
-impl From<Duration> for Expiry {
-    fn from(duration: Duration) -> Self {
-        Instant::now().checked_add(duration).unwrap().into()
+// Conversion from the time left before expiring.
+impl TryFrom<Duration> for Expiry {
+    type Error = Error;
+
+    fn try_from(duration: Duration) -> Result<Self> {
+        Self::after(duration)
     }
 }
 
@@ -143,11 +158,13 @@ mod tests {
         assert_eq!(expiry_from_instant.instant(), &Some(instant));
 
         let millis = 1000;
-        let expiry_from_millis: Expiry = millis.into();
+        let expiry_from_millis = Expiry::try_from(millis).unwrap();
         assert!(expiry_from_millis.instant().is_some());
+        assert!(Expiry::try_from((u64::MAX, &"EX".to_string())).is_err());
+        assert!(Expiry::after(Duration::MAX).is_err());
 
         let duration = Duration::from_secs(1);
-        let expiry_from_duration: Expiry = duration.into();
+        let expiry_from_duration = Expiry::try_from(duration).unwrap();
         assert!(expiry_from_duration.instant().is_some());
     }
 }
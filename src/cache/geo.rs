@@ -0,0 +1,541 @@
+use crate::cache::sorted_set::{
+    AddOptions, AddOutcome, Limit, RangeBy, ScoreBound, ScoreRange, SortedSet,
+};
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use std::cmp::Ordering;
+
+/// The limits of the coordinates that can be indexed, latitudes being bounded by the Web
+/// Mercator projection like in Redis.
+const LON_MIN: f64 = -180.0;
+const LON_MAX: f64 = 180.0;
+const LAT_MIN: f64 = -85.05112878;
+const LAT_MAX: f64 = 85.05112878;
+/// Number of bits per coordinate of a geohash, making a 52 bits score.
+const STEPS: u32 = 26;
+const EARTH_RADIUS: f64 = 6372797.560856;
+const MERCATOR_MAX: f64 = 20037726.37;
+
+/// A member to index, with its longitude and latitude.
+pub type Location = (f64, f64, String);
+
+/// Interleave the bits of `x` and `y`, `x` taking the even bits.
+fn interleave(x: u32, y: u32) -> u64 {
+    let spread = |v: u32| {
+        let mut v = v as u64;
+        v = (v | (v << 16)) & 0x0000ffff0000ffff;
+        v = (v | (v << 8)) & 0x00ff00ff00ff00ff;
+        v = (v | (v << 4)) & 0x0f0f0f0f0f0f0f0f;
+        v = (v | (v << 2)) & 0x3333333333333333;
+        (v | (v << 1)) & 0x5555555555555555
+    };
+    spread(x) | (spread(y) << 1)
+}
+
+/// Split the interleaved bits of `hash` back into `x` and `y`.
+fn deinterleave(hash: u64) -> (u32, u32) {
+    let squash = |v: u64| {
+        let mut v = v & 0x5555555555555555;
+        v = (v | (v >> 1)) & 0x3333333333333333;
+        v = (v | (v >> 2)) & 0x0f0f0f0f0f0f0f0f;
+        v = (v | (v >> 4)) & 0x00ff00ff00ff00ff;
+        v = (v | (v >> 8)) & 0x0000ffff0000ffff;
+        ((v | (v >> 16)) & 0x00000000ffffffff) as u32
+    };
+    (squash(hash), squash(hash >> 1))
+}
+
+/// Check that a point can be indexed.
+pub fn validate(lon: f64, lat: f64) -> Result<()> {
+    if (LON_MIN..=LON_MAX).contains(&lon) && (LAT_MIN..=LAT_MAX).contains(&lat) {
+        Ok(())
+    } else {
+        Err(Error::msg(format!(
+            "invalid longitude,latitude pair {:.6},{:.6}",
+            lon, lat
+        )))
+    }
+}
+
+/// Encode a point as a geohash of `steps` bits per coordinate, latitudes in the even bits.
+fn encode(lon: f64, lat: f64, steps: u32) -> u64 {
+    let cells = (1u64 << steps) as f64;
+    let cell = |value: f64, min: f64, max: f64| {
+        (((value - min) / (max - min) * cells) as u64).min((1 << steps) - 1) as u32
+    };
+    interleave(cell(lat, LAT_MIN, LAT_MAX), cell(lon, LON_MIN, LON_MAX))
+}
+
+/// The area covered by a geohash of `steps` bits per coordinate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cell {
+    lon: (f64, f64),
+    lat: (f64, f64),
+}
+
+impl Cell {
+    fn of(hash: u64, steps: u32) -> Self {
+        let (lat, lon) = deinterleave(hash);
+        let cells = (1u64 << steps) as f64;
+        let bounds = |value: u32, min: f64, max: f64| {
+            let width = (max - min) / cells;
+            (
+                min + value as f64 * width,
+                min + (value as f64 + 1.0) * width,
+            )
+        };
+        Cell {
+            lon: bounds(lon, LON_MIN, LON_MAX),
+            lat: bounds(lat, LAT_MIN, LAT_MAX),
+        }
+    }
+
+    fn center(&self) -> (f64, f64) {
+        (
+            ((self.lon.0 + self.lon.1) / 2.0).clamp(LON_MIN, LON_MAX),
+            ((self.lat.0 + self.lat.1) / 2.0).clamp(LAT_MIN, LAT_MAX),
+        )
+    }
+}
+
+/// Decode the score of a member into the center of its cell.
+pub fn decode(score: f64) -> (f64, f64) {
+    Cell::of(score as u64, STEPS).center()
+}
+
+/// The great-circle distance in meters between two points.
+pub fn distance(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lon1, lat1) = (from.0.to_radians(), from.1.to_radians());
+    let (lon2, lat2) = (to.0.to_radians(), to.1.to_radians());
+    let u = ((lat2 - lat1) / 2.0).sin();
+    let v = ((lon2 - lon1) / 2.0).sin();
+    2.0 * EARTH_RADIUS * (u * u + lat1.cos() * lat2.cos() * v * v).sqrt().asin()
+}
+
+/// A distance unit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    Meters,
+    Kilometers,
+    Feet,
+    Miles,
+}
+
+impl Unit {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "m" => Ok(Unit::Meters),
+            "km" => Ok(Unit::Kilometers),
+            "ft" => Ok(Unit::Feet),
+            "mi" => Ok(Unit::Miles),
+            _ => Err(Error::msg(
+                "unsupported unit provided. please use M, KM, FT, MI",
+            )),
+        }
+    }
+
+    /// The number of meters in one unit.
+    pub fn meters(&self) -> f64 {
+        match self {
+            Unit::Meters => 1.0,
+            Unit::Kilometers => 1000.0,
+            Unit::Feet => 0.3048,
+            Unit::Miles => 1609.34,
+        }
+    }
+}
+
+/// Where a search is centered.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Origin {
+    Member(String),
+    Point(f64, f64),
+}
+
+/// The area a search covers, in meters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Radius(f64),
+    Box { width: f64, height: f64 },
+}
+
+impl Shape {
+    /// The radius of the smallest circle around the shape.
+    fn radius(&self) -> f64 {
+        match *self {
+            Shape::Radius(radius) => radius,
+            Shape::Box { width, height } => (width / 2.0).hypot(height / 2.0),
+        }
+    }
+
+    /// Retrieve whether `point` is within the shape centered on `center`, with its distance.
+    fn contains(&self, center: (f64, f64), point: (f64, f64)) -> Option<f64> {
+        let distance = distance(center, point);
+        match *self {
+            Shape::Radius(radius) => (distance <= radius).then_some(distance),
+            Shape::Box { width, height } => {
+                // the distance along the meridian and along the parallel of the center
+                let lat_distance = self::distance(center, (center.0, point.1));
+                let lon_distance = self::distance(center, (point.0, center.1));
+                (lat_distance <= height / 2.0 && lon_distance <= width / 2.0).then_some(distance)
+            }
+        }
+    }
+}
+
+/// The options of GEOSEARCH.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Search {
+    pub origin: Origin,
+    pub shape: Shape,
+    /// Sort by distance, ascending when `true`.
+    pub ascending: Option<bool>,
+    pub count: Option<usize>,
+    /// Stop at the first `count` matches found instead of the `count` nearest.
+    pub any: bool,
+}
+
+/// A member found by a search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Found {
+    pub member: String,
+    /// Distance from the center, in meters.
+    pub distance: f64,
+    pub hash: u64,
+    pub lon: f64,
+    pub lat: f64,
+}
+
+/// Estimate the geohash precision whose cells are about as big as `radius`.
+fn estimate_steps(radius: f64, lat: f64) -> u32 {
+    if radius == 0.0 {
+        return STEPS;
+    }
+    let mut radius = radius;
+    let mut steps: i32 = 1;
+    while radius < MERCATOR_MAX {
+        radius *= 2.0;
+        steps += 1;
+    }
+    steps -= 2;
+
+    // cells get narrower towards the poles
+    if !(-66.0..=66.0).contains(&lat) {
+        steps -= 1;
+        if !(-80.0..=80.0).contains(&lat) {
+            steps -= 1;
+        }
+    }
+    steps.clamp(1, STEPS as i32) as u32
+}
+
+/// Retrieve the score ranges of the cells around `center` covering a circle of `radius`.
+fn covering_ranges(center: (f64, f64), radius: f64) -> Vec<ScoreRange> {
+    let (lon, lat) = center;
+    let lat_delta = (radius / EARTH_RADIUS).to_degrees();
+    let lon_delta = (radius / (EARTH_RADIUS * lat.to_radians().cos())).to_degrees();
+
+    // the center cell and its neighbours must cover the bounding box of the circle
+    let mut steps = estimate_steps(radius, lat);
+    let cell = loop {
+        let cell = Cell::of(encode(lon, lat, steps), steps);
+        let width = cell.lon.1 - cell.lon.0;
+        let height = cell.lat.1 - cell.lat.0;
+        let covered = lon - lon_delta >= cell.lon.0 - width
+            && lon + lon_delta <= cell.lon.1 + width
+            && lat - lat_delta >= cell.lat.0 - height
+            && lat + lat_delta <= cell.lat.1 + height;
+        if covered || steps == 1 {
+            break cell;
+        }
+        steps -= 1;
+    };
+
+    let width = cell.lon.1 - cell.lon.0;
+    let height = cell.lat.1 - cell.lat.0;
+    let (center_lon, center_lat) = cell.center();
+    let shift = 2 * (STEPS - steps);
+    let mut hashes = Vec::with_capacity(9);
+    for dlat in [-1.0, 0.0, 1.0] {
+        for dlon in [-1.0, 0.0, 1.0] {
+            let lat = center_lat + dlat * height;
+            if !(LAT_MIN..=LAT_MAX).contains(&lat) {
+                continue;
+            }
+            let mut lon = center_lon + dlon * width;
+            // wrap around the antimeridian
+            if lon > LON_MAX {
+                lon -= 360.0;
+            } else if lon < LON_MIN {
+                lon += 360.0;
+            }
+            hashes.push(encode(lon, lat, steps));
+        }
+    }
+    hashes.sort_unstable();
+    hashes.dedup();
+
+    hashes
+        .into_iter()
+        .map(|hash| {
+            let min = ScoreBound {
+                value: (hash << shift) as f64,
+                exclusive: false,
+            };
+            let max = ScoreBound {
+                value: ((hash + 1) << shift) as f64,
+                exclusive: true,
+            };
+            ScoreRange::new(min, max)
+        })
+        .collect()
+}
+
+/// Find the members of `set` within `search`.
+fn search_set(set: &SortedSet, search: &Search) -> Result<Vec<Found>> {
+    let center = match &search.origin {
+        Origin::Point(lon, lat) => (*lon, *lat),
+        Origin::Member(member) => match set.score(member) {
+            Some(score) => decode(score),
+            None => return Err(Error::msg("could not decode requested zset member")),
+        },
+    };
+
+    let mut found = Vec::new();
+    'ranges: for range in covering_ranges(center, search.shape.radius()) {
+        for (member, score) in set.range(&RangeBy::Score(range), false, &Limit::default()) {
+            let (lon, lat) = decode(score);
+            if let Some(distance) = search.shape.contains(center, (lon, lat)) {
+                found.push(Found {
+                    member,
+                    distance,
+                    hash: score as u64,
+                    lon,
+                    lat,
+                });
+                if search.any && Some(found.len()) == search.count {
+                    break 'ranges;
+                }
+            }
+        }
+    }
+
+    // the nearest members are needed to apply a count, even when unsorted
+    let ascending = match (search.ascending, search.count) {
+        (Some(ascending), _) => Some(ascending),
+        (None, Some(_)) if !search.any => Some(true),
+        (None, _) => None,
+    };
+    if let Some(ascending) = ascending {
+        found.sort_by(|a, b| {
+            let ordering = a
+                .distance
+                .partial_cmp(&b.distance)
+                .unwrap_or(Ordering::Equal);
+            if ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+    }
+    if let Some(count) = search.count {
+        found.truncate(count);
+    }
+    Ok(found)
+}
+
+impl Cache {
+    /// Add members at `(longitude, latitude, member)` to the geospatial index at `key`.
+    pub async fn geoadd(
+        &self,
+        key: String,
+        options: AddOptions,
+        points: Vec<Location>,
+    ) -> Result<Vec<AddOutcome>> {
+        let mut pairs = Vec::with_capacity(points.len());
+        for (lon, lat, member) in points {
+            validate(lon, lat)?;
+            pairs.push((encode(lon, lat, STEPS) as f64, member));
+        }
+        self.zadd(key, options, pairs).await
+    }
+
+    /// Retrieve the positions of `members` as `(longitude, latitude)`.
+    pub async fn geopos(
+        &self,
+        key: String,
+        members: Vec<String>,
+    ) -> Result<Vec<Option<(f64, f64)>>> {
+        let positions = self.read_object(&key, |object| {
+            let set = object.as_sorted_set()?;
+            Ok(members
+                .iter()
+                .map(|member| set.score(member).map(decode))
+                .collect())
+        })?;
+        Ok(positions.unwrap_or_else(|| vec![None; members.len()]))
+    }
+
+    /// Retrieve the distance in meters between two members, or `None` if either is missing.
+    pub async fn geodist(&self, key: String, from: String, to: String) -> Result<Option<f64>> {
+        let positions = self.geopos(key, vec![from, to]).await?;
+        match positions[..] {
+            [Some(from), Some(to)] => Ok(Some(distance(from, to))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Find the members of the geospatial index at `key` within the area of `search`.
+    pub async fn geosearch(&self, key: String, search: Search) -> Result<Vec<Found>> {
+        let found =
+            self.read_object(&key, |object| search_set(object.as_sorted_set()?, &search))?;
+        Ok(found.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sicily() -> Vec<(f64, f64, String)> {
+        vec![
+            (13.361389, 38.115556, "Palermo".to_string()),
+            (15.087269, 37.502669, "Catania".to_string()),
+            (12.758489, 38.788135, "edge1".to_string()),
+            (17.241510, 38.788135, "edge2".to_string()),
+        ]
+    }
+
+    fn search(origin: Origin, shape: Shape) -> Search {
+        Search {
+            origin,
+            shape,
+            ascending: Some(true),
+            count: None,
+            any: false,
+        }
+    }
+
+    fn members(found: &[Found]) -> Vec<&str> {
+        found.iter().map(|found| found.member.as_str()).collect()
+    }
+
+    #[test]
+    fn test_interleave() {
+        assert_eq!(interleave(0b11, 0b00), 0b0101);
+        assert_eq!(interleave(0b00, 0b11), 0b1010);
+        assert_eq!(deinterleave(interleave(12345, 67890)), (12345, 67890));
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let hash = encode(13.361389, 38.115556, STEPS);
+        // the score Redis gives to Palermo
+        assert_eq!(hash, 3479099956230698);
+        let (lon, lat) = decode(hash as f64);
+        assert!((lon - 13.361389).abs() < 1e-5);
+        assert!((lat - 38.115556).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_distance() {
+        let palermo = (13.361389, 38.115556);
+        let catania = (15.087269, 37.502669);
+        assert!((distance(palermo, catania) - 166274.1516).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(180.0, 85.0).is_ok());
+        assert!(validate(181.0, 0.0).is_err());
+        assert!(validate(0.0, 86.0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_geosearch() -> Result<()> {
+        let cache = Cache::default();
+        let added = cache
+            .geoadd("sicily".to_string(), AddOptions::default(), sicily())
+            .await?;
+        assert_eq!(added.len(), 4);
+
+        let by_radius = search(Origin::Point(15.0, 37.0), Shape::Radius(200_000.0));
+        let found = cache.geosearch("sicily".to_string(), by_radius).await?;
+        assert_eq!(members(&found), vec!["Catania", "Palermo"]);
+        assert!((found[0].distance - 56441.2573).abs() < 1.0);
+
+        let by_box = search(
+            Origin::Point(15.0, 37.0),
+            Shape::Box {
+                width: 400_000.0,
+                height: 400_000.0,
+            },
+        );
+        let found = cache.geosearch("sicily".to_string(), by_box).await?;
+        assert_eq!(
+            members(&found),
+            vec!["Catania", "Palermo", "edge2", "edge1"]
+        );
+
+        let mut nearest = search(
+            Origin::Member("Palermo".to_string()),
+            Shape::Radius(500_000.0),
+        );
+        nearest.ascending = None;
+        nearest.count = Some(2);
+        let found = cache.geosearch("sicily".to_string(), nearest).await?;
+        assert_eq!(members(&found), vec!["Palermo", "edge1"]);
+
+        let missing = search(Origin::Member("Rome".to_string()), Shape::Radius(1.0));
+        assert!(cache
+            .geosearch("sicily".to_string(), missing)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_small_radius() -> Result<()> {
+        let cache = Cache::default();
+        let couriers = (0..100)
+            .map(|i| {
+                let offset = i as f64 * 0.001;
+                (2.35 + offset, 48.85, format!("courier{}", i))
+            })
+            .collect();
+        cache
+            .geoadd("couriers".to_string(), AddOptions::default(), couriers)
+            .await?;
+
+        // about 73 meters between couriers at this latitude
+        let nearby = search(Origin::Point(2.35, 48.85), Shape::Radius(2950.0));
+        let found = cache.geosearch("couriers".to_string(), nearby).await?;
+        assert_eq!(found.len(), 41);
+        assert!(found.iter().all(|found| found.distance <= 2950.0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_geopos_geodist() -> Result<()> {
+        let cache = Cache::default();
+        cache
+            .geoadd("sicily".to_string(), AddOptions::default(), sicily())
+            .await?;
+
+        let members = vec!["Palermo".to_string(), "Rome".to_string()];
+        let positions = cache.geopos("sicily".to_string(), members).await?;
+        assert!(positions[0].is_some());
+        assert_eq!(positions[1], None);
+
+        let dist = cache
+            .geodist(
+                "sicily".to_string(),
+                "Palermo".to_string(),
+                "Catania".to_string(),
+            )
+            .await?;
+        assert!((dist.unwrap() - 166274.1516).abs() < 1.0);
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+/// Retrieve whether `string` matches the glob `pattern`, in which `*` matches any run of
+/// characters, `?` any single one, `[abc]`, `[^abc]` and `[a-z]` match classes, and `\`
+/// escapes the next character.
+pub fn matches(pattern: &str, string: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let string = string.chars().collect::<Vec<_>>();
+
+    let (mut p, mut s) = (0, 0);
+    // the pattern position after the last star, and how far into the string it reaches
+    let mut star: Option<(usize, usize)> = None;
+    while s < string.len() {
+        if pattern.get(p) == Some(&'*') {
+            star = Some((p + 1, s));
+            p += 1;
+            continue;
+        }
+        if let Some(next) = (p < pattern.len())
+            .then(|| single(&pattern, p, string[s]))
+            .flatten()
+        {
+            p = next;
+            s += 1;
+            continue;
+        }
+        // backtrack, letting the last star swallow one more character
+        match star {
+            Some((after, reach)) => {
+                star = Some((after, reach + 1));
+                p = after;
+                s = reach + 1;
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Retrieve the part of `pattern` before its first special character, which every match
+/// starts with.
+pub fn literal_prefix(pattern: &str) -> &str {
+    let end = pattern.find(['*', '?', '[', '\\']).unwrap_or(pattern.len());
+    &pattern[..end]
+}
+
+/// Match `c` against the pattern element at `p`, returning the position after the element.
+fn single(pattern: &[char], p: usize, c: char) -> Option<usize> {
+    match pattern[p] {
+        '?' => Some(p + 1),
+        '\\' if p + 1 < pattern.len() => (pattern[p + 1] == c).then_some(p + 2),
+        '[' => class(pattern, p + 1, c),
+        literal => (literal == c).then_some(p + 1),
+    }
+}
+
+/// Match `c` against the class starting at `p`, just after its `[`. An unterminated class
+/// runs to the end of the pattern.
+fn class(pattern: &[char], mut p: usize, c: char) -> Option<usize> {
+    let negated = pattern.get(p) == Some(&'^');
+    if negated {
+        p += 1;
+    }
+    let mut matched = false;
+    loop {
+        match pattern.get(p) {
+            None => return (matched != negated).then_some(p),
+            Some(']') => return (matched != negated).then_some(p + 1),
+            Some('\\') if p + 1 < pattern.len() => {
+                matched |= pattern[p + 1] == c;
+                p += 2;
+            }
+            Some(&low) if pattern.get(p + 1) == Some(&'-') && p + 2 < pattern.len() => {
+                let high = pattern[p + 2];
+                matched |= (low.min(high)..=low.max(high)).contains(&c);
+                p += 3;
+            }
+            Some(&other) => {
+                matched |= other == c;
+                p += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*", ""));
+        assert!(matches("user:*", "user:1"));
+        assert!(!matches("user:*", "users"));
+        assert!(matches("h?llo", "hello"));
+        assert!(!matches("h?llo", "hllo"));
+        assert!(matches("h*llo", "heeeello"));
+        assert!(matches("*:*:end", "a:b:c:end"));
+        assert!(matches("h[ae]llo", "hallo"));
+        assert!(!matches("h[^e]llo", "hello"));
+        assert!(matches("h[a-c]llo", "hbllo"));
+        assert!(matches("h[c-a]llo", "hbllo"));
+        assert!(matches("a\\*b", "a*b"));
+        assert!(!matches("a\\*b", "axb"));
+        assert!(matches("événement:*", "événement:1"));
+        assert!(!matches("abc", "abcd"));
+    }
+
+    #[test]
+    fn test_literal_prefix() {
+        assert_eq!(literal_prefix("events:2026-*"), "events:2026-");
+        assert_eq!(literal_prefix("a?c"), "a");
+        assert_eq!(literal_prefix("plain"), "plain");
+        assert_eq!(literal_prefix("*"), "");
+    }
+}
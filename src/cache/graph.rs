@@ -0,0 +1,573 @@
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, VecDeque};
+
+pub type Properties = BTreeMap<String, String>;
+
+/// A directed edge, weighing 1 unless given a weight.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edge {
+    pub weight: f64,
+    pub properties: Properties,
+}
+
+/// Which edges lead from a node to its neighbours.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Out,
+    In,
+    Both,
+}
+
+impl Direction {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "out" => Ok(Direction::Out),
+            "in" => Ok(Direction::In),
+            "both" => Ok(Direction::Both),
+            _ => Err(Error::msg("direction must be OUT, IN or BOTH")),
+        }
+    }
+}
+
+/// The order in which a traversal visits nodes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Traversal {
+    BreadthFirst,
+    DepthFirst,
+}
+
+/// A path cost ordered by `total_cmp`, so that it can be kept in a heap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cost(f64);
+
+impl Eq for Cost {}
+
+impl PartialOrd for Cost {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Cost {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// A property graph: nodes with properties, and weighted directed edges between them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Graph {
+    nodes: BTreeMap<String, Properties>,
+    outgoing: BTreeMap<String, BTreeMap<String, Edge>>,
+    incoming: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Graph::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&self, node: &str) -> Option<&Properties> {
+        self.nodes.get(node)
+    }
+
+    /// Add `node`, or set `properties` on it if it exists. Returns whether the node is new.
+    pub fn add_node(&mut self, node: String, properties: Properties) -> bool {
+        match self.nodes.get_mut(&node) {
+            Some(existing) => {
+                existing.extend(properties);
+                false
+            }
+            None => {
+                self.nodes.insert(node, properties);
+                true
+            }
+        }
+    }
+
+    /// Add the edge from `from` to `to`, or replace it, adding the missing nodes. Returns
+    /// whether the edge is new.
+    pub fn add_edge(&mut self, from: String, to: String, edge: Edge) -> Result<bool> {
+        if !edge.weight.is_finite() || edge.weight < 0.0 {
+            return Err(Error::msg("weight must be a non-negative number"));
+        }
+        self.add_node(from.clone(), Properties::new());
+        self.add_node(to.clone(), Properties::new());
+        self.incoming
+            .entry(to.clone())
+            .or_default()
+            .insert(from.clone());
+        let edges = self.outgoing.entry(from).or_default();
+        Ok(edges.insert(to, edge).is_none())
+    }
+
+    /// Remove the edge from `from` to `to`, returning whether it existed.
+    pub fn remove_edge(&mut self, from: &str, to: &str) -> bool {
+        let removed = match self.outgoing.get_mut(from) {
+            Some(edges) => edges.remove(to).is_some(),
+            None => false,
+        };
+        if removed {
+            if self.outgoing[from].is_empty() {
+                self.outgoing.remove(from);
+            }
+            let sources = self.incoming.get_mut(to).unwrap();
+            sources.remove(from);
+            if sources.is_empty() {
+                self.incoming.remove(to);
+            }
+        }
+        removed
+    }
+
+    /// Remove `node` and its edges, returning whether it existed.
+    pub fn remove_node(&mut self, node: &str) -> bool {
+        if self.nodes.remove(node).is_none() {
+            return false;
+        }
+        let targets = self
+            .outgoing
+            .get(node)
+            .map(|edges| edges.keys().cloned().collect::<Vec<_>>());
+        for to in targets.unwrap_or_default() {
+            self.remove_edge(node, &to);
+        }
+        let sources = self
+            .incoming
+            .get(node)
+            .map(|sources| sources.iter().cloned().collect::<Vec<_>>());
+        for from in sources.unwrap_or_default() {
+            self.remove_edge(&from, node);
+        }
+        true
+    }
+
+    /// The neighbours of `node` in `direction` and the weights of the edges to them, the
+    /// lightest edge counting for nodes linked both ways.
+    fn adjacent(&self, node: &str, direction: Direction) -> BTreeMap<&String, f64> {
+        let mut adjacent = BTreeMap::new();
+        if direction != Direction::In {
+            for (to, edge) in self.outgoing.get(node).into_iter().flatten() {
+                adjacent.insert(to, edge.weight);
+            }
+        }
+        if direction != Direction::Out {
+            for from in self.incoming.get(node).into_iter().flatten() {
+                let weight = self.outgoing[from][node].weight;
+                let lightest = adjacent.entry(from).or_insert(weight);
+                *lightest = lightest.min(weight);
+            }
+        }
+        adjacent
+    }
+
+    /// The neighbours of `node` in `direction`, in order.
+    pub fn neighbours(&self, node: &str, direction: Direction) -> Vec<String> {
+        self.adjacent(node, direction)
+            .into_keys()
+            .cloned()
+            .collect()
+    }
+
+    /// Visit the nodes up to `depth` edges away from `start` in `direction`, returning them in
+    /// the order they are reached with their distance in edges.
+    pub fn traverse(
+        &self,
+        start: &str,
+        traversal: Traversal,
+        depth: usize,
+        direction: Direction,
+    ) -> Vec<(String, usize)> {
+        let Some((start, _)) = self.nodes.get_key_value(start) else {
+            return Vec::new();
+        };
+        let mut visited: Vec<(&String, usize)> = Vec::new();
+        let mut positions: HashMap<&String, usize> = HashMap::new();
+        let mut pending = VecDeque::from([(start, 0)]);
+        while let Some((node, distance)) = match traversal {
+            Traversal::BreadthFirst => pending.pop_front(),
+            Traversal::DepthFirst => pending.pop_back(),
+        } {
+            // depth first may reach a node again by a shorter path, which must be expanded
+            // again for the nodes behind it to be within reach
+            match positions.get(node) {
+                Some(&i) if visited[i].1 <= distance => continue,
+                Some(&i) => visited[i].1 = distance,
+                None => {
+                    positions.insert(node, visited.len());
+                    visited.push((node, distance));
+                }
+            }
+            if distance == depth {
+                continue;
+            }
+            let neighbours = self.adjacent(node, direction).into_keys();
+            match traversal {
+                Traversal::BreadthFirst => pending.extend(neighbours.map(|n| (n, distance + 1))),
+                Traversal::DepthFirst => {
+                    pending.extend(neighbours.rev().map(|n| (n, distance + 1)))
+                }
+            }
+        }
+        visited
+            .into_iter()
+            .map(|(node, distance)| (node.clone(), distance))
+            .collect()
+    }
+
+    /// Find the lightest path from `from` to `to` following edges in `direction`, returning
+    /// its weight and its nodes.
+    pub fn shortest_path(
+        &self,
+        from: &str,
+        to: &str,
+        direction: Direction,
+    ) -> Option<(f64, Vec<String>)> {
+        let (from, _) = self.nodes.get_key_value(from)?;
+        let mut costs: HashMap<&String, f64> = HashMap::from([(from, 0.0)]);
+        let mut previous: HashMap<&String, &String> = HashMap::new();
+        let mut heap = BinaryHeap::from([Reverse((Cost(0.0), from))]);
+        while let Some(Reverse((Cost(cost), node))) = heap.pop() {
+            if node == to {
+                let mut path = vec![node.clone()];
+                let mut current = node;
+                while let Some(&before) = previous.get(current) {
+                    path.push(before.clone());
+                    current = before;
+                }
+                path.reverse();
+                return Some((cost, path));
+            }
+            if cost > costs[node] {
+                continue;
+            }
+            for (next, weight) in self.adjacent(node, direction) {
+                let cost = cost + weight;
+                if costs.get(next).is_none_or(|&known| cost < known) {
+                    costs.insert(next, cost);
+                    previous.insert(next, node);
+                    heap.push(Reverse((Cost(cost), next)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Retrieve an estimate of the memory used by the nodes and edges, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        fn properties(properties: &Properties) -> usize {
+            properties.iter().map(|(k, v)| k.len() + v.len()).sum()
+        }
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|(node, props)| node.len() + properties(props))
+            .sum::<usize>();
+        let edges = self
+            .outgoing
+            .values()
+            .flatten()
+            .map(|(to, edge)| {
+                2 * to.len() + std::mem::size_of::<Edge>() + properties(&edge.properties)
+            })
+            .sum::<usize>();
+        nodes + edges
+    }
+}
+
+impl Cache {
+    /// Add `node` to the graph at `key`, creating it if missing, or set `properties` on it.
+    /// Returns whether the node is new.
+    pub async fn graph_addnode(
+        &self,
+        key: String,
+        node: String,
+        properties: Properties,
+    ) -> Result<bool> {
+        let added = self.write_object(
+            &key,
+            || Some(Graph::new().into()),
+            |object| Ok(object.as_graph_mut()?.add_node(node, properties)),
+        )?;
+        Ok(added.unwrap_or(false))
+    }
+
+    /// Add an edge to the graph at `key`, creating it if missing. Returns whether the edge
+    /// is new.
+    pub async fn graph_addedge(
+        &self,
+        key: String,
+        from: String,
+        to: String,
+        edge: Edge,
+    ) -> Result<bool> {
+        let added = self.write_object(
+            &key,
+            || Some(Graph::new().into()),
+            |object| object.as_graph_mut()?.add_edge(from, to, edge),
+        )?;
+        Ok(added.unwrap_or(false))
+    }
+
+    /// Remove `node` and its edges from the graph at `key`, returning whether it existed.
+    pub async fn graph_delnode(&self, key: String, node: String) -> Result<bool> {
+        let removed =
+            self.shrink_object(&key, |object| Ok(object.as_graph_mut()?.remove_node(&node)))?;
+        Ok(removed.unwrap_or(false))
+    }
+
+    /// Remove an edge from the graph at `key`, returning whether it existed.
+    pub async fn graph_deledge(&self, key: String, from: String, to: String) -> Result<bool> {
+        let removed = self.shrink_object(&key, |object| {
+            Ok(object.as_graph_mut()?.remove_edge(&from, &to))
+        })?;
+        Ok(removed.unwrap_or(false))
+    }
+
+    /// Retrieve the properties of `node` in the graph at `key`.
+    pub async fn graph_getnode(&self, key: String, node: String) -> Result<Option<Properties>> {
+        let properties =
+            self.read_object(&key, |object| Ok(object.as_graph()?.node(&node).cloned()))?;
+        Ok(properties.flatten())
+    }
+
+    pub async fn graph_neighbours(
+        &self,
+        key: String,
+        node: String,
+        direction: Direction,
+    ) -> Result<Vec<String>> {
+        let neighbours = self.read_object(&key, |object| {
+            Ok(object.as_graph()?.neighbours(&node, direction))
+        })?;
+        Ok(neighbours.unwrap_or_default())
+    }
+
+    /// Visit the graph at `key` from `start`, see [`Graph::traverse`].
+    pub async fn graph_traverse(
+        &self,
+        key: String,
+        start: String,
+        traversal: Traversal,
+        depth: usize,
+        direction: Direction,
+    ) -> Result<Vec<(String, usize)>> {
+        let visited = self.read_object(&key, |object| {
+            Ok(object
+                .as_graph()?
+                .traverse(&start, traversal, depth, direction))
+        })?;
+        Ok(visited.unwrap_or_default())
+    }
+
+    /// Find the lightest path between two nodes of the graph at `key`.
+    pub async fn graph_shortest_path(
+        &self,
+        key: String,
+        from: String,
+        to: String,
+        direction: Direction,
+    ) -> Result<Option<(f64, Vec<String>)>> {
+        let path = self.read_object(&key, |object| {
+            Ok(object.as_graph()?.shortest_path(&from, &to, direction))
+        })?;
+        Ok(path.flatten())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::expiry::Expiry;
+    use std::time::Duration;
+
+    fn edge(weight: f64) -> Edge {
+        Edge {
+            weight,
+            properties: Properties::new(),
+        }
+    }
+
+    /// ceo -> cto -> dev1, cto -> dev2, ceo -> cfo, and a lateral dev2 -> dev1.
+    fn org() -> Result<Graph> {
+        let mut graph = Graph::new();
+        for (from, to) in [
+            ("ceo", "cto"),
+            ("ceo", "cfo"),
+            ("cto", "dev1"),
+            ("cto", "dev2"),
+            ("dev2", "dev1"),
+        ] {
+            graph.add_edge(from.to_string(), to.to_string(), edge(1.0))?;
+        }
+        Ok(graph)
+    }
+
+    fn visit(names: &[(&str, usize)]) -> Vec<(String, usize)> {
+        names
+            .iter()
+            .map(|(name, distance)| (name.to_string(), *distance))
+            .collect()
+    }
+
+    #[test]
+    fn test_nodes_and_edges() -> Result<()> {
+        let mut graph = org()?;
+        assert_eq!(graph.nodes.len(), 5);
+        assert!(!graph.add_node(
+            "ceo".to_string(),
+            Properties::from([("name".to_string(), "Ada".to_string())])
+        ));
+        assert_eq!(graph.node("ceo").unwrap()["name"], "Ada");
+        assert!(graph
+            .add_edge("a".to_string(), "b".to_string(), edge(-1.0))
+            .is_err());
+
+        assert_eq!(
+            graph.neighbours("cto", Direction::Out),
+            vec!["dev1", "dev2"]
+        );
+        assert_eq!(graph.neighbours("dev1", Direction::In), vec!["cto", "dev2"]);
+        assert_eq!(
+            graph.neighbours("cto", Direction::Both),
+            vec!["ceo", "dev1", "dev2"]
+        );
+
+        assert!(graph.remove_edge("dev2", "dev1"));
+        assert!(!graph.remove_edge("dev2", "dev1"));
+        assert_eq!(graph.neighbours("dev1", Direction::In), vec!["cto"]);
+
+        assert!(graph.remove_node("cto"));
+        assert!(!graph.outgoing["ceo"].contains_key("cto"));
+        assert!(graph.neighbours("dev1", Direction::In).is_empty());
+        assert!(!graph.incoming.contains_key("dev1"));
+        assert_eq!(graph.nodes.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_traverse() -> Result<()> {
+        let graph = org()?;
+        assert_eq!(
+            graph.traverse("ceo", Traversal::BreadthFirst, 5, Direction::Out),
+            visit(&[("ceo", 0), ("cfo", 1), ("cto", 1), ("dev1", 2), ("dev2", 2)])
+        );
+        assert_eq!(
+            graph.traverse("ceo", Traversal::DepthFirst, 5, Direction::Out),
+            visit(&[("ceo", 0), ("cfo", 1), ("cto", 1), ("dev1", 2), ("dev2", 2)])
+        );
+        assert_eq!(
+            graph.traverse("ceo", Traversal::BreadthFirst, 1, Direction::Out),
+            visit(&[("ceo", 0), ("cfo", 1), ("cto", 1)])
+        );
+        // walking up from a developer reaches the managers above
+        assert_eq!(
+            graph.traverse("dev1", Traversal::BreadthFirst, 5, Direction::In),
+            visit(&[("dev1", 0), ("cto", 1), ("dev2", 1), ("ceo", 2)])
+        );
+        assert!(graph
+            .traverse("nobody", Traversal::DepthFirst, 5, Direction::Out)
+            .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_depth_first_shorter_path() -> Result<()> {
+        // a -> b -> c -> d and a -> c: depth first goes through b first, yet d is 2 edges away
+        let mut graph = Graph::new();
+        for (from, to) in [("a", "b"), ("b", "c"), ("c", "d"), ("a", "c")] {
+            graph.add_edge(from.to_string(), to.to_string(), edge(1.0))?;
+        }
+        assert_eq!(
+            graph.traverse("a", Traversal::DepthFirst, 2, Direction::Out),
+            visit(&[("a", 0), ("b", 1), ("c", 1), ("d", 2)])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_shortest_path() -> Result<()> {
+        let mut graph = Graph::new();
+        for (from, to, weight) in [
+            ("a", "b", 1.0),
+            ("b", "c", 1.0),
+            ("a", "c", 5.0),
+            ("c", "d", 1.0),
+        ] {
+            graph.add_edge(from.to_string(), to.to_string(), edge(weight))?;
+        }
+        let path = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            graph.shortest_path("a", "d", Direction::Out),
+            Some((3.0, path(&["a", "b", "c", "d"])))
+        );
+        assert_eq!(graph.shortest_path("d", "a", Direction::Out), None);
+        assert_eq!(
+            graph.shortest_path("d", "a", Direction::In),
+            Some((3.0, path(&["d", "c", "b", "a"])))
+        );
+        assert_eq!(
+            graph.shortest_path("a", "a", Direction::Out),
+            Some((0.0, path(&["a"])))
+        );
+        assert_eq!(graph.shortest_path("x", "a", Direction::Out), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_graph_commands() -> Result<()> {
+        let cache = Cache::default();
+        let key = || "org".to_string();
+        assert!(
+            cache
+                .graph_addnode(key(), "ceo".to_string(), Properties::new())
+                .await?
+        );
+        assert!(
+            cache
+                .graph_addedge(key(), "ceo".to_string(), "cto".to_string(), edge(1.0))
+                .await?
+        );
+        assert_eq!(
+            cache
+                .graph_neighbours(key(), "ceo".to_string(), Direction::Out)
+                .await?,
+            vec!["cto"]
+        );
+        assert!(cache
+            .graph_getnode(key(), "cfo".to_string())
+            .await?
+            .is_none());
+
+        // the graph expires as a whole
+        cache
+            .expire(key(), Expiry::after(Duration::from_millis(1))?)
+            .await;
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(cache
+            .graph_traverse(
+                key(),
+                "ceo".to_string(),
+                Traversal::BreadthFirst,
+                3,
+                Direction::Out
+            )
+            .await?
+            .is_empty());
+
+        assert!(
+            cache
+                .graph_addnode(key(), "a".to_string(), Properties::new())
+                .await?
+        );
+        assert!(cache.graph_delnode(key(), "a".to_string()).await?);
+        assert!(!cache.exists(key()).await);
+        Ok(())
+    }
+}
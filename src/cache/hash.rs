@@ -0,0 +1,244 @@
+use crate::cache::members::Members;
+use crate::cache::Cache;
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+/// A map of fields to string values, iterated in field order. Fields may expire on their own.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Hash {
+    fields: Members<String>,
+}
+
+impl Hash {
+    pub fn new() -> Self {
+        Hash::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn get(&self, field: &str) -> Option<&String> {
+        self.fields.get(field)
+    }
+
+    /// Set `field` to `value`, dropping any expiry it had. Returns whether the field is new.
+    pub fn set(&mut self, field: String, value: String) -> bool {
+        self.fields.insert(field, value)
+    }
+
+    /// Remove `field`, returning whether it existed.
+    pub fn remove(&mut self, field: &str) -> bool {
+        self.fields.remove(field)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.fields.iter()
+    }
+
+    pub fn fields(&self) -> &Members<String> {
+        &self.fields
+    }
+
+    pub fn fields_mut(&mut self) -> &mut Members<String> {
+        &mut self.fields
+    }
+
+    /// Retrieve an estimate of the memory used by the fields, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.iter()
+            .map(|(field, value)| field.len() + value.len() + 2 * std::mem::size_of::<String>())
+            .sum()
+    }
+}
+
+impl Cache {
+    /// Set `fields` of the hash at `key`, creating it if missing. Returns how many fields
+    /// were added.
+    pub async fn hset(&self, key: String, fields: Vec<(String, String)>) -> Result<usize> {
+        let added = self.write_object(
+            &key,
+            || Some(Hash::new().into()),
+            |object| {
+                let hash = object.as_hash_mut()?;
+                Ok(fields
+                    .into_iter()
+                    .filter(|(field, value)| hash.set(field.clone(), value.clone()))
+                    .count())
+            },
+        )?;
+        Ok(added.unwrap_or(0))
+    }
+
+    /// Retrieve the values of `fields` of the hash at `key`.
+    pub async fn hmget(&self, key: String, fields: Vec<String>) -> Result<Vec<Option<String>>> {
+        let values = self.read_object(&key, |object| {
+            let hash = object.as_hash()?;
+            Ok(fields
+                .iter()
+                .map(|field| hash.get(field).cloned())
+                .collect())
+        })?;
+        Ok(values.unwrap_or_else(|| vec![None; fields.len()]))
+    }
+
+    /// Remove `fields` from the hash at `key`, returning how many existed.
+    pub async fn hdel(&self, key: String, fields: Vec<String>) -> Result<usize> {
+        let removed = self.shrink_object(&key, |object| {
+            let hash = object.as_hash_mut()?;
+            Ok(fields.iter().filter(|field| hash.remove(field)).count())
+        })?;
+        Ok(removed.unwrap_or(0))
+    }
+
+    /// Retrieve the fields and values of the hash at `key`.
+    pub async fn hgetall(&self, key: String) -> Result<Vec<(String, String)>> {
+        let fields = self.read_object(&key, |object| {
+            let hash = object.as_hash()?;
+            Ok(hash
+                .iter()
+                .map(|(field, value)| (field.clone(), value.clone()))
+                .collect())
+        })?;
+        Ok(fields.unwrap_or_default())
+    }
+
+    pub async fn hlen(&self, key: String) -> Result<usize> {
+        let len = self.read_object(&key, |object| Ok(object.as_hash()?.len()))?;
+        Ok(len.unwrap_or(0))
+    }
+
+    /// Expire `fields` of the hash at `key` at `instant`. For every field, returns `None` if
+    /// it is missing, or whether it is still there otherwise.
+    pub async fn hexpire(
+        &self,
+        key: String,
+        fields: Vec<String>,
+        instant: Instant,
+    ) -> Result<Vec<Option<bool>>> {
+        let expired = self.shrink_object(&key, |object| {
+            let members = object.as_hash_mut()?.fields_mut();
+            Ok(fields
+                .iter()
+                .map(|field| members.expire(field, instant))
+                .collect())
+        })?;
+        Ok(expired.unwrap_or_else(|| vec![None; fields.len()]))
+    }
+
+    /// Retrieve the time left before `fields` of the hash at `key` expire.
+    pub async fn httl(
+        &self,
+        key: String,
+        fields: Vec<String>,
+    ) -> Result<Vec<Option<Option<Duration>>>> {
+        let remaining = self.read_object(&key, |object| {
+            let hash = object.as_hash()?;
+            Ok(fields
+                .iter()
+                .map(|field| hash.fields().ttl(field))
+                .collect())
+        })?;
+        Ok(remaining.unwrap_or_else(|| vec![None; fields.len()]))
+    }
+
+    /// Drop the expiry of `fields` of the hash at `key`. For every field, returns `None` if it
+    /// is missing, or whether it had an expiry otherwise.
+    pub async fn hpersist(&self, key: String, fields: Vec<String>) -> Result<Vec<Option<bool>>> {
+        let persisted = self.shrink_object(&key, |object| {
+            let members = object.as_hash_mut()?.fields_mut();
+            Ok(fields.iter().map(|field| members.persist(field)).collect())
+        })?;
+        Ok(persisted.unwrap_or_else(|| vec![None; fields.len()]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(field, value)| (field.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_hash_commands() -> Result<()> {
+        let cache = Cache::default();
+        let key = || "user:1".to_string();
+        let added = cache
+            .hset(key(), pairs(&[("name", "Ada"), ("age", "36")]))
+            .await?;
+        assert_eq!(added, 2);
+        let added = cache
+            .hset(key(), pairs(&[("age", "37"), ("city", "London")]))
+            .await?;
+        assert_eq!(added, 1);
+
+        let values = cache
+            .hmget(key(), vec!["age".to_string(), "missing".to_string()])
+            .await?;
+        assert_eq!(values, vec![Some("37".to_string()), None]);
+        assert_eq!(cache.hlen(key()).await?, 3);
+        assert_eq!(
+            cache.hgetall(key()).await?,
+            pairs(&[("age", "37"), ("city", "London"), ("name", "Ada")])
+        );
+
+        let fields = vec!["age".to_string(), "city".to_string(), "name".to_string()];
+        assert_eq!(cache.hdel(key(), fields).await?, 3);
+        assert!(!cache.exists(key()).await);
+
+        cache.set("plain".to_string(), "value".to_string()).await?;
+        assert!(cache.hlen("plain".to_string()).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_field_expiry() -> Result<()> {
+        let cache = Cache::new(10, 0.5);
+        let key = || "sessions:ada".to_string();
+        let fields = |fields: &[&str]| fields.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        cache
+            .hset(key(), pairs(&[("phone", "t1"), ("laptop", "t2")]))
+            .await?;
+        cache
+            .hset("plain".to_string(), pairs(&[("a", "b")]))
+            .await?;
+
+        // every device's session expires on its own
+        let soon = Instant::now() + Duration::from_millis(20);
+        let later = Instant::now() + Duration::from_millis(60);
+        let expired = cache
+            .hexpire(key(), fields(&["phone", "tablet"]), soon)
+            .await?;
+        assert_eq!(expired, vec![Some(true), None]);
+        cache.hexpire(key(), fields(&["laptop"]), later).await?;
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(cache.hgetall(key()).await?, pairs(&[("laptop", "t2")]));
+        assert_eq!(cache.hlen(key()).await?, 1);
+        assert_eq!(
+            cache.httl(key(), fields(&["phone", "laptop"])).await?[0],
+            None
+        );
+
+        // the purge loop drops the expired fields, and then the emptied hash
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        cache.purge().await;
+        assert!(!cache.exists(key()).await);
+        assert_eq!(cache.len().await, 1);
+
+        assert_eq!(
+            cache.hpersist("plain".to_string(), fields(&["a"])).await?,
+            vec![Some(false)]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,465 @@
+use crate::cache::entry::Entry;
+use crate::cache::Cache;
+use anyhow::Result;
+
+/// Number of bits of the hash selecting a register, giving a standard error of
+/// `1.04 / sqrt(2^14)`, about 0.81%.
+const PRECISION: u32 = 14;
+const REGISTERS: usize = 1 << PRECISION;
+/// Number of hash bits left to count the run of zeroes in.
+const Q: u32 = 64 - PRECISION;
+const REGISTER_BITS: usize = 6;
+const REGISTER_MAX: u8 = (1 << REGISTER_BITS) - 1;
+/// A sparse sketch turns dense past this many registers, when it would be about as big.
+const SPARSE_MAX: usize = 3000 / 3;
+
+/// The MurmurHash64A hash of `data`, the hash function Redis uses for HyperLogLogs.
+pub(crate) fn murmur_hash64a(data: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4a7935bd1e995;
+    const R: u32 = 47;
+
+    let mut h = seed ^ (data.len() as u64).wrapping_mul(M);
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+/// Hash `element` into the index of its register and the length of the run of zeroes it
+/// counts, plus one.
+fn register_of(element: &[u8]) -> (usize, u8) {
+    let hash = murmur_hash64a(element, 0xadc83b19);
+    let index = (hash & (REGISTERS as u64 - 1)) as usize;
+    // the sentinel bit bounds the run to Q zeroes
+    let rest = (hash >> PRECISION) | (1 << Q);
+    (index, rest.trailing_zeros() as u8 + 1)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Registers {
+    /// The non zero registers only, sorted by index.
+    Sparse(Vec<(u16, u8)>),
+    /// Every register, packed in 6 bits each.
+    Dense(Vec<u8>),
+}
+
+fn dense_get(bytes: &[u8], index: usize) -> u8 {
+    let bit = index * REGISTER_BITS;
+    let (byte, shift) = (bit / 8, bit % 8);
+    let low = bytes[byte] as u16;
+    let high = bytes.get(byte + 1).copied().unwrap_or(0) as u16;
+    (((low | high << 8) >> shift) as u8) & REGISTER_MAX
+}
+
+fn dense_set(bytes: &mut [u8], index: usize, value: u8) {
+    let bit = index * REGISTER_BITS;
+    let (byte, shift) = (bit / 8, bit % 8);
+    let mask = (REGISTER_MAX as u16) << shift;
+    let value = (value as u16) << shift;
+    bytes[byte] = (bytes[byte] & !(mask as u8)) | value as u8;
+    if shift + REGISTER_BITS > 8 {
+        let high = (mask >> 8) as u8;
+        bytes[byte + 1] = (bytes[byte + 1] & !high) | (value >> 8) as u8;
+    }
+}
+
+/// The correction of the estimator for registers at zero, from Ertl's "New cardinality
+/// estimation algorithms for HyperLogLog sketches".
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if previous == z {
+            return z;
+        }
+    }
+}
+
+/// The correction of the estimator for registers at their maximum, from the same paper.
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if previous == z {
+            return z / 3.0;
+        }
+    }
+}
+
+/// A HyperLogLog sketch, estimating the number of distinct elements added to it.
+///
+/// Small sketches keep their few non zero registers in a sparse encoding, and switch to a
+/// dense one of 12KB once that saves memory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HyperLogLog {
+    registers: Registers,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        HyperLogLog {
+            registers: Registers::Sparse(Vec::new()),
+        }
+    }
+}
+
+impl HyperLogLog {
+    pub fn new() -> Self {
+        HyperLogLog::default()
+    }
+
+    /// Retrieve whether the sketch uses the sparse encoding.
+    #[cfg(test)]
+    pub fn is_sparse(&self) -> bool {
+        matches!(self.registers, Registers::Sparse(_))
+    }
+
+    /// Add an element, returning whether the sketch changed.
+    pub fn add(&mut self, element: &[u8]) -> bool {
+        let (index, value) = register_of(element);
+        self.raise(index, value)
+    }
+
+    /// Raise a register to `value`, returning whether it was lower.
+    fn raise(&mut self, index: usize, value: u8) -> bool {
+        match &mut self.registers {
+            Registers::Dense(bytes) => {
+                if dense_get(bytes, index) >= value {
+                    return false;
+                }
+                dense_set(bytes, index, value);
+            }
+            Registers::Sparse(registers) => {
+                match registers.binary_search_by_key(&(index as u16), |(index, _)| *index) {
+                    Ok(i) if registers[i].1 >= value => return false,
+                    Ok(i) => registers[i].1 = value,
+                    Err(i) => registers.insert(i, (index as u16, value)),
+                }
+                if registers.len() > SPARSE_MAX {
+                    self.densify();
+                }
+            }
+        }
+        true
+    }
+
+    fn densify(&mut self) {
+        if let Registers::Sparse(registers) = &self.registers {
+            let mut bytes = vec![0; (REGISTERS * REGISTER_BITS).div_ceil(8)];
+            for (index, value) in registers {
+                dense_set(&mut bytes, *index as usize, *value);
+            }
+            self.registers = Registers::Dense(bytes);
+        }
+    }
+
+    /// Iterate over the non zero registers as `(index, value)`.
+    fn registers(&self) -> Box<dyn Iterator<Item = (usize, u8)> + '_> {
+        match &self.registers {
+            Registers::Sparse(registers) => Box::new(
+                registers
+                    .iter()
+                    .map(|(index, value)| (*index as usize, *value)),
+            ),
+            Registers::Dense(bytes) => Box::new(
+                (0..REGISTERS)
+                    .map(|index| (index, dense_get(bytes, index)))
+                    .filter(|(_, value)| *value > 0),
+            ),
+        }
+    }
+
+    /// Merge another sketch into this one, which then counts the union of both.
+    pub fn merge(&mut self, other: &HyperLogLog) -> bool {
+        let mut changed = false;
+        for (index, value) in other.registers() {
+            changed |= self.raise(index, value);
+        }
+        changed
+    }
+
+    /// Estimate the number of distinct elements added.
+    pub fn count(&self) -> u64 {
+        let mut histogram = [0u32; Q as usize + 2];
+        let mut non_zero = 0;
+        for (_, value) in self.registers() {
+            histogram[value as usize] += 1;
+            non_zero += 1;
+        }
+        histogram[0] = (REGISTERS - non_zero) as u32;
+
+        let m = REGISTERS as f64;
+        let mut z = m * tau((m - histogram[Q as usize + 1] as f64) / m);
+        for k in (1..=Q as usize).rev() {
+            z = 0.5 * (z + histogram[k] as f64);
+        }
+        z += m * sigma(histogram[0] as f64 / m);
+        let alpha = 0.5 / std::f64::consts::LN_2;
+        (alpha * m * m / z).round() as u64
+    }
+
+    /// Retrieve an estimate of the memory used by the registers, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        match &self.registers {
+            Registers::Sparse(registers) => registers.len() * std::mem::size_of::<(u16, u8)>(),
+            Registers::Dense(registers) => registers.len(),
+        }
+    }
+}
+
+impl Cache {
+    /// Add `elements` to the sketch at `key`, creating it if needed. Returns whether the
+    /// sketch was created or changed.
+    pub async fn pfadd(&self, key: String, elements: Vec<String>) -> Result<bool> {
+        let mut created = false;
+        let changed = self.write_object(
+            &key,
+            || {
+                created = true;
+                Some(HyperLogLog::new().into())
+            },
+            |object| {
+                let sketch = object.as_hyperloglog_mut()?;
+                let mut changed = false;
+                for element in &elements {
+                    changed |= sketch.add(element.as_bytes());
+                }
+                Ok(changed)
+            },
+        )?;
+        Ok(created || changed.unwrap_or(false))
+    }
+
+    /// Estimate the number of distinct elements in the union of the sketches at `keys`.
+    pub async fn pfcount(&self, keys: Vec<String>) -> Result<u64> {
+        let store = self.store.read().unwrap();
+        let mut union: Option<HyperLogLog> = None;
+        for key in &keys {
+            let sketch = match store.get(key) {
+                Some(entry) if !entry.expiration().is_expired() => {
+                    entry.value().as_hyperloglog()?
+                }
+                _ => continue,
+            };
+            match union.as_mut() {
+                Some(union) => {
+                    union.merge(sketch);
+                }
+                None => union = Some(sketch.clone()),
+            }
+        }
+        Ok(union.map(|union| union.count()).unwrap_or(0))
+    }
+
+    /// Merge the sketches at `keys` into the one at `destination`, creating it if needed. An
+    /// existing destination keeps its expiry.
+    pub async fn pfmerge(&self, destination: String, keys: Vec<String>) -> Result<()> {
+        let mut store = self.store.write().unwrap();
+        self.evict_if_expired(&mut store, &destination);
+        for key in &keys {
+            self.evict_if_expired(&mut store, key);
+        }
+
+        let mut union = match store.get(&destination) {
+            Some(entry) => entry.value().as_hyperloglog()?.clone(),
+            None => HyperLogLog::new(),
+        };
+        for key in &keys {
+            if let Some(entry) = store.get(key) {
+                union.merge(entry.value().as_hyperloglog()?);
+            }
+        }
+
+        self.admit(&mut store, &destination)?;
+        let before = self.footprint(&store, &destination);
+        match store.get_mut(&destination) {
+            Some(entry) => *entry.value_mut() = union.into(),
+            None => {
+                let entry = Entry::new(union, self.default_expiry());
+                store.insert(destination.clone(), entry);
+            }
+        }
+        self.charge(self.footprint(&store, &destination).saturating_sub(before));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::expiry::Expiry;
+    use crate::cache::quota::Quota;
+    use std::sync::atomic::Ordering;
+    use std::time::{Duration, Instant};
+
+    fn sketch(elements: std::ops::Range<u32>) -> HyperLogLog {
+        let mut sketch = HyperLogLog::new();
+        for element in elements {
+            sketch.add(element.to_string().as_bytes());
+        }
+        sketch
+    }
+
+    fn assert_close(estimate: u64, exact: u64) {
+        // four standard errors
+        let error = (estimate as f64 - exact as f64).abs() / exact as f64;
+        assert!(error < 4.0 * 0.0081, "estimate {} for {}", estimate, exact);
+    }
+
+    #[test]
+    fn test_murmur_hash64a() {
+        assert_eq!(murmur_hash64a(b"", 0), 0);
+        assert_ne!(murmur_hash64a(b"a", 0), murmur_hash64a(b"b", 0));
+        assert_ne!(murmur_hash64a(b"hello", 0), murmur_hash64a(b"hello", 1));
+    }
+
+    #[test]
+    fn test_dense_registers() {
+        let mut bytes = vec![0; (REGISTERS * REGISTER_BITS).div_ceil(8)];
+        dense_set(&mut bytes, 1, 63);
+        dense_set(&mut bytes, 2, 5);
+        dense_set(&mut bytes, REGISTERS - 1, 42);
+        assert_eq!(dense_get(&bytes, 0), 0);
+        assert_eq!(dense_get(&bytes, 1), 63);
+        assert_eq!(dense_get(&bytes, 2), 5);
+        assert_eq!(dense_get(&bytes, REGISTERS - 1), 42);
+        dense_set(&mut bytes, 1, 1);
+        assert_eq!(dense_get(&bytes, 1), 1);
+        assert_eq!(dense_get(&bytes, 2), 5);
+    }
+
+    #[test]
+    fn test_count() {
+        assert_eq!(HyperLogLog::new().count(), 0);
+
+        let small = sketch(0..100);
+        assert!(small.is_sparse());
+        assert_close(small.count(), 100);
+
+        let large = sketch(0..100_000);
+        assert!(!large.is_sparse());
+        assert_close(large.count(), 100_000);
+    }
+
+    #[test]
+    fn test_add_reports_changes() {
+        let mut sketch = HyperLogLog::new();
+        assert!(sketch.add(b"a"));
+        assert!(!sketch.add(b"a"));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut union = sketch(0..5000);
+        union.merge(&sketch(2500..10_000));
+        assert_close(union.count(), 10_000);
+
+        // the encoding doesn't change the estimate
+        let mut dense = sketch(0..500);
+        dense.densify();
+        assert_eq!(dense.count(), sketch(0..500).count());
+    }
+
+    #[tokio::test]
+    async fn test_pf_commands() -> Result<()> {
+        let cache = Cache::default();
+        let elements = |range: std::ops::Range<u32>| range.map(|n| n.to_string()).collect();
+
+        assert!(cache.pfadd("a".to_string(), elements(0..1000)).await?);
+        assert!(!cache.pfadd("a".to_string(), elements(0..10)).await?);
+        assert!(cache.pfadd("empty".to_string(), vec![]).await?);
+        cache.pfadd("b".to_string(), elements(500..2000)).await?;
+
+        assert_close(cache.pfcount(vec!["a".to_string()]).await?, 1000);
+        let keys = vec!["a".to_string(), "b".to_string(), "missing".to_string()];
+        assert_close(cache.pfcount(keys.clone()).await?, 2000);
+
+        cache.pfmerge("union".to_string(), keys).await?;
+        assert_close(cache.pfcount(vec!["union".to_string()]).await?, 2000);
+
+        cache.set("string".to_string(), "value".to_string()).await?;
+        assert!(cache.pfcount(vec!["string".to_string()]).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sketch_expires() -> Result<()> {
+        let cache = Cache::default();
+        cache
+            .pfadd("hour".to_string(), vec!["a".to_string()])
+            .await?;
+        let expiry = Expiry::new(Instant::now() - Duration::from_secs(1));
+        assert!(cache.expire("hour".to_string(), expiry).await);
+
+        cache.purge().await;
+        assert_eq!(cache.len().await, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_over_quota() -> Result<()> {
+        let cache = Cache::default();
+        let quota = Quota {
+            max_memory: Some(100_000),
+            ..Quota::default()
+        };
+        cache.set_quota(quota).await;
+        let elements = |range: std::ops::Range<u32>| range.map(|n| n.to_string()).collect();
+        cache.pfadd("union".to_string(), elements(0..1)).await?;
+        cache.pfadd("a".to_string(), elements(0..5000)).await?;
+
+        // merging into an existing sketch is charged for its growth
+        let used = cache.measure_memory().await;
+        cache
+            .pfmerge("union".to_string(), vec!["a".to_string()])
+            .await?;
+        let charged = cache.memory.load(Ordering::Relaxed);
+        assert!(charged > used);
+        assert_eq!(charged, cache.measure_memory().await);
+
+        cache
+            .set_quota(Quota {
+                max_memory: Some(1),
+                ..Quota::default()
+            })
+            .await;
+        let error = cache
+            .pfmerge("union".to_string(), vec!["a".to_string()])
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("OOM"));
+        Ok(())
+    }
+}
@@ -0,0 +1,691 @@
+use crate::cache::entry::Entry;
+use crate::cache::object::Object;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use serde_json::{Number, Value};
+
+/// Selects children of a JSON value.
+#[derive(Clone, Debug, PartialEq)]
+enum Selector {
+    Name(String),
+    /// Negative indices count from the end of the array.
+    Index(i64),
+    Wildcard,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    /// The children of the current values.
+    Child(Selector),
+    /// The children of the current values and of all their descendants, written `..`.
+    Descendants(Selector),
+}
+
+/// One step of the concrete location of a value within a document.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+type Pointer = Vec<Step>;
+
+/// A JSONPath expression like `$.users[0].name` or `$..price`. Legacy paths without the
+/// leading `$`, like `.users[0].name`, select the same values but commands reply with
+/// their first match only.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+    legacy: bool,
+}
+
+impl JsonPath {
+    /// The path of the whole document.
+    pub fn root() -> Self {
+        JsonPath {
+            segments: Vec::new(),
+            legacy: true,
+        }
+    }
+
+    pub fn parse(path: &str) -> Result<Self> {
+        let (expression, legacy) = match path.strip_prefix('$') {
+            Some(expression) => (expression.to_owned(), false),
+            None if path == "." => (String::new(), true),
+            None if path.starts_with('.') || path.starts_with('[') => (path.to_owned(), true),
+            None => (format!(".{}", path), true),
+        };
+        let invalid = || Error::msg(format!("invalid JSONPath {:?}", path));
+
+        let chars = expression.chars().collect::<Vec<_>>();
+        let mut segments = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let descendants = chars[i] == '.' && chars.get(i + 1) == Some(&'.');
+            let selector = match chars[i] {
+                '.' => {
+                    i += if descendants { 2 } else { 1 };
+                    if chars.get(i) == Some(&'[') {
+                        let (selector, end) = Self::parse_bracket(&chars, i).ok_or_else(invalid)?;
+                        i = end;
+                        selector
+                    } else {
+                        let start = i;
+                        while i < chars.len() && chars[i] != '.' && chars[i] != '[' {
+                            i += 1;
+                        }
+                        match chars[start..i].iter().collect::<String>() {
+                            name if name.is_empty() => return Err(invalid()),
+                            name if name == "*" => Selector::Wildcard,
+                            name => Selector::Name(name),
+                        }
+                    }
+                }
+                '[' => {
+                    let (selector, end) = Self::parse_bracket(&chars, i).ok_or_else(invalid)?;
+                    i = end;
+                    selector
+                }
+                _ => return Err(invalid()),
+            };
+            segments.push(if descendants {
+                Segment::Descendants(selector)
+            } else {
+                Segment::Child(selector)
+            });
+        }
+        Ok(JsonPath { segments, legacy })
+    }
+
+    /// Parse `[*]`, `[index]` or `['name']` starting at `start`, returning the selector and
+    /// the position following the closing bracket.
+    fn parse_bracket(chars: &[char], start: usize) -> Option<(Selector, usize)> {
+        let end = start + chars[start..].iter().position(|c| *c == ']')?;
+        let inner = chars[start + 1..end].iter().collect::<String>();
+        let inner = inner.trim();
+        let selector = if inner == "*" {
+            Selector::Wildcard
+        } else if let Ok(index) = inner.parse::<i64>() {
+            Selector::Index(index)
+        } else {
+            let quoted = inner
+                .strip_prefix('\'')
+                .and_then(|inner| inner.strip_suffix('\''))
+                .or_else(|| {
+                    inner
+                        .strip_prefix('"')
+                        .and_then(|inner| inner.strip_suffix('"'))
+                })?;
+            Selector::Name(quoted.to_owned())
+        };
+        Some((selector, end + 1))
+    }
+
+    /// Retrieve whether commands reply with the first match only.
+    pub fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+
+    /// Retrieve whether this path selects the whole document.
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Retrieve the locations of the values matching this path in `root`.
+    fn locate(&self, root: &Value) -> Vec<Pointer> {
+        Self::locate_segments(&self.segments, root)
+    }
+
+    fn locate_segments(segments: &[Segment], root: &Value) -> Vec<Pointer> {
+        let mut current = vec![(Vec::new(), root)];
+        for segment in segments {
+            let mut next = Vec::new();
+            for (pointer, value) in current {
+                match segment {
+                    Segment::Child(selector) => select(selector, pointer, value, &mut next),
+                    Segment::Descendants(selector) => {
+                        for (pointer, value) in descendants(pointer, value) {
+                            select(selector, pointer, value, &mut next);
+                        }
+                    }
+                }
+            }
+            current = next;
+        }
+        current.into_iter().map(|(pointer, _)| pointer).collect()
+    }
+}
+
+/// Push the children of `value` matching `selector` to `matches`.
+fn select<'a>(
+    selector: &Selector,
+    pointer: Pointer,
+    value: &'a Value,
+    matches: &mut Vec<(Pointer, &'a Value)>,
+) {
+    let child = |step: Step| {
+        let mut pointer = pointer.clone();
+        pointer.push(step);
+        pointer
+    };
+    match (selector, value) {
+        (Selector::Name(name), Value::Object(object)) => {
+            if let Some(value) = object.get(name) {
+                matches.push((child(Step::Key(name.clone())), value));
+            }
+        }
+        (Selector::Index(index), Value::Array(array)) => {
+            let index = if *index < 0 {
+                array.len() as i64 + index
+            } else {
+                *index
+            };
+            if let Some(value) = usize::try_from(index).ok().and_then(|i| array.get(i)) {
+                matches.push((child(Step::Index(index as usize)), value));
+            }
+        }
+        (Selector::Wildcard, Value::Object(object)) => {
+            for (key, value) in object {
+                matches.push((child(Step::Key(key.clone())), value));
+            }
+        }
+        (Selector::Wildcard, Value::Array(array)) => {
+            for (i, value) in array.iter().enumerate() {
+                matches.push((child(Step::Index(i)), value));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Retrieve `value` and all its descendants, parents first.
+fn descendants(pointer: Pointer, value: &Value) -> Vec<(Pointer, &Value)> {
+    let mut all = Vec::new();
+    let mut stack = vec![(pointer, value)];
+    while let Some((pointer, value)) = stack.pop() {
+        let mut children = Vec::new();
+        select(&Selector::Wildcard, pointer.clone(), value, &mut children);
+        all.push((pointer, value));
+        stack.extend(children.into_iter().rev());
+    }
+    all
+}
+
+fn resolve<'a>(root: &'a Value, pointer: &[Step]) -> Option<&'a Value> {
+    pointer.iter().try_fold(root, |value, step| match step {
+        Step::Key(key) => value.get(key),
+        Step::Index(index) => value.get(index),
+    })
+}
+
+fn resolve_mut<'a>(root: &'a mut Value, pointer: &[Step]) -> Option<&'a mut Value> {
+    pointer.iter().try_fold(root, |value, step| match step {
+        Step::Key(key) => value.get_mut(key),
+        Step::Index(index) => value.get_mut(index),
+    })
+}
+
+/// Retrieve the name of the type of `value` as reported by JSON.TYPE.
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Add two JSON numbers, staying an integer when both are.
+fn add(number: &Number, by: &Number) -> Result<Number> {
+    if let (Some(a), Some(b)) = (number.as_i64(), by.as_i64()) {
+        if let Some(sum) = a.checked_add(b) {
+            return Ok(sum.into());
+        }
+    }
+    let sum = number.as_f64().unwrap_or(0.0) + by.as_f64().unwrap_or(0.0);
+    Number::from_f64(sum).ok_or_else(|| Error::msg("result is not a finite number"))
+}
+
+/// The JSON.SET conditions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SetCondition {
+    /// Only set paths that don't exist yet.
+    Nx,
+    /// Only set paths that already exist.
+    Xx,
+}
+
+/// Set the values at `path` in the document `root` to `value`, adding a missing last member of
+/// an object. Returns whether anything was set, which `condition` may prevent.
+fn set_path(
+    root: &mut Value,
+    path: &JsonPath,
+    value: Value,
+    condition: Option<SetCondition>,
+) -> bool {
+    let pointers = path.locate(root);
+    if !pointers.is_empty() {
+        if condition == Some(SetCondition::Nx) {
+            return false;
+        }
+        for pointer in pointers {
+            if let Some(target) = resolve_mut(root, &pointer) {
+                *target = value.clone();
+            }
+        }
+        return true;
+    }
+
+    // a missing member is added to the objects matching the parent path
+    let (parent, name) = match path.segments.split_last() {
+        Some((Segment::Child(Selector::Name(name)), parent)) => (parent, name),
+        _ => return false,
+    };
+    if condition == Some(SetCondition::Xx) {
+        return false;
+    }
+    let mut added = false;
+    for pointer in JsonPath::locate_segments(parent, root) {
+        if let Some(Value::Object(object)) = resolve_mut(root, &pointer) {
+            object.insert(name.clone(), value.clone());
+            added = true;
+        }
+    }
+    added
+}
+
+const MISSING_KEY: &str = "could not perform this operation on a key that doesn't exist";
+
+impl Cache {
+    /// Set the values at `path` in the document at `key` to `value`. A missing last member
+    /// of an object is created. New documents must be set at the root. Returns whether
+    /// anything was set, which `condition` may prevent.
+    pub async fn json_set(
+        &self,
+        key: String,
+        path: JsonPath,
+        value: Value,
+        condition: Option<SetCondition>,
+    ) -> Result<bool> {
+        let mut store = self.store.write().unwrap();
+        self.evict_if_expired(&mut store, &key);
+
+        if !store.contains_key(&key) {
+            if !path.is_root() {
+                return Err(Error::msg("new objects must be created at the root"));
+            }
+            if condition == Some(SetCondition::Xx) {
+                return Ok(false);
+            }
+            self.admit(&mut store, &key)?;
+            let entry = Entry::new(Object::from(value), self.default_expiry());
+            self.charge(key.len() + entry.value().memory_usage());
+            store.insert(key, entry);
+            return Ok(true);
+        }
+
+        // the document may grow, which is charged like the writes of `write_object`
+        self.admit(&mut store, &key)?;
+        let before = self.footprint(&store, &key);
+        let root = store.get_mut(&key).unwrap().value_mut().as_json_mut()?;
+        let set = set_path(root, &path, value, condition);
+        self.charge(self.footprint(&store, &key).saturating_sub(before));
+        Ok(set)
+    }
+
+    /// Retrieve the values matching each of `paths` in the document at `key`.
+    pub async fn json_get(
+        &self,
+        key: String,
+        paths: Vec<JsonPath>,
+    ) -> Result<Option<Vec<Vec<Value>>>> {
+        self.read_object(&key, |object| {
+            let root = object.as_json()?;
+            Ok(paths
+                .iter()
+                .map(|path| {
+                    path.locate(root)
+                        .iter()
+                        .filter_map(|pointer| resolve(root, pointer).cloned())
+                        .collect()
+                })
+                .collect())
+        })
+    }
+
+    /// Delete the values matching `path` in the document at `key`, returning how many were
+    /// deleted. Deleting the root deletes the key.
+    pub async fn json_del(&self, key: String, path: JsonPath) -> Result<usize> {
+        let mut store = self.store.write().unwrap();
+        self.evict_if_expired(&mut store, &key);
+
+        let root = match store.get_mut(&key) {
+            Some(entry) => entry.value_mut().as_json_mut()?,
+            None => return Ok(0),
+        };
+        if path.is_root() {
+            self.remove_entry(&mut store, &key);
+            return Ok(1);
+        }
+
+        // later siblings go first so that removals don't shift the indices of the others
+        let mut pointers = path.locate(root);
+        pointers.sort();
+        pointers.dedup();
+        let mut deleted = 0;
+        for pointer in pointers.into_iter().rev() {
+            let (last, parent) = pointer.split_last().unwrap();
+            let removed = match (resolve_mut(root, parent), last) {
+                (Some(Value::Object(object)), Step::Key(key)) => object.shift_remove(key).is_some(),
+                (Some(Value::Array(array)), Step::Index(index)) if *index < array.len() => {
+                    array.remove(*index);
+                    true
+                }
+                _ => false,
+            };
+            deleted += removed as usize;
+        }
+        Ok(deleted)
+    }
+
+    /// Increment the numbers matching `path` in the document at `key` by `by`, returning
+    /// their new values, `None` for matches that aren't numbers.
+    pub async fn json_numincrby(
+        &self,
+        key: String,
+        path: JsonPath,
+        by: Number,
+    ) -> Result<Vec<Option<Number>>> {
+        self.write_object(
+            &key,
+            || None,
+            |object| {
+                let root = object.as_json_mut()?;
+                let pointers = path.locate(root);
+                let mut results = Vec::with_capacity(pointers.len());
+                for pointer in pointers {
+                    let result = match resolve_mut(root, &pointer) {
+                        Some(Value::Number(number)) => {
+                            *number = add(number, &by)?;
+                            Some(number.clone())
+                        }
+                        _ => None,
+                    };
+                    results.push(result);
+                }
+                Ok(results)
+            },
+        )?
+        .ok_or_else(|| Error::msg(MISSING_KEY))
+    }
+
+    /// Append `values` to the arrays matching `path` in the document at `key`, returning
+    /// their new lengths, `None` for matches that aren't arrays.
+    pub async fn json_arrappend(
+        &self,
+        key: String,
+        path: JsonPath,
+        values: Vec<Value>,
+    ) -> Result<Vec<Option<usize>>> {
+        self.write_object(
+            &key,
+            || None,
+            |object| {
+                let root = object.as_json_mut()?;
+                let pointers = path.locate(root);
+                Ok(pointers
+                    .into_iter()
+                    .map(|pointer| match resolve_mut(root, &pointer) {
+                        Some(Value::Array(array)) => {
+                            array.extend(values.iter().cloned());
+                            Some(array.len())
+                        }
+                        _ => None,
+                    })
+                    .collect())
+            },
+        )?
+        .ok_or_else(|| Error::msg(MISSING_KEY))
+    }
+
+    /// Retrieve the type names of the values matching `path` in the document at `key`.
+    pub async fn json_type(
+        &self,
+        key: String,
+        path: JsonPath,
+    ) -> Result<Option<Vec<&'static str>>> {
+        self.read_object(&key, |object| {
+            let root = object.as_json()?;
+            Ok(path
+                .locate(root)
+                .iter()
+                .filter_map(|pointer| resolve(root, pointer).map(type_name))
+                .collect())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::quota::Quota;
+    use serde_json::json;
+    use std::sync::atomic::Ordering;
+
+    fn path(path: &str) -> JsonPath {
+        JsonPath::parse(path).unwrap()
+    }
+
+    fn store() -> Value {
+        json!({
+            "store": {
+                "books": [
+                    {"title": "Dune", "price": 9},
+                    {"title": "Emma", "price": 5.5},
+                ],
+                "bike": {"price": 120},
+            }
+        })
+    }
+
+    fn matches(path: &str, root: &Value) -> Vec<Value> {
+        JsonPath::parse(path)
+            .unwrap()
+            .locate(root)
+            .iter()
+            .map(|pointer| resolve(root, pointer).unwrap().clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(path("$").is_root());
+        assert!(path(".").is_root());
+        assert!(path(".").is_legacy());
+        assert!(!path("$.a").is_legacy());
+        assert_eq!(path("a.b[0]"), path(".a['b'][0]"));
+        assert_eq!(
+            path("$..price").segments,
+            vec![Segment::Descendants(Selector::Name("price".to_string()))]
+        );
+        assert!(JsonPath::parse("$.").is_err());
+        assert!(JsonPath::parse("$[unquoted]").is_err());
+        assert!(JsonPath::parse("$a").is_err());
+    }
+
+    #[test]
+    fn test_locate() {
+        let root = store();
+        assert_eq!(
+            matches("$.store.books[0].title", &root),
+            vec![json!("Dune")]
+        );
+        assert_eq!(matches("$.store.books[-1].price", &root), vec![json!(5.5)]);
+        assert_eq!(
+            matches("$.store.books[*].title", &root),
+            vec![json!("Dune"), json!("Emma")]
+        );
+        assert_eq!(
+            matches("$..price", &root),
+            vec![json!(9), json!(5.5), json!(120)]
+        );
+        assert_eq!(matches("$.store.missing", &root), Vec::<Value>::new());
+        assert_eq!(matches("$", &root), vec![root.clone()]);
+    }
+
+    #[test]
+    fn test_type_name() {
+        let root = json!([null, true, 1, 1.5, "a", [], {}]);
+        let names = root
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(type_name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!["null", "boolean", "integer", "number", "string", "array", "object"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_get() -> Result<()> {
+        let cache = Cache::default();
+        let key = || "doc".to_string();
+        assert!(cache
+            .json_set(key(), path("$.a"), json!(1), None)
+            .await
+            .is_err());
+        assert!(cache.json_set(key(), path("$"), store(), None).await?);
+
+        assert!(
+            cache
+                .json_set(key(), path("$.store.bike.color"), json!("red"), None)
+                .await?
+        );
+        assert!(
+            !cache
+                .json_set(
+                    key(),
+                    path("$.store.bike.color"),
+                    json!("blue"),
+                    Some(SetCondition::Nx)
+                )
+                .await?
+        );
+        assert!(
+            !cache
+                .json_set(
+                    key(),
+                    path("$.store.owner"),
+                    json!("me"),
+                    Some(SetCondition::Xx)
+                )
+                .await?
+        );
+        assert!(
+            cache
+                .json_set(key(), path("$..price"), json!(1), Some(SetCondition::Xx))
+                .await?
+        );
+
+        let values = cache
+            .json_get(key(), vec![path("$.store.bike"), path("$..price")])
+            .await?
+            .unwrap();
+        assert_eq!(values[0], vec![json!({"price": 1, "color": "red"})]);
+        assert_eq!(values[1], vec![json!(1), json!(1), json!(1)]);
+        assert_eq!(cache.json_get("missing".to_string(), vec![]).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_del() -> Result<()> {
+        let cache = Cache::default();
+        let key = || "doc".to_string();
+        cache
+            .json_set(
+                key(),
+                JsonPath::root(),
+                json!({"a": [1, 2, 3, 4], "b": 1}),
+                None,
+            )
+            .await?;
+
+        assert_eq!(cache.json_del(key(), path("$.a[*]")).await?, 4);
+        assert_eq!(cache.json_del(key(), path("$.missing")).await?, 0);
+        let values = cache.json_get(key(), vec![JsonPath::root()]).await?;
+        assert_eq!(values, Some(vec![vec![json!({"a": [], "b": 1})]]));
+
+        assert_eq!(cache.json_del(key(), path("$")).await?, 1);
+        assert!(!cache.exists(key()).await);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_numincrby_arrappend_type() -> Result<()> {
+        let cache = Cache::default();
+        let key = || "doc".to_string();
+        cache
+            .json_set(key(), JsonPath::root(), store(), None)
+            .await?;
+
+        let results = cache
+            .json_numincrby(key(), path("$..price"), 2.into())
+            .await?;
+        let expected = [json!(11), json!(7.5), json!(122)];
+        let results = results
+            .into_iter()
+            .map(|number| Value::Number(number.unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(results, expected);
+        let results = cache
+            .json_numincrby(key(), path("$.store.books"), 2.into())
+            .await?;
+        assert_eq!(results, vec![None]);
+
+        let lengths = cache
+            .json_arrappend(
+                key(),
+                path("$.store.books"),
+                vec![json!({"title": "Ulysses"})],
+            )
+            .await?;
+        assert_eq!(lengths, vec![Some(3)]);
+        assert!(cache
+            .json_arrappend("missing".to_string(), path("$"), vec![json!(1)])
+            .await
+            .is_err());
+
+        let types = cache.json_type(key(), path("$.store.*")).await?;
+        assert_eq!(types, Some(vec!["array", "object"]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_set_over_quota() -> Result<()> {
+        let cache = Cache::default();
+        let quota = Quota {
+            max_memory: Some(1000),
+            ..Quota::default()
+        };
+        cache.set_quota(quota).await;
+        let key = || "doc".to_string();
+        cache.json_set(key(), path("$"), store(), None).await?;
+
+        // growing a document is charged, then refused once over the quota
+        let used = cache.memory.load(Ordering::Relaxed);
+        let note = json!("x".repeat(5000));
+        cache.json_set(key(), path("$.note"), note, None).await?;
+        assert!(cache.memory.load(Ordering::Relaxed) >= used + 5000);
+        let error = cache
+            .json_set(key(), path("$.b"), json!(1), None)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("OOM"));
+        Ok(())
+    }
+}
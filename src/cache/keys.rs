@@ -0,0 +1,392 @@
+use crate::cache::entry::Entry;
+use crate::cache::notifications::Event;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use std::collections::BTreeMap;
+use std::sync::RwLockWriteGuard;
+
+type Store<'a> = RwLockWriteGuard<'a, BTreeMap<String, Entry>>;
+
+/// Count the entries that hadn't expired yet, freeing them all on a blocking task.
+fn free_in_background(removed: Vec<(String, Entry)>) -> usize {
+    let existing = removed
+        .iter()
+        .filter(|(_, entry)| !entry.expiration().is_expired())
+        .count();
+
+    log::debug!("freeing {} removed keys in the background", removed.len());
+    tokio::task::spawn_blocking(move || drop(removed));
+    existing
+}
+
+impl Cache {
+    /// Move the entry under `source` to `destination` with its expiration, replacing the
+    /// entry there unless `replace` is unset. Returns whether the entry was moved.
+    pub async fn rename(&self, source: String, destination: String, replace: bool) -> Result<bool> {
+        {
+            let mut store = self.store.write().unwrap();
+            self.evict_if_expired(&mut store, &source);
+            self.evict_if_expired(&mut store, &destination);
+            if !store.contains_key(&source) {
+                return Err(Error::msg("no such key"));
+            }
+            if source == destination {
+                return Ok(replace);
+            }
+            if !replace && store.contains_key(&destination) {
+                return Ok(false);
+            }
+
+            log::debug!("renaming key {} to {}", source, destination);
+            self.notify(Event::RenameFrom, &source);
+            self.notify(Event::RenameTo, &destination);
+            let entry = self.remove_entry(&mut store, &source).unwrap();
+            self.place(&mut store, destination.clone(), entry);
+        }
+        self.wake_blocked(&destination);
+        Ok(true)
+    }
+
+    /// Copy the entry under `source` to `destination` in `target`, this database or another
+    /// one, with its expiration. The entry there is replaced only if `replace` is set. Returns
+    /// whether the entry was copied.
+    pub async fn copy(
+        &self,
+        source: String,
+        target: &Cache,
+        destination: String,
+        replace: bool,
+    ) -> Result<bool> {
+        let same = std::ptr::eq(self, target);
+        if same && source == destination {
+            return Err(Error::msg("source and destination objects are the same"));
+        }
+        if same {
+            let mut store = self.store.write().unwrap();
+            self.evict_if_expired(&mut store, &source);
+            self.evict_if_expired(&mut store, &destination);
+            let entry = match store.get(&source) {
+                Some(entry) => entry.clone(),
+                None => return Ok(false),
+            };
+            if !replace && store.contains_key(&destination) {
+                return Ok(false);
+            }
+            self.admit(&mut store, &destination)?;
+            self.charge(destination.len() + entry.value().memory_usage());
+
+            log::debug!("copying key {} to {}", source, destination);
+            self.place(&mut store, destination.clone(), entry);
+        } else {
+            let (mut from, mut to) = self.lock_both(target);
+            self.evict_if_expired(&mut from, &source);
+            target.evict_if_expired(&mut to, &destination);
+            let entry = match from.get(&source) {
+                Some(entry) => entry.clone(),
+                None => return Ok(false),
+            };
+            if !replace && to.contains_key(&destination) {
+                return Ok(false);
+            }
+            target.admit(&mut to, &destination)?;
+            target.charge(destination.len() + entry.value().memory_usage());
+
+            log::debug!(
+                "copying key {} to {} in another database",
+                source,
+                destination
+            );
+            target.place(&mut to, destination.clone(), entry);
+        }
+        target.wake_blocked(&destination);
+        Ok(true)
+    }
+
+    /// Move the entry under `key` to `target`, another database, with its expiration. Returns
+    /// whether the entry was moved, which it isn't if it is missing here or exists there.
+    pub async fn move_to(&self, key: String, target: &Cache) -> Result<bool> {
+        if std::ptr::eq(self, target) {
+            return Err(Error::msg("source and destination objects are the same"));
+        }
+        {
+            let (mut from, mut to) = self.lock_both(target);
+            self.evict_if_expired(&mut from, &key);
+            target.evict_if_expired(&mut to, &key);
+            if !from.contains_key(&key) || to.contains_key(&key) {
+                return Ok(false);
+            }
+            target.admit(&mut to, &key)?;
+
+            log::debug!("moving key {} to another database", key);
+            let entry = self.remove_entry(&mut from, &key).unwrap();
+            target.charge(key.len() + entry.value().memory_usage());
+            target.place(&mut to, key.clone(), entry);
+        }
+        target.wake_blocked(&key);
+        Ok(true)
+    }
+
+    /// Lock the store of this database and then the one of `other` for writing. Both are
+    /// always taken in the order of their address, so that two connections moving keys in
+    /// opposite directions can't deadlock.
+    fn lock_both<'a>(&'a self, other: &'a Cache) -> (Store<'a>, Store<'a>) {
+        if (self as *const Cache) < (other as *const Cache) {
+            let this = self.store.write().unwrap();
+            (this, other.store.write().unwrap())
+        } else {
+            let that = other.store.write().unwrap();
+            (self.store.write().unwrap(), that)
+        }
+    }
+
+    /// Retrieve how many of `keys` exist, counting repeated keys every time.
+    pub async fn touch(&self, keys: Vec<String>) -> usize {
+        let store = self.store.read().unwrap();
+        keys.iter()
+            .filter(|key| {
+                store
+                    .get(key.as_str())
+                    .map(|entry| !entry.expiration().is_expired())
+                    .unwrap_or(false)
+            })
+            .count()
+    }
+
+    /// Remove `keys`, returning how many existed. Only unlinking them from the store happens
+    /// under the store lock, their values are freed on a blocking task afterwards.
+    pub async fn unlink(&self, keys: Vec<String>) -> usize {
+        let removed = {
+            let mut store = self.store.write().unwrap();
+            let removed = keys
+                .into_iter()
+                .filter_map(|key| store.remove(&key).map(|entry| (key, entry)))
+                .collect::<Vec<_>>();
+            self.forget_removed(&removed);
+            self.notify_removed(&removed);
+            removed
+        };
+        free_in_background(removed)
+    }
+
+    /// Notify the removal of keys, as deleted or as expired if they had expired already.
+    pub(super) fn notify_removed(&self, removed: &[(String, Entry)]) {
+        for (key, entry) in removed {
+            match entry.expiration().is_expired() {
+                true => self.notify(Event::Expired, key),
+                false => self.notify(Event::Del, key),
+            }
+        }
+    }
+
+    /// Remove every key, returning how many existed. Like with `unlink`, the values are freed
+    /// on a blocking task once the store is empty.
+    pub async fn flush(&self) -> usize {
+        let removed = {
+            let mut store = self.store.write().unwrap();
+            let removed = std::mem::take(&mut *store).into_iter().collect::<Vec<_>>();
+            self.forget_removed(&removed);
+            self.next_epoch();
+            removed
+        };
+        free_in_background(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::expiry::Expiry;
+    use crate::cache::list::End;
+    use crate::cache::vector::{Embedding, Metric, VectorIndex};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_rename_keeps_expiry() -> Result<()> {
+        let cache = Cache::default();
+        let expiry = Expiry::after(Duration::from_secs(100))?;
+        cache
+            .set_with_expiry("staging".to_string(), "v2".to_string(), expiry)
+            .await?;
+        cache.set("live".to_string(), "v1".to_string()).await?;
+
+        let renamed = cache
+            .rename("staging".to_string(), "live".to_string(), false)
+            .await?;
+        assert!(!renamed);
+        let renamed = cache
+            .rename("staging".to_string(), "live".to_string(), true)
+            .await?;
+        assert!(renamed);
+        assert_eq!(cache.get("live".to_string()).await, Some("v2".to_string()));
+        assert!(!cache.exists("staging".to_string()).await);
+        let ttl = cache.ttl("live".to_string()).await.unwrap().unwrap();
+        assert!(ttl > Duration::from_secs(99));
+
+        assert!(cache
+            .rename("staging".to_string(), "live".to_string(), true)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_copy() -> Result<()> {
+        let cache = Cache::default();
+        cache
+            .hset("a".to_string(), vec![("f".into(), "1".into())])
+            .await?;
+        cache.set("b".to_string(), "taken".to_string()).await?;
+
+        assert!(
+            !cache
+                .copy("a".to_string(), &cache, "b".to_string(), false)
+                .await?
+        );
+        assert!(
+            cache
+                .copy("a".to_string(), &cache, "b".to_string(), true)
+                .await?
+        );
+        assert!(
+            !cache
+                .copy("missing".to_string(), &cache, "c".to_string(), true)
+                .await?
+        );
+        assert!(cache
+            .copy("a".to_string(), &cache, "a".to_string(), true)
+            .await
+            .is_err());
+
+        // the copy is independent from the original
+        cache.hdel("a".to_string(), vec!["f".to_string()]).await?;
+        assert_eq!(cache.hlen("b".to_string()).await?, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rename_wakes_blocked_clients() -> Result<()> {
+        let cache = Arc::new(Cache::default());
+        let client = {
+            let cache = cache.clone();
+            tokio::spawn(async move {
+                let keys = vec!["jobs".to_string()];
+                cache
+                    .block_on(&keys, None, || cache.pop("jobs".to_string(), End::Left, 1))
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let values = vec!["job".to_string()];
+        cache
+            .push("incoming".to_string(), End::Right, values, true)
+            .await?;
+        cache
+            .rename("incoming".to_string(), "jobs".to_string(), true)
+            .await?;
+        assert_eq!(client.await??, Some(vec!["job".to_string()]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_renamed_embeddings_stay_indexed() -> Result<()> {
+        let cache = Cache::default();
+        let index = VectorIndex::flat(2, Metric::L2);
+        cache.vec_create("docs".to_string(), index).await?;
+        let embedding = Embedding {
+            index: "docs".to_string(),
+            vector: vec![1.0, 0.0],
+            metadata: vec![],
+        };
+        cache.vec_set("tmp".to_string(), embedding).await?;
+        cache
+            .rename("tmp".to_string(), "doc:1".to_string(), true)
+            .await?;
+        cache
+            .copy("doc:1".to_string(), &cache, "doc:2".to_string(), false)
+            .await?;
+
+        let nearest = cache
+            .vec_search("docs".to_string(), vec![1.0, 0.0], 5, None, None)
+            .await?;
+        let keys = nearest
+            .into_iter()
+            .map(|(key, _, _)| key)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec!["doc:1", "doc:2"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_touch_and_unlink() -> Result<()> {
+        let cache = Cache::default();
+        cache.set("a".to_string(), "1".to_string()).await?;
+        cache.set("b".to_string(), "2".to_string()).await?;
+        let keys = vec![
+            "a".to_string(),
+            "b".to_string(),
+            "a".to_string(),
+            "c".to_string(),
+        ];
+        assert_eq!(cache.touch(keys.clone()).await, 3);
+        assert_eq!(cache.unlink(keys).await, 2);
+        assert!(cache.is_empty().await);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_move_and_copy_between_databases() -> Result<()> {
+        let (first, second) = (Cache::default(), Cache::default());
+        let expiry = Expiry::after(Duration::from_secs(100))?;
+        first
+            .set_with_expiry("session".to_string(), "a".to_string(), expiry)
+            .await?;
+        second.set("taken".to_string(), "b".to_string()).await?;
+        first.set("taken".to_string(), "a".to_string()).await?;
+
+        assert!(first.move_to("session".to_string(), &second).await?);
+        assert!(!first.exists("session".to_string()).await);
+        let ttl = second.ttl("session".to_string()).await.unwrap().unwrap();
+        assert!(ttl > Duration::from_secs(99));
+        assert!(!first.move_to("taken".to_string(), &second).await?);
+        assert!(!first.move_to("missing".to_string(), &second).await?);
+        assert!(first.move_to("taken".to_string(), &first).await.is_err());
+
+        let copied = second
+            .copy("session".to_string(), &first, "session".to_string(), false)
+            .await?;
+        assert!(copied);
+        let copied = first
+            .copy("taken".to_string(), &second, "taken".to_string(), false)
+            .await?;
+        assert!(!copied);
+        assert_eq!(
+            first.get("session".to_string()).await,
+            second.get("session".to_string()).await
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_flush() -> Result<()> {
+        let cache = Cache::default();
+        let index = VectorIndex::flat(2, Metric::L2);
+        cache.vec_create("docs".to_string(), index).await?;
+        let embedding = Embedding {
+            index: "docs".to_string(),
+            vector: vec![1.0, 0.0],
+            metadata: vec![],
+        };
+        cache.vec_set("doc:1".to_string(), embedding).await?;
+        cache.set("a".to_string(), "1".to_string()).await?;
+
+        assert_eq!(cache.flush().await, 2);
+        assert!(cache.is_empty().await);
+        let nearest = cache
+            .vec_search("docs".to_string(), vec![1.0, 0.0], 5, None, None)
+            .await?;
+        assert!(nearest.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,394 @@
+use crate::cache::entry::Entry;
+use crate::cache::expiry::Expiry;
+use crate::cache::{glob, Cache};
+use anyhow::{Error, Result};
+use rand::seq::IteratorRandom;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::atomic::Ordering;
+
+/// The cursor starting an iteration, and returned once it is over.
+pub const START: u64 = 0;
+
+/// The number of cursors a keyspace remembers, the oldest being forgotten first.
+///
+/// As the store is ordered, a cursor only needs the last key it looked at to resume after it,
+/// so keys present for the whole iteration are returned exactly once however the store changes
+/// in between. Clients expect a number they can parse though, so the key stays here and the
+/// cursor is an id for it.
+const CURSORS: usize = 4096;
+
+/// Which keys a SCAN step looks at and returns.
+#[derive(Clone, Debug)]
+pub struct ScanOptions {
+    pub pattern: Option<String>,
+    /// The number of keys looked at, some of which may be filtered out.
+    pub count: usize,
+    pub type_name: Option<String>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            pattern: None,
+            count: 10,
+            type_name: None,
+        }
+    }
+}
+
+/// Iterate over the entries whose key starts with `prefix`, expired ones included.
+fn prefixed<'a>(
+    store: &'a BTreeMap<String, Entry>,
+    prefix: &'a str,
+) -> impl Iterator<Item = (&'a String, &'a Entry)> {
+    store
+        .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+        .take_while(move |(key, _)| key.starts_with(prefix))
+}
+
+impl Cache {
+    /// Look at the next `options.count` keys after `cursor`, starting over at cursor 0.
+    /// Returns the matching keys with the cursor to continue from, which is 0 once every key
+    /// has been looked at.
+    pub async fn scan(&self, cursor: u64, options: ScanOptions) -> Result<(u64, Vec<String>)> {
+        let position = self.resume_after(cursor)?;
+        let start = match &position {
+            Some(key) => Bound::Excluded(key.as_str()),
+            None => Bound::Unbounded,
+        };
+
+        let count = options.count.max(1);
+        let mut keys = Vec::new();
+        let mut last = None;
+        let mut seen = 0;
+        {
+            let store = self.store.read().unwrap();
+            for (key, entry) in store.range::<str, _>((start, Bound::Unbounded)).take(count) {
+                last = Some(key.clone());
+                seen += 1;
+                if entry.expiration().is_expired() {
+                    continue;
+                }
+                if let Some(pattern) = &options.pattern {
+                    if !glob::matches(pattern, key) {
+                        continue;
+                    }
+                }
+                if let Some(type_name) = &options.type_name {
+                    if !entry.value().type_name().eq_ignore_ascii_case(type_name) {
+                        continue;
+                    }
+                }
+                keys.push(key.clone());
+            }
+        }
+
+        let cursor = match last {
+            Some(key) if seen == count => self.remember_cursor(key),
+            _ => START,
+        };
+        Ok((cursor, keys))
+    }
+
+    /// Retrieve the key `cursor` resumes after, or `None` when it starts an iteration.
+    fn resume_after(&self, cursor: u64) -> Result<Option<String>> {
+        if cursor == START {
+            return Ok(None);
+        }
+        let cursors = self.cursors.lock().unwrap();
+        match cursors.get(&cursor) {
+            Some(key) => Ok(Some(key.clone())),
+            None => Err(Error::msg("invalid cursor")),
+        }
+    }
+
+    /// Remember the cursor resuming after `key`, forgetting the oldest over `CURSORS`.
+    fn remember_cursor(&self, key: String) -> u64 {
+        let cursor = self.next_cursor.fetch_add(1, Ordering::Relaxed);
+        let mut cursors = self.cursors.lock().unwrap();
+        cursors.insert(cursor, key);
+        if cursors.len() > CURSORS {
+            cursors.pop_first();
+        }
+        cursor
+    }
+
+    /// Retrieve every key matching `pattern`. Only the keys starting with the literal prefix
+    /// of the pattern are looked at.
+    pub async fn keys(&self, pattern: String) -> Vec<String> {
+        let store = self.store.read().unwrap();
+        prefixed(&store, glob::literal_prefix(&pattern))
+            .filter(|(key, entry)| !entry.expiration().is_expired() && glob::matches(&pattern, key))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// Retrieve up to `limit` keys from `start` included to `end` excluded, or to the last key
+    /// if `end` is `None`, in order or in reverse. Keys come with their value if they hold a
+    /// string.
+    pub async fn range(
+        &self,
+        start: String,
+        end: Option<String>,
+        reverse: bool,
+        limit: Option<usize>,
+    ) -> Vec<(String, Option<Vec<u8>>)> {
+        let end = match &end {
+            Some(end) if *end <= start => return vec![],
+            Some(end) => Bound::Excluded(end.as_str()),
+            None => Bound::Unbounded,
+        };
+        let store = self.store.read().unwrap();
+        let range = store.range::<str, _>((Bound::Included(start.as_str()), end));
+        let entries: Box<dyn Iterator<Item = (&String, &Entry)>> = match reverse {
+            true => Box::new(range.rev()),
+            false => Box::new(range),
+        };
+        entries
+            .filter(|(_, entry)| !entry.expiration().is_expired())
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|(key, entry)| {
+                let value = entry.value().as_bytes().ok().cloned();
+                (key.clone(), value)
+            })
+            .collect()
+    }
+
+    /// Retrieve the number of keys starting with `prefix`.
+    pub async fn prefix_count(&self, prefix: String) -> usize {
+        let store = self.store.read().unwrap();
+        prefixed(&store, &prefix)
+            .filter(|(_, entry)| !entry.expiration().is_expired())
+            .count()
+    }
+
+    /// Remove every key starting with `prefix` at once, returning how many there were.
+    pub async fn prefix_delete(&self, prefix: String) -> usize {
+        let mut store = self.store.write().unwrap();
+        let keys = prefixed(&store, &prefix)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        let removed = keys
+            .into_iter()
+            .filter_map(|key| store.remove(&key).map(|entry| (key, entry)))
+            .collect::<Vec<_>>();
+        self.forget_removed(&removed);
+        self.notify_removed(&removed);
+
+        log::debug!("removed {} keys starting with {}", removed.len(), prefix);
+        removed
+            .iter()
+            .filter(|(_, entry)| !entry.expiration().is_expired())
+            .count()
+    }
+
+    /// Set the expiration of every key starting with `prefix` at once, returning how many
+    /// there are.
+    pub async fn prefix_expire(&self, prefix: String, expiry: Expiry) -> usize {
+        let mut store = self.store.write().unwrap();
+        let keys = prefixed(&store, &prefix)
+            .filter(|(_, entry)| !entry.expiration().is_expired())
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in &keys {
+            store.get_mut(key).unwrap().set_expiration(expiry.clone());
+        }
+        log::debug!(
+            "set the expiration of {} keys starting with {}",
+            keys.len(),
+            prefix
+        );
+        keys.len()
+    }
+
+    /// Retrieve the name of the data type stored under `key`, or "none" if it is missing.
+    pub async fn key_type(&self, key: String) -> &'static str {
+        let type_name = self.read_object(&key, |object| Ok(object.type_name()));
+        type_name.ok().flatten().unwrap_or("none")
+    }
+
+    /// Retrieve a key picked uniformly at random, or `None` if the store is empty.
+    pub async fn random_key(&self) -> Option<String> {
+        let store = self.store.read().unwrap();
+        store
+            .iter()
+            .filter(|(_, entry)| !entry.expiration().is_expired())
+            .map(|(key, _)| key)
+            .choose(&mut rand::thread_rng())
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_scan_whole_keyspace() -> Result<()> {
+        let cache = Cache::default();
+        for i in 0..25 {
+            cache
+                .set(format!("key:{:02}", i), "value".to_string())
+                .await?;
+        }
+
+        let mut cursor = START;
+        let mut keys = Vec::new();
+        loop {
+            let options = ScanOptions {
+                count: 7,
+                ..ScanOptions::default()
+            };
+            let (next, found) = cache.scan(cursor, options).await?;
+            keys.extend(found);
+            if next == START {
+                break;
+            }
+            // keys changing between calls don't disturb the iteration
+            if cursor == START {
+                cache.remove("key:00".to_string()).await?;
+                cache.set("key:99".to_string(), "value".to_string()).await?;
+            }
+            cursor = next;
+        }
+        assert_eq!(keys.len(), 26);
+        assert_eq!(keys.last().unwrap(), "key:99");
+
+        assert!(cache.scan(u64::MAX, ScanOptions::default()).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_scan_forgets_old_cursors() -> Result<()> {
+        let cache = Cache::default();
+        for key in ["a", "b"] {
+            cache.set(key.to_string(), "value".to_string()).await?;
+        }
+
+        let options = ScanOptions {
+            count: 1,
+            ..ScanOptions::default()
+        };
+        let (first, _) = cache.scan(START, options.clone()).await?;
+        for _ in 0..CURSORS {
+            cache.scan(START, options.clone()).await?;
+        }
+        assert!(cache.scan(first, options.clone()).await.is_err());
+        let (cursor, _) = cache.scan(START, options.clone()).await?;
+        let (_, keys) = cache.scan(cursor, options).await?;
+        assert_eq!(keys, vec!["b".to_string()]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_scan_filters() -> Result<()> {
+        let cache = Cache::default();
+        cache.set("user:1".to_string(), "ada".to_string()).await?;
+        cache.set("user:2".to_string(), "bob".to_string()).await?;
+        cache
+            .hset("user:3".to_string(), vec![("name".into(), "eve".into())])
+            .await?;
+        cache
+            .set_with_expiry(
+                "user:4".to_string(),
+                "gone".to_string(),
+                Duration::from_secs(0),
+            )
+            .await?;
+        cache.set("other".to_string(), "value".to_string()).await?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let options = ScanOptions {
+            pattern: Some("user:*".to_string()),
+            count: 100,
+            type_name: Some("STRING".to_string()),
+        };
+        let (cursor, keys) = cache.scan(START, options).await?;
+        assert_eq!(cursor, START);
+        assert_eq!(keys, vec!["user:1", "user:2"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_keys_type_and_random_key() -> Result<()> {
+        let cache = Cache::default();
+        assert_eq!(cache.random_key().await, None);
+        cache
+            .set("events:2026-10-16:a".to_string(), "1".to_string())
+            .await?;
+        cache
+            .set("events:2026-10-17:b".to_string(), "2".to_string())
+            .await?;
+        cache
+            .sadd("tags".to_string(), vec!["x".to_string()])
+            .await?;
+
+        assert_eq!(
+            cache.keys("events:*-16:?".to_string()).await,
+            vec!["events:2026-10-16:a"]
+        );
+        assert_eq!(cache.keys("*".to_string()).await.len(), 3);
+        assert_eq!(cache.key_type("tags".to_string()).await, "set");
+        assert_eq!(cache.key_type("missing".to_string()).await, "none");
+        assert!(cache.random_key().await.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ranges_and_prefixes() -> Result<()> {
+        let cache = Cache::default();
+        for day in ["15", "16", "17"] {
+            for id in ["a", "b"] {
+                let key = format!("events:2026-10-{}:{}", day, id);
+                cache.set(key, day.to_string()).await?;
+            }
+        }
+        cache
+            .sadd("events:index".to_string(), vec!["x".to_string()])
+            .await?;
+
+        let range = cache
+            .range(
+                "events:2026-10-16".to_string(),
+                Some("events:2026-10-17".to_string()),
+                false,
+                None,
+            )
+            .await;
+        assert_eq!(
+            range,
+            vec![
+                ("events:2026-10-16:a".to_string(), Some(b"16".to_vec())),
+                ("events:2026-10-16:b".to_string(), Some(b"16".to_vec())),
+            ]
+        );
+        let range = cache
+            .range("events:".to_string(), None, true, Some(2))
+            .await;
+        assert_eq!(range[0], ("events:index".to_string(), None));
+        assert_eq!(range[1].0, "events:2026-10-17:b");
+        let range = cache
+            .range("b".to_string(), Some("a".to_string()), false, None)
+            .await;
+        assert!(range.is_empty());
+
+        assert_eq!(cache.prefix_count("events:2026-10-1".to_string()).await, 6);
+        assert_eq!(
+            cache.prefix_delete("events:2026-10-15:".to_string()).await,
+            2
+        );
+        assert_eq!(cache.prefix_count("events:".to_string()).await, 5);
+
+        let expiry = Expiry::after(Duration::from_millis(10))?;
+        assert_eq!(
+            cache.prefix_expire("events:2026".to_string(), expiry).await,
+            4
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.prefix_count("events:".to_string()).await, 1);
+        assert_eq!(cache.prefix_delete("events:2026".to_string()).await, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+use crate::cache::entry::Entry;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use std::collections::VecDeque;
+
+/// A list of strings, pushed and popped at both ends.
+pub type List = VecDeque<String>;
+
+/// An end of a list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum End {
+    Left,
+    Right,
+}
+
+impl End {
+    /// Parse the `LEFT` or `RIGHT` argument of LMOVE.
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "left" => Ok(End::Left),
+            "right" => Ok(End::Right),
+            _ => Err(Error::msg("syntax error")),
+        }
+    }
+}
+
+fn push(list: &mut List, end: End, value: String) {
+    match end {
+        End::Left => list.push_front(value),
+        End::Right => list.push_back(value),
+    }
+}
+
+fn pop(list: &mut List, end: End) -> Option<String> {
+    match end {
+        End::Left => list.pop_front(),
+        End::Right => list.pop_back(),
+    }
+}
+
+/// Resolve the inclusive `start` and `stop` indexes of LRANGE, negative indexes counting from
+/// the tail, into a range of positions in a list of `len` items.
+fn resolve_range(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+    if start > stop || start >= len {
+        None
+    } else {
+        Some((start as usize, stop as usize))
+    }
+}
+
+impl Cache {
+    /// Push `values` one after the other at an end of the list at `key`, returning its new
+    /// length. A missing list is only created when `create` is set, otherwise nothing is
+    /// pushed and 0 is returned.
+    pub async fn push(
+        &self,
+        key: String,
+        end: End,
+        values: Vec<String>,
+        create: bool,
+    ) -> Result<usize> {
+        let len = self.write_object(
+            &key,
+            || create.then(|| List::new().into()),
+            |object| {
+                let list = object.as_list_mut()?;
+                for value in values {
+                    push(list, end, value);
+                }
+                Ok(list.len())
+            },
+        )?;
+        if len.is_some() {
+            self.wake_blocked(&key);
+        }
+        Ok(len.unwrap_or(0))
+    }
+
+    /// Pop up to `count` values from an end of the list at `key`, or `None` if it doesn't
+    /// exist.
+    pub async fn pop(&self, key: String, end: End, count: usize) -> Result<Option<Vec<String>>> {
+        self.shrink_object(&key, |object| {
+            let list = object.as_list_mut()?;
+            Ok((0..count).map_while(|_| pop(list, end)).collect())
+        })
+    }
+
+    pub async fn llen(&self, key: String) -> Result<usize> {
+        let len = self.read_object(&key, |object| Ok(object.as_list()?.len()))?;
+        Ok(len.unwrap_or(0))
+    }
+
+    /// Retrieve the values between the inclusive `start` and `stop` indexes of the list at
+    /// `key`.
+    pub async fn lrange(&self, key: String, start: i64, stop: i64) -> Result<Vec<String>> {
+        let values = self.read_object(&key, |object| {
+            let list = object.as_list()?;
+            Ok(match resolve_range(start, stop, list.len()) {
+                Some((start, stop)) => list.range(start..=stop).cloned().collect(),
+                None => Vec::new(),
+            })
+        })?;
+        Ok(values.unwrap_or_default())
+    }
+
+    /// Atomically pop a value from an end of the list at `source` and push it to an end of the
+    /// list at `destination`, returning the moved value or `None` if `source` doesn't exist.
+    pub async fn lmove(
+        &self,
+        source: String,
+        destination: String,
+        from: End,
+        to: End,
+    ) -> Result<Option<String>> {
+        let value = {
+            let mut store = self.store.write().unwrap();
+            self.evict_if_expired(&mut store, &source);
+            self.evict_if_expired(&mut store, &destination);
+
+            // check both types before touching anything
+            if !store.contains_key(&source) {
+                return Ok(None);
+            }
+            store[&source].value().as_list()?;
+            if let Some(entry) = store.get(&destination) {
+                entry.value().as_list()?;
+            }
+            self.admit(&mut store, &destination)?;
+            // making room may have evicted the source
+            if !store.contains_key(&source) {
+                return Ok(None);
+            }
+
+            let list = store.get_mut(&source).unwrap().value_mut().as_list_mut()?;
+            let value = pop(list, from).unwrap();
+            if list.is_empty() {
+                self.remove_entry(&mut store, &source);
+            }
+
+            // the value moves within the keyspace, only a new destination key grows it
+            if !store.contains_key(&destination) {
+                self.charge(destination.len());
+            }
+            let entry = store
+                .entry(destination.clone())
+                .or_insert_with(|| Entry::new(List::new(), self.default_expiry()));
+            push(entry.value_mut().as_list_mut()?, to, value.clone());
+            value
+        };
+        self.wake_blocked(&destination);
+        Ok(Some(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_resolve_range() {
+        assert_eq!(resolve_range(0, -1, 3), Some((0, 2)));
+        assert_eq!(resolve_range(-2, 10, 3), Some((1, 2)));
+        assert_eq!(resolve_range(-10, 0, 3), Some((0, 0)));
+        assert_eq!(resolve_range(2, 1, 3), None);
+        assert_eq!(resolve_range(5, 10, 3), None);
+        assert_eq!(resolve_range(0, -1, 0), None);
+    }
+
+    #[tokio::test]
+    async fn test_push_pop() -> Result<()> {
+        let cache = Cache::default();
+        let key = "list".to_string();
+        assert_eq!(
+            cache
+                .push(key.clone(), End::Left, strings(&["a"]), false)
+                .await?,
+            0
+        );
+        assert_eq!(
+            cache
+                .push(key.clone(), End::Left, strings(&["b", "a"]), true)
+                .await?,
+            2
+        );
+        cache
+            .push(key.clone(), End::Right, strings(&["c"]), true)
+            .await?;
+        assert_eq!(
+            cache.lrange(key.clone(), 0, -1).await?,
+            strings(&["a", "b", "c"])
+        );
+
+        let popped = cache.pop(key.clone(), End::Right, 2).await?;
+        assert_eq!(popped, Some(strings(&["c", "b"])));
+        let popped = cache.pop(key.clone(), End::Left, 5).await?;
+        assert_eq!(popped, Some(strings(&["a"])));
+
+        // the emptied list is gone with its key
+        assert!(!cache.exists(key.clone()).await);
+        assert_eq!(cache.pop(key, End::Left, 1).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lmove() -> Result<()> {
+        let cache = Cache::default();
+        cache
+            .push("src".to_string(), End::Right, strings(&["a", "b"]), true)
+            .await?;
+
+        let moved = cache
+            .lmove("src".to_string(), "dst".to_string(), End::Right, End::Left)
+            .await?;
+        assert_eq!(moved, Some("b".to_string()));
+        let moved = cache
+            .lmove("src".to_string(), "src".to_string(), End::Left, End::Right)
+            .await?;
+        assert_eq!(moved, Some("a".to_string()));
+        assert_eq!(cache.llen("src".to_string()).await?, 1);
+        assert_eq!(cache.llen("dst".to_string()).await?, 1);
+
+        let moved = cache
+            .lmove(
+                "missing".to_string(),
+                "dst".to_string(),
+                End::Left,
+                End::Left,
+            )
+            .await?;
+        assert_eq!(moved, None);
+
+        cache.set("string".to_string(), "value".to_string()).await?;
+        let moved = cache
+            .lmove(
+                "src".to_string(),
+                "string".to_string(),
+                End::Left,
+                End::Left,
+            )
+            .await;
+        assert!(moved.is_err());
+        assert_eq!(cache.llen("src".to_string()).await?, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant};
+
+/// The members of a hash or set, each of which may expire on its own.
+///
+/// Expired members are hidden from every read, and dropped by `purge_expired` the next time
+/// the collection is written to or sampled by the purge loop.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Members<V> {
+    values: BTreeMap<String, V>,
+    expiries: BTreeMap<String, Instant>,
+    /// The expiring members ordered by expiry, to find the expired ones without a full scan.
+    deadlines: BTreeSet<(Instant, String)>,
+}
+
+impl<V> Default for Members<V> {
+    fn default() -> Self {
+        Members {
+            values: BTreeMap::new(),
+            expiries: BTreeMap::new(),
+            deadlines: BTreeSet::new(),
+        }
+    }
+}
+
+impl<V> Members<V> {
+    /// Retrieve the number of members, expired ones excluded.
+    pub fn len(&self) -> usize {
+        self.values.len() - self.expired().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn is_expired(&self, member: &str) -> bool {
+        self.expiries
+            .get(member)
+            .map(|instant| *instant < Instant::now())
+            .unwrap_or(false)
+    }
+
+    fn expired(&self) -> impl Iterator<Item = &String> {
+        let now = Instant::now();
+        self.deadlines
+            .iter()
+            .take_while(move |(instant, _)| *instant < now)
+            .map(|(_, member)| member)
+    }
+
+    pub fn get(&self, member: &str) -> Option<&V> {
+        match self.is_expired(member) {
+            true => None,
+            false => self.values.get(member),
+        }
+    }
+
+    pub fn contains(&self, member: &str) -> bool {
+        self.get(member).is_some()
+    }
+
+    /// Set `member` to `value`, dropping any expiry it had. Returns whether the member is new.
+    pub fn insert(&mut self, member: String, value: V) -> bool {
+        let new = !self.contains(&member);
+        self.persist_unchecked(&member);
+        self.values.insert(member, value);
+        new
+    }
+
+    /// Remove `member`, returning whether it existed.
+    pub fn remove(&mut self, member: &str) -> bool {
+        let existed = self.contains(member);
+        self.persist_unchecked(member);
+        self.values.remove(member);
+        existed
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &V)> {
+        self.values
+            .iter()
+            .filter(|(member, _)| !self.is_expired(member))
+    }
+
+    /// Expire `member` at `instant`, removing it right away if that has passed. Returns
+    /// `None` if the member is missing, or whether it is still there otherwise.
+    pub fn expire(&mut self, member: &str, instant: Instant) -> Option<bool> {
+        if !self.contains(member) {
+            return None;
+        }
+        if instant <= Instant::now() {
+            self.remove(member);
+            return Some(false);
+        }
+        self.persist_unchecked(member);
+        self.expiries.insert(member.to_owned(), instant);
+        self.deadlines.insert((instant, member.to_owned()));
+        Some(true)
+    }
+
+    /// Retrieve the time left before `member` expires, or `None` if the member is missing.
+    pub fn ttl(&self, member: &str) -> Option<Option<Duration>> {
+        if !self.contains(member) {
+            return None;
+        }
+        let remaining = self
+            .expiries
+            .get(member)
+            .map(|instant| instant.saturating_duration_since(Instant::now()));
+        Some(remaining)
+    }
+
+    /// Drop the expiry of `member`. Returns `None` if the member is missing, or whether it
+    /// had an expiry otherwise.
+    pub fn persist(&mut self, member: &str) -> Option<bool> {
+        match self.contains(member) {
+            true => Some(self.persist_unchecked(member)),
+            false => None,
+        }
+    }
+
+    fn persist_unchecked(&mut self, member: &str) -> bool {
+        match self.expiries.remove(member) {
+            Some(instant) => self.deadlines.remove(&(instant, member.to_owned())),
+            None => false,
+        }
+    }
+
+    /// Retrieve whether any member has expired but not been removed yet.
+    pub fn has_expired(&self) -> bool {
+        self.expired().next().is_some()
+    }
+
+    /// Remove the expired members, returning how many there were.
+    pub fn purge_expired(&mut self) -> usize {
+        let expired = self.expired().cloned().collect::<Vec<_>>();
+        for member in &expired {
+            self.persist_unchecked(member);
+            self.values.remove(member);
+        }
+        expired.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_member_expiry() {
+        let mut members = Members::default();
+        members.insert("phone".to_string(), 1);
+        members.insert("laptop".to_string(), 2);
+        let past = Instant::now() - Duration::from_millis(1);
+
+        assert_eq!(members.expire("tablet", past), None);
+        assert_eq!(
+            members.expire("phone", Instant::now() + Duration::from_secs(60)),
+            Some(true)
+        );
+        assert!(members.ttl("phone").unwrap().unwrap() > Duration::from_secs(59));
+        assert_eq!(members.ttl("laptop"), Some(None));
+        assert_eq!(members.persist("phone"), Some(true));
+        assert_eq!(members.persist("phone"), Some(false));
+        assert_eq!(members.expire("laptop", past), Some(false));
+        assert_eq!(members.len(), 1);
+        assert!(!members.contains("laptop"));
+    }
+
+    #[test]
+    fn test_expired_members_are_hidden_until_purged() {
+        let mut members = Members::default();
+        members.insert("phone".to_string(), 1);
+        members.insert("laptop".to_string(), 2);
+        members.expire("phone", Instant::now() + Duration::from_millis(5));
+        std::thread::sleep(Duration::from_millis(10));
+
+        assert!(members.has_expired());
+        assert_eq!(members.get("phone"), None);
+        assert_eq!(members.len(), 1);
+        assert_eq!(members.iter().count(), 1);
+
+        // setting an expired member again brings it back without the old expiry
+        let mut revived = members.clone();
+        assert!(revived.insert("phone".to_string(), 3));
+        assert_eq!(revived.ttl("phone"), Some(None));
+        assert!(!revived.has_expired());
+
+        assert_eq!(members.purge_expired(), 1);
+        assert!(!members.has_expired());
+        assert_eq!(members.values.len(), 1);
+        assert!(members.deadlines.is_empty());
+    }
+}
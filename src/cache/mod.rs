@@ -1,14 +1,55 @@
+pub mod bitmap;
+mod blocking;
+pub mod bloom;
+pub mod count_min;
+pub mod cuckoo;
+pub mod databases;
 mod entry;
 pub mod expiry;
-
+pub mod geo;
+pub mod glob;
+pub mod graph;
+pub mod hash;
+pub mod hyperloglog;
+pub mod json;
+pub mod keys;
+pub mod keyspace;
+pub mod list;
+pub mod members;
+pub mod notifications;
+pub mod object;
+pub mod pubsub;
+pub mod quota;
+pub mod scripts;
+pub mod search;
+pub mod set;
+pub mod sorted_set;
+pub mod stream;
+pub mod suggestion;
+pub mod sweeper;
+pub mod tdigest;
+pub mod tenants;
+pub mod text;
+pub mod time_series;
+pub mod top_k;
+pub mod transactions;
+pub mod vector;
+
+use crate::cache::blocking::Waiters;
 use crate::cache::entry::Entry;
 use crate::cache::expiry::Expiry;
+use crate::cache::notifications::{Event, Notifier};
+use crate::cache::object::Object;
+use crate::cache::quota::Quota;
+use crate::cache::search::SearchIndex;
+use crate::cache::stream::now_millis;
+use crate::cache::vector::VectorIndex;
 use anyhow::{Error, Result};
-use async_timer::Interval;
 use rand::prelude::*;
 use std::cmp;
 use std::collections::{BTreeMap, BTreeSet};
-use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, AtomicUsize};
+use std::sync::{Mutex, RwLock};
 use std::time::{Duration, Instant};
 
 #[derive(Debug)]
@@ -16,24 +57,61 @@ pub struct Cache {
     store: RwLock<BTreeMap<String, Entry>>,
     sample: usize,
     threshold: f64,
-    frequency: Duration,
     is_leader: bool,
+    blocked: Waiters,
+    /// The vector indexes, locked after the store when both are.
+    vectors: RwLock<BTreeMap<String, VectorIndex>>,
+    /// The secondary indexes over hashes, locked after the store when both are.
+    indexes: RwLock<BTreeMap<String, SearchIndex>>,
+    /// The keys of the time series kept for a retention period, which the sweeper trims,
+    /// locked after the store when both are. Keys holding something else since are dropped
+    /// from it as they are found.
+    retained: Mutex<BTreeSet<String>>,
+    quota: RwLock<Quota>,
+    /// The memory used as last measured, which writes are checked against.
+    memory: AtomicUsize,
+    notifier: RwLock<Option<Notifier>>,
+    /// Changes whenever the keyspace is flushed or swapped with another database, which
+    /// transactions treat as a write to every key.
+    epoch: AtomicU64,
+    /// The keys watched by transactions, with how many watch each and how many times each was
+    /// removed since, locked after the store when both are.
+    watched: Mutex<BTreeMap<String, (usize, u64)>>,
+    /// The keys SCAN cursors resume after, by cursor.
+    cursors: Mutex<BTreeMap<u64, String>>,
+    next_cursor: AtomicU64,
+    /// Taken shared by every command and exclusively by transactions, so that they don't
+    /// interleave with the commands of other clients.
+    gate: tokio::sync::RwLock<()>,
 }
 
 impl Cache {
-    pub fn new(sample: usize, threshold: f64, frequency: Duration) -> Self {
+    pub fn new(sample: usize, threshold: f64) -> Self {
         Cache {
             store: RwLock::new(BTreeMap::new()),
             sample,
             threshold,
-            frequency,
             is_leader: false,
+            blocked: Waiters::default(),
+            vectors: RwLock::new(BTreeMap::new()),
+            indexes: RwLock::new(BTreeMap::new()),
+            retained: Mutex::new(BTreeSet::new()),
+            quota: RwLock::new(Quota::default()),
+            memory: AtomicUsize::new(0),
+            notifier: RwLock::new(None),
+            epoch: AtomicU64::new(0),
+            watched: Mutex::new(BTreeMap::new()),
+            cursors: Mutex::new(BTreeMap::new()),
+            next_cursor: AtomicU64::new(1),
+            gate: tokio::sync::RwLock::new(()),
         }
     }
 
-    pub async fn set(&self, key: String, value: String) {
-        let expiry = Expiry::none();
-        let entry = Entry::new(value, expiry);
+    /// Store the string `value`, any bytes, under `key`, expiring after the default time to
+    /// live if any.
+    pub async fn set(&self, key: String, value: impl Into<Vec<u8>>) -> Result<()> {
+        let expiry = self.default_expiry();
+        let entry = Entry::new(value.into(), expiry);
 
         if self.is_leader {
             todo!()
@@ -42,14 +120,20 @@ impl Cache {
         log::debug!("inserting key {} and value {:?}", key.clone(), entry);
 
         let mut store = self.store.write().unwrap();
-        store.insert(key, entry);
+        self.admit(&mut store, &key)?;
+        self.charge(key.len() + entry.value().memory_usage());
+        self.notify(Event::Set, &key);
+        self.place(&mut store, key, entry);
+        Ok(())
     }
 
-    pub async fn set_with_expiry<E>(&self, key: String, value: String, e: E)
+    pub async fn set_with_expiry<V, E>(&self, key: String, value: V, e: E) -> Result<()>
     where
-        E: Into<Expiry>,
+        V: Into<Vec<u8>>,
+        E: TryInto<Expiry>,
+        Error: From<E::Error>,
     {
-        let entry = Entry::new(value, e.into());
+        let entry = Entry::new(value.into(), e.try_into()?);
 
         if self.is_leader {
             todo!()
@@ -58,25 +142,37 @@ impl Cache {
         log::debug!("inserting key {} and value {:?}", key.clone(), entry);
 
         let mut store = self.store.write().unwrap();
-        store.insert(key, entry);
+        self.admit(&mut store, &key)?;
+        self.charge(key.len() + entry.value().memory_usage());
+        self.notify(Event::Set, &key);
+        self.place(&mut store, key, entry);
+        Ok(())
     }
 
+    #[cfg(test)]
     pub async fn get(&self, key: String) -> Option<String> {
+        let value = self.get_bytes(key).await.ok().flatten();
+        value.map(|value| String::from_utf8_lossy(&value).into_owned())
+    }
+
+    /// Retrieve the bytes of the string stored under `key`, failing if the key holds another
+    /// data type.
+    pub async fn get_bytes(&self, key: String) -> Result<Option<Vec<u8>>> {
         let store = self.store.read().unwrap();
         match store.get(key.as_str()) {
             Some(entry) => {
                 log::debug!("getting key {} and value {:?}", key.clone(), entry);
 
                 if !entry.expiration().is_expired() {
-                    Some(entry.value().clone())
+                    entry.value().as_bytes().map(|value| Some(value.clone()))
                 } else {
                     drop(store);
                     let mut store = self.store.write().unwrap();
-                    store.remove(key.as_str());
-                    None
+                    self.evict_if_expired(&mut store, key.as_str());
+                    Ok(None)
                 }
             }
-            None => None,
+            None => Ok(None),
         }
     }
 
@@ -89,7 +185,8 @@ impl Cache {
                 }
 
                 log::debug!("removing key {} and value {:?}", key.clone(), entry);
-                store.remove(key.as_str());
+                self.remove_entry(&mut store, &key);
+                self.notify(Event::Del, &key);
                 Ok(())
             }
             _ => Err(Error::msg(format!("key {:?} doesn't exist", key))),
@@ -97,18 +194,175 @@ impl Cache {
     }
 
     pub async fn exists(&self, key: String) -> bool {
-        let store = self.store.write().unwrap();
+        let store = self.store.read().unwrap();
         store.contains_key(key.as_str())
     }
 
-    pub async fn monitor_for_expiry(&self) {
-        log::debug!("removing garbage in the background");
+    /// Set the expiration of `key`, returning whether it exists.
+    pub async fn expire(&self, key: String, expiry: Expiry) -> bool {
+        let mut store = self.store.write().unwrap();
+        self.evict_if_expired(&mut store, key.as_str());
+        match store.get_mut(key.as_str()) {
+            Some(entry) => {
+                log::debug!("setting the expiration of key {} to {:?}", key, expiry);
+                entry.set_expiration(expiry);
+                true
+            }
+            None => false,
+        }
+    }
 
-        let frequency = self.frequency;
-        let mut interval = Interval::platform_new(frequency);
-        loop {
-            interval.as_mut().await;
-            self.purge().await;
+    /// Retrieve the time to live of `key`, `None` if it doesn't exist and `Some(None)` if it
+    /// never expires.
+    pub async fn ttl(&self, key: String) -> Option<Option<Duration>> {
+        let store = self.store.read().unwrap();
+        match store.get(key.as_str()) {
+            Some(entry) if !entry.expiration().is_expired() => Some(entry.expiration().remaining()),
+            _ => None,
+        }
+    }
+
+    /// Run `f` against the object stored under `key`. Expired entries are treated as missing.
+    fn read_object<T, F>(&self, key: &str, f: F) -> Result<Option<T>>
+    where
+        F: FnOnce(&Object) -> Result<T>,
+    {
+        let store = self.store.read().unwrap();
+        match store.get(key) {
+            Some(entry) if !entry.expiration().is_expired() => f(entry.value()).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Run `f` against the object stored under `key`, creating it with `init` first if the key
+    /// is missing. Returns `None` if the key is missing and `init` doesn't create it.
+    ///
+    /// Members past their own expiry are removed before running `f`, and collections left empty
+    /// by `f` are removed together with their key. Writes that don't fit in the quota fail.
+    fn write_object<T, I, F>(&self, key: &str, init: I, f: F) -> Result<Option<T>>
+    where
+        I: FnOnce() -> Option<Object>,
+        F: FnOnce(&mut Object) -> Result<T>,
+    {
+        self.update_object(key, init, f, true)
+    }
+
+    /// Run `f`, which doesn't grow the object, against the object stored under `key` like
+    /// `write_object`. Returns `None` if the key is missing. It runs over the quota too, so
+    /// that a full keyspace can be freed.
+    fn shrink_object<T, F>(&self, key: &str, f: F) -> Result<Option<T>>
+    where
+        F: FnOnce(&mut Object) -> Result<T>,
+    {
+        self.update_object(key, || None, f, false)
+    }
+
+    fn update_object<T, I, F>(&self, key: &str, init: I, f: F, limited: bool) -> Result<Option<T>>
+    where
+        I: FnOnce() -> Option<Object>,
+        F: FnOnce(&mut Object) -> Result<T>,
+    {
+        let mut store = self.store.write().unwrap();
+        self.evict_if_expired(&mut store, key);
+
+        // the growth of the object is charged
+        let before = if limited {
+            self.footprint(&store, key)
+        } else {
+            0
+        };
+
+        if !store.contains_key(key) {
+            match init() {
+                Some(object) => {
+                    self.admit(&mut store, key)?;
+                    log::debug!("creating key {} with value {:?}", key, object);
+                    store.insert(key.to_owned(), Entry::new(object, self.default_expiry()));
+                }
+                None => return Ok(None),
+            }
+        } else if limited {
+            self.admit(&mut store, key)?;
+        }
+
+        let entry = store.get_mut(key).unwrap();
+        entry.value_mut().purge_expired_members();
+        let result = f(entry.value_mut());
+        if entry.value().is_empty() {
+            log::debug!("removing emptied key {}", key);
+            self.remove_entry(&mut store, key);
+        }
+        if limited {
+            self.charge(self.footprint(&store, key).saturating_sub(before));
+        }
+        self.reindex(key, store.get(key).map(Entry::value));
+        result.map(Some)
+    }
+
+    /// Store `object` under `key`, failing if the key already exists.
+    fn create_object(&self, key: String, object: Object) -> Result<()> {
+        let mut store = self.store.write().unwrap();
+        self.evict_if_expired(&mut store, &key);
+        if store.contains_key(&key) {
+            return Err(Error::msg("item exists"));
+        }
+        self.admit(&mut store, &key)?;
+        self.charge(key.len() + object.memory_usage());
+
+        log::debug!("creating key {} with value {:?}", key, object);
+        store.insert(key, Entry::new(object, self.default_expiry()));
+        Ok(())
+    }
+
+    /// Store `entry` under `key` in place of the entry there, as a new version, keeping the
+    /// vector and secondary indexes in step.
+    fn place(&self, store: &mut BTreeMap<String, Entry>, key: String, mut entry: Entry) {
+        entry.touch();
+        self.remove_entry(store, &key);
+        self.index_placed(&key, entry.value());
+        self.reindex(&key, Some(entry.value()));
+        self.retain_placed(&key, entry.value());
+        store.insert(key, entry);
+    }
+
+    /// Remove the entry under `key` from the store, and from the vector and secondary indexes.
+    /// Every single key leaves the store through here, or through `forget_removed` for many.
+    fn remove_entry(&self, store: &mut BTreeMap<String, Entry>, key: &str) -> Option<Entry> {
+        let removed = [(key.to_owned(), store.remove(key)?)];
+        self.forget_removed(&removed);
+        let [(_, entry)] = removed;
+        Some(entry)
+    }
+
+    /// Remove the entries removed from the store from the vector and secondary indexes, and
+    /// count the removal of the watched ones.
+    fn forget_removed(&self, removed: &[(String, Entry)]) {
+        self.unindex_removed(removed);
+        for (key, _) in removed {
+            self.reindex(key, None);
+        }
+        self.count_removed(removed);
+    }
+
+    /// Remove the entry under `key` if it has expired.
+    fn evict_if_expired(&self, store: &mut BTreeMap<String, Entry>, key: &str) {
+        if store
+            .get(key)
+            .map(|entry| entry.expiration().is_expired())
+            .unwrap_or(false)
+        {
+            self.remove_entry(store, key);
+            self.notify(Event::Expired, key);
+        }
+    }
+
+    /// Remove the expired keys the sampler finds, trim the time series out of their retention
+    /// period and measure the memory of a keyspace limited in memory.
+    pub async fn sweep(&self) {
+        self.purge().await;
+        self.trim_time_series(now_millis()).await;
+        if self.is_memory_limited() {
+            self.measure_memory().await;
         }
     }
 
@@ -135,6 +389,7 @@ impl Cache {
             let mut gone = 0;
 
             let mut expired_keys = Vec::with_capacity(sample);
+            let mut trimmed_keys = Vec::new();
             let mut indices: BTreeSet<usize> = BTreeSet::new();
 
             {
@@ -167,8 +422,12 @@ impl Cache {
                     // fetch the next pair (at our index)
                     let (key, entry) = iter.next().unwrap();
 
-                    // skip if not expired
+                    // collections with expired members are trimmed rather than removed
                     if !entry.expiration().is_expired() {
+                        if entry.value().has_expired_members() {
+                            trimmed_keys.push(key.to_owned());
+                            gone += 1;
+                        }
                         continue;
                     }
 
@@ -189,10 +448,34 @@ impl Cache {
 
                 let mut store = self.store.write().unwrap();
 
-                // remove all expired keys
-                for key in &expired_keys {
-                    store.remove(key);
+                // remove all expired keys that weren't written since, and their documents from
+                // the indexes
+                let mut removed = expired_keys
+                    .into_iter()
+                    .filter_map(|key| {
+                        let expired = store
+                            .get(&key)
+                            .is_some_and(|entry| entry.expiration().is_expired());
+                        expired.then(|| (key.clone(), store.remove(&key).unwrap()))
+                    })
+                    .collect::<Vec<_>>();
+                for (key, _) in &removed {
+                    self.notify(Event::Expired, key);
+                }
+
+                // drop the expired members, and the collections they leave empty
+                for key in trimmed_keys {
+                    let Some(entry) = store.get_mut(&key) else {
+                        continue;
+                    };
+                    entry.value_mut().purge_expired_members();
+                    if entry.value().is_empty() {
+                        removed.extend(store.remove(&key).map(|entry| (key, entry)));
+                    } else {
+                        self.reindex(&key, Some(entry.value()));
+                    }
                 }
+                self.forget_removed(&removed);
 
                 // increment the lock timer tracking directly
                 locked = locked.checked_add(acquired.elapsed()).unwrap();
@@ -220,14 +503,16 @@ impl Cache {
         );
     }
 
+    #[cfg(test)]
     pub async fn len(&self) -> usize {
         let store = self.store.read().unwrap();
         store.len()
     }
 
+    #[cfg(test)]
     pub async fn is_empty(&self) -> bool {
         let store = self.store.read().unwrap();
-        return store.is_empty();
+        store.is_empty()
     }
 
     pub async fn existing(&self) -> usize {
@@ -238,6 +523,7 @@ impl Cache {
             .count()
     }
 
+    #[cfg(test)]
     pub async fn expired(&self) -> usize {
         let store = self.store.read().unwrap();
         store
@@ -246,6 +532,7 @@ impl Cache {
             .count()
     }
 
+    #[cfg(test)]
     pub async fn clear(&self) {
         let mut store = self.store.write().unwrap();
         store.clear();
@@ -254,14 +541,15 @@ impl Cache {
 
 impl Default for Cache {
     fn default() -> Cache {
-        Cache::new(25, 0.25, Duration::from_secs(1))
+        Cache::new(25, 0.25)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use async_std::task;
+    use crate::cache::databases::Databases;
+    use crate::cache::tenants::Tenants;
     use std::sync::Arc;
     use std::thread::sleep;
 
@@ -271,7 +559,7 @@ mod tests {
         let key = "key".to_string();
         let value = "value".to_string();
 
-        cache.set(key.clone(), value.clone()).await;
+        cache.set(key.clone(), value.clone()).await.unwrap();
         let result = cache.get(key.clone()).await;
         assert_eq!(result, Some(value.clone()));
     }
@@ -282,7 +570,8 @@ mod tests {
         let expiry = Expiry::new(Instant::now() + Duration::from_secs(2));
         cache
             .set_with_expiry("key".to_string(), "value".to_string(), expiry.clone())
-            .await;
+            .await
+            .unwrap();
         let count = cache.expired().await;
         assert_eq!(count, 0);
     }
@@ -293,7 +582,8 @@ mod tests {
         let expiry = Expiry::new(Instant::now());
         cache
             .set_with_expiry("key".to_string(), "value".to_string(), expiry.clone())
-            .await;
+            .await
+            .unwrap();
         let count = cache.expired().await;
         assert_eq!(count, 1);
     }
@@ -304,7 +594,8 @@ mod tests {
         let expiry = Expiry::new(Instant::now());
         cache
             .set_with_expiry("key".to_string(), "value".to_string(), expiry.clone())
-            .await;
+            .await
+            .unwrap();
         assert_eq!(cache.len().await, 1);
     }
 
@@ -314,7 +605,8 @@ mod tests {
         let expiry = Expiry::new(Instant::now());
         cache
             .set_with_expiry("key".to_string(), "value".to_string(), expiry.clone())
-            .await;
+            .await
+            .unwrap();
         cache.clear().await;
         assert_eq!(cache.len().await, 0);
     }
@@ -325,7 +617,8 @@ mod tests {
         let expiry = Expiry::new(Instant::now());
         cache
             .set_with_expiry("key".to_string(), "value".to_string(), expiry.clone())
-            .await;
+            .await
+            .unwrap();
         cache.clear().await;
         assert!(cache.is_empty().await);
     }
@@ -339,7 +632,8 @@ mod tests {
         let expiry = Expiry::new(Instant::now() + Duration::from_secs(2));
         cache
             .set_with_expiry(key.clone(), value.clone(), expiry.clone())
-            .await;
+            .await
+            .unwrap();
         let result = cache.get(key.clone()).await;
         assert_eq!(result, Some(value.clone()));
 
@@ -359,7 +653,8 @@ mod tests {
         let expiry = Expiry::new(Instant::now() + Duration::from_secs(5));
         cache
             .set_with_expiry(key.clone(), value.clone(), expiry.clone())
-            .await;
+            .await
+            .unwrap();
         let result = cache.get(key.clone()).await;
         assert_eq!(result, Some(value.clone()));
     }
@@ -373,7 +668,8 @@ mod tests {
         let expiry1 = Expiry::new(Instant::now() + Duration::from_secs(2));
         cache
             .set_with_expiry(key.clone(), value.clone(), expiry1.clone())
-            .await;
+            .await
+            .unwrap();
         let result1 = cache.get(key.clone()).await;
         assert_eq!(result1, Some(value.clone()));
 
@@ -381,7 +677,8 @@ mod tests {
         let expiry2 = Expiry::new(Instant::now() + Duration::from_secs(5));
         cache
             .set_with_expiry(key.clone(), value.clone(), expiry2.clone())
-            .await;
+            .await
+            .unwrap();
         let result2 = cache.get(key.clone()).await;
         assert_eq!(result2, Some(value.clone()));
 
@@ -405,7 +702,7 @@ mod tests {
         let cache = Cache::default();
         let key = "key".to_string();
         let value = "value".to_string();
-        cache.set(key.clone(), value.clone()).await;
+        cache.set(key.clone(), value.clone()).await.unwrap();
         let result = cache.get(key.clone()).await.unwrap();
         assert_eq!(result, value.to_string());
         _ = cache.remove(key.clone()).await;
@@ -427,42 +724,46 @@ mod tests {
         let expiry = Expiry::new(Instant::now() + Duration::from_secs(2));
         cache
             .set_with_expiry("key".to_string(), "value".to_string(), expiry.clone())
-            .await;
+            .await
+            .unwrap();
         let count = cache.existing().await;
         assert_eq!(count, 1);
     }
 
     #[tokio::test]
     async fn test_purge_empty_cache() {
-        let cache = Cache::new(10, 0.5, Duration::from_secs(1));
+        let cache = Cache::new(10, 0.5);
         cache.purge().await;
         assert_eq!(cache.len().await, 0);
     }
 
     #[tokio::test]
     async fn test_purge_expired_keys() {
-        let cache = Cache::new(10, 0.5, Duration::from_millis(1));
+        let cache = Cache::new(10, 0.5);
         cache
             .set_with_expiry(
                 "key1".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(1),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key2".to_string(),
                 "value2".to_string(),
                 Duration::from_secs(2),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key3".to_string(),
                 "value3".to_string(),
                 Duration::from_secs(3),
             )
-            .await;
+            .await
+            .unwrap();
         sleep(Duration::from_secs(2));
         cache.purge().await;
         assert_eq!(cache.len().await, 1);
@@ -476,28 +777,31 @@ mod tests {
 
     #[tokio::test]
     async fn test_expiry_formats() {
-        let cache = Cache::new(10, 0.5, Duration::from_millis(1));
+        let cache = Cache::new(10, 0.5);
         cache
             .set_with_expiry(
                 "key1".to_string(),
                 "value1".to_string(),
                 (10u64, &"PX".to_string()),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key2".to_string(),
                 "value2".to_string(),
                 (1u64, &"EX".to_string()),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key3".to_string(),
                 "value3".to_string(),
                 (3u64, &"EX".to_string()),
             )
-            .await;
+            .await
+            .unwrap();
         sleep(Duration::from_secs(2));
         cache.purge().await;
         assert_eq!(cache.len().await, 1);
@@ -511,16 +815,18 @@ mod tests {
 
     #[tokio::test]
     async fn test_purge_all_expired_entries() {
-        let cache = Cache::new(2, 0.5, Duration::from_secs(1));
+        let cache = Cache::new(2, 0.5);
         let key1 = "key1".to_string();
         let key2 = "key2".to_string();
 
         cache
             .set_with_expiry(key1.clone(), "value1".to_string(), Duration::from_secs(0))
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(key2.clone(), "value2".to_string(), Duration::from_secs(0))
-            .await;
+            .await
+            .unwrap();
 
         // wait for the entries to expire
         sleep(Duration::from_millis(100));
@@ -533,20 +839,23 @@ mod tests {
 
     #[tokio::test]
     async fn test_purge_some_expired_entries() {
-        let cache = Cache::new(3, 0.5, Duration::from_secs(1));
+        let cache = Cache::new(3, 0.5);
         let key1 = "key1".to_string();
         let key2 = "key2".to_string();
         let key3 = "key3".to_string();
 
         cache
             .set_with_expiry(key1.clone(), "value1".to_string(), Duration::from_secs(0))
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(key2.clone(), "value2".to_string(), Duration::from_secs(0))
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(key3.clone(), "value3".to_string(), Duration::from_secs(60))
-            .await;
+            .await
+            .unwrap();
 
         // wait for the entries to expire
         sleep(Duration::from_millis(100));
@@ -561,10 +870,10 @@ mod tests {
         assert_eq!(cache.get(key3.clone()).await, Some("value3".to_string()));
     }
 
-    #[async_std::test]
+    #[tokio::test(flavor = "multi_thread")]
     async fn test_monitor() {
-        let cache = Arc::new(Cache::new(10, 0.5, Duration::from_millis(100)));
-        let clone = cache.clone();
+        let cache = Arc::new(Cache::new(10, 0.5));
+        let databases = Databases::from(cache.clone());
         // Insert some values with an expiry time of 3 seconds
         cache
             .set_with_expiry(
@@ -572,217 +881,246 @@ mod tests {
                 "value1".to_string(),
                 Duration::from_secs(3),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key2".to_string(),
                 "value2".to_string(),
                 Duration::from_secs(2),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key3".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(1),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key4".to_string(),
                 "value2".to_string(),
                 Duration::from_secs(1),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key5".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(2),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key6".to_string(),
                 "value2".to_string(),
                 Duration::from_secs(2),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key7".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(3),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key8".to_string(),
                 "value2".to_string(),
                 Duration::from_secs(2),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key9".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(3),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key11".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(3),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key12".to_string(),
                 "value2".to_string(),
                 Duration::from_secs(2),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key13".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(1),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key14".to_string(),
                 "value2".to_string(),
                 Duration::from_secs(1),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key15".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(2),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key16".to_string(),
                 "value2".to_string(),
                 Duration::from_secs(2),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key17".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(3),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key18".to_string(),
                 "value2".to_string(),
                 Duration::from_secs(2),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key19".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(4),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key21".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(3),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key22".to_string(),
                 "value2".to_string(),
                 Duration::from_secs(2),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key23".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(1),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key24".to_string(),
                 "value2".to_string(),
                 Duration::from_secs(1),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key25".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(2),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key26".to_string(),
                 "value2".to_string(),
                 Duration::from_secs(2),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key27".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(4),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key28".to_string(),
                 "value2".to_string(),
                 Duration::from_secs(2),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key29".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(3),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key31".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(3),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key39".to_string(),
                 "value1".to_string(),
                 Duration::from_secs(6),
             )
-            .await;
+            .await
+            .unwrap();
         cache
             .set_with_expiry(
                 "key10".to_string(),
                 "value2".to_string(),
                 Duration::from_secs(7),
             )
-            .await;
+            .await
+            .unwrap();
 
-        task::spawn(async move {
-            clone.monitor_for_expiry().await;
-        });
+        let frequency = Duration::from_millis(100);
+        tokio::spawn(sweeper::monitor(databases, Tenants::default(), frequency));
 
         // Sleep for 5 seconds to allow the monitoring task to run
-        sleep(Duration::from_secs(5));
+        tokio::time::sleep(Duration::from_secs(5)).await;
 
         // Check that the expired keys were removed
         assert_eq!(2, cache.len().await);
@@ -0,0 +1,287 @@
+use crate::cache::pubsub::PubSub;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+/// `K`, events published on `__keyspace@<db>__:<key>` with the event as payload.
+const KEYSPACE: u8 = 1;
+/// `E`, events published on `__keyevent@<db>__:<event>` with the key as payload.
+const KEYEVENT: u8 = 1 << 1;
+/// `g`, generic commands like DEL and RENAME.
+const GENERIC: u8 = 1 << 2;
+/// `$`, string commands.
+const STRING: u8 = 1 << 3;
+/// `x`, keys expiring.
+const EXPIRED: u8 = 1 << 4;
+/// `e`, keys evicted to free memory.
+const EVICTED: u8 = 1 << 5;
+/// `A`, every class of events.
+const ALL: u8 = GENERIC | STRING | EXPIRED | EVICTED;
+
+/// An event happening to a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Set,
+    Del,
+    /// The key expired, removed either by the expiry sampler or when accessed.
+    Expired,
+    /// The key was removed to free memory, by a keyspace over its quota whose eviction policy
+    /// evicts keys.
+    Evicted,
+    /// The key was renamed, fired for the source key.
+    RenameFrom,
+    /// The key was renamed, fired for the destination key.
+    RenameTo,
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Set => "set",
+            Event::Del => "del",
+            Event::Expired => "expired",
+            Event::Evicted => "evicted",
+            Event::RenameFrom => "rename_from",
+            Event::RenameTo => "rename_to",
+        }
+    }
+
+    fn class(&self) -> u8 {
+        match self {
+            Event::Set => STRING,
+            Event::Del | Event::RenameFrom | Event::RenameTo => GENERIC,
+            Event::Expired => EXPIRED,
+            Event::Evicted => EVICTED,
+        }
+    }
+}
+
+/// The keyspace notifications of a server, configured like `notify-keyspace-events` and
+/// published through the channels of its `PubSub`. Cloning only clones a handle, the
+/// configuration is shared.
+///
+/// Notifications are disabled until configured, and only the numbered databases publish them:
+/// tenant keyspaces would leak their key names to the subscribers of other tenants.
+#[derive(Clone, Debug)]
+pub struct Notifications {
+    pubsub: PubSub,
+    flags: Arc<AtomicU8>,
+}
+
+impl Notifications {
+    pub fn new(pubsub: PubSub) -> Self {
+        Notifications {
+            pubsub,
+            flags: Arc::new(AtomicU8::new(0)),
+        }
+    }
+
+    pub fn pubsub(&self) -> &PubSub {
+        &self.pubsub
+    }
+
+    /// Configure the events published from `flags`, made of `K`, `E`, `g`, `$`, `x`, `e` and
+    /// `A`. Nothing is published unless `K` or `E` is set, the empty string disabling
+    /// notifications.
+    pub fn configure(&self, flags: &str) -> Result<()> {
+        let mut parsed = 0;
+        for flag in flags.chars() {
+            parsed |= match flag {
+                'K' => KEYSPACE,
+                'E' => KEYEVENT,
+                'g' => GENERIC,
+                '$' => STRING,
+                'x' => EXPIRED,
+                'e' => EVICTED,
+                'A' => ALL,
+                _ => {
+                    return Err(Error::msg(format!(
+                        "unsupported keyspace notification class {:?}",
+                        flag
+                    )))
+                }
+            };
+        }
+        self.flags.store(parsed, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Retrieve the configured flags, with `A` standing for every class.
+    pub fn flags(&self) -> String {
+        let flags = self.flags.load(Ordering::Relaxed);
+        let mut configured = String::new();
+        if flags & ALL == ALL {
+            configured.push('A');
+        } else {
+            for (flag, class) in [
+                ('g', GENERIC),
+                ('$', STRING),
+                ('x', EXPIRED),
+                ('e', EVICTED),
+            ] {
+                if flags & class != 0 {
+                    configured.push(flag);
+                }
+            }
+        }
+        for (flag, kind) in [('K', KEYSPACE), ('E', KEYEVENT)] {
+            if flags & kind != 0 {
+                configured.push(flag);
+            }
+        }
+        configured
+    }
+
+    /// Publish `event` happening to `key` in database `db`, if its class is configured.
+    fn notify(&self, db: usize, event: Event, key: &str) {
+        let flags = self.flags.load(Ordering::Relaxed);
+        if flags & event.class() == 0 {
+            return;
+        }
+        if flags & KEYSPACE != 0 {
+            let channel = format!("__keyspace@{}__:{}", db, key);
+            self.pubsub.publish(&channel, event.name());
+        }
+        if flags & KEYEVENT != 0 {
+            let channel = format!("__keyevent@{}__:{}", db, event.name());
+            self.pubsub.publish(&channel, key);
+        }
+    }
+}
+
+/// The notifications a keyspace publishes, as the database it is numbered.
+#[derive(Debug)]
+pub(super) struct Notifier {
+    notifications: Notifications,
+    db: usize,
+}
+
+impl Cache {
+    /// Publish the events of this keyspace through `notifications`, as database `db`.
+    pub fn notify_through(&self, notifications: Notifications, db: usize) {
+        *self.notifier.write().unwrap() = Some(Notifier { notifications, db });
+    }
+
+    /// Number this keyspace `db` in its notifications, once swapped with another database.
+    pub(super) fn renumber(&self, db: usize) {
+        if let Some(notifier) = self.notifier.write().unwrap().as_mut() {
+            notifier.db = db;
+        }
+    }
+
+    /// Publish `event` happening to `key`, if this keyspace publishes its events.
+    pub(super) fn notify(&self, event: Event, key: &str) {
+        if let Some(notifier) = self.notifier.read().unwrap().as_ref() {
+            notifier.notifications.notify(notifier.db, event, key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::pubsub::{Message, Subscriber};
+    use crate::cache::quota::{Eviction, Quota};
+    use std::time::Duration;
+
+    async fn next(subscriber: &mut Subscriber) -> (String, String) {
+        let Message {
+            channel, payload, ..
+        } = subscriber.recv().await.unwrap();
+        (channel, payload)
+    }
+
+    fn event(channel: &str, payload: &str) -> (String, String) {
+        (channel.to_string(), payload.to_string())
+    }
+
+    #[test]
+    fn test_configure() -> Result<()> {
+        let notifications = Notifications::new(PubSub::default());
+        assert_eq!(notifications.flags(), "");
+        notifications.configure("Kx$")?;
+        assert_eq!(notifications.flags(), "$xK");
+        notifications.configure("AKE")?;
+        assert_eq!(notifications.flags(), "AKE");
+        assert!(notifications.configure("Kl").is_err());
+        assert_eq!(notifications.flags(), "AKE");
+        notifications.configure("Kex")?;
+        assert_eq!(notifications.flags(), "xeK");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_key_events() -> Result<()> {
+        let notifications = Notifications::new(PubSub::default());
+        notifications.configure("KEA")?;
+        let cache = Cache::default();
+        cache.notify_through(notifications.clone(), 3);
+        let mut subscriber = notifications.pubsub().subscriber();
+        subscriber.psubscribe("__keyspace@3__:*".to_string());
+        subscriber.subscribe("__keyevent@3__:expired".to_string());
+
+        cache.set("a".to_string(), "1".to_string()).await?;
+        assert_eq!(
+            next(&mut subscriber).await,
+            event("__keyspace@3__:a", "set")
+        );
+        cache.rename("a".to_string(), "b".to_string(), true).await?;
+        assert_eq!(
+            next(&mut subscriber).await,
+            event("__keyspace@3__:a", "rename_from")
+        );
+        assert_eq!(
+            next(&mut subscriber).await,
+            event("__keyspace@3__:b", "rename_to")
+        );
+        cache.remove("b".to_string()).await?;
+        assert_eq!(
+            next(&mut subscriber).await,
+            event("__keyspace@3__:b", "del")
+        );
+
+        // expired keys are notified when accessed
+        cache
+            .set_with_expiry("session".to_string(), "1".to_string(), Duration::ZERO)
+            .await?;
+        next(&mut subscriber).await;
+        assert_eq!(cache.get("session".to_string()).await, None);
+        assert_eq!(
+            next(&mut subscriber).await,
+            event("__keyspace@3__:session", "expired")
+        );
+        assert_eq!(
+            next(&mut subscriber).await,
+            event("__keyevent@3__:expired", "session")
+        );
+
+        // and by the sampler
+        notifications.configure("Ex")?;
+        cache
+            .set_with_expiry("other".to_string(), "1".to_string(), Duration::ZERO)
+            .await?;
+        cache.purge().await;
+        assert_eq!(
+            next(&mut subscriber).await,
+            event("__keyevent@3__:expired", "other")
+        );
+
+        // and evicted keys when making room
+        notifications.configure("Ee")?;
+        subscriber.subscribe("__keyevent@3__:evicted".to_string());
+        let quota = Quota {
+            max_memory: Some(10),
+            eviction: Eviction::AllKeysRandom,
+            ..Quota::default()
+        };
+        cache.set_quota(quota).await;
+        cache.set("large".to_string(), "x".repeat(20)).await?;
+        cache.set("small".to_string(), "1".to_string()).await?;
+        assert_eq!(
+            next(&mut subscriber).await,
+            event("__keyevent@3__:evicted", "large")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,562 @@
+use crate::cache::bloom::BloomFilter;
+use crate::cache::count_min::CountMinSketch;
+use crate::cache::cuckoo::CuckooFilter;
+use crate::cache::graph::Graph;
+use crate::cache::hash::Hash;
+use crate::cache::hyperloglog::HyperLogLog;
+use crate::cache::list::List;
+use crate::cache::set::Set;
+use crate::cache::sorted_set::SortedSet;
+use crate::cache::stream::Stream;
+use crate::cache::suggestion::Suggestions;
+use crate::cache::tdigest::TDigest;
+use crate::cache::time_series::TimeSeries;
+use crate::cache::top_k::TopK;
+use crate::cache::vector::Embedding;
+use anyhow::{Error, Result};
+use serde_json::Value as Json;
+
+/// Error returned when a command runs against a key holding another data type.
+pub const WRONG_TYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+/// The value stored in a cache entry, one variant per supported data type.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Object {
+    /// Strings are binary safe, bitmap commands work on their raw bytes.
+    String(Vec<u8>),
+    List(List),
+    Hash(Hash),
+    Set(Set),
+    SortedSet(SortedSet),
+    HyperLogLog(HyperLogLog),
+    BloomFilter(BloomFilter),
+    CuckooFilter(CuckooFilter),
+    CountMinSketch(CountMinSketch),
+    TopK(TopK),
+    TDigest(TDigest),
+    /// JSON documents are kept parsed so that paths can be updated in place.
+    Json(Json),
+    TimeSeries(TimeSeries),
+    Embedding(Embedding),
+    Suggestions(Suggestions),
+    Graph(Graph),
+    Stream(Stream),
+}
+
+impl Object {
+    /// Retrieve whether this object is an empty collection. Empty collections are never kept
+    /// in the store, their key is removed instead.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Object::String(_) => false,
+            Object::List(list) => list.is_empty(),
+            Object::Hash(hash) => hash.is_empty(),
+            Object::Set(set) => set.is_empty(),
+            Object::SortedSet(set) => set.is_empty(),
+            // like in Redis, a stream outlives its entries
+            Object::Stream(_) => false,
+            Object::HyperLogLog(_) => false,
+            // filters are reserved with their settings, which deleting items must not lose
+            Object::BloomFilter(_) | Object::CuckooFilter(_) => false,
+            Object::CountMinSketch(_) | Object::TopK(_) | Object::TDigest(_) => false,
+            Object::Json(_) => false,
+            // a series keeps its retention, labels and rules once its samples expire
+            Object::TimeSeries(_) => false,
+            Object::Embedding(_) => false,
+            Object::Suggestions(suggestions) => suggestions.is_empty(),
+            Object::Graph(graph) => graph.is_empty(),
+        }
+    }
+
+    /// Retrieve the name of the data type of this object, as reported by TYPE.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::String(_) => "string",
+            Object::List(_) => "list",
+            Object::Hash(_) => "hash",
+            Object::Set(_) => "set",
+            Object::SortedSet(_) => "zset",
+            Object::HyperLogLog(_) => "hyperloglog",
+            Object::BloomFilter(_) => "bloom",
+            Object::CuckooFilter(_) => "cuckoo",
+            Object::CountMinSketch(_) => "cms",
+            Object::TopK(_) => "topk",
+            Object::TDigest(_) => "tdigest",
+            Object::Json(_) => "json",
+            Object::TimeSeries(_) => "timeseries",
+            Object::Embedding(_) => "vector",
+            Object::Suggestions(_) => "suggestions",
+            Object::Graph(_) => "graph",
+            Object::Stream(_) => "stream",
+        }
+    }
+
+    /// Retrieve whether this object holds members past their own expiry, which are still
+    /// waiting to be removed.
+    pub fn has_expired_members(&self) -> bool {
+        match self {
+            Object::Hash(hash) => hash.fields().has_expired(),
+            Object::Set(set) => set.members().has_expired(),
+            _ => false,
+        }
+    }
+
+    /// Remove the members past their own expiry, returning how many there were.
+    pub fn purge_expired_members(&mut self) -> usize {
+        match self {
+            Object::Hash(hash) => hash.fields_mut().purge_expired(),
+            Object::Set(set) => set.members_mut().purge_expired(),
+            _ => 0,
+        }
+    }
+
+    /// Retrieve an estimate of the memory used by this object, in bytes. Only the data held is
+    /// accounted for, not the allocator overhead.
+    pub fn memory_usage(&self) -> usize {
+        let string = std::mem::size_of::<String>();
+        match self {
+            Object::String(value) => value.len(),
+            Object::List(list) => list.iter().map(|value| value.len() + string).sum(),
+            Object::Hash(hash) => hash.memory_usage(),
+            Object::Set(set) => set.memory_usage(),
+            Object::SortedSet(set) => set.memory_usage(),
+            Object::HyperLogLog(hll) => hll.memory_usage(),
+            Object::BloomFilter(filter) => filter.memory_usage(),
+            Object::CuckooFilter(filter) => filter.memory_usage(),
+            Object::CountMinSketch(sketch) => sketch.memory_usage(),
+            Object::TopK(top_k) => top_k.memory_usage(),
+            Object::TDigest(digest) => digest.memory_usage(),
+            // as serialized, which is close to what the parsed document holds
+            Object::Json(json) => json.to_string().len(),
+            Object::TimeSeries(series) => series.memory_usage(),
+            Object::Embedding(embedding) => {
+                let metadata = embedding.metadata.iter();
+                let metadata = metadata.map(|(k, v)| k.len() + v.len()).sum::<usize>();
+                embedding.vector.len() * std::mem::size_of::<f32>()
+                    + embedding.index.len()
+                    + metadata
+            }
+            Object::Suggestions(suggestions) => suggestions.memory_usage(),
+            Object::Graph(graph) => graph.memory_usage(),
+            Object::Stream(stream) => stream.memory_usage(),
+        }
+    }
+
+    /// Retrieve the bytes of the internal string.
+    pub fn as_bytes(&self) -> Result<&Vec<u8>> {
+        match self {
+            Object::String(value) => Ok(value),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable bytes of the internal string.
+    pub fn as_bytes_mut(&mut self) -> Result<&mut Vec<u8>> {
+        match self {
+            Object::String(value) => Ok(value),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal list.
+    pub fn as_list(&self) -> Result<&List> {
+        match self {
+            Object::List(list) => Ok(list),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal list.
+    pub fn as_list_mut(&mut self) -> Result<&mut List> {
+        match self {
+            Object::List(list) => Ok(list),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal sorted set.
+    pub fn as_sorted_set(&self) -> Result<&SortedSet> {
+        match self {
+            Object::SortedSet(set) => Ok(set),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal sorted set.
+    pub fn as_sorted_set_mut(&mut self) -> Result<&mut SortedSet> {
+        match self {
+            Object::SortedSet(set) => Ok(set),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal HyperLogLog sketch.
+    pub fn as_hyperloglog(&self) -> Result<&HyperLogLog> {
+        match self {
+            Object::HyperLogLog(sketch) => Ok(sketch),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal HyperLogLog sketch.
+    pub fn as_hyperloglog_mut(&mut self) -> Result<&mut HyperLogLog> {
+        match self {
+            Object::HyperLogLog(sketch) => Ok(sketch),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal Bloom filter.
+    pub fn as_bloom_filter(&self) -> Result<&BloomFilter> {
+        match self {
+            Object::BloomFilter(filter) => Ok(filter),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal Bloom filter.
+    pub fn as_bloom_filter_mut(&mut self) -> Result<&mut BloomFilter> {
+        match self {
+            Object::BloomFilter(filter) => Ok(filter),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal cuckoo filter.
+    pub fn as_cuckoo_filter(&self) -> Result<&CuckooFilter> {
+        match self {
+            Object::CuckooFilter(filter) => Ok(filter),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal cuckoo filter.
+    pub fn as_cuckoo_filter_mut(&mut self) -> Result<&mut CuckooFilter> {
+        match self {
+            Object::CuckooFilter(filter) => Ok(filter),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal Count-Min sketch.
+    pub fn as_count_min_sketch(&self) -> Result<&CountMinSketch> {
+        match self {
+            Object::CountMinSketch(sketch) => Ok(sketch),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal Count-Min sketch.
+    pub fn as_count_min_sketch_mut(&mut self) -> Result<&mut CountMinSketch> {
+        match self {
+            Object::CountMinSketch(sketch) => Ok(sketch),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal top-k list.
+    pub fn as_top_k(&self) -> Result<&TopK> {
+        match self {
+            Object::TopK(top_k) => Ok(top_k),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal top-k list.
+    pub fn as_top_k_mut(&mut self) -> Result<&mut TopK> {
+        match self {
+            Object::TopK(top_k) => Ok(top_k),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal hash.
+    pub fn as_hash(&self) -> Result<&Hash> {
+        match self {
+            Object::Hash(hash) => Ok(hash),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal hash.
+    pub fn as_hash_mut(&mut self) -> Result<&mut Hash> {
+        match self {
+            Object::Hash(hash) => Ok(hash),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal set.
+    pub fn as_set(&self) -> Result<&Set> {
+        match self {
+            Object::Set(set) => Ok(set),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal set.
+    pub fn as_set_mut(&mut self) -> Result<&mut Set> {
+        match self {
+            Object::Set(set) => Ok(set),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal suggestion dictionary.
+    pub fn as_suggestions(&self) -> Result<&Suggestions> {
+        match self {
+            Object::Suggestions(suggestions) => Ok(suggestions),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal suggestion dictionary.
+    pub fn as_suggestions_mut(&mut self) -> Result<&mut Suggestions> {
+        match self {
+            Object::Suggestions(suggestions) => Ok(suggestions),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal graph.
+    pub fn as_graph(&self) -> Result<&Graph> {
+        match self {
+            Object::Graph(graph) => Ok(graph),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal graph.
+    pub fn as_graph_mut(&mut self) -> Result<&mut Graph> {
+        match self {
+            Object::Graph(graph) => Ok(graph),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal t-digest.
+    pub fn as_tdigest(&self) -> Result<&TDigest> {
+        match self {
+            Object::TDigest(digest) => Ok(digest),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal t-digest.
+    pub fn as_tdigest_mut(&mut self) -> Result<&mut TDigest> {
+        match self {
+            Object::TDigest(digest) => Ok(digest),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal JSON document.
+    pub fn as_json(&self) -> Result<&Json> {
+        match self {
+            Object::Json(json) => Ok(json),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal JSON document.
+    pub fn as_json_mut(&mut self) -> Result<&mut Json> {
+        match self {
+            Object::Json(json) => Ok(json),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal time series.
+    pub fn as_time_series(&self) -> Result<&TimeSeries> {
+        match self {
+            Object::TimeSeries(series) => Ok(series),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal time series.
+    pub fn as_time_series_mut(&mut self) -> Result<&mut TimeSeries> {
+        match self {
+            Object::TimeSeries(series) => Ok(series),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal embedding.
+    pub fn as_embedding(&self) -> Result<&Embedding> {
+        match self {
+            Object::Embedding(embedding) => Ok(embedding),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the internal stream.
+    pub fn as_stream(&self) -> Result<&Stream> {
+        match self {
+            Object::Stream(stream) => Ok(stream),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+
+    /// Retrieve the mutable internal stream.
+    pub fn as_stream_mut(&mut self) -> Result<&mut Stream> {
+        match self {
+            Object::Stream(stream) => Ok(stream),
+            _ => Err(Error::msg(WRONG_TYPE)),
+        }
+    }
+}
+
+// Automatic conversation from `String`.
+impl From<String> for Object {
+    fn from(value: String) -> Self {
+        Object::String(value.into_bytes())
+    }
+}
+
+// Automatic conversation from raw bytes.
+impl From<Vec<u8>> for Object {
+    fn from(value: Vec<u8>) -> Self {
+        Object::String(value)
+    }
+}
+
+// Automatic conversation from `List`.
+impl From<List> for Object {
+    fn from(list: List) -> Self {
+        Object::List(list)
+    }
+}
+
+// Automatic conversation from `SortedSet`.
+impl From<SortedSet> for Object {
+    fn from(set: SortedSet) -> Self {
+        Object::SortedSet(set)
+    }
+}
+
+// Automatic conversation from `Stream`.
+impl From<Stream> for Object {
+    fn from(stream: Stream) -> Self {
+        Object::Stream(stream)
+    }
+}
+
+// Automatic conversation from `HyperLogLog`.
+impl From<HyperLogLog> for Object {
+    fn from(sketch: HyperLogLog) -> Self {
+        Object::HyperLogLog(sketch)
+    }
+}
+
+// Automatic conversation from `BloomFilter`.
+impl From<BloomFilter> for Object {
+    fn from(filter: BloomFilter) -> Self {
+        Object::BloomFilter(filter)
+    }
+}
+
+// Automatic conversation from `CuckooFilter`.
+impl From<CuckooFilter> for Object {
+    fn from(filter: CuckooFilter) -> Self {
+        Object::CuckooFilter(filter)
+    }
+}
+
+// Automatic conversation from `CountMinSketch`.
+impl From<CountMinSketch> for Object {
+    fn from(sketch: CountMinSketch) -> Self {
+        Object::CountMinSketch(sketch)
+    }
+}
+
+// Automatic conversation from `Hash`.
+impl From<Hash> for Object {
+    fn from(hash: Hash) -> Self {
+        Object::Hash(hash)
+    }
+}
+
+// Automatic conversation from `Set`.
+impl From<Set> for Object {
+    fn from(set: Set) -> Self {
+        Object::Set(set)
+    }
+}
+
+// Automatic conversation from `Suggestions`.
+impl From<Suggestions> for Object {
+    fn from(suggestions: Suggestions) -> Self {
+        Object::Suggestions(suggestions)
+    }
+}
+
+// Automatic conversation from `Graph`.
+impl From<Graph> for Object {
+    fn from(graph: Graph) -> Self {
+        Object::Graph(graph)
+    }
+}
+
+// Automatic conversation from `TopK`.
+impl From<TopK> for Object {
+    fn from(top_k: TopK) -> Self {
+        Object::TopK(top_k)
+    }
+}
+
+// Automatic conversation from `TDigest`.
+impl From<TDigest> for Object {
+    fn from(digest: TDigest) -> Self {
+        Object::TDigest(digest)
+    }
+}
+
+// Automatic conversation from `Json`.
+impl From<Json> for Object {
+    fn from(json: Json) -> Self {
+        Object::Json(json)
+    }
+}
+
+// Automatic conversation from `TimeSeries`.
+impl From<TimeSeries> for Object {
+    fn from(series: TimeSeries) -> Self {
+        Object::TimeSeries(series)
+    }
+}
+
+// Automatic conversation from `Embedding`.
+impl From<Embedding> for Object {
+    fn from(embedding: Embedding) -> Self {
+        Object::Embedding(embedding)
+    }
+}
+
+impl PartialEq<String> for Object {
+    fn eq(&self, other: &String) -> bool {
+        matches!(self, Object::String(value) if value == other.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_empty() {
+        assert!(!Object::from(String::new()).is_empty());
+        assert!(Object::from(List::new()).is_empty());
+        assert!(Object::from(SortedSet::new()).is_empty());
+        assert!(Object::from(Set::new()).is_empty());
+        assert!(!Object::from(Stream::new()).is_empty());
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let mut object = Object::from("value".to_string());
+        assert!(object.as_bytes().is_ok());
+        let err = object.as_sorted_set_mut().unwrap_err();
+        assert_eq!(err.to_string(), WRONG_TYPE);
+    }
+}
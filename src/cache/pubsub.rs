@@ -0,0 +1,286 @@
+use crate::cache::glob;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+/// The number of messages buffered for a subscriber that hasn't received them yet.
+const BUFFER: usize = 1024;
+
+/// A message published to a channel, with the pattern it was received through if any.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub pattern: Option<String>,
+    pub channel: String,
+    pub payload: String,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    next: u64,
+    senders: HashMap<u64, Sender<Message>>,
+    channels: BTreeMap<String, BTreeSet<u64>>,
+    patterns: BTreeMap<String, BTreeSet<u64>>,
+}
+
+impl Registry {
+    /// Forget the subscriber `id` and its subscriptions, closing its buffer.
+    fn remove(&mut self, id: u64) {
+        self.senders.remove(&id);
+        for subscriptions in [&mut self.channels, &mut self.patterns] {
+            subscriptions.retain(|_, ids| {
+                ids.remove(&id);
+                !ids.is_empty()
+            });
+        }
+    }
+}
+
+/// The channels of a server and their subscribers, shared by every connection.
+///
+/// Every subscriber gets a bounded buffer of messages. Publishing never waits for a slow
+/// subscriber: one whose buffer is full is dropped instead, like Redis closes the clients over
+/// their pub/sub output buffer limit.
+#[derive(Clone, Debug)]
+pub struct PubSub {
+    registry: Arc<Mutex<Registry>>,
+    buffer: usize,
+}
+
+impl Default for PubSub {
+    fn default() -> Self {
+        PubSub::new(BUFFER)
+    }
+}
+
+impl PubSub {
+    /// Create a registry buffering up to `buffer` messages per subscriber.
+    pub fn new(buffer: usize) -> Self {
+        PubSub {
+            registry: Arc::new(Mutex::new(Registry::default())),
+            buffer: buffer.max(1),
+        }
+    }
+
+    /// Register a subscriber, without any subscription yet.
+    pub fn subscriber(&self) -> Subscriber {
+        let (sender, receiver) = channel(self.buffer);
+        let mut registry = self.registry.lock().unwrap();
+        let id = registry.next;
+        registry.next += 1;
+        registry.senders.insert(id, sender);
+        Subscriber {
+            id,
+            receiver,
+            pubsub: self.clone(),
+            channels: BTreeSet::new(),
+            patterns: BTreeSet::new(),
+        }
+    }
+
+    /// Publish `payload` to `channel`, returning the number of subscriptions it was delivered
+    /// through, counting a subscriber both subscribed to the channel and a matching pattern
+    /// twice.
+    pub fn publish(&self, channel: &str, payload: &str) -> usize {
+        let mut registry = self.registry.lock().unwrap();
+        let Registry {
+            senders,
+            channels,
+            patterns,
+            ..
+        } = &mut *registry;
+
+        let direct = channels
+            .get(channel)
+            .into_iter()
+            .flatten()
+            .map(|id| (*id, None));
+        let matched = patterns
+            .iter()
+            .filter(|(pattern, _)| glob::matches(pattern, channel))
+            .flat_map(|(pattern, ids)| ids.iter().map(move |id| (*id, Some(pattern.clone()))));
+
+        let mut delivered = 0;
+        let mut dropped = Vec::new();
+        for (id, pattern) in direct.chain(matched) {
+            let message = Message {
+                pattern,
+                channel: channel.to_string(),
+                payload: payload.to_string(),
+            };
+            match senders.get(&id).map(|sender| sender.try_send(message)) {
+                Some(Ok(())) => delivered += 1,
+                Some(Err(TrySendError::Full(_))) => {
+                    log::warn!("dropping subscriber {} for falling behind", id);
+                    dropped.push(id);
+                }
+                _ => dropped.push(id),
+            }
+        }
+        for id in dropped {
+            registry.remove(id);
+        }
+        delivered
+    }
+
+    /// Retrieve the channels with at least one subscriber, matching `pattern` if given.
+    pub fn channels(&self, pattern: Option<&str>) -> Vec<String> {
+        let registry = self.registry.lock().unwrap();
+        registry
+            .channels
+            .keys()
+            .filter(|channel| pattern.is_none_or(|pattern| glob::matches(pattern, channel)))
+            .cloned()
+            .collect()
+    }
+
+    /// Retrieve the number of subscribers of each of `channels`, patterns left out.
+    pub fn numsub(&self, channels: &[String]) -> Vec<usize> {
+        let registry = self.registry.lock().unwrap();
+        channels
+            .iter()
+            .map(|channel| registry.channels.get(channel).map_or(0, BTreeSet::len))
+            .collect()
+    }
+
+    /// Retrieve the number of patterns subscribed to, by any subscriber.
+    pub fn numpat(&self) -> usize {
+        self.registry.lock().unwrap().patterns.len()
+    }
+}
+
+/// The subscriptions of a connection, receiving the messages published to them. Dropping it
+/// unsubscribes from everything.
+#[derive(Debug)]
+pub struct Subscriber {
+    id: u64,
+    receiver: Receiver<Message>,
+    pubsub: PubSub,
+    channels: BTreeSet<String>,
+    patterns: BTreeSet<String>,
+}
+
+impl Subscriber {
+    /// Subscribe to `channel`, returning the number of subscriptions.
+    pub fn subscribe(&mut self, channel: String) -> usize {
+        let mut registry = self.pubsub.registry.lock().unwrap();
+        let entry = registry.channels.entry(channel.clone()).or_default();
+        entry.insert(self.id);
+        self.channels.insert(channel);
+        self.count()
+    }
+
+    /// Unsubscribe from `channel`, returning the number of subscriptions left.
+    pub fn unsubscribe(&mut self, channel: &str) -> usize {
+        let mut registry = self.pubsub.registry.lock().unwrap();
+        if let Some(ids) = registry.channels.get_mut(channel) {
+            ids.remove(&self.id);
+            if ids.is_empty() {
+                registry.channels.remove(channel);
+            }
+        }
+        self.channels.remove(channel);
+        self.count()
+    }
+
+    /// Subscribe to the channels matching the glob `pattern`, returning the number of
+    /// subscriptions.
+    pub fn psubscribe(&mut self, pattern: String) -> usize {
+        let mut registry = self.pubsub.registry.lock().unwrap();
+        let entry = registry.patterns.entry(pattern.clone()).or_default();
+        entry.insert(self.id);
+        self.patterns.insert(pattern);
+        self.count()
+    }
+
+    /// Unsubscribe from `pattern`, returning the number of subscriptions left.
+    pub fn punsubscribe(&mut self, pattern: &str) -> usize {
+        let mut registry = self.pubsub.registry.lock().unwrap();
+        if let Some(ids) = registry.patterns.get_mut(pattern) {
+            ids.remove(&self.id);
+            if ids.is_empty() {
+                registry.patterns.remove(pattern);
+            }
+        }
+        self.patterns.remove(pattern);
+        self.count()
+    }
+
+    pub fn channels(&self) -> Vec<String> {
+        self.channels.iter().cloned().collect()
+    }
+
+    pub fn patterns(&self) -> Vec<String> {
+        self.patterns.iter().cloned().collect()
+    }
+
+    /// Retrieve the number of channels and patterns subscribed to.
+    pub fn count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+
+    /// Wait for the next message, or `None` once dropped for falling behind.
+    pub async fn recv(&mut self) -> Option<Message> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.pubsub.registry.lock().unwrap().remove(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_publish() {
+        let pubsub = PubSub::default();
+        let mut first = pubsub.subscriber();
+        let mut second = pubsub.subscriber();
+        assert_eq!(first.subscribe("cache:users".to_string()), 1);
+        assert_eq!(first.psubscribe("cache:*".to_string()), 2);
+        assert_eq!(second.psubscribe("cache:user?".to_string()), 1);
+
+        assert_eq!(pubsub.publish("cache:users", "42"), 3);
+        assert_eq!(pubsub.publish("other", "42"), 0);
+        let message = first.recv().await.unwrap();
+        assert_eq!(message.pattern, None);
+        assert_eq!(message.payload, "42");
+        let message = first.recv().await.unwrap();
+        assert_eq!(message.pattern, Some("cache:*".to_string()));
+        let message = second.recv().await.unwrap();
+        assert_eq!(message.channel, "cache:users");
+
+        assert_eq!(pubsub.channels(None), vec!["cache:users"]);
+        assert_eq!(pubsub.channels(Some("other*")), Vec::<String>::new());
+        let channels = ["cache:users".to_string(), "other".to_string()];
+        assert_eq!(pubsub.numsub(&channels), vec![1, 0]);
+        assert_eq!(pubsub.numpat(), 2);
+
+        assert_eq!(first.unsubscribe("cache:users"), 1);
+        drop(second);
+        assert!(pubsub.channels(None).is_empty());
+        assert_eq!(pubsub.numpat(), 1);
+        assert_eq!(pubsub.publish("cache:users", "43"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscribers_are_dropped() {
+        let pubsub = PubSub::new(2);
+        let mut slow = pubsub.subscriber();
+        slow.subscribe("events".to_string());
+
+        assert_eq!(pubsub.publish("events", "1"), 1);
+        assert_eq!(pubsub.publish("events", "2"), 1);
+        assert_eq!(pubsub.publish("events", "3"), 0);
+        assert_eq!(pubsub.numsub(&["events".to_string()]), vec![0]);
+
+        // the buffered messages are still received before the end
+        assert_eq!(slow.recv().await.unwrap().payload, "1");
+        assert_eq!(slow.recv().await.unwrap().payload, "2");
+        assert_eq!(slow.recv().await, None);
+    }
+}
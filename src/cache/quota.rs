@@ -0,0 +1,309 @@
+use crate::cache::entry::Entry;
+use crate::cache::expiry::Expiry;
+use crate::cache::notifications::Event;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use rand::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+/// The limits of a keyspace, each unset one being unlimited.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Quota {
+    pub max_keys: Option<usize>,
+    /// The bytes the keys and values may take, as estimated by `Object::memory_usage`.
+    pub max_memory: Option<usize>,
+    /// The time to live of keys created without an expiration.
+    pub default_ttl: Option<Duration>,
+    /// What to do with writes once over the memory.
+    pub eviction: Eviction,
+}
+
+/// How a keyspace over its memory makes room for a write, named like the Redis policies.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Eviction {
+    /// Evict nothing, refusing the write.
+    #[default]
+    Never,
+    /// Evict keys picked at random.
+    AllKeysRandom,
+    /// Evict keys with an expiration picked at random, refusing the write once there is none.
+    VolatileRandom,
+}
+
+impl Eviction {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "noeviction" => Ok(Eviction::Never),
+            "allkeys-random" => Ok(Eviction::AllKeysRandom),
+            "volatile-random" => Ok(Eviction::VolatileRandom),
+            _ => Err(Error::msg(format!("unknown eviction policy {}", s))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Eviction::Never => "noeviction",
+            Eviction::AllKeysRandom => "allkeys-random",
+            Eviction::VolatileRandom => "volatile-random",
+        }
+    }
+}
+
+impl Cache {
+    /// Replace the limits of this keyspace. Memory is measured again right away when limited.
+    pub async fn set_quota(&self, quota: Quota) {
+        let measure = quota.max_memory.is_some();
+        *self.quota.write().unwrap() = quota;
+        if measure {
+            self.measure_memory().await;
+        }
+    }
+
+    pub async fn quota(&self) -> Quota {
+        self.quota.read().unwrap().clone()
+    }
+
+    /// Measure the memory taken by the keys and values that haven't expired, in bytes. Writes
+    /// are checked against the last measure, which admitted writes add to and the monitor
+    /// refreshes when memory is limited.
+    pub async fn measure_memory(&self) -> usize {
+        let used = {
+            let store = self.store.read().unwrap();
+            store
+                .iter()
+                .filter(|(_, entry)| !entry.expiration().is_expired())
+                .map(|(key, entry)| key.len() + entry.value().memory_usage())
+                .sum::<usize>()
+        };
+        self.memory.store(used, Ordering::Relaxed);
+        used
+    }
+
+    /// Retrieve whether the memory is limited, and so needs to be measured periodically.
+    pub(super) fn is_memory_limited(&self) -> bool {
+        self.quota.read().unwrap().max_memory.is_some()
+    }
+
+    /// Check that a write to `key` fits in the quota. Once over its memory, a keyspace evicts
+    /// other keys as its eviction policy allows, or else only accepts removals until enough is
+    /// freed, and a new key is only accepted under the maximum number of keys. Expired keys
+    /// count until the sampler removes them.
+    pub(super) fn admit(&self, store: &mut BTreeMap<String, Entry>, key: &str) -> Result<()> {
+        let quota = self.quota.read().unwrap();
+        if let Some(max_memory) = quota.max_memory {
+            while self.memory.load(Ordering::Relaxed) > max_memory {
+                if !self.evict(store, key, quota.eviction) {
+                    return Err(Error::msg(
+                        "OOM command not allowed when used memory > 'maxmemory'",
+                    ));
+                }
+            }
+        }
+        if let Some(max_keys) = quota.max_keys {
+            if !store.contains_key(key) && store.len() >= max_keys {
+                return Err(Error::msg(
+                    "OOM command not allowed when the number of keys reached 'maxkeys'",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Evict a key other than `key` picked at random as `eviction` allows, crediting the memory
+    /// it took. Returns whether one was evicted.
+    fn evict(&self, store: &mut BTreeMap<String, Entry>, key: &str, eviction: Eviction) -> bool {
+        let candidates = store.iter().filter(|(candidate, entry)| {
+            *candidate != key
+                && match eviction {
+                    Eviction::Never => false,
+                    Eviction::AllKeysRandom => true,
+                    Eviction::VolatileRandom => entry.expiration().instant().is_some(),
+                }
+        });
+        let victim = match candidates.choose(&mut rand::thread_rng()) {
+            Some((victim, _)) => victim.clone(),
+            None => return false,
+        };
+
+        log::debug!("evicting key {} to free memory", victim);
+        let freed = self.footprint(store, &victim);
+        self.remove_entry(store, &victim);
+        self.notify(Event::Evicted, &victim);
+        let _ = self
+            .memory
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                Some(used.saturating_sub(freed))
+            });
+        true
+    }
+
+    /// Add the `bytes` taken by an admitted write to the last measure of the memory, so that
+    /// the writes between two measures add up against the limit.
+    pub(super) fn charge(&self, bytes: usize) {
+        if self.is_memory_limited() {
+            self.memory.fetch_add(bytes, Ordering::Relaxed);
+        }
+    }
+
+    /// Retrieve the bytes `key` and its value take in `store`, to charge the growth of a write.
+    /// Values are only measured when memory is limited, as some are costly to measure.
+    pub(super) fn footprint(&self, store: &BTreeMap<String, Entry>, key: &str) -> usize {
+        match store.get(key) {
+            Some(entry) if self.is_memory_limited() => key.len() + entry.value().memory_usage(),
+            _ => 0,
+        }
+    }
+
+    /// Retrieve the expiration of keys created without one, from the default time to live. A
+    /// time to live ending later than the clock can hold never ends.
+    pub(super) fn default_expiry(&self) -> Expiry {
+        match self.quota.read().unwrap().default_ttl {
+            Some(ttl) => Expiry::after(ttl).unwrap_or_else(|_| Expiry::none()),
+            None => Expiry::none(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::list::End;
+
+    #[tokio::test]
+    async fn test_max_keys() -> Result<()> {
+        let cache = Cache::default();
+        let quota = Quota {
+            max_keys: Some(2),
+            ..Quota::default()
+        };
+        cache.set_quota(quota).await;
+        cache.set("a".to_string(), "1".to_string()).await?;
+        cache.sadd("b".to_string(), vec!["x".to_string()]).await?;
+
+        assert!(cache.set("c".to_string(), "1".to_string()).await.is_err());
+        assert!(cache
+            .sadd("c".to_string(), vec!["x".to_string()])
+            .await
+            .is_err());
+        // existing keys can still be written
+        cache.set("a".to_string(), "2".to_string()).await?;
+        cache.sadd("b".to_string(), vec!["y".to_string()]).await?;
+        cache.remove("a".to_string()).await?;
+        cache.set("c".to_string(), "1".to_string()).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_memory() -> Result<()> {
+        let cache = Cache::default();
+        let quota = Quota {
+            max_memory: Some(100),
+            ..Quota::default()
+        };
+        cache.set_quota(quota).await;
+        cache
+            .push("l".to_string(), End::Left, vec!["x".to_string()], true)
+            .await?;
+        cache
+            .hset("h".to_string(), vec![("f".into(), "v".into())])
+            .await?;
+        cache.set("a".to_string(), "x".repeat(10)).await?;
+        // writes are charged as they are admitted, before the memory is measured again
+        cache.set("b".to_string(), "x".repeat(110)).await?;
+        assert_eq!(cache.memory.load(Ordering::Relaxed), 199);
+        assert_eq!(cache.measure_memory().await, 199);
+
+        let error = cache
+            .set("c".to_string(), "1".to_string())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("OOM"));
+        assert!(cache
+            .hset("d".to_string(), vec![("f".into(), "v".into())])
+            .await
+            .is_err());
+        // but pops and deletes are let through to free it
+        let popped = cache.pop("l".to_string(), End::Left, 1).await?;
+        assert_eq!(popped, Some(vec!["x".to_string()]));
+        assert_eq!(cache.hdel("h".to_string(), vec!["f".into()]).await?, 1);
+        cache.remove("b".to_string()).await?;
+        cache.measure_memory().await;
+        cache.set("c".to_string(), "1".to_string()).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_eviction() -> Result<()> {
+        let cache = Cache::default();
+        let quota = Quota {
+            max_memory: Some(100),
+            eviction: Eviction::VolatileRandom,
+            ..Quota::default()
+        };
+        cache.set_quota(quota).await;
+        cache.set("a".to_string(), "x".repeat(60)).await?;
+        cache
+            .set_with_expiry("b".to_string(), "x".repeat(60), Duration::from_secs(60))
+            .await?;
+
+        // keys with an expiration are evicted to make room
+        cache.set("c".to_string(), "1".to_string()).await?;
+        assert_eq!(cache.get("b".to_string()).await, None);
+        assert_eq!(cache.memory.load(Ordering::Relaxed), 63);
+        cache.set("d".to_string(), "x".repeat(60)).await?;
+        let error = cache
+            .set("e".to_string(), "1".to_string())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("OOM"));
+
+        // or any key, though never the one written
+        let quota = Quota {
+            max_memory: Some(100),
+            eviction: Eviction::AllKeysRandom,
+            ..Quota::default()
+        };
+        cache.set_quota(quota).await;
+        cache.set("a".to_string(), "1".to_string()).await?;
+        assert_eq!(cache.get("a".to_string()).await, Some("1".to_string()));
+        assert!(cache.measure_memory().await <= 100);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_default_ttl() -> Result<()> {
+        let cache = Cache::default();
+        let quota = Quota {
+            default_ttl: Some(Duration::from_secs(60)),
+            ..Quota::default()
+        };
+        cache.set_quota(quota).await;
+        cache.set("a".to_string(), "1".to_string()).await?;
+        cache
+            .set_with_expiry("b".to_string(), "1".to_string(), Duration::from_secs(5))
+            .await?;
+        cache
+            .hset("c".to_string(), vec![("f".into(), "v".into())])
+            .await?;
+
+        let ttl = cache.ttl("a".to_string()).await.unwrap().unwrap();
+        assert!(ttl > Duration::from_secs(59));
+        let ttl = cache.ttl("b".to_string()).await.unwrap().unwrap();
+        assert!(ttl <= Duration::from_secs(5));
+        assert!(cache.ttl("c".to_string()).await.unwrap().is_some());
+
+        // a time to live too long for the clock never ends
+        let quota = Quota {
+            default_ttl: Some(Duration::MAX),
+            ..Quota::default()
+        };
+        cache.set_quota(quota).await;
+        cache
+            .hset("d".to_string(), vec![("f".into(), "v".into())])
+            .await?;
+        assert_eq!(cache.ttl("d".to_string()).await, Some(None));
+        Ok(())
+    }
+}
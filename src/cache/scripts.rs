@@ -0,0 +1,158 @@
+use anyhow::{Error, Result};
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Dynamic, Engine, AST};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// The operations a script may run before being stopped.
+const MAX_OPERATIONS: u64 = 1_000_000;
+/// The time a script may run for before being stopped.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// The size of the strings, arrays and maps a script may build.
+const MAX_SIZE: usize = 1 << 20;
+
+/// Retrieve the SHA1 digest of `source` in hex, which names the script.
+pub fn digest(source: &str) -> String {
+    sha1_smol::Sha1::from(source).digest().to_string()
+}
+
+/// The scripts of a server, compiled once and shared by every connection.
+///
+/// Scripts are written in Rhai, an embedded language with no access to the filesystem, the
+/// network or other modules. Each run is stopped past `max_operations` operations or after
+/// `timeout`, and what it built is bounded in size; writes it made until then are kept.
+#[derive(Clone, Debug)]
+pub struct Scripts {
+    scripts: Arc<RwLock<HashMap<String, Arc<AST>>>>,
+    max_operations: u64,
+    timeout: Duration,
+}
+
+impl Default for Scripts {
+    fn default() -> Self {
+        Scripts::new(MAX_OPERATIONS, TIMEOUT)
+    }
+}
+
+impl Scripts {
+    pub fn new(max_operations: u64, timeout: Duration) -> Self {
+        Scripts {
+            scripts: Arc::new(RwLock::new(HashMap::new())),
+            max_operations,
+            timeout,
+        }
+    }
+
+    /// Compile `source` and keep it under its digest, returning both.
+    pub fn load(&self, source: &str) -> Result<(String, Arc<AST>)> {
+        let sha = digest(source);
+        if let Some(ast) = self.get(&sha) {
+            return Ok((sha, ast));
+        }
+
+        let ast = self
+            .engine()
+            .compile(source)
+            .map_err(|e| Error::msg(format!("Error compiling script: {}", e)))?;
+        let ast = Arc::new(ast);
+        log::debug!("loading script {}", sha);
+        self.scripts
+            .write()
+            .unwrap()
+            .insert(sha.clone(), ast.clone());
+        Ok((sha, ast))
+    }
+
+    /// Retrieve the script named `sha`, case insensitively.
+    pub fn get(&self, sha: &str) -> Option<Arc<AST>> {
+        let scripts = self.scripts.read().unwrap();
+        scripts.get(&sha.to_ascii_lowercase()).cloned()
+    }
+
+    /// Retrieve whether each of `shas` names a loaded script.
+    pub fn exists(&self, shas: &[String]) -> Vec<bool> {
+        let scripts = self.scripts.read().unwrap();
+        shas.iter()
+            .map(|sha| scripts.contains_key(&sha.to_ascii_lowercase()))
+            .collect()
+    }
+
+    pub fn flush(&self) {
+        self.scripts.write().unwrap().clear();
+    }
+
+    /// Create the sandboxed engine of a single run, whose time limit starts now.
+    pub fn engine(&self) -> Engine {
+        let mut engine = Engine::new();
+        engine
+            .set_module_resolver(DummyModuleResolver::new())
+            .disable_symbol("eval")
+            .set_max_operations(self.max_operations)
+            .set_max_call_levels(32)
+            .set_max_expr_depths(64, 32)
+            .set_max_string_size(MAX_SIZE)
+            .set_max_array_size(MAX_SIZE)
+            .set_max_map_size(MAX_SIZE)
+            .on_print(|s| log::debug!("script printed {}", s))
+            .on_debug(|s, _, position| log::debug!("script debugged {} at {}", s, position));
+
+        let start = Instant::now();
+        let timeout = self.timeout;
+        engine.on_progress(move |_| (start.elapsed() > timeout).then(|| Dynamic::from(timeout)));
+        engine
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rhai::EvalAltResult;
+
+    #[test]
+    fn test_load() -> Result<()> {
+        let scripts = Scripts::default();
+        let (sha, _) = scripts.load("40 + 2")?;
+        assert_eq!(sha, "b0d6be7e6d510a20853e3a179f20922ef699a7a7");
+        assert!(scripts.get(&sha.to_ascii_uppercase()).is_some());
+        assert_eq!(
+            scripts.exists(&[sha.clone(), digest("other")]),
+            vec![true, false]
+        );
+        assert!(scripts.load("let x = ;").is_err());
+
+        scripts.flush();
+        assert!(scripts.get(&sha).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let scripts = Scripts::default();
+        assert!(scripts.load(r#"eval("40 + 2")"#).is_err());
+        let engine = scripts.engine();
+        assert!(engine.run(r#"import "secrets" as secrets;"#).is_err());
+        let result = engine.eval::<String>(r#"let s = "x"; loop { s += s; }"#);
+        assert!(matches!(
+            *result.unwrap_err(),
+            EvalAltResult::ErrorDataTooLarge(..)
+        ));
+    }
+
+    #[test]
+    fn test_limits() {
+        let scripts = Scripts::new(1_000, TIMEOUT);
+        let result = scripts.engine().run("loop {}");
+        assert!(matches!(
+            *result.unwrap_err(),
+            EvalAltResult::ErrorTooManyOperations(..)
+        ));
+
+        let scripts = Scripts::new(0, Duration::from_millis(20));
+        let result = scripts.engine().run("loop {}");
+        assert!(matches!(
+            *result.unwrap_err(),
+            EvalAltResult::ErrorTerminated(..)
+        ));
+    }
+}
@@ -0,0 +1,929 @@
+use crate::cache::entry::Entry;
+use crate::cache::object::Object;
+use crate::cache::text::{parse_terms, Analyzer, Term, TextIndex};
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
+
+/// The values documented by an index.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Source {
+    /// Hashes, whose fields are those of the documents.
+    #[default]
+    Hash,
+    /// Strings, each a document with the single field `value`.
+    String,
+}
+
+impl Source {
+    fn documents(self, object: &Object) -> bool {
+        matches!(
+            (self, object),
+            (Source::Hash, Object::Hash(_)) | (Source::String, Object::String(_))
+        )
+    }
+
+    /// The value of `field` in the document `object`.
+    fn value<'a>(self, object: &'a Object, field: &str) -> Option<Cow<'a, str>> {
+        match (self, object) {
+            (Source::Hash, Object::Hash(hash)) => hash.get(field).map(|value| value.into()),
+            (Source::String, Object::String(value)) if field == "value" => {
+                Some(String::from_utf8_lossy(value))
+            }
+            _ => None,
+        }
+    }
+
+    /// All the fields of the document `object`.
+    fn fields(self, object: &Object) -> Vec<(String, String)> {
+        match (self, object) {
+            (Source::Hash, Object::Hash(hash)) => hash
+                .iter()
+                .map(|(field, value)| (field.clone(), value.clone()))
+                .collect(),
+            (Source::String, Object::String(value)) => {
+                vec![("value".to_string(), String::from_utf8_lossy(value).into())]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// How the values of a hash field are indexed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldType {
+    /// Numbers, queried by range.
+    Numeric,
+    /// Comma separated labels, matched exactly but ignoring case.
+    Tag,
+    /// Words, matched by stem and ranked by relevance.
+    Text,
+}
+
+impl FieldType {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "numeric" => Ok(FieldType::Numeric),
+            "tag" => Ok(FieldType::Tag),
+            "text" => Ok(FieldType::Text),
+            _ => Err(Error::msg("unknown field type")),
+        }
+    }
+}
+
+/// A float ordered by `total_cmp`, so that it can key a map.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Number(f64);
+
+impl Eq for Number {}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+type Keys = BTreeSet<String>;
+
+/// The keys of the documents holding each value of a field.
+#[derive(Clone, Debug, PartialEq)]
+enum FieldIndex {
+    Numeric(BTreeMap<Number, Keys>),
+    Tag(BTreeMap<String, Keys>),
+    Text(TextIndex),
+}
+
+fn tags(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(',')
+        .map(|tag| tag.trim().to_lowercase())
+        .filter(|tag| !tag.is_empty())
+}
+
+/// Add or remove `key` from the keys of `token`, dropping tokens left without keys.
+fn update_keys<T: Ord>(postings: &mut BTreeMap<T, Keys>, token: T, key: &str, add: bool) {
+    if add {
+        postings.entry(token).or_default().insert(key.to_owned());
+    } else if let Some(keys) = postings.get_mut(&token) {
+        keys.remove(key);
+        if keys.is_empty() {
+            postings.remove(&token);
+        }
+    }
+}
+
+impl FieldIndex {
+    fn new(kind: FieldType) -> Self {
+        match kind {
+            FieldType::Numeric => FieldIndex::Numeric(BTreeMap::new()),
+            FieldType::Tag => FieldIndex::Tag(BTreeMap::new()),
+            FieldType::Text => FieldIndex::Text(TextIndex::default()),
+        }
+    }
+
+    /// Add or remove `key` from the entries of `value`.
+    fn update(&mut self, analyzer: &Analyzer, key: &str, value: &str, add: bool) {
+        match self {
+            FieldIndex::Numeric(numbers) => {
+                if let Ok(number) = value.trim().parse::<f64>() {
+                    update_keys(numbers, Number(number), key, add);
+                }
+            }
+            FieldIndex::Tag(values) => {
+                for tag in tags(value) {
+                    update_keys(values, tag, key, add);
+                }
+            }
+            FieldIndex::Text(text) if add => text.add(analyzer, key, value),
+            FieldIndex::Text(text) => text.remove(analyzer, key, value),
+        }
+    }
+}
+
+/// A condition on the documents of an index.
+#[derive(Clone, Debug, PartialEq)]
+enum Clause {
+    All,
+    Numeric {
+        field: String,
+        min: Bound<f64>,
+        max: Bound<f64>,
+    },
+    /// Any of the tags.
+    Tag {
+        field: String,
+        tags: Vec<String>,
+    },
+    /// All of the terms, in the given text field or in any.
+    Text {
+        field: Option<String>,
+        terms: Vec<Term>,
+    },
+}
+
+/// The intersection of clauses, some of them negated, written like
+/// `@age:[18 (65] @city:{london | paris} -@name:(smith) "software engineer" lead*`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    clauses: Vec<(bool, Clause)>,
+}
+
+fn parse_bound(s: &str) -> Result<Bound<f64>> {
+    let (s, exclusive) = match s.strip_prefix('(') {
+        Some(s) => (s, true),
+        None => (s, false),
+    };
+    let value = match s.to_ascii_lowercase().as_str() {
+        "-inf" => f64::NEG_INFINITY,
+        "+inf" | "inf" => f64::INFINITY,
+        _ => s
+            .parse::<f64>()
+            .map_err(|_| Error::msg(format!("invalid numeric bound {:?}", s)))?,
+    };
+    Ok(if exclusive {
+        Bound::Excluded(value)
+    } else {
+        Bound::Included(value)
+    })
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Self> {
+        let invalid = || Error::msg(format!("invalid query {:?}", query));
+        let mut clauses = Vec::new();
+        let mut rest = query.trim_start();
+        while !rest.is_empty() {
+            let negated = rest.starts_with('-');
+            if negated {
+                rest = &rest[1..];
+            }
+
+            // a group or a phrase runs to its closing delimiter, a word to the next whitespace
+            let (field, body) = match rest.strip_prefix('@') {
+                Some(after) => {
+                    let colon = after.find(':').ok_or_else(invalid)?;
+                    (Some(&after[..colon]), &after[colon + 1..])
+                }
+                None => (None, rest),
+            };
+            let close = match body.chars().next() {
+                Some('[') => Some(']'),
+                Some('{') => Some('}'),
+                Some('(') => Some(')'),
+                Some('"') => Some('"'),
+                _ => None,
+            };
+            let (group, after) = match close {
+                Some(close) => {
+                    let end = body[1..].find(close).ok_or_else(invalid)? + 1;
+                    (&body[1..end], &body[end + 1..])
+                }
+                None => {
+                    let end = body.find(char::is_whitespace).unwrap_or(body.len());
+                    (&body[..end], &body[end..])
+                }
+            };
+            rest = after.trim_start();
+
+            let clause = match (field, close) {
+                (None, None) if group == "*" => Clause::All,
+                (Some(field), Some(']')) => {
+                    let bounds = group.split_whitespace().collect::<Vec<_>>();
+                    let [min, max] = bounds[..] else {
+                        return Err(invalid());
+                    };
+                    Clause::Numeric {
+                        field: field.to_owned(),
+                        min: parse_bound(min)?,
+                        max: parse_bound(max)?,
+                    }
+                }
+                (Some(field), Some('}')) => Clause::Tag {
+                    field: field.to_owned(),
+                    tags: group
+                        .split('|')
+                        .map(|tag| tag.trim().to_lowercase())
+                        .filter(|tag| !tag.is_empty())
+                        .collect(),
+                },
+                (field, Some('"')) => Clause::Text {
+                    field: field.map(|field| field.to_owned()),
+                    terms: vec![Term::Phrase(group.to_owned())],
+                },
+                (field, None | Some(')')) => Clause::Text {
+                    field: field.map(|field| field.to_owned()),
+                    terms: parse_terms(group)?,
+                },
+                _ => return Err(invalid()),
+            };
+            clauses.push((negated, clause));
+        }
+        if clauses.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Query { clauses })
+    }
+}
+
+/// How the matches of a search are ordered, paginated and presented.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchOptions {
+    /// The field to sort by and whether the order is descending. Matches are sorted by
+    /// relevance to the text terms of the query otherwise, then by key.
+    pub sort_by: Option<(String, bool)>,
+    pub offset: usize,
+    pub limit: usize,
+    /// The tags to wrap the words matching the query in, in text fields.
+    pub highlight: Option<(String, String)>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            sort_by: None,
+            offset: 0,
+            limit: 10,
+            highlight: None,
+        }
+    }
+}
+
+/// Which values an index documents and how it analyzes text.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IndexOptions {
+    pub source: Source,
+    /// The prefixes of the keys indexed, all of them if empty.
+    pub prefixes: Vec<String>,
+    pub analyzer: Analyzer,
+}
+
+/// A secondary index over the fields of the hashes or strings stored under some key
+/// prefixes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchIndex {
+    source: Source,
+    prefixes: Vec<String>,
+    analyzer: Analyzer,
+    schema: Vec<(String, FieldType)>,
+    indexes: Vec<FieldIndex>,
+    /// The indexed fields of each document, to unindex and sort them.
+    documents: BTreeMap<String, HashMap<String, String>>,
+}
+
+impl SearchIndex {
+    /// Create an index of the `schema` fields of the documents `options` describe.
+    pub fn new(options: IndexOptions, schema: Vec<(String, FieldType)>) -> Self {
+        let indexes = schema
+            .iter()
+            .map(|(_, kind)| FieldIndex::new(*kind))
+            .collect();
+        SearchIndex {
+            source: options.source,
+            prefixes: options.prefixes,
+            analyzer: options.analyzer,
+            schema,
+            indexes,
+            documents: BTreeMap::new(),
+        }
+    }
+
+    fn covers(&self, key: &str) -> bool {
+        self.prefixes.is_empty() || self.prefixes.iter().any(|prefix| key.starts_with(prefix))
+    }
+
+    /// Index the fields of the document `object` stored under `key`, replacing those indexed
+    /// before.
+    fn add(&mut self, key: &str, object: &Object) {
+        self.remove(key);
+        let mut document = HashMap::new();
+        for ((field, _), index) in self.schema.iter().zip(&mut self.indexes) {
+            if let Some(value) = self.source.value(object, field) {
+                index.update(&self.analyzer, key, &value, true);
+                document.insert(field.clone(), value.into_owned());
+            }
+        }
+        self.documents.insert(key.to_owned(), document);
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(document) = self.documents.remove(key) {
+            for ((field, _), index) in self.schema.iter().zip(&mut self.indexes) {
+                if let Some(value) = document.get(field) {
+                    index.update(&self.analyzer, key, value, false);
+                }
+            }
+        }
+    }
+
+    fn field(&self, name: &str) -> Result<&FieldIndex> {
+        self.schema
+            .iter()
+            .position(|(field, _)| field == name)
+            .map(|i| &self.indexes[i])
+            .ok_or_else(|| Error::msg(format!("unknown field {:?}", name)))
+    }
+
+    /// The text fields a text clause on `field` searches, all of them if none.
+    fn text_fields<'a>(
+        &'a self,
+        field: &'a Option<String>,
+    ) -> Result<Vec<(&'a String, &'a TextIndex)>> {
+        let fields = self
+            .schema
+            .iter()
+            .map(|(field, _)| field)
+            .zip(&self.indexes);
+        match field {
+            Some(name) => match self.field(name)? {
+                FieldIndex::Text(index) => Ok(vec![(name, index)]),
+                _ => Err(Error::msg(format!("field {:?} is not text", name))),
+            },
+            None => Ok(fields
+                .filter_map(|(field, index)| match index {
+                    FieldIndex::Text(index) => Some((field, index)),
+                    _ => None,
+                })
+                .collect()),
+        }
+    }
+
+    /// The terms of the text clauses that `query` requires, with the fields they search.
+    fn text_terms<'a>(
+        &'a self,
+        query: &'a Query,
+    ) -> Result<Vec<(&'a String, &'a TextIndex, &'a Term)>> {
+        let mut terms = Vec::new();
+        for (negated, clause) in &query.clauses {
+            if let (
+                false,
+                Clause::Text {
+                    field,
+                    terms: clause_terms,
+                },
+            ) = (negated, clause)
+            {
+                for (field, index) in self.text_fields(field)? {
+                    for term in clause_terms
+                        .iter()
+                        .filter(|term| !self.analyzer.ignores(term))
+                    {
+                        terms.push((field, index, term));
+                    }
+                }
+            }
+        }
+        Ok(terms)
+    }
+
+    /// The indexed terms the query matches in each text field, to highlight them.
+    fn highlights(&self, query: &Query) -> Result<HashMap<String, BTreeSet<String>>> {
+        let mut highlights: HashMap<String, BTreeSet<String>> = HashMap::new();
+        for (field, index, term) in self.text_terms(query)? {
+            let terms = index.terms(&self.analyzer, term);
+            highlights.entry(field.clone()).or_default().extend(terms);
+        }
+        Ok(highlights)
+    }
+
+    fn evaluate(&self, clause: &Clause) -> Result<Keys> {
+        let union = |sets: &mut dyn Iterator<Item = &Keys>| sets.flatten().cloned().collect();
+        Ok(match clause {
+            Clause::All => self.documents.keys().cloned().collect(),
+            Clause::Numeric { field, min, max } => match self.field(field)? {
+                FieldIndex::Numeric(numbers) => {
+                    let bound = |bound: &Bound<f64>| bound.map(Number);
+                    if matches!((min, max), (Bound::Included(a) | Bound::Excluded(a), Bound::Included(b) | Bound::Excluded(b)) if a > b)
+                    {
+                        return Ok(Keys::new());
+                    }
+                    union(
+                        &mut numbers
+                            .range((bound(min), bound(max)))
+                            .map(|(_, keys)| keys),
+                    )
+                }
+                _ => return Err(Error::msg(format!("field {:?} is not numeric", field))),
+            },
+            Clause::Tag { field, tags } => match self.field(field)? {
+                FieldIndex::Tag(values) => {
+                    union(&mut tags.iter().filter_map(|tag| values.get(tag)))
+                }
+                _ => return Err(Error::msg(format!("field {:?} is not a tag", field))),
+            },
+            Clause::Text { field, terms } => {
+                let fields = self.text_fields(field)?;
+                let mut matches: Option<Keys> = None;
+                // stopwords are left out of the query as they are of the index
+                for term in terms.iter().filter(|term| !self.analyzer.ignores(term)) {
+                    let found = fields
+                        .iter()
+                        .flat_map(|(_, index)| index.matches(&self.analyzer, term))
+                        .flat_map(|frequencies| frequencies.into_keys())
+                        .collect::<Keys>();
+                    matches = Some(match matches {
+                        Some(matches) => matches.intersection(&found).cloned().collect(),
+                        None => found,
+                    });
+                }
+                matches.unwrap_or_default()
+            }
+        })
+    }
+
+    /// Retrieve the keys of all the documents matching `query` and their relevance to its
+    /// text terms, in the order of `options`.
+    fn search(&self, query: &Query, options: &SearchOptions) -> Result<Vec<(String, f64)>> {
+        let mut matches: Option<Keys> = None;
+        for (negated, clause) in &query.clauses {
+            let found = self.evaluate(clause)?;
+            let current = matches.unwrap_or_else(|| self.documents.keys().cloned().collect());
+            matches = Some(if *negated {
+                current.difference(&found).cloned().collect()
+            } else {
+                current.intersection(&found).cloned().collect()
+            });
+        }
+        let keys = matches.unwrap_or_default();
+
+        // BM25, summed over the terms and the fields they are searched in
+        let frequencies = self
+            .text_terms(query)?
+            .into_iter()
+            .flat_map(|(_, index, term)| {
+                let matches = index.matches(&self.analyzer, term);
+                matches
+                    .into_iter()
+                    .map(move |frequencies| (index, frequencies))
+            })
+            .collect::<Vec<_>>();
+        let mut keys = keys
+            .into_iter()
+            .map(|key| {
+                let score = frequencies
+                    .iter()
+                    .map(|(index, frequencies)| index.score(frequencies, &key))
+                    .sum::<f64>();
+                (key, score)
+            })
+            .collect::<Vec<_>>();
+
+        if let Some((field, descending)) = &options.sort_by {
+            let numeric = matches!(self.field(field)?, FieldIndex::Numeric(_));
+            let value = |key: &String| self.documents[key].get(field);
+            let compare = |a: &String, b: &String| -> Ordering {
+                match (numeric, a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+                    (true, Ok(a), Ok(b)) => a.total_cmp(&b),
+                    _ => a.cmp(b),
+                }
+            };
+            // documents without the field come last either way
+            keys.sort_by(|(a, _), (b, _)| match (value(a), value(b)) {
+                (Some(x), Some(y)) if *descending => compare(y, x),
+                (Some(x), Some(y)) => compare(x, y),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            });
+        } else if !frequencies.is_empty() {
+            keys.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        }
+        Ok(keys)
+    }
+}
+
+/// The number of documents matching a search, and the keys, relevance and fields of those on
+/// the page.
+pub type SearchResults = (usize, Vec<(String, f64, Vec<(String, String)>)>);
+
+fn no_index() -> Error {
+    Error::msg("no such index")
+}
+
+impl Cache {
+    /// Create the index `name` and index the documents already stored under its prefixes.
+    pub async fn ft_create(&self, name: String, mut index: SearchIndex) -> Result<()> {
+        let store = self.store.read().unwrap();
+        let mut indexes = self.indexes.write().unwrap();
+        if indexes.contains_key(&name) {
+            return Err(Error::msg("index already exists"));
+        }
+
+        let prefixes = match index.prefixes.is_empty() {
+            true => vec![String::new()],
+            false => index.prefixes.clone(),
+        };
+        for prefix in prefixes {
+            let keys = store
+                .range(prefix.clone()..)
+                .take_while(|(key, _)| key.starts_with(&prefix));
+            for (key, entry) in keys {
+                if !entry.expiration().is_expired() && index.source.documents(entry.value()) {
+                    index.add(key, entry.value());
+                }
+            }
+        }
+        indexes.insert(name, index);
+        Ok(())
+    }
+
+    /// Drop the index `name`, leaving the documents it indexed alone.
+    pub async fn ft_dropindex(&self, name: String) -> Result<()> {
+        let mut indexes = self.indexes.write().unwrap();
+        indexes.remove(&name).map(|_| ()).ok_or_else(no_index)
+    }
+
+    /// Retrieve the documents of the index `name` matching `query`, paginated and highlighted
+    /// as `options` say.
+    pub async fn ft_search(
+        &self,
+        name: String,
+        query: Query,
+        options: SearchOptions,
+    ) -> Result<SearchResults> {
+        let store = self.store.read().unwrap();
+        let indexes = self.indexes.read().unwrap();
+        let index = indexes.get(&name).ok_or_else(no_index)?;
+
+        // keys expired but not evicted yet are skipped here
+        let document = |key: &str| {
+            store
+                .get(key)
+                .filter(|entry| !entry.expiration().is_expired())
+                .map(Entry::value)
+                .filter(|object| index.source.documents(object))
+        };
+        let matches = index
+            .search(&query, &options)?
+            .into_iter()
+            .filter(|(key, _)| document(key).is_some())
+            .collect::<Vec<_>>();
+
+        let highlights = match options.highlight {
+            Some(_) => index.highlights(&query)?,
+            None => HashMap::new(),
+        };
+        let highlight =
+            |field: String, value: String| match (&options.highlight, highlights.get(&field)) {
+                (Some((open, close)), Some(terms)) => {
+                    let value = index.analyzer.highlight(&value, terms, open, close);
+                    (field, value)
+                }
+                _ => (field, value),
+            };
+        let page = matches
+            .iter()
+            .skip(options.offset)
+            .take(options.limit)
+            .map(|(key, score)| {
+                let fields = index.source.fields(document(key).unwrap());
+                let fields = fields
+                    .into_iter()
+                    .map(|(field, value)| highlight(field, value))
+                    .collect();
+                (key.clone(), *score, fields)
+            })
+            .collect();
+        Ok((matches.len(), page))
+    }
+
+    /// Bring the indexes covering `key` up to date with the object now stored there. Callers
+    /// hold the store lock, so that indexes change in the same order as the store.
+    pub(super) fn reindex(&self, key: &str, object: Option<&Object>) {
+        {
+            let indexes = self.indexes.read().unwrap();
+            if !indexes.values().any(|index| index.covers(key)) {
+                return;
+            }
+        }
+        let mut indexes = self.indexes.write().unwrap();
+        for index in indexes.values_mut().filter(|index| index.covers(key)) {
+            match object {
+                Some(object) if index.source.documents(object) => index.add(key, object),
+                _ => index.remove(key),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::expiry::Expiry;
+    use std::time::Duration;
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(field, value)| (field.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn sessions() -> SearchIndex {
+        let options = IndexOptions {
+            prefixes: vec!["session:".to_string()],
+            ..IndexOptions::default()
+        };
+        SearchIndex::new(
+            options,
+            vec![
+                ("tenant".to_string(), FieldType::Tag),
+                ("age".to_string(), FieldType::Numeric),
+                ("agent".to_string(), FieldType::Text),
+            ],
+        )
+    }
+
+    async fn search(cache: &Cache, query: &str, options: SearchOptions) -> Result<Vec<String>> {
+        let (_, page) = cache
+            .ft_search("sessions".to_string(), Query::parse(query)?, options)
+            .await?;
+        Ok(page.into_iter().map(|(key, _, _)| key).collect())
+    }
+
+    #[test]
+    fn test_parse_query() -> Result<()> {
+        let query = Query::parse("@age:[18 (65] -@tenant:{Acme | b} firefox @agent:(linux x11)")?;
+        assert_eq!(
+            query.clauses,
+            vec![
+                (
+                    false,
+                    Clause::Numeric {
+                        field: "age".to_string(),
+                        min: Bound::Included(18.0),
+                        max: Bound::Excluded(65.0),
+                    }
+                ),
+                (
+                    true,
+                    Clause::Tag {
+                        field: "tenant".to_string(),
+                        tags: vec!["acme".to_string(), "b".to_string()],
+                    }
+                ),
+                (
+                    false,
+                    Clause::Text {
+                        field: None,
+                        terms: vec![Term::Word("firefox".to_string())],
+                    }
+                ),
+                (
+                    false,
+                    Clause::Text {
+                        field: Some("agent".to_string()),
+                        terms: vec![
+                            Term::Word("linux".to_string()),
+                            Term::Word("x11".to_string()),
+                        ],
+                    }
+                ),
+            ]
+        );
+        assert!(Query::parse("").is_err());
+        assert!(Query::parse("@age:[1]").is_err());
+        assert!(Query::parse("@age:[1 2").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search() -> Result<()> {
+        let cache = Cache::default();
+        cache
+            .hset(
+                "session:1".to_string(),
+                pairs(&[
+                    ("tenant", "acme"),
+                    ("age", "30"),
+                    ("agent", "Firefox on Linux"),
+                ]),
+            )
+            .await?;
+        cache.ft_create("sessions".to_string(), sessions()).await?;
+        assert!(cache
+            .ft_create("sessions".to_string(), sessions())
+            .await
+            .is_err());
+
+        cache
+            .hset(
+                "session:2".to_string(),
+                pairs(&[("tenant", "Acme, beta"), ("age", "5"), ("agent", "Safari")]),
+            )
+            .await?;
+        cache
+            .hset(
+                "session:3".to_string(),
+                pairs(&[("tenant", "other"), ("age", "50")]),
+            )
+            .await?;
+        cache
+            .hset("user:1".to_string(), pairs(&[("tenant", "acme")]))
+            .await?;
+
+        let all = SearchOptions::default();
+        assert_eq!(
+            search(&cache, "@tenant:{acme}", all.clone()).await?,
+            vec!["session:1", "session:2"]
+        );
+        assert_eq!(
+            search(&cache, "@age:[(5 +inf] -@tenant:{other}", all.clone()).await?,
+            vec!["session:1"]
+        );
+        assert_eq!(
+            search(&cache, "linux", all.clone()).await?,
+            vec!["session:1"]
+        );
+
+        let by_age = SearchOptions {
+            sort_by: Some(("age".to_string(), true)),
+            offset: 1,
+            limit: 1,
+            highlight: None,
+        };
+        let (total, page) = cache
+            .ft_search("sessions".to_string(), Query::parse("*")?, by_age)
+            .await?;
+        assert_eq!(total, 3);
+        assert_eq!(page[0].0, "session:1");
+        assert_eq!(page[0].2[0], ("age".to_string(), "30".to_string()));
+
+        // writes, deletes and expiry update the index
+        cache
+            .hset("session:1".to_string(), pairs(&[("tenant", "other")]))
+            .await?;
+        cache.remove("session:2".to_string()).await?;
+        cache
+            .expire(
+                "session:3".to_string(),
+                Expiry::after(Duration::from_millis(1))?,
+            )
+            .await;
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(search(&cache, "*", all.clone()).await?, vec!["session:1"]);
+        cache.purge().await;
+        assert_eq!(
+            search(&cache, "@tenant:{other}", all.clone()).await?,
+            vec!["session:1"]
+        );
+        assert!(cache.indexes.read().unwrap()["sessions"]
+            .documents
+            .contains_key("session:1"));
+        assert_eq!(cache.indexes.read().unwrap()["sessions"].documents.len(), 1);
+
+        cache.ft_dropindex("sessions".to_string()).await?;
+        assert!(cache.ft_dropindex("sessions".to_string()).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_evicted_hash_leaves_the_index() -> Result<()> {
+        let cache = Cache::default();
+        cache.ft_create("sessions".to_string(), sessions()).await?;
+        cache
+            .hset("session:1".to_string(), pairs(&[("tenant", "acme")]))
+            .await?;
+        cache
+            .expire(
+                "session:1".to_string(),
+                Expiry::after(Duration::from_millis(1))?,
+            )
+            .await;
+        std::thread::sleep(Duration::from_millis(5));
+
+        // evicted by a read rather than by the sampler
+        assert!(!cache.expire("session:1".to_string(), Expiry::none()).await);
+        let all = SearchOptions::default();
+        assert!(search(&cache, "@tenant:{acme}", all).await?.is_empty());
+        assert!(cache.indexes.read().unwrap()["sessions"]
+            .documents
+            .is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_full_text() -> Result<()> {
+        let cache = Cache::default();
+        for (key, value) in [
+            (
+                "product:1",
+                "Wireless noise cancelling headphones with a long battery life",
+            ),
+            ("product:2", "Wired headphones"),
+            ("product:3", "Laptop stand, adjustable"),
+            ("product:4", "Noise, noise everywhere"),
+        ] {
+            cache.set(key.to_string(), value.to_string()).await?;
+        }
+        let options = IndexOptions {
+            source: Source::String,
+            prefixes: vec!["product:".to_string()],
+            analyzer: Analyzer::default(),
+        };
+        let schema = vec![("value".to_string(), FieldType::Text)];
+        cache
+            .ft_create("products".to_string(), SearchIndex::new(options, schema))
+            .await?;
+
+        let find = |query: &'static str| {
+            let cache = &cache;
+            async move {
+                let (_, page) = cache
+                    .ft_search(
+                        "products".to_string(),
+                        Query::parse(query)?,
+                        SearchOptions::default(),
+                    )
+                    .await?;
+                Ok::<_, Error>(page.into_iter().map(|(key, _, _)| key).collect::<Vec<_>>())
+            }
+        };
+        // shorter documents and more frequent terms rank first
+        assert_eq!(find("headphone").await?, vec!["product:2", "product:1"]);
+        assert_eq!(find("noise").await?, vec!["product:4", "product:1"]);
+        assert_eq!(find("\"noise cancelled\"").await?, vec!["product:1"]);
+        assert_eq!(find("\"cancelling noise\"").await?, Vec::<String>::new());
+        assert_eq!(find("lap*").await?, vec!["product:3"]);
+        assert_eq!(find("headphones -wired").await?, vec!["product:1"]);
+        assert_eq!(find("the").await?, Vec::<String>::new());
+
+        let highlighted = SearchOptions {
+            highlight: Some(("[".to_string(), "]".to_string())),
+            ..SearchOptions::default()
+        };
+        let (_, page) = cache
+            .ft_search(
+                "products".to_string(),
+                Query::parse("cancel* life")?,
+                highlighted,
+            )
+            .await?;
+        assert!(page[0].1 > 0.0);
+        assert_eq!(
+            page[0].2,
+            vec![(
+                "value".to_string(),
+                "Wireless noise [cancelling] headphones with a long battery [life]".to_string()
+            )]
+        );
+
+        // overwrites and deletes update the index
+        cache
+            .set("product:3".to_string(), "Standing desk".to_string())
+            .await?;
+        cache.remove("product:2".to_string()).await?;
+        assert_eq!(find("laptop").await?, Vec::<String>::new());
+        assert_eq!(find("stand").await?, vec!["product:3"]);
+        assert_eq!(find("wired").await?, Vec::<String>::new());
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use crate::cache::members::Members;
+use crate::cache::Cache;
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+/// A collection of unique strings, iterated in order. Members may expire on their own.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Set {
+    members: Members<()>,
+}
+
+impl Set {
+    pub fn new() -> Self {
+        Set::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Add `member`, returning whether it is new. Existing members keep their expiry.
+    pub fn add(&mut self, member: String) -> bool {
+        if self.members.contains(&member) {
+            return false;
+        }
+        self.members.insert(member, ())
+    }
+
+    /// Remove `member`, returning whether it existed.
+    pub fn remove(&mut self, member: &str) -> bool {
+        self.members.remove(member)
+    }
+
+    pub fn contains(&self, member: &str) -> bool {
+        self.members.contains(member)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.members.iter().map(|(member, _)| member)
+    }
+
+    pub fn members(&self) -> &Members<()> {
+        &self.members
+    }
+
+    pub fn members_mut(&mut self) -> &mut Members<()> {
+        &mut self.members
+    }
+
+    /// Retrieve an estimate of the memory used by the members, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.iter()
+            .map(|member| member.len() + std::mem::size_of::<String>())
+            .sum()
+    }
+}
+
+impl Cache {
+    /// Add `members` to the set at `key`, creating it if missing. Returns how many were added.
+    pub async fn sadd(&self, key: String, members: Vec<String>) -> Result<usize> {
+        let added = self.write_object(
+            &key,
+            || Some(Set::new().into()),
+            |object| {
+                let set = object.as_set_mut()?;
+                Ok(members
+                    .into_iter()
+                    .filter(|member| set.add(member.clone()))
+                    .count())
+            },
+        )?;
+        Ok(added.unwrap_or(0))
+    }
+
+    /// Remove `members` from the set at `key`, returning how many existed.
+    pub async fn srem(&self, key: String, members: Vec<String>) -> Result<usize> {
+        let removed = self.shrink_object(&key, |object| {
+            let set = object.as_set_mut()?;
+            Ok(members.iter().filter(|member| set.remove(member)).count())
+        })?;
+        Ok(removed.unwrap_or(0))
+    }
+
+    pub async fn smembers(&self, key: String) -> Result<Vec<String>> {
+        let members = self.read_object(&key, |object| {
+            Ok(object.as_set()?.iter().cloned().collect())
+        })?;
+        Ok(members.unwrap_or_default())
+    }
+
+    pub async fn sismember(&self, key: String, member: String) -> Result<bool> {
+        let found = self.read_object(&key, |object| Ok(object.as_set()?.contains(&member)))?;
+        Ok(found.unwrap_or(false))
+    }
+
+    pub async fn scard(&self, key: String) -> Result<usize> {
+        let len = self.read_object(&key, |object| Ok(object.as_set()?.len()))?;
+        Ok(len.unwrap_or(0))
+    }
+
+    /// Expire `members` of the set at `key` at `instant`. For every member, returns `None` if
+    /// it is missing, or whether it is still there otherwise.
+    pub async fn sexpire(
+        &self,
+        key: String,
+        members: Vec<String>,
+        instant: Instant,
+    ) -> Result<Vec<Option<bool>>> {
+        let expired = self.shrink_object(&key, |object| {
+            let set = object.as_set_mut()?.members_mut();
+            Ok(members
+                .iter()
+                .map(|member| set.expire(member, instant))
+                .collect())
+        })?;
+        Ok(expired.unwrap_or_else(|| vec![None; members.len()]))
+    }
+
+    /// Retrieve the time left before `members` of the set at `key` expire.
+    pub async fn sttl(
+        &self,
+        key: String,
+        members: Vec<String>,
+    ) -> Result<Vec<Option<Option<Duration>>>> {
+        let remaining = self.read_object(&key, |object| {
+            let set = object.as_set()?;
+            Ok(members
+                .iter()
+                .map(|member| set.members().ttl(member))
+                .collect())
+        })?;
+        Ok(remaining.unwrap_or_else(|| vec![None; members.len()]))
+    }
+
+    /// Drop the expiry of `members` of the set at `key`. For every member, returns `None` if
+    /// it is missing, or whether it had an expiry otherwise.
+    pub async fn spersist(&self, key: String, members: Vec<String>) -> Result<Vec<Option<bool>>> {
+        let persisted = self.shrink_object(&key, |object| {
+            let set = object.as_set_mut()?.members_mut();
+            Ok(members.iter().map(|member| set.persist(member)).collect())
+        })?;
+        Ok(persisted.unwrap_or_else(|| vec![None; members.len()]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(strings: &[&str]) -> Vec<String> {
+        strings.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_set_commands() -> Result<()> {
+        let cache = Cache::default();
+        let key = || "tags".to_string();
+        assert_eq!(cache.sadd(key(), strings(&["b", "a", "b"])).await?, 2);
+        assert_eq!(cache.sadd(key(), strings(&["a", "c"])).await?, 1);
+        assert_eq!(cache.smembers(key()).await?, strings(&["a", "b", "c"]));
+        assert!(cache.sismember(key(), "c".to_string()).await?);
+        assert!(!cache.sismember(key(), "d".to_string()).await?);
+        assert_eq!(cache.scard(key()).await?, 3);
+
+        assert_eq!(cache.srem(key(), strings(&["a", "d"])).await?, 1);
+        assert_eq!(cache.srem(key(), strings(&["b", "c"])).await?, 2);
+        assert!(!cache.exists(key()).await);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_member_expiry() -> Result<()> {
+        let cache = Cache::default();
+        let key = || "online".to_string();
+        cache.sadd(key(), strings(&["ada", "bob"])).await?;
+
+        let soon = Instant::now() + Duration::from_millis(20);
+        let expired = cache.sexpire(key(), strings(&["ada", "eve"]), soon).await?;
+        assert_eq!(expired, vec![Some(true), None]);
+        let remaining = cache.sttl(key(), strings(&["ada", "bob"])).await?;
+        assert!(remaining[0].unwrap().is_some());
+        assert_eq!(remaining[1], Some(None));
+
+        // re-adding a member keeps its expiry
+        assert_eq!(cache.sadd(key(), strings(&["ada"])).await?, 0);
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(cache.smembers(key()).await?, strings(&["bob"]));
+
+        let later = Instant::now() + Duration::from_secs(60);
+        cache.sexpire(key(), strings(&["bob"]), later).await?;
+        assert_eq!(
+            cache.spersist(key(), strings(&["bob"])).await?,
+            vec![Some(true)]
+        );
+        let past = Instant::now() - Duration::from_millis(1);
+        assert_eq!(
+            cache.sexpire(key(), strings(&["bob"]), past).await?,
+            vec![Some(false)]
+        );
+        assert!(!cache.exists(key()).await);
+        Ok(())
+    }
+}
@@ -0,0 +1,1300 @@
+use crate::cache::entry::Entry;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use rand::Rng;
+use std::collections::HashMap;
+use std::iter;
+
+/// Maximum number of levels a skip list node can be linked on.
+const MAX_LEVEL: usize = 32;
+
+/// Index of the header node in the skip list arena.
+const HEAD: usize = 0;
+
+#[derive(Clone, Debug)]
+struct Link {
+    forward: Option<usize>,
+    span: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    member: String,
+    score: f64,
+    backward: Option<usize>,
+    links: Vec<Link>,
+}
+
+impl Node {
+    /// Retrieve whether this node sorts strictly before the given score and member.
+    fn precedes(&self, score: f64, member: &str) -> bool {
+        self.score < score || (self.score == score && self.member.as_str() < member)
+    }
+}
+
+/// A skip list ordered by score then member, the same structure Redis uses for sorted sets.
+///
+/// Every link records how many nodes it jumps over, which lets rank lookups and rank based
+/// access run in O(log n). Nodes live in an arena and refer to each other by index, freed
+/// slots are reused by later inserts.
+#[derive(Clone, Debug)]
+struct SkipList {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    tail: Option<usize>,
+    level: usize,
+    len: usize,
+}
+
+impl SkipList {
+    fn new() -> Self {
+        let head = Node {
+            member: String::new(),
+            score: 0.0,
+            backward: None,
+            links: vec![
+                Link {
+                    forward: None,
+                    span: 0
+                };
+                MAX_LEVEL
+            ],
+        };
+        SkipList {
+            nodes: vec![head],
+            free: Vec::new(),
+            tail: None,
+            level: 1,
+            len: 0,
+        }
+    }
+
+    /// Pick the level of a new node, each extra level has a 1 in 4 chance.
+    fn random_level() -> usize {
+        let mut rng = rand::thread_rng();
+        let mut level = 1;
+        while level < MAX_LEVEL && rng.gen_ratio(1, 4) {
+            level += 1;
+        }
+        level
+    }
+
+    fn forward(&self, node: usize, level: usize) -> Option<usize> {
+        self.nodes[node].links[level].forward
+    }
+
+    fn insert(&mut self, score: f64, member: String) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0usize; MAX_LEVEL];
+
+        // find the insert position on every level, tracking the rank crossed to reach it
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            rank[i] = if i == self.level - 1 { 0 } else { rank[i + 1] };
+            while let Some(next) = self.forward(x, i) {
+                if !self.nodes[next].precedes(score, &member) {
+                    break;
+                }
+                rank[i] += self.nodes[x].links[i].span;
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let level = Self::random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEAD;
+                self.nodes[HEAD].links[i].span = self.len;
+            }
+            self.level = level;
+        }
+
+        let node = Node {
+            member,
+            score,
+            backward: None,
+            links: vec![
+                Link {
+                    forward: None,
+                    span: 0
+                };
+                level
+            ],
+        };
+        let node = match self.free.pop() {
+            Some(idx) => {
+                self.nodes[idx] = node;
+                idx
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+
+        for i in 0..level {
+            let prev = update[i];
+            self.nodes[node].links[i].forward = self.nodes[prev].links[i].forward;
+            self.nodes[prev].links[i].forward = Some(node);
+            self.nodes[node].links[i].span = self.nodes[prev].links[i].span - (rank[0] - rank[i]);
+            self.nodes[prev].links[i].span = rank[0] - rank[i] + 1;
+        }
+
+        // links on the levels above the new node now jump over one more node
+        for (i, prev) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[*prev].links[i].span += 1;
+        }
+
+        self.nodes[node].backward = if update[0] == HEAD {
+            None
+        } else {
+            Some(update[0])
+        };
+        match self.forward(node, 0) {
+            Some(next) => self.nodes[next].backward = Some(node),
+            None => self.tail = Some(node),
+        }
+        self.len += 1;
+    }
+
+    fn remove(&mut self, score: f64, member: &str) -> bool {
+        let mut update = [HEAD; MAX_LEVEL];
+
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if !self.nodes[next].precedes(score, member) {
+                    break;
+                }
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let node = match self.forward(x, 0) {
+            Some(node) if self.nodes[node].score == score && self.nodes[node].member == member => {
+                node
+            }
+            _ => return false,
+        };
+
+        for (i, prev) in update.iter().enumerate().take(self.level) {
+            let link = self.nodes[node].links.get(i).cloned();
+            let prev_link = &mut self.nodes[*prev].links[i];
+            match link {
+                Some(link) if prev_link.forward == Some(node) => {
+                    prev_link.span += link.span;
+                    prev_link.span -= 1;
+                    prev_link.forward = link.forward;
+                }
+                _ => prev_link.span -= 1,
+            }
+        }
+
+        let backward = self.nodes[node].backward;
+        match self.forward(node, 0) {
+            Some(next) => self.nodes[next].backward = backward,
+            None => self.tail = backward,
+        }
+        while self.level > 1 && self.forward(HEAD, self.level - 1).is_none() {
+            self.level -= 1;
+        }
+
+        self.nodes[node].member = String::new();
+        self.nodes[node].links = Vec::new();
+        self.free.push(node);
+        self.len -= 1;
+        true
+    }
+
+    /// Retrieve the 0-based rank of a member.
+    fn rank(&self, score: f64, member: &str) -> Option<usize> {
+        let mut rank = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                let node = &self.nodes[next];
+                if !(node.precedes(score, member) || (node.score == score && node.member == member))
+                {
+                    break;
+                }
+                rank += self.nodes[x].links[i].span;
+                x = next;
+            }
+            if x != HEAD && self.nodes[x].member == member {
+                return Some(rank - 1);
+            }
+        }
+        None
+    }
+
+    /// Retrieve the node at the given 0-based rank.
+    fn by_rank(&self, rank: usize) -> Option<usize> {
+        let target = rank + 1;
+        let mut traversed = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if traversed + self.nodes[x].links[i].span > target {
+                    break;
+                }
+                traversed += self.nodes[x].links[i].span;
+                x = next;
+            }
+            if traversed == target {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    /// Retrieve the first node `before` doesn't hold for. `before` must hold for a prefix of
+    /// the list and not after it.
+    fn first_where_not(&self, before: impl Fn(&Node) -> bool) -> Option<usize> {
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if !before(&self.nodes[next]) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        self.forward(x, 0)
+    }
+
+    /// Retrieve the last node `within` holds for. `within` must hold for a prefix of the list
+    /// and not after it.
+    fn last_where(&self, within: impl Fn(&Node) -> bool) -> Option<usize> {
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if !within(&self.nodes[next]) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        if x == HEAD {
+            None
+        } else {
+            Some(x)
+        }
+    }
+
+    /// Walk the list from `start`, forwards or backwards.
+    fn walk(&self, start: Option<usize>, reverse: bool) -> impl Iterator<Item = &Node> + '_ {
+        iter::successors(start, move |&node| {
+            if reverse {
+                self.nodes[node].backward
+            } else {
+                self.forward(node, 0)
+            }
+        })
+        .map(move |node| &self.nodes[node])
+    }
+}
+
+/// Parse a score, accepting `inf`, `+inf` and `-inf` but rejecting `nan`.
+pub fn parse_score(s: &str) -> Result<f64> {
+    match s.parse::<f64>() {
+        Ok(score) if !score.is_nan() => Ok(score),
+        _ => Err(Error::msg("value is not a valid float")),
+    }
+}
+
+/// One end of a score interval, e.g. `1.5` or the exclusive `(1.5`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScoreBound {
+    pub value: f64,
+    pub exclusive: bool,
+}
+
+impl ScoreBound {
+    pub fn parse(s: &str) -> Result<Self> {
+        let (exclusive, value) = match s.strip_prefix('(') {
+            Some(value) => (true, value),
+            None => (false, s),
+        };
+        match parse_score(value) {
+            Ok(value) => Ok(ScoreBound { value, exclusive }),
+            Err(_) => Err(Error::msg("min or max is not a float")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScoreRange {
+    pub min: ScoreBound,
+    pub max: ScoreBound,
+}
+
+impl ScoreRange {
+    pub fn new(min: ScoreBound, max: ScoreBound) -> Self {
+        ScoreRange { min, max }
+    }
+
+    fn above_min(&self, score: f64) -> bool {
+        if self.min.exclusive {
+            score > self.min.value
+        } else {
+            score >= self.min.value
+        }
+    }
+
+    fn below_max(&self, score: f64) -> bool {
+        if self.max.exclusive {
+            score < self.max.value
+        } else {
+            score <= self.max.value
+        }
+    }
+}
+
+/// One end of a lexicographical interval, `-` and `+` being the smallest and largest strings.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LexBound {
+    Min,
+    Max,
+    Inclusive(String),
+    Exclusive(String),
+}
+
+impl LexBound {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "-" => Ok(LexBound::Min),
+            "+" => Ok(LexBound::Max),
+            _ if s.starts_with('[') => Ok(LexBound::Inclusive(s[1..].to_string())),
+            _ if s.starts_with('(') => Ok(LexBound::Exclusive(s[1..].to_string())),
+            _ => Err(Error::msg("min or max not valid string range item")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexRange {
+    pub min: LexBound,
+    pub max: LexBound,
+}
+
+impl LexRange {
+    pub fn new(min: LexBound, max: LexBound) -> Self {
+        LexRange { min, max }
+    }
+
+    fn above_min(&self, member: &str) -> bool {
+        match &self.min {
+            LexBound::Min => true,
+            LexBound::Max => false,
+            LexBound::Inclusive(value) => member >= value.as_str(),
+            LexBound::Exclusive(value) => member > value.as_str(),
+        }
+    }
+
+    fn below_max(&self, member: &str) -> bool {
+        match &self.max {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(value) => member <= value.as_str(),
+            LexBound::Exclusive(value) => member < value.as_str(),
+        }
+    }
+}
+
+/// Which members a range query selects.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RangeBy {
+    /// Inclusive ranks, negative ranks count from the end.
+    Rank(i64, i64),
+    Score(ScoreRange),
+    Lex(LexRange),
+}
+
+/// Paging applied to a score or lex range, `count` of `None` returns everything after `offset`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limit {
+    pub offset: usize,
+    pub count: Option<usize>,
+}
+
+/// The ZADD flags.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AddOptions {
+    /// Only add new members.
+    pub nx: bool,
+    /// Only update existing members.
+    pub xx: bool,
+    /// Only update when the new score is greater.
+    pub gt: bool,
+    /// Only update when the new score is lower.
+    pub lt: bool,
+    /// Count updated members as well as added ones in the reply.
+    pub ch: bool,
+    /// Increment the score instead of replacing it.
+    pub incr: bool,
+}
+
+impl AddOptions {
+    pub fn validate(&self, pairs: usize) -> Result<()> {
+        if self.nx && self.xx {
+            return Err(Error::msg(
+                "XX and NX options at the same time are not compatible",
+            ));
+        }
+        if (self.nx && (self.gt || self.lt)) || (self.gt && self.lt) {
+            return Err(Error::msg(
+                "GT, LT, and/or NX options at the same time are not compatible",
+            ));
+        }
+        if self.incr && pairs != 1 {
+            return Err(Error::msg(
+                "INCR option supports a single increment-element pair",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// What a ZADD did to a single member, along with the member's resulting score.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AddOutcome {
+    Added(f64),
+    Updated(f64),
+    Unchanged(f64),
+    Skipped,
+}
+
+impl AddOutcome {
+    pub fn score(&self) -> Option<f64> {
+        match self {
+            AddOutcome::Added(score)
+            | AddOutcome::Updated(score)
+            | AddOutcome::Unchanged(score) => Some(*score),
+            AddOutcome::Skipped => None,
+        }
+    }
+}
+
+/// How scores are combined by ZUNIONSTORE and ZINTERSTORE.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aggregate {
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "sum" => Ok(Aggregate::Sum),
+            "min" => Ok(Aggregate::Min),
+            "max" => Ok(Aggregate::Max),
+            _ => Err(Error::msg("syntax error")),
+        }
+    }
+
+    fn combine(&self, a: f64, b: f64) -> f64 {
+        match self {
+            // adding opposite infinities gives NaN, Redis settles it as zero
+            Aggregate::Sum => zero_if_nan(a + b),
+            Aggregate::Min => a.min(b),
+            Aggregate::Max => a.max(b),
+        }
+    }
+}
+
+fn zero_if_nan(score: f64) -> f64 {
+    if score.is_nan() {
+        0.0
+    } else {
+        score
+    }
+}
+
+/// A set of unique members ordered by score, with ties broken lexicographically.
+///
+/// Scores are indexed by member for O(1) lookups, while the order lives in a skip list for
+/// O(log n) inserts, removals and rank queries.
+#[derive(Clone, Debug)]
+pub struct SortedSet {
+    scores: HashMap<String, f64>,
+    list: SkipList,
+}
+
+impl SortedSet {
+    pub fn new() -> Self {
+        SortedSet {
+            scores: HashMap::new(),
+            list: SkipList::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub fn score(&self, member: &str) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Insert a member or update its score, returning whether it was newly added.
+    pub fn insert(&mut self, member: String, score: f64) -> bool {
+        match self.scores.insert(member.clone(), score) {
+            Some(current) => {
+                if current != score {
+                    self.list.remove(current, &member);
+                    self.list.insert(score, member);
+                }
+                false
+            }
+            None => {
+                self.list.insert(score, member);
+                true
+            }
+        }
+    }
+
+    /// Remove a member, returning whether it was present.
+    pub fn remove(&mut self, member: &str) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => self.list.remove(score, member),
+            None => false,
+        }
+    }
+
+    /// Add or update a member following the ZADD flags.
+    pub fn add(&mut self, member: String, score: f64, options: &AddOptions) -> Result<AddOutcome> {
+        match self.score(&member) {
+            Some(current) => {
+                if options.nx {
+                    return Ok(AddOutcome::Skipped);
+                }
+                let score = if options.incr { current + score } else { score };
+                if score.is_nan() {
+                    return Err(Error::msg("resulting score is not a number (NaN)"));
+                }
+                if (options.gt && score <= current) || (options.lt && score >= current) {
+                    return Ok(AddOutcome::Skipped);
+                }
+                if score == current {
+                    return Ok(AddOutcome::Unchanged(score));
+                }
+                self.insert(member, score);
+                Ok(AddOutcome::Updated(score))
+            }
+            None => {
+                if options.xx {
+                    return Ok(AddOutcome::Skipped);
+                }
+                self.insert(member, score);
+                Ok(AddOutcome::Added(score))
+            }
+        }
+    }
+
+    /// Retrieve the 0-based rank of a member, counted from the highest score when `reverse`.
+    pub fn rank(&self, member: &str, reverse: bool) -> Option<usize> {
+        let score = self.score(member)?;
+        let rank = self.list.rank(score, member)?;
+        if reverse {
+            Some(self.len() - 1 - rank)
+        } else {
+            Some(rank)
+        }
+    }
+
+    /// Retrieve the members selected by `by`, in descending order when `reverse`.
+    pub fn range(&self, by: &RangeBy, reverse: bool, limit: &Limit) -> Vec<(String, f64)> {
+        let nodes: Box<dyn Iterator<Item = &Node>> = match by {
+            RangeBy::Rank(start, stop) => {
+                let len = self.len() as i64;
+                let start = if *start < 0 { start + len } else { *start }.max(0);
+                let stop = if *stop < 0 { stop + len } else { *stop }.min(len - 1);
+                if start > stop || start >= len {
+                    return Vec::new();
+                }
+                let first = if reverse { len - 1 - start } else { start };
+                let first = self.list.by_rank(first as usize);
+                Box::new(
+                    self.list
+                        .walk(first, reverse)
+                        .take((stop - start + 1) as usize),
+                )
+            }
+            RangeBy::Score(range) => {
+                let first = if reverse {
+                    self.list.last_where(|node| range.below_max(node.score))
+                } else {
+                    self.list
+                        .first_where_not(|node| !range.above_min(node.score))
+                };
+                Box::new(self.list.walk(first, reverse).take_while(move |node| {
+                    range.above_min(node.score) && range.below_max(node.score)
+                }))
+            }
+            RangeBy::Lex(range) => {
+                let first = if reverse {
+                    self.list.last_where(|node| range.below_max(&node.member))
+                } else {
+                    self.list
+                        .first_where_not(|node| !range.above_min(&node.member))
+                };
+                Box::new(self.list.walk(first, reverse).take_while(move |node| {
+                    range.above_min(&node.member) && range.below_max(&node.member)
+                }))
+            }
+        };
+
+        nodes
+            .skip(limit.offset)
+            .take(limit.count.unwrap_or(usize::MAX))
+            .map(|node| (node.member.clone(), node.score))
+            .collect()
+    }
+
+    /// Remove every member whose score is within `range`, returning how many were removed.
+    pub fn remove_range_by_score(&mut self, range: &ScoreRange) -> usize {
+        let members = self.range(&RangeBy::Score(*range), false, &Limit::default());
+        for (member, _) in &members {
+            self.remove(member);
+        }
+        members.len()
+    }
+
+    /// Remove and return up to `count` members with the lowest scores, or the highest when
+    /// `max`.
+    pub fn pop(&mut self, count: usize, max: bool) -> Vec<(String, f64)> {
+        let limit = Limit {
+            offset: 0,
+            count: Some(count),
+        };
+        let members = self.range(&RangeBy::Rank(0, -1), max, &limit);
+        for (member, _) in &members {
+            self.remove(member);
+        }
+        members
+    }
+
+    /// Iterate over members and scores in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.list
+            .walk(self.list.forward(HEAD, 0), false)
+            .map(|node| (node.member.as_str(), node.score))
+    }
+
+    /// Retrieve an estimate of the memory used by the members, in bytes. Members are held by
+    /// both the score map and the skip list.
+    pub fn memory_usage(&self) -> usize {
+        self.iter()
+            .map(|(member, _)| 2 * (member.len() + std::mem::size_of::<(String, f64)>()))
+            .sum()
+    }
+}
+
+impl Default for SortedSet {
+    fn default() -> Self {
+        SortedSet::new()
+    }
+}
+
+impl PartialEq for SortedSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.scores == other.scores
+    }
+}
+
+impl FromIterator<(String, f64)> for SortedSet {
+    fn from_iter<T: IntoIterator<Item = (String, f64)>>(iter: T) -> Self {
+        let mut set = SortedSet::new();
+        for (member, score) in iter {
+            set.insert(member, score);
+        }
+        set
+    }
+}
+
+impl Cache {
+    pub async fn zadd(
+        &self,
+        key: String,
+        options: AddOptions,
+        pairs: Vec<(f64, String)>,
+    ) -> Result<Vec<AddOutcome>> {
+        options.validate(pairs.len())?;
+
+        let skipped = vec![AddOutcome::Skipped; pairs.len()];
+        let outcomes = self.write_object(
+            &key,
+            || (!options.xx).then(|| SortedSet::new().into()),
+            |object| {
+                let set = object.as_sorted_set_mut()?;
+                pairs
+                    .into_iter()
+                    .map(|(score, member)| set.add(member, score, &options))
+                    .collect()
+            },
+        )?;
+        if outcomes.is_some() {
+            self.wake_blocked(&key);
+        }
+        Ok(outcomes.unwrap_or(skipped))
+    }
+
+    pub async fn zcard(&self, key: String) -> Result<usize> {
+        let len = self.read_object(&key, |object| Ok(object.as_sorted_set()?.len()))?;
+        Ok(len.unwrap_or(0))
+    }
+
+    pub async fn zscore(&self, key: String, member: String) -> Result<Option<f64>> {
+        let score = self.read_object(&key, |object| Ok(object.as_sorted_set()?.score(&member)))?;
+        Ok(score.flatten())
+    }
+
+    /// Retrieve the rank of a member along with its score.
+    pub async fn zrank(
+        &self,
+        key: String,
+        member: String,
+        reverse: bool,
+    ) -> Result<Option<(usize, f64)>> {
+        let rank = self.read_object(&key, |object| {
+            let set = object.as_sorted_set()?;
+            Ok(set.rank(&member, reverse).zip(set.score(&member)))
+        })?;
+        Ok(rank.flatten())
+    }
+
+    pub async fn zrange(
+        &self,
+        key: String,
+        by: RangeBy,
+        reverse: bool,
+        limit: Limit,
+    ) -> Result<Vec<(String, f64)>> {
+        let members = self.read_object(&key, |object| {
+            Ok(object.as_sorted_set()?.range(&by, reverse, &limit))
+        })?;
+        Ok(members.unwrap_or_default())
+    }
+
+    pub async fn zrem(&self, key: String, members: Vec<String>) -> Result<usize> {
+        let removed = self.shrink_object(&key, |object| {
+            let set = object.as_sorted_set_mut()?;
+            Ok(members.iter().filter(|member| set.remove(member)).count())
+        })?;
+        Ok(removed.unwrap_or(0))
+    }
+
+    pub async fn zremrangebyscore(&self, key: String, range: ScoreRange) -> Result<usize> {
+        let removed = self.shrink_object(&key, |object| {
+            Ok(object.as_sorted_set_mut()?.remove_range_by_score(&range))
+        })?;
+        Ok(removed.unwrap_or(0))
+    }
+
+    /// Remove and return up to `count` of the lowest scored members, or the highest when `max`.
+    pub async fn zpop(&self, key: String, count: usize, max: bool) -> Result<Vec<(String, f64)>> {
+        let members = self.shrink_object(&key, |object| {
+            Ok(object.as_sorted_set_mut()?.pop(count, max))
+        })?;
+        Ok(members.unwrap_or_default())
+    }
+
+    /// Store the union of the sorted sets at `keys` in `destination`, returning its size.
+    pub async fn zunionstore(
+        &self,
+        destination: String,
+        keys: Vec<String>,
+        weights: Option<Vec<f64>>,
+        aggregate: Aggregate,
+    ) -> Result<usize> {
+        self.zstore(destination, keys, weights, aggregate, false)
+    }
+
+    /// Store the intersection of the sorted sets at `keys` in `destination`, returning its
+    /// size.
+    pub async fn zinterstore(
+        &self,
+        destination: String,
+        keys: Vec<String>,
+        weights: Option<Vec<f64>>,
+        aggregate: Aggregate,
+    ) -> Result<usize> {
+        self.zstore(destination, keys, weights, aggregate, true)
+    }
+
+    fn zstore(
+        &self,
+        destination: String,
+        keys: Vec<String>,
+        weights: Option<Vec<f64>>,
+        aggregate: Aggregate,
+        intersect: bool,
+    ) -> Result<usize> {
+        let weights = weights.unwrap_or_else(|| vec![1.0; keys.len()]);
+        if weights.len() != keys.len() {
+            return Err(Error::msg("syntax error"));
+        }
+
+        let mut store = self.store.write().unwrap();
+        for key in &keys {
+            self.evict_if_expired(&mut store, key);
+        }
+
+        let mut sets = Vec::with_capacity(keys.len());
+        for key in &keys {
+            let set = match store.get(key) {
+                Some(entry) => Some(entry.value().as_sorted_set()?),
+                None => None,
+            };
+            sets.push(set);
+        }
+
+        let weighted = |score: f64, weight: f64| zero_if_nan(score * weight);
+        let mut scores: HashMap<String, f64> = HashMap::new();
+        if intersect {
+            if let Some(Some(first)) = sets.first() {
+                'members: for (member, score) in first.iter() {
+                    let mut total = weighted(score, weights[0]);
+                    for (set, weight) in sets.iter().zip(&weights).skip(1) {
+                        match set.and_then(|set| set.score(member)) {
+                            Some(score) => {
+                                total = aggregate.combine(total, weighted(score, *weight))
+                            }
+                            None => continue 'members,
+                        }
+                    }
+                    scores.insert(member.to_string(), total);
+                }
+            }
+        } else {
+            for (set, weight) in sets.iter().zip(&weights) {
+                for (member, score) in set.iter().flat_map(|set| set.iter()) {
+                    let score = weighted(score, *weight);
+                    scores
+                        .entry(member.to_string())
+                        .and_modify(|total| *total = aggregate.combine(*total, score))
+                        .or_insert(score);
+                }
+            }
+        }
+
+        let result: SortedSet = scores.into_iter().collect();
+        let len = result.len();
+        if result.is_empty() {
+            self.remove_entry(&mut store, &destination);
+        } else {
+            self.admit(&mut store, &destination)?;
+            let entry = Entry::new(result, self.default_expiry());
+            self.charge(destination.len() + entry.value().memory_usage());
+            self.place(&mut store, destination.clone(), entry);
+            drop(store);
+            self.wake_blocked(&destination);
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(range: Vec<(String, f64)>) -> Vec<String> {
+        range.into_iter().map(|(member, _)| member).collect()
+    }
+
+    fn sample() -> SortedSet {
+        vec![
+            ("a".to_string(), 1.0),
+            ("b".to_string(), 2.0),
+            ("c".to_string(), 3.0),
+            ("d".to_string(), 4.0),
+            ("e".to_string(), 5.0),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn test_insert_and_score() {
+        let mut set = SortedSet::new();
+        assert!(set.insert("a".to_string(), 1.0));
+        assert!(!set.insert("a".to_string(), 2.0));
+        assert_eq!(set.len(), 1);
+        assert_eq!(set.score("a"), Some(2.0));
+        assert_eq!(set.score("b"), None);
+    }
+
+    #[test]
+    fn test_order_ties_broken_by_member() {
+        let mut set = SortedSet::new();
+        set.insert("b".to_string(), 1.0);
+        set.insert("a".to_string(), 1.0);
+        set.insert("c".to_string(), 0.0);
+        let order: Vec<&str> = set.iter().map(|(member, _)| member).collect();
+        assert_eq!(order, vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_rank_with_many_members() {
+        let mut set = SortedSet::new();
+        for i in (0..1000).rev() {
+            set.insert(format!("m{}", i), i as f64);
+        }
+        for i in 0..1000 {
+            assert_eq!(set.rank(&format!("m{}", i), false), Some(i));
+            assert_eq!(set.rank(&format!("m{}", i), true), Some(999 - i));
+        }
+        for i in (0..1000).step_by(2) {
+            assert!(set.remove(&format!("m{}", i)));
+        }
+        assert_eq!(set.len(), 500);
+        for i in (1..1000).step_by(2) {
+            assert_eq!(set.rank(&format!("m{}", i), false), Some(i / 2));
+        }
+        assert_eq!(set.rank("m0", false), None);
+    }
+
+    #[test]
+    fn test_range_by_rank() {
+        let set = sample();
+        let all = RangeBy::Rank(0, -1);
+        assert_eq!(
+            members(set.range(&all, false, &Limit::default())),
+            vec!["a", "b", "c", "d", "e"]
+        );
+        assert_eq!(
+            members(set.range(&RangeBy::Rank(1, 2), false, &Limit::default())),
+            vec!["b", "c"]
+        );
+        assert_eq!(
+            members(set.range(&RangeBy::Rank(-2, -1), false, &Limit::default())),
+            vec!["d", "e"]
+        );
+        assert_eq!(
+            members(set.range(&RangeBy::Rank(0, 1), true, &Limit::default())),
+            vec!["e", "d"]
+        );
+        assert!(set
+            .range(&RangeBy::Rank(3, 1), false, &Limit::default())
+            .is_empty());
+        assert!(set
+            .range(&RangeBy::Rank(10, 20), false, &Limit::default())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_range_by_score() -> Result<()> {
+        let set = sample();
+        let range = ScoreRange::new(ScoreBound::parse("(1")?, ScoreBound::parse("4")?);
+        assert_eq!(
+            members(set.range(&RangeBy::Score(range), false, &Limit::default())),
+            vec!["b", "c", "d"]
+        );
+        assert_eq!(
+            members(set.range(&RangeBy::Score(range), true, &Limit::default())),
+            vec!["d", "c", "b"]
+        );
+
+        let limit = Limit {
+            offset: 1,
+            count: Some(1),
+        };
+        assert_eq!(
+            members(set.range(&RangeBy::Score(range), false, &limit)),
+            vec!["c"]
+        );
+
+        let all = ScoreRange::new(ScoreBound::parse("-inf")?, ScoreBound::parse("+inf")?);
+        assert_eq!(
+            set.range(&RangeBy::Score(all), false, &Limit::default())
+                .len(),
+            5
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_by_lex() -> Result<()> {
+        let set: SortedSet = ["apple", "banana", "cherry", "date"]
+            .iter()
+            .map(|member| (member.to_string(), 0.0))
+            .collect();
+        let range = LexRange::new(LexBound::parse("[b")?, LexBound::parse("(d")?);
+        assert_eq!(
+            members(set.range(&RangeBy::Lex(range.clone()), false, &Limit::default())),
+            vec!["banana", "cherry"]
+        );
+        assert_eq!(
+            members(set.range(&RangeBy::Lex(range), true, &Limit::default())),
+            vec!["cherry", "banana"]
+        );
+        let all = LexRange::new(LexBound::parse("-")?, LexBound::parse("+")?);
+        assert_eq!(
+            set.range(&RangeBy::Lex(all), false, &Limit::default())
+                .len(),
+            4
+        );
+        assert!(LexBound::parse("b").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_range_by_score() -> Result<()> {
+        let mut set = sample();
+        let range = ScoreRange::new(ScoreBound::parse("2")?, ScoreBound::parse("(4")?);
+        assert_eq!(set.remove_range_by_score(&range), 2);
+        assert_eq!(
+            members(set.range(&RangeBy::Rank(0, -1), false, &Limit::default())),
+            vec!["a", "d", "e"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_pop() {
+        let mut set = sample();
+        assert_eq!(members(set.pop(2, false)), vec!["a", "b"]);
+        assert_eq!(members(set.pop(1, true)), vec!["e"]);
+        assert_eq!(set.len(), 2);
+        assert_eq!(members(set.pop(10, false)), vec!["c", "d"]);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn test_add_options() {
+        let mut set = sample();
+        let nx = AddOptions {
+            nx: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            set.add("a".to_string(), 9.0, &nx).unwrap(),
+            AddOutcome::Skipped
+        );
+        assert_eq!(
+            set.add("z".to_string(), 9.0, &nx).unwrap(),
+            AddOutcome::Added(9.0)
+        );
+
+        let xx = AddOptions {
+            xx: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            set.add("y".to_string(), 9.0, &xx).unwrap(),
+            AddOutcome::Skipped
+        );
+
+        let gt = AddOptions {
+            gt: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            set.add("c".to_string(), 1.0, &gt).unwrap(),
+            AddOutcome::Skipped
+        );
+        assert_eq!(
+            set.add("c".to_string(), 10.0, &gt).unwrap(),
+            AddOutcome::Updated(10.0)
+        );
+
+        let incr = AddOptions {
+            incr: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            set.add("a".to_string(), 2.5, &incr).unwrap(),
+            AddOutcome::Updated(3.5)
+        );
+        assert_eq!(set.rank("a", false), Some(1));
+    }
+
+    #[test]
+    fn test_add_options_validation() {
+        let both = AddOptions {
+            nx: true,
+            xx: true,
+            ..Default::default()
+        };
+        assert!(both.validate(1).is_err());
+        let gt_lt = AddOptions {
+            gt: true,
+            lt: true,
+            ..Default::default()
+        };
+        assert!(gt_lt.validate(1).is_err());
+        let incr = AddOptions {
+            incr: true,
+            ..Default::default()
+        };
+        assert!(incr.validate(2).is_err());
+        assert!(incr.validate(1).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_cache_zadd_and_zrange() -> Result<()> {
+        let cache = Cache::default();
+        let outcomes = cache
+            .zadd(
+                "board".to_string(),
+                AddOptions::default(),
+                vec![(10.0, "alice".to_string()), (20.0, "bob".to_string())],
+            )
+            .await?;
+        assert_eq!(
+            outcomes,
+            vec![AddOutcome::Added(10.0), AddOutcome::Added(20.0)]
+        );
+        assert_eq!(cache.zcard("board".to_string()).await?, 2);
+        assert_eq!(
+            cache.zscore("board".to_string(), "bob".to_string()).await?,
+            Some(20.0)
+        );
+        assert_eq!(
+            cache
+                .zrank("board".to_string(), "bob".to_string(), true)
+                .await?,
+            Some((0, 20.0))
+        );
+        let range = cache
+            .zrange(
+                "board".to_string(),
+                RangeBy::Rank(0, -1),
+                true,
+                Limit::default(),
+            )
+            .await?;
+        assert_eq!(members(range), vec!["bob", "alice"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_zadd_xx_does_not_create_key() -> Result<()> {
+        let cache = Cache::default();
+        let xx = AddOptions {
+            xx: true,
+            ..Default::default()
+        };
+        let outcomes = cache
+            .zadd("board".to_string(), xx, vec![(1.0, "alice".to_string())])
+            .await?;
+        assert_eq!(outcomes, vec![AddOutcome::Skipped]);
+        assert!(!cache.exists("board".to_string()).await);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_wrong_type() {
+        let cache = Cache::default();
+        cache
+            .set("key".to_string(), "value".to_string())
+            .await
+            .unwrap();
+        let result = cache
+            .zadd(
+                "key".to_string(),
+                AddOptions::default(),
+                vec![(1.0, "a".to_string())],
+            )
+            .await;
+        assert!(result.is_err());
+        assert!(cache.zcard("key".to_string()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cache_zrem_removes_empty_key() -> Result<()> {
+        let cache = Cache::default();
+        cache
+            .zadd(
+                "set".to_string(),
+                AddOptions::default(),
+                vec![(1.0, "a".to_string())],
+            )
+            .await?;
+        let removed = cache
+            .zrem("set".to_string(), vec!["a".to_string(), "b".to_string()])
+            .await?;
+        assert_eq!(removed, 1);
+        assert!(!cache.exists("set".to_string()).await);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_zunionstore_and_zinterstore() -> Result<()> {
+        let cache = Cache::default();
+        cache
+            .zadd(
+                "a".to_string(),
+                AddOptions::default(),
+                vec![(1.0, "x".to_string()), (2.0, "y".to_string())],
+            )
+            .await?;
+        cache
+            .zadd(
+                "b".to_string(),
+                AddOptions::default(),
+                vec![(10.0, "y".to_string()), (20.0, "z".to_string())],
+            )
+            .await?;
+
+        let keys = vec!["a".to_string(), "b".to_string()];
+        let len = cache
+            .zunionstore("u".to_string(), keys.clone(), None, Aggregate::Sum)
+            .await?;
+        assert_eq!(len, 3);
+        assert_eq!(
+            cache.zscore("u".to_string(), "y".to_string()).await?,
+            Some(12.0)
+        );
+
+        let len = cache
+            .zinterstore(
+                "i".to_string(),
+                keys.clone(),
+                Some(vec![2.0, 1.0]),
+                Aggregate::Max,
+            )
+            .await?;
+        assert_eq!(len, 1);
+        assert_eq!(
+            cache.zscore("i".to_string(), "y".to_string()).await?,
+            Some(10.0)
+        );
+
+        let len = cache
+            .zinterstore(
+                "i".to_string(),
+                vec!["a".to_string(), "missing".to_string()],
+                None,
+                Aggregate::Sum,
+            )
+            .await?;
+        assert_eq!(len, 0);
+        assert!(!cache.exists("i".to_string()).await);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_zpop() -> Result<()> {
+        let cache = Cache::default();
+        cache
+            .zadd(
+                "set".to_string(),
+                AddOptions::default(),
+                vec![(1.0, "a".to_string()), (2.0, "b".to_string())],
+            )
+            .await?;
+        let popped = cache.zpop("set".to_string(), 1, true).await?;
+        assert_eq!(popped, vec![("b".to_string(), 2.0)]);
+        assert_eq!(cache.zcard("set".to_string()).await?, 1);
+        Ok(())
+    }
+}
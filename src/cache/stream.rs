@@ -0,0 +1,1149 @@
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Bound;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The field value pairs of a stream entry.
+pub type Fields = Vec<(String, String)>;
+
+/// Entries delivered to a consumer group, with no fields for entries deleted since they were
+/// delivered.
+pub type GroupEntries = Vec<(StreamId, Option<Fields>)>;
+
+/// Retrieve the current unix time in milliseconds.
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn invalid_id() -> Error {
+    Error::msg("Invalid stream ID specified as stream command argument")
+}
+
+/// The id of a stream entry, a millisecond timestamp followed by a sequence number.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    pub fn new(ms: u64, seq: u64) -> Self {
+        StreamId { ms, seq }
+    }
+
+    /// Parse `ms-seq`, or a bare `ms` in which case the sequence is `missing_seq`.
+    pub fn parse(s: &str, missing_seq: u64) -> Result<Self> {
+        let (ms, seq) = match s.split_once('-') {
+            Some((ms, seq)) => (ms, seq.parse::<u64>().map_err(|_| invalid_id())?),
+            None => (s, missing_seq),
+        };
+        let ms = ms.parse::<u64>().map_err(|_| invalid_id())?;
+        Ok(StreamId { ms, seq })
+    }
+
+    /// Parse the start of an XRANGE interval, `-` being the smallest id and `(` marking an
+    /// exclusive bound.
+    pub fn parse_start(s: &str) -> Result<Bound<StreamId>> {
+        match s {
+            "-" => Ok(Bound::Included(StreamId::MIN)),
+            "+" => Ok(Bound::Included(StreamId::MAX)),
+            _ => match s.strip_prefix('(') {
+                Some(id) => Ok(Bound::Excluded(StreamId::parse(id, 0)?)),
+                None => Ok(Bound::Included(StreamId::parse(s, 0)?)),
+            },
+        }
+    }
+
+    /// Parse the end of an XRANGE interval, `+` being the largest id and `(` marking an
+    /// exclusive bound.
+    pub fn parse_end(s: &str) -> Result<Bound<StreamId>> {
+        match s {
+            "-" => Ok(Bound::Included(StreamId::MIN)),
+            "+" => Ok(Bound::Included(StreamId::MAX)),
+            _ => match s.strip_prefix('(') {
+                Some(id) => Ok(Bound::Excluded(StreamId::parse(id, u64::MAX)?)),
+                None => Ok(Bound::Included(StreamId::parse(s, u64::MAX)?)),
+            },
+        }
+    }
+
+    fn successor(&self) -> Option<StreamId> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId::new(self.ms, seq)),
+            None => self.ms.checked_add(1).map(|ms| StreamId::new(ms, 0)),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+/// The id given to XADD.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IdSpec {
+    /// `*`, generate the whole id.
+    Auto,
+    /// `ms-*`, generate the sequence only.
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+impl IdSpec {
+    pub fn parse(s: &str) -> Result<Self> {
+        if s == "*" {
+            return Ok(IdSpec::Auto);
+        }
+        match s.strip_suffix("-*") {
+            Some(ms) => Ok(IdSpec::AutoSeq(
+                ms.parse::<u64>().map_err(|_| invalid_id())?,
+            )),
+            None => Ok(IdSpec::Explicit(StreamId::parse(s, 0)?)),
+        }
+    }
+}
+
+/// Where a consumer group, or a plain XREAD, starts reading from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReadFrom {
+    /// `$`, only entries added after the command runs.
+    Last,
+    /// Entries with an id greater than the given one.
+    After(StreamId),
+}
+
+impl ReadFrom {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "$" => Ok(ReadFrom::Last),
+            _ => Ok(ReadFrom::After(StreamId::parse(s, 0)?)),
+        }
+    }
+}
+
+/// What XREADGROUP reads for a stream.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GroupRead {
+    /// `>`, entries never delivered to the group.
+    New,
+    /// The consumer's own pending entries with an id greater than the given one.
+    Pending(StreamId),
+}
+
+impl GroupRead {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            ">" => Ok(GroupRead::New),
+            _ => Ok(GroupRead::Pending(StreamId::parse(s, 0)?)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrimStrategy {
+    /// Keep at most this many entries.
+    MaxLen(usize),
+    /// Drop entries with an id lower than this one.
+    MinId(StreamId),
+}
+
+/// The trimming requested by XTRIM or XADD.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trim {
+    pub strategy: TrimStrategy,
+    /// Maximum number of entries evicted at once.
+    pub limit: Option<usize>,
+}
+
+/// The XCLAIM options.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ClaimOptions {
+    /// Set the idle time of claimed entries, in milliseconds.
+    pub idle: Option<u64>,
+    /// Set the last delivery of claimed entries, as a unix time in milliseconds.
+    pub time: Option<u64>,
+    /// Set the delivery count of claimed entries.
+    pub retry_count: Option<u64>,
+    /// Claim ids that aren't pending yet, as long as the entry exists.
+    pub force: bool,
+    /// Don't increment the delivery count.
+    pub just_id: bool,
+}
+
+/// The pending entries the extended form of XPENDING lists.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingQuery {
+    pub start: Bound<StreamId>,
+    pub end: Bound<StreamId>,
+    pub count: usize,
+    /// Only list the entries of this consumer.
+    pub consumer: Option<String>,
+    /// Only list the entries idle for at least this many milliseconds.
+    pub min_idle: Option<u64>,
+}
+
+/// An entry delivered to a consumer but not acknowledged yet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingEntry {
+    pub consumer: String,
+    /// Last delivery, as a unix time in milliseconds.
+    pub delivered: u64,
+    pub deliveries: u64,
+}
+
+/// The XPENDING summary form.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PendingSummary {
+    pub count: usize,
+    pub first: Option<StreamId>,
+    pub last: Option<StreamId>,
+    pub consumers: Vec<(String, usize)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Consumer {
+    seen: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct ConsumerGroup {
+    last_delivered: StreamId,
+    pending: BTreeMap<StreamId, PendingEntry>,
+    consumers: BTreeMap<String, Consumer>,
+}
+
+impl ConsumerGroup {
+    fn touch(&mut self, consumer: &str, now: u64) {
+        self.consumers
+            .entry(consumer.to_string())
+            .or_insert(Consumer { seen: now })
+            .seen = now;
+    }
+}
+
+/// An append only log of entries ordered by id, with consumer groups tracking what each of
+/// their consumers has been delivered and acknowledged.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stream {
+    entries: BTreeMap<StreamId, Fields>,
+    last_id: StreamId,
+    groups: BTreeMap<String, ConsumerGroup>,
+}
+
+impl Stream {
+    pub fn new() -> Self {
+        Stream::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[cfg(test)]
+    pub fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    /// Append an entry, returning its id. Ids must always grow.
+    pub fn add(&mut self, spec: IdSpec, fields: Fields, now: u64) -> Result<StreamId> {
+        let last = self.last_id;
+        let id = match spec {
+            IdSpec::Auto if now > last.ms => Some(StreamId::new(now, 0)),
+            IdSpec::Auto => last.successor(),
+            IdSpec::AutoSeq(ms) if ms > last.ms => Some(StreamId::new(ms, 0)),
+            IdSpec::AutoSeq(ms) if ms == last.ms => {
+                last.seq.checked_add(1).map(|seq| StreamId::new(ms, seq))
+            }
+            IdSpec::AutoSeq(_) => None,
+            IdSpec::Explicit(id) if id == StreamId::MIN => {
+                return Err(Error::msg(
+                    "The ID specified in XADD must be greater than 0-0",
+                ))
+            }
+            IdSpec::Explicit(id) if id > last => Some(id),
+            IdSpec::Explicit(_) => None,
+        };
+
+        match id {
+            // 0-* can't produce 0-0 either
+            Some(id) if id != StreamId::MIN => {
+                self.entries.insert(id, fields);
+                self.last_id = id;
+                Ok(id)
+            }
+            _ => Err(Error::msg(
+                "The ID specified in XADD is equal or smaller than the target stream top item",
+            )),
+        }
+    }
+
+    /// Retrieve the entries between `start` and `end`, newest first when `reverse`.
+    pub fn range(
+        &self,
+        start: Bound<StreamId>,
+        end: Bound<StreamId>,
+        count: Option<usize>,
+        reverse: bool,
+    ) -> Vec<(StreamId, Fields)> {
+        let empty = match (start, end) {
+            (Bound::Included(s), Bound::Included(e)) => s > e,
+            (Bound::Included(s), Bound::Excluded(e))
+            | (Bound::Excluded(s), Bound::Included(e))
+            | (Bound::Excluded(s), Bound::Excluded(e)) => s >= e,
+            _ => false,
+        };
+        if empty {
+            return Vec::new();
+        }
+
+        let range = self.entries.range((start, end));
+        let count = count.unwrap_or(usize::MAX);
+        let entries: Box<dyn Iterator<Item = (&StreamId, &Fields)>> = if reverse {
+            Box::new(range.rev())
+        } else {
+            Box::new(range)
+        };
+        entries
+            .take(count)
+            .map(|(id, fields)| (*id, fields.clone()))
+            .collect()
+    }
+
+    /// Evict the oldest entries following `trim`, returning how many were evicted.
+    pub fn trim(&mut self, trim: &Trim) -> usize {
+        let limit = trim.limit.unwrap_or(usize::MAX);
+        let mut evicted = 0;
+        while evicted < limit {
+            let oldest = match self.entries.keys().next() {
+                Some(id) => *id,
+                None => break,
+            };
+            let evict = match trim.strategy {
+                TrimStrategy::MaxLen(len) => self.entries.len() > len,
+                TrimStrategy::MinId(id) => oldest < id,
+            };
+            if !evict {
+                break;
+            }
+            self.entries.remove(&oldest);
+            evicted += 1;
+        }
+        evicted
+    }
+
+    /// Create a consumer group that will deliver the entries after `from`.
+    pub fn create_group(&mut self, name: String, from: ReadFrom) -> Result<()> {
+        if self.groups.contains_key(&name) {
+            return Err(Error::msg("BUSYGROUP Consumer Group name already exists"));
+        }
+        let group = ConsumerGroup {
+            last_delivered: self.resolve(from),
+            pending: BTreeMap::new(),
+            consumers: BTreeMap::new(),
+        };
+        self.groups.insert(name, group);
+        Ok(())
+    }
+
+    pub fn destroy_group(&mut self, name: &str) -> bool {
+        self.groups.remove(name).is_some()
+    }
+
+    /// Move the last delivered id of a group, returning `false` if the group doesn't exist.
+    pub fn set_group_id(&mut self, name: &str, from: ReadFrom) -> bool {
+        let last_delivered = self.resolve(from);
+        match self.groups.get_mut(name) {
+            Some(group) => {
+                group.last_delivered = last_delivered;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Create a consumer in a group, returning whether it was created.
+    pub fn create_consumer(&mut self, group: &str, consumer: &str, now: u64) -> Option<bool> {
+        let group = self.groups.get_mut(group)?;
+        if group.consumers.contains_key(consumer) {
+            return Some(false);
+        }
+        group.touch(consumer, now);
+        Some(true)
+    }
+
+    /// Delete a consumer from a group along with its pending entries, returning how many
+    /// entries were pending.
+    pub fn delete_consumer(&mut self, group: &str, consumer: &str) -> Option<usize> {
+        let group = self.groups.get_mut(group)?;
+        group.consumers.remove(consumer);
+        let before = group.pending.len();
+        group.pending.retain(|_, entry| entry.consumer != consumer);
+        Some(before - group.pending.len())
+    }
+
+    /// Deliver up to `count` entries to a consumer of a group, returning `None` if the group
+    /// doesn't exist. Entries that are no longer in the stream come back without fields.
+    pub fn read_group(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        read: GroupRead,
+        count: Option<usize>,
+        no_ack: bool,
+        now: u64,
+    ) -> Option<GroupEntries> {
+        let group = self.groups.get_mut(group)?;
+        group.touch(consumer, now);
+        let count = count.unwrap_or(usize::MAX);
+
+        let delivered = match read {
+            GroupRead::New => {
+                let start = Bound::Excluded(group.last_delivered);
+                let entries: GroupEntries = self
+                    .entries
+                    .range((start, Bound::Unbounded))
+                    .take(count)
+                    .map(|(id, fields)| (*id, Some(fields.clone())))
+                    .collect();
+                for (id, _) in &entries {
+                    group.last_delivered = *id;
+                    if !no_ack {
+                        let pending = PendingEntry {
+                            consumer: consumer.to_string(),
+                            delivered: now,
+                            deliveries: 1,
+                        };
+                        group.pending.insert(*id, pending);
+                    }
+                }
+                entries
+            }
+            GroupRead::Pending(after) => {
+                let entries = &self.entries;
+                group
+                    .pending
+                    .range((Bound::Excluded(after), Bound::Unbounded))
+                    .filter(|(_, pending)| pending.consumer == consumer)
+                    .take(count)
+                    .map(|(id, _)| (*id, entries.get(id).cloned()))
+                    .collect()
+            }
+        };
+        Some(delivered)
+    }
+
+    /// Acknowledge entries for a group, returning how many were pending.
+    pub fn ack(&mut self, group: &str, ids: &[StreamId]) -> Option<usize> {
+        let group = self.groups.get_mut(group)?;
+        Some(
+            ids.iter()
+                .filter(|id| group.pending.remove(id).is_some())
+                .count(),
+        )
+    }
+
+    pub fn pending_summary(&self, group: &str) -> Option<PendingSummary> {
+        let group = self.groups.get(group)?;
+        let mut consumers: BTreeMap<&str, usize> = BTreeMap::new();
+        for entry in group.pending.values() {
+            *consumers.entry(entry.consumer.as_str()).or_insert(0) += 1;
+        }
+        Some(PendingSummary {
+            count: group.pending.len(),
+            first: group.pending.keys().next().copied(),
+            last: group.pending.keys().next_back().copied(),
+            consumers: consumers
+                .into_iter()
+                .map(|(consumer, count)| (consumer.to_string(), count))
+                .collect(),
+        })
+    }
+
+    /// Retrieve the pending entries of a group matching `query`.
+    pub fn pending(
+        &self,
+        group: &str,
+        query: &PendingQuery,
+        now: u64,
+    ) -> Option<Vec<(StreamId, PendingEntry)>> {
+        let group = self.groups.get(group)?;
+        Some(
+            group
+                .pending
+                .range((query.start, query.end))
+                .filter(|(_, entry)| {
+                    query
+                        .consumer
+                        .as_ref()
+                        .is_none_or(|consumer| entry.consumer == *consumer)
+                })
+                .filter(|(_, entry)| {
+                    query
+                        .min_idle
+                        .is_none_or(|idle| now.saturating_sub(entry.delivered) >= idle)
+                })
+                .take(query.count)
+                .map(|(id, entry)| (*id, entry.clone()))
+                .collect(),
+        )
+    }
+
+    /// Transfer pending entries idle for at least `min_idle` milliseconds to `consumer`,
+    /// returning the claimed entries.
+    pub fn claim(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        min_idle: u64,
+        ids: &[StreamId],
+        options: &ClaimOptions,
+        now: u64,
+    ) -> Option<Vec<(StreamId, Fields)>> {
+        let group = self.groups.get_mut(group)?;
+        let delivered = match (options.time, options.idle) {
+            (Some(time), _) => time,
+            (None, Some(idle)) => now.saturating_sub(idle),
+            (None, None) => now,
+        };
+
+        let mut claimed = Vec::new();
+        for id in ids {
+            let fields = match self.entries.get(id) {
+                Some(fields) => fields,
+                None => {
+                    // the entry was trimmed away, it can never be processed
+                    group.pending.remove(id);
+                    continue;
+                }
+            };
+            let entry = match group.pending.get_mut(id) {
+                Some(entry) if now.saturating_sub(entry.delivered) >= min_idle => entry,
+                Some(_) => continue,
+                None if options.force => group.pending.entry(*id).or_insert(PendingEntry {
+                    consumer: consumer.to_string(),
+                    delivered,
+                    deliveries: 0,
+                }),
+                None => continue,
+            };
+
+            entry.consumer = consumer.to_string();
+            entry.delivered = delivered;
+            match options.retry_count {
+                Some(count) => entry.deliveries = count,
+                None if !options.just_id => entry.deliveries += 1,
+                None => {}
+            }
+            claimed.push((*id, fields.clone()));
+        }
+
+        if !claimed.is_empty() {
+            group.touch(consumer, now);
+        }
+        Some(claimed)
+    }
+
+    fn resolve(&self, from: ReadFrom) -> StreamId {
+        match from {
+            ReadFrom::Last => self.last_id,
+            ReadFrom::After(id) => id,
+        }
+    }
+
+    /// Retrieve an estimate of the memory used by the entries and consumer groups, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        let entries = self
+            .entries
+            .values()
+            .map(|fields| {
+                let fields = fields.iter().map(|(f, v)| f.len() + v.len()).sum::<usize>();
+                std::mem::size_of::<StreamId>() + fields
+            })
+            .sum::<usize>();
+        let pending = self
+            .groups
+            .values()
+            .map(|group| {
+                group.pending.len() * std::mem::size_of::<(StreamId, PendingEntry)>()
+                    + group.consumers.keys().map(String::len).sum::<usize>()
+            })
+            .sum::<usize>();
+        entries + pending
+    }
+}
+
+fn no_group(key: &str, group: &str) -> Error {
+    Error::msg(format!(
+        "NOGROUP No such key '{}' or consumer group '{}'",
+        key, group
+    ))
+}
+
+impl Cache {
+    /// Append an entry to the stream at `key`, returning its id, or `None` if the stream
+    /// doesn't exist and `create` is `false`.
+    pub async fn xadd(
+        &self,
+        key: String,
+        id: IdSpec,
+        fields: Fields,
+        trim: Option<Trim>,
+        create: bool,
+    ) -> Result<Option<StreamId>> {
+        self.write_object(
+            &key,
+            || create.then(|| Stream::new().into()),
+            |object| {
+                let stream = object.as_stream_mut()?;
+                let id = stream.add(id, fields, now_millis())?;
+                if let Some(trim) = trim {
+                    stream.trim(&trim);
+                }
+                Ok(id)
+            },
+        )
+    }
+
+    pub async fn xlen(&self, key: String) -> Result<usize> {
+        let len = self.read_object(&key, |object| Ok(object.as_stream()?.len()))?;
+        Ok(len.unwrap_or(0))
+    }
+
+    pub async fn xrange(
+        &self,
+        key: String,
+        start: Bound<StreamId>,
+        end: Bound<StreamId>,
+        count: Option<usize>,
+        reverse: bool,
+    ) -> Result<Vec<(StreamId, Fields)>> {
+        let entries = self.read_object(&key, |object| {
+            Ok(object.as_stream()?.range(start, end, count, reverse))
+        })?;
+        Ok(entries.unwrap_or_default())
+    }
+
+    pub async fn xtrim(&self, key: String, trim: Trim) -> Result<usize> {
+        let evicted = self.shrink_object(&key, |object| Ok(object.as_stream_mut()?.trim(&trim)))?;
+        Ok(evicted.unwrap_or(0))
+    }
+
+    /// Read the entries after the given ids from several streams, leaving out streams with
+    /// nothing new.
+    pub async fn xread(
+        &self,
+        streams: Vec<(String, ReadFrom)>,
+        count: Option<usize>,
+    ) -> Result<Vec<(String, Vec<(StreamId, Fields)>)>> {
+        let mut result = Vec::new();
+        for (key, from) in streams {
+            let entries = self.read_object(&key, |object| {
+                let stream = object.as_stream()?;
+                let start = Bound::Excluded(stream.resolve(from));
+                Ok(stream.range(start, Bound::Unbounded, count, false))
+            })?;
+            match entries {
+                Some(entries) if !entries.is_empty() => result.push((key, entries)),
+                _ => {}
+            }
+        }
+        Ok(result)
+    }
+
+    /// Create a consumer group, creating an empty stream first when `create` is set.
+    pub async fn xgroup_create(
+        &self,
+        key: String,
+        group: String,
+        from: ReadFrom,
+        create: bool,
+    ) -> Result<()> {
+        let created = self.write_object(
+            &key,
+            || create.then(|| Stream::new().into()),
+            |object| object.as_stream_mut()?.create_group(group, from),
+        )?;
+        created.ok_or_else(|| {
+            Error::msg("The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.")
+        })
+    }
+
+    /// Destroy a consumer group, returning whether it existed.
+    pub async fn xgroup_destroy(&self, key: String, group: String) -> Result<bool> {
+        let destroyed = self.shrink_object(&key, |object| {
+            Ok(object.as_stream_mut()?.destroy_group(&group))
+        })?;
+        Ok(destroyed.unwrap_or(false))
+    }
+
+    pub async fn xgroup_setid(&self, key: String, group: String, from: ReadFrom) -> Result<()> {
+        let found = self.write_object(
+            &key,
+            || None,
+            |object| Ok(object.as_stream_mut()?.set_group_id(&group, from)),
+        )?;
+        match found {
+            Some(true) => Ok(()),
+            _ => Err(no_group(&key, &group)),
+        }
+    }
+
+    /// Create a consumer in a group, returning whether it didn't exist yet.
+    pub async fn xgroup_createconsumer(
+        &self,
+        key: String,
+        group: String,
+        consumer: String,
+    ) -> Result<bool> {
+        let created = self.write_object(
+            &key,
+            || None,
+            |object| {
+                let stream = object.as_stream_mut()?;
+                Ok(stream.create_consumer(&group, &consumer, now_millis()))
+            },
+        )?;
+        created.flatten().ok_or_else(|| no_group(&key, &group))
+    }
+
+    /// Delete a consumer from a group, returning how many entries it had pending.
+    pub async fn xgroup_delconsumer(
+        &self,
+        key: String,
+        group: String,
+        consumer: String,
+    ) -> Result<usize> {
+        let pending = self.shrink_object(&key, |object| {
+            Ok(object.as_stream_mut()?.delete_consumer(&group, &consumer))
+        })?;
+        pending.flatten().ok_or_else(|| no_group(&key, &group))
+    }
+
+    /// Read entries from several streams on behalf of a consumer of a group.
+    pub async fn xreadgroup(
+        &self,
+        group: String,
+        consumer: String,
+        streams: Vec<(String, GroupRead)>,
+        count: Option<usize>,
+        no_ack: bool,
+    ) -> Result<Vec<(String, GroupEntries)>> {
+        let mut result = Vec::new();
+        for (key, read) in streams {
+            let entries = self.write_object(
+                &key,
+                || None,
+                |object| {
+                    let stream = object.as_stream_mut()?;
+                    let now = now_millis();
+                    Ok(stream.read_group(&group, &consumer, read, count, no_ack, now))
+                },
+            )?;
+            match entries.flatten() {
+                Some(entries) => {
+                    // reading new entries only reports streams that had some
+                    if !entries.is_empty() || read != GroupRead::New {
+                        result.push((key, entries));
+                    }
+                }
+                None => return Err(no_group(&key, &group)),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Acknowledge entries for a group, returning how many were pending.
+    pub async fn xack(&self, key: String, group: String, ids: Vec<StreamId>) -> Result<usize> {
+        let acked =
+            self.shrink_object(&key, |object| Ok(object.as_stream_mut()?.ack(&group, &ids)))?;
+        Ok(acked.flatten().unwrap_or(0))
+    }
+
+    pub async fn xpending_summary(&self, key: String, group: String) -> Result<PendingSummary> {
+        let summary = self.read_object(&key, |object| {
+            Ok(object.as_stream()?.pending_summary(&group))
+        })?;
+        summary.flatten().ok_or_else(|| no_group(&key, &group))
+    }
+
+    pub async fn xpending(
+        &self,
+        key: String,
+        group: String,
+        query: PendingQuery,
+    ) -> Result<Vec<(StreamId, PendingEntry)>> {
+        let pending = self.read_object(&key, |object| {
+            Ok(object.as_stream()?.pending(&group, &query, now_millis()))
+        })?;
+        pending.flatten().ok_or_else(|| no_group(&key, &group))
+    }
+
+    pub async fn xclaim(
+        &self,
+        key: String,
+        group: String,
+        consumer: String,
+        min_idle: u64,
+        ids: Vec<StreamId>,
+        options: ClaimOptions,
+    ) -> Result<Vec<(StreamId, Fields)>> {
+        let claimed = self.write_object(
+            &key,
+            || None,
+            |object| {
+                let stream = object.as_stream_mut()?;
+                let now = now_millis();
+                Ok(stream.claim(&group, &consumer, min_idle, &ids, &options, now))
+            },
+        )?;
+        claimed.flatten().ok_or_else(|| no_group(&key, &group))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(pairs: &[(&str, &str)]) -> Fields {
+        pairs
+            .iter()
+            .map(|(f, v)| (f.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn ids(entries: &[(StreamId, Fields)]) -> Vec<StreamId> {
+        entries.iter().map(|(id, _)| *id).collect()
+    }
+
+    fn sample() -> Stream {
+        let mut stream = Stream::new();
+        for ms in 1..=5 {
+            let spec = IdSpec::Explicit(StreamId::new(ms, 0));
+            stream.add(spec, fields(&[("n", "v")]), 0).unwrap();
+        }
+        stream
+    }
+
+    #[test]
+    fn test_parse_ids() -> Result<()> {
+        assert_eq!(StreamId::parse("5-3", 0)?, StreamId::new(5, 3));
+        assert_eq!(StreamId::parse("5", 7)?, StreamId::new(5, 7));
+        assert!(StreamId::parse("x-1", 0).is_err());
+        assert_eq!(IdSpec::parse("*")?, IdSpec::Auto);
+        assert_eq!(IdSpec::parse("5-*")?, IdSpec::AutoSeq(5));
+        assert_eq!(
+            StreamId::parse_end("5")?,
+            Bound::Included(StreamId::new(5, u64::MAX))
+        );
+        assert_eq!(
+            StreamId::parse_start("(5-1")?,
+            Bound::Excluded(StreamId::new(5, 1))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_generates_growing_ids() -> Result<()> {
+        let mut stream = Stream::new();
+        assert_eq!(
+            stream.add(IdSpec::Auto, Fields::new(), 100)?,
+            StreamId::new(100, 0)
+        );
+        assert_eq!(
+            stream.add(IdSpec::Auto, Fields::new(), 100)?,
+            StreamId::new(100, 1)
+        );
+        // the clock going backwards keeps ids growing
+        assert_eq!(
+            stream.add(IdSpec::Auto, Fields::new(), 50)?,
+            StreamId::new(100, 2)
+        );
+        assert_eq!(
+            stream.add(IdSpec::AutoSeq(100), Fields::new(), 0)?,
+            StreamId::new(100, 3)
+        );
+        assert!(stream.add(IdSpec::AutoSeq(99), Fields::new(), 0).is_err());
+        assert!(stream
+            .add(IdSpec::Explicit(StreamId::new(100, 3)), Fields::new(), 0)
+            .is_err());
+        assert!(Stream::new()
+            .add(IdSpec::Explicit(StreamId::MIN), Fields::new(), 0)
+            .is_err());
+        assert_eq!(
+            Stream::new().add(IdSpec::AutoSeq(0), Fields::new(), 0)?,
+            StreamId::new(0, 1)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_range() {
+        let stream = sample();
+        let all = stream.range(Bound::Unbounded, Bound::Unbounded, None, false);
+        assert_eq!(all.len(), 5);
+
+        let range = stream.range(
+            Bound::Excluded(StreamId::new(1, 0)),
+            Bound::Included(StreamId::new(4, 0)),
+            Some(2),
+            false,
+        );
+        assert_eq!(ids(&range), vec![StreamId::new(2, 0), StreamId::new(3, 0)]);
+
+        let range = stream.range(Bound::Unbounded, Bound::Unbounded, Some(2), true);
+        assert_eq!(ids(&range), vec![StreamId::new(5, 0), StreamId::new(4, 0)]);
+
+        let empty = stream.range(
+            Bound::Included(StreamId::new(4, 0)),
+            Bound::Included(StreamId::new(2, 0)),
+            None,
+            false,
+        );
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_trim() {
+        let mut stream = sample();
+        let trim = Trim {
+            strategy: TrimStrategy::MaxLen(3),
+            limit: None,
+        };
+        assert_eq!(stream.trim(&trim), 2);
+        assert_eq!(stream.len(), 3);
+
+        let trim = Trim {
+            strategy: TrimStrategy::MinId(StreamId::new(5, 0)),
+            limit: Some(1),
+        };
+        assert_eq!(stream.trim(&trim), 1);
+        assert_eq!(stream.len(), 2);
+        // trimming never resets the last id
+        assert_eq!(stream.last_id(), StreamId::new(5, 0));
+    }
+
+    #[test]
+    fn test_consumer_group_delivery_and_ack() -> Result<()> {
+        let mut stream = sample();
+        stream.create_group("g".to_string(), ReadFrom::After(StreamId::MIN))?;
+        assert!(stream
+            .create_group("g".to_string(), ReadFrom::Last)
+            .is_err());
+
+        let read = stream
+            .read_group("g", "alice", GroupRead::New, Some(2), false, 10)
+            .unwrap();
+        assert_eq!(read.len(), 2);
+        let read = stream
+            .read_group("g", "bob", GroupRead::New, None, false, 10)
+            .unwrap();
+        assert_eq!(read.len(), 3);
+
+        // alice's history only holds her own entries
+        let history = stream
+            .read_group(
+                "g",
+                "alice",
+                GroupRead::Pending(StreamId::MIN),
+                None,
+                false,
+                10,
+            )
+            .unwrap();
+        assert_eq!(
+            history.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            vec![StreamId::new(1, 0), StreamId::new(2, 0)]
+        );
+
+        assert_eq!(
+            stream.ack("g", &[StreamId::new(1, 0), StreamId::new(9, 0)]),
+            Some(1)
+        );
+        let summary = stream.pending_summary("g").unwrap();
+        assert_eq!(summary.count, 4);
+        assert_eq!(summary.first, Some(StreamId::new(2, 0)));
+        assert_eq!(summary.last, Some(StreamId::new(5, 0)));
+        assert_eq!(
+            summary.consumers,
+            vec![("alice".to_string(), 1), ("bob".to_string(), 3)]
+        );
+        assert!(stream
+            .read_group("missing", "alice", GroupRead::New, None, false, 10)
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_ack_reads_skip_pending() -> Result<()> {
+        let mut stream = sample();
+        stream.create_group("g".to_string(), ReadFrom::After(StreamId::MIN))?;
+        stream.read_group("g", "alice", GroupRead::New, None, true, 0);
+        assert_eq!(stream.pending_summary("g").unwrap().count, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_claim() -> Result<()> {
+        let mut stream = sample();
+        stream.create_group("g".to_string(), ReadFrom::After(StreamId::MIN))?;
+        stream.read_group("g", "alice", GroupRead::New, Some(2), false, 100);
+
+        let first = StreamId::new(1, 0);
+        let claimed = stream
+            .claim("g", "bob", 50, &[first], &ClaimOptions::default(), 120)
+            .unwrap();
+        assert!(claimed.is_empty());
+
+        let claimed = stream
+            .claim("g", "bob", 50, &[first], &ClaimOptions::default(), 200)
+            .unwrap();
+        assert_eq!(ids(&claimed), vec![first]);
+        let pending = stream
+            .pending(
+                "g",
+                &PendingQuery {
+                    start: Bound::Unbounded,
+                    end: Bound::Unbounded,
+                    count: 10,
+                    consumer: Some("bob".to_string()),
+                    min_idle: None,
+                },
+                200,
+            )
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].1.deliveries, 2);
+
+        // trimmed entries are dropped from the pending list instead of being claimed
+        stream.trim(&Trim {
+            strategy: TrimStrategy::MaxLen(3),
+            limit: None,
+        });
+        let second = StreamId::new(2, 0);
+        let claimed = stream
+            .claim("g", "bob", 0, &[second], &ClaimOptions::default(), 300)
+            .unwrap();
+        assert!(claimed.is_empty());
+        assert_eq!(stream.pending_summary("g").unwrap().count, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_consumer() -> Result<()> {
+        let mut stream = sample();
+        stream.create_group("g".to_string(), ReadFrom::After(StreamId::MIN))?;
+        assert_eq!(stream.create_consumer("g", "alice", 0), Some(true));
+        assert_eq!(stream.create_consumer("g", "alice", 0), Some(false));
+        stream.read_group("g", "alice", GroupRead::New, Some(3), false, 0);
+        assert_eq!(stream.delete_consumer("g", "alice"), Some(3));
+        assert_eq!(stream.pending_summary("g").unwrap().count, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_xadd_and_xrange() -> Result<()> {
+        let cache = Cache::default();
+        let missing = cache
+            .xadd(
+                "events".to_string(),
+                IdSpec::Auto,
+                fields(&[("a", "1")]),
+                None,
+                false,
+            )
+            .await?;
+        assert_eq!(missing, None);
+        assert!(!cache.exists("events".to_string()).await);
+
+        for _ in 0..3 {
+            cache
+                .xadd(
+                    "events".to_string(),
+                    IdSpec::Auto,
+                    fields(&[("a", "1")]),
+                    Some(Trim {
+                        strategy: TrimStrategy::MaxLen(2),
+                        limit: None,
+                    }),
+                    true,
+                )
+                .await?;
+        }
+        assert_eq!(cache.xlen("events".to_string()).await?, 2);
+        let entries = cache
+            .xrange(
+                "events".to_string(),
+                Bound::Unbounded,
+                Bound::Unbounded,
+                None,
+                false,
+            )
+            .await?;
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].0 < entries[1].0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_trim_keeps_empty_stream() -> Result<()> {
+        let cache = Cache::default();
+        cache
+            .xadd(
+                "events".to_string(),
+                IdSpec::Auto,
+                fields(&[("a", "1")]),
+                None,
+                true,
+            )
+            .await?;
+        let trim = Trim {
+            strategy: TrimStrategy::MaxLen(0),
+            limit: None,
+        };
+        assert_eq!(cache.xtrim("events".to_string(), trim).await?, 1);
+        assert!(cache.exists("events".to_string()).await);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_xreadgroup_missing_group() {
+        let cache = Cache::default();
+        let result = cache
+            .xreadgroup(
+                "g".to_string(),
+                "c".to_string(),
+                vec![("events".to_string(), GroupRead::New)],
+                None,
+                false,
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().starts_with("NOGROUP"));
+    }
+
+    #[tokio::test]
+    async fn test_cache_xgroup_create_requires_stream() -> Result<()> {
+        let cache = Cache::default();
+        let result = cache
+            .xgroup_create("events".to_string(), "g".to_string(), ReadFrom::Last, false)
+            .await;
+        assert!(result.is_err());
+        cache
+            .xgroup_create("events".to_string(), "g".to_string(), ReadFrom::Last, true)
+            .await?;
+        assert_eq!(cache.xlen("events".to_string()).await?, 0);
+        Ok(())
+    }
+}
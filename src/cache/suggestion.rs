@@ -0,0 +1,361 @@
+use crate::cache::Cache;
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+
+/// The edit distance up to which fuzzy lookups match a prefix.
+const MAX_DISTANCE: usize = 1;
+
+/// A completion of a dictionary and its score.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub string: String,
+    pub score: f64,
+    pub payload: Option<String>,
+}
+
+/// A trie node, reached by the lowercase characters of the suggestions below it.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Node {
+    children: BTreeMap<char, Node>,
+    suggestion: Option<Suggestion>,
+}
+
+impl Node {
+    fn collect<'a>(&'a self, found: &mut Vec<&'a Suggestion>) {
+        found.extend(&self.suggestion);
+        for child in self.children.values() {
+            child.collect(found);
+        }
+    }
+
+    /// Remove the suggestion at `path` below this node, pruning the nodes left without
+    /// suggestions.
+    fn remove(&mut self, path: &[char]) -> bool {
+        let Some((c, rest)) = path.split_first() else {
+            return self.suggestion.take().is_some();
+        };
+        let Some(child) = self.children.get_mut(c) else {
+            return false;
+        };
+        let removed = child.remove(rest);
+        if child.suggestion.is_none() && child.children.is_empty() {
+            self.children.remove(c);
+        }
+        removed
+    }
+
+    /// Find the nodes whose path is within `MAX_DISTANCE` edits of `query`, given the edit
+    /// distances from the prefixes of `query` to the path of this node.
+    fn fuzzy<'a>(&'a self, query: &[char], row: &[usize], found: &mut Vec<(&'a Node, usize)>) {
+        for (&c, child) in &self.children {
+            let mut next = vec![row[0] + 1];
+            for j in 1..row.len() {
+                let substitution = row[j - 1] + usize::from(query[j - 1] != c);
+                next.push(substitution.min(row[j] + 1).min(next[j - 1] + 1));
+            }
+            if next[query.len()] <= MAX_DISTANCE {
+                found.push((child, next[query.len()]));
+            }
+            if next.iter().min().unwrap() <= &MAX_DISTANCE {
+                child.fuzzy(query, &next, found);
+            }
+        }
+    }
+}
+
+/// A dictionary of completions, looked up by case insensitive prefix.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Suggestions {
+    root: Node,
+    len: usize,
+}
+
+impl Suggestions {
+    pub fn new() -> Self {
+        Suggestions::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add `suggestion`, or update the one with the same string ignoring case, adding to its
+    /// score if `increment` is set. Its payload is kept unless a new one is given.
+    pub fn add(&mut self, suggestion: Suggestion, increment: bool) {
+        let mut node = &mut self.root;
+        for c in suggestion.string.to_lowercase().chars() {
+            node = node.children.entry(c).or_default();
+        }
+        match &mut node.suggestion {
+            Some(existing) => {
+                existing.score = match increment {
+                    true => existing.score + suggestion.score,
+                    false => suggestion.score,
+                };
+                existing.string = suggestion.string;
+                if suggestion.payload.is_some() {
+                    existing.payload = suggestion.payload;
+                }
+            }
+            None => {
+                node.suggestion = Some(suggestion);
+                self.len += 1;
+            }
+        }
+    }
+
+    /// Remove the suggestion `string`, ignoring case, returning whether it existed.
+    pub fn remove(&mut self, string: &str) -> bool {
+        let removed = self
+            .root
+            .remove(&string.to_lowercase().chars().collect::<Vec<_>>());
+        if removed {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Retrieve up to `max` suggestions starting with `prefix`, or with a prefix one edit away
+    /// from it if `fuzzy` is set. Their scores are weighed by the share of their string the
+    /// prefix covers, and divided by one plus the edits, to rank the closest completions first.
+    pub fn get(&self, prefix: &str, fuzzy: bool, max: usize) -> Vec<Suggestion> {
+        let query = prefix.to_lowercase().chars().collect::<Vec<_>>();
+        let mut nodes = Vec::new();
+        if fuzzy {
+            let row = (0..=query.len()).collect::<Vec<_>>();
+            if query.len() <= MAX_DISTANCE {
+                nodes.push((&self.root, query.len()));
+            }
+            self.root.fuzzy(&query, &row, &mut nodes);
+        } else {
+            let node = query
+                .iter()
+                .try_fold(&self.root, |node, c| node.children.get(c));
+            nodes.extend(node.map(|node| (node, 0)));
+        }
+
+        // a suggestion under several matching nodes counts with its fewest edits
+        let mut distances: HashMap<&str, (&Suggestion, usize)> = HashMap::new();
+        for (node, distance) in nodes {
+            let mut found = Vec::new();
+            node.collect(&mut found);
+            for suggestion in found {
+                let best = distances
+                    .entry(&suggestion.string)
+                    .or_insert((suggestion, distance));
+                best.1 = best.1.min(distance);
+            }
+        }
+
+        let mut suggestions = distances
+            .into_values()
+            .map(|(suggestion, distance)| {
+                let length = suggestion.string.chars().count().max(1);
+                let coverage = (query.len() as f64 / length as f64).min(1.0);
+                Suggestion {
+                    score: suggestion.score * coverage / (1 + distance) as f64,
+                    ..suggestion.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+        suggestions.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.string.cmp(&b.string))
+        });
+        suggestions.truncate(max);
+        suggestions
+    }
+
+    /// Retrieve an estimate of the memory used by the trie and its suggestions, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        let mut found = Vec::new();
+        self.root.collect(&mut found);
+        found
+            .iter()
+            .map(|suggestion| {
+                let payload = suggestion.payload.as_ref().map_or(0, String::len);
+                // a node for every character of the suggestion at most
+                suggestion.string.len() * std::mem::size_of::<Node>()
+                    + suggestion.string.len()
+                    + payload
+            })
+            .sum()
+    }
+}
+
+impl Cache {
+    /// Add `suggestion` to the dictionary at `key`, creating it if missing. Returns the size
+    /// of the dictionary.
+    pub async fn sugadd(
+        &self,
+        key: String,
+        suggestion: Suggestion,
+        increment: bool,
+    ) -> Result<usize> {
+        let len = self.write_object(
+            &key,
+            || Some(Suggestions::new().into()),
+            |object| {
+                let suggestions = object.as_suggestions_mut()?;
+                suggestions.add(suggestion, increment);
+                Ok(suggestions.len())
+            },
+        )?;
+        Ok(len.unwrap_or(0))
+    }
+
+    /// Retrieve the best completions of `prefix` in the dictionary at `key`.
+    pub async fn sugget(
+        &self,
+        key: String,
+        prefix: String,
+        fuzzy: bool,
+        max: usize,
+    ) -> Result<Vec<Suggestion>> {
+        let suggestions = self.read_object(&key, |object| {
+            Ok(object.as_suggestions()?.get(&prefix, fuzzy, max))
+        })?;
+        Ok(suggestions.unwrap_or_default())
+    }
+
+    /// Remove `string` from the dictionary at `key`, returning whether it was there.
+    pub async fn sugdel(&self, key: String, string: String) -> Result<bool> {
+        let removed = self.shrink_object(&key, |object| {
+            Ok(object.as_suggestions_mut()?.remove(&string))
+        })?;
+        Ok(removed.unwrap_or(false))
+    }
+
+    pub async fn suglen(&self, key: String) -> Result<usize> {
+        let len = self.read_object(&key, |object| Ok(object.as_suggestions()?.len()))?;
+        Ok(len.unwrap_or(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suggestion(string: &str, score: f64) -> Suggestion {
+        Suggestion {
+            string: string.to_string(),
+            score,
+            payload: None,
+        }
+    }
+
+    fn strings(suggestions: Vec<Suggestion>) -> Vec<String> {
+        suggestions.into_iter().map(|s| s.string).collect()
+    }
+
+    #[test]
+    fn test_prefix_lookup() {
+        let mut suggestions = Suggestions::new();
+        suggestions.add(suggestion("hello world", 1.0), false);
+        suggestions.add(suggestion("Help", 1.0), false);
+        suggestions.add(suggestion("helicopter", 4.0), false);
+        suggestions.add(suggestion("world", 1.0), false);
+        assert_eq!(suggestions.len(), 4);
+
+        // the helicopter scores most, help is covered the most by the prefix
+        assert_eq!(
+            strings(suggestions.get("HEL", false, 5)),
+            vec!["helicopter", "Help", "hello world"]
+        );
+        assert_eq!(
+            strings(suggestions.get("hel", false, 1)),
+            vec!["helicopter"]
+        );
+        let help = &suggestions.get("help", false, 5)[0];
+        assert_eq!(help.score, 1.0);
+        assert!(suggestions.get("helx", false, 5).is_empty());
+
+        suggestions.add(suggestion("HELP", 2.0), true);
+        suggestions.add(
+            Suggestion {
+                payload: Some("id:7".to_string()),
+                ..suggestion("help", 0.0)
+            },
+            true,
+        );
+        assert_eq!(suggestions.len(), 4);
+        assert_eq!(
+            suggestions.get("help", false, 5),
+            vec![Suggestion {
+                string: "help".to_string(),
+                score: 3.0,
+                payload: Some("id:7".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_lookup() {
+        let mut suggestions = Suggestions::new();
+        for string in ["hello", "jello", "yellow", "help", "world"] {
+            suggestions.add(suggestion(string, 1.0), false);
+        }
+        assert_eq!(strings(suggestions.get("jell", false, 5)), vec!["jello"]);
+        // exact matches rank above those a substitution away
+        assert_eq!(
+            strings(suggestions.get("jell", true, 5)),
+            vec!["jello", "hello", "yellow"]
+        );
+        assert_eq!(strings(suggestions.get("wrld", true, 5)), vec!["world"]);
+        assert_eq!(suggestions.get("w", true, 10).len(), 5);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut suggestions = Suggestions::new();
+        suggestions.add(suggestion("car", 1.0), false);
+        suggestions.add(suggestion("cart", 1.0), false);
+        assert!(suggestions.remove("CART"));
+        assert!(!suggestions.remove("cart"));
+        assert!(!suggestions.remove("ca"));
+        assert_eq!(suggestions.len(), 1);
+        assert!(
+            suggestions.root.children[&'c'].children[&'a'].children[&'r']
+                .children
+                .is_empty()
+        );
+        assert!(suggestions.remove("car"));
+        assert!(suggestions.is_empty());
+        assert!(suggestions.root.children.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_suggestion_commands() -> Result<()> {
+        let cache = Cache::default();
+        let key = || "cities".to_string();
+        assert_eq!(
+            cache
+                .sugadd(key(), suggestion("London", 2.0), false)
+                .await?,
+            1
+        );
+        assert_eq!(
+            cache
+                .sugadd(key(), suggestion("Lisbon", 1.0), false)
+                .await?,
+            2
+        );
+
+        let found = cache.sugget(key(), "l".to_string(), false, 5).await?;
+        assert_eq!(strings(found), vec!["London", "Lisbon"]);
+        assert!(cache.sugdel(key(), "lisbon".to_string()).await?);
+        assert_eq!(cache.suglen(key()).await?, 1);
+        assert!(cache.sugdel(key(), "london".to_string()).await?);
+        assert!(!cache.exists(key()).await);
+        assert!(cache
+            .sugget(key(), "l".to_string(), true, 5)
+            .await?
+            .is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+use crate::cache::databases::Databases;
+use crate::cache::tenants::Tenants;
+use std::time::Duration;
+use tokio::time::{self, MissedTickBehavior};
+
+/// Sweep every database and then every tenant every `frequency` until aborted, one after the
+/// other, so that a server ticks a single timer however many keyspaces it holds. Swapped
+/// databases and tenants created or deleted are picked up on the next tick.
+pub async fn monitor(databases: Databases, tenants: Tenants, frequency: Duration) {
+    log::debug!("removing garbage in the background");
+
+    let mut interval = time::interval(frequency);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        for cache in databases.all().into_iter().chain(tenants.all()) {
+            cache.sweep().await;
+        }
+    }
+}
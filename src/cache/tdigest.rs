@@ -0,0 +1,413 @@
+use crate::cache::entry::Entry;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use std::f64::consts::PI;
+
+/// The default of TDIGEST.CREATE, matching RedisBloom.
+pub const DEFAULT_COMPRESSION: f64 = 100.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+/// A t-digest, summarizing a distribution with clusters of values that are kept small near
+/// the extremes, so extreme quantiles like latency percentiles stay accurate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TDigest {
+    /// Bounds the number of centroids, higher values trading memory for accuracy.
+    compression: f64,
+    /// Sorted by mean.
+    centroids: Vec<Centroid>,
+    count: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for TDigest {
+    fn default() -> Self {
+        Self::new(DEFAULT_COMPRESSION)
+    }
+}
+
+impl TDigest {
+    pub fn new(compression: f64) -> Self {
+        TDigest {
+            compression,
+            centroids: Vec::new(),
+            count: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// The scale function mapping a quantile to the index of its centroid, steep near the
+    /// extremes.
+    fn scale(&self, q: f64) -> f64 {
+        self.compression / (2.0 * PI) * (2.0 * q - 1.0).asin()
+    }
+
+    fn scale_inverse(&self, k: f64) -> f64 {
+        ((2.0 * PI * k / self.compression).sin() + 1.0) / 2.0
+    }
+
+    /// Merge `centroids` into the digest, clustering neighbours as long as each cluster
+    /// spans at most one unit of the scale function.
+    fn compress(&mut self, mut centroids: Vec<Centroid>) {
+        centroids.append(&mut self.centroids);
+        centroids.sort_by(|a, b| a.mean.total_cmp(&b.mean));
+        let total = centroids
+            .iter()
+            .map(|centroid| centroid.weight)
+            .sum::<f64>();
+
+        let mut iter = centroids.into_iter();
+        let mut current = match iter.next() {
+            Some(first) => first,
+            None => return,
+        };
+        let mut merged = Vec::new();
+        let mut before = 0.0;
+        let mut limit = self.scale_inverse(self.scale(0.0) + 1.0);
+        for next in iter {
+            if (before + current.weight + next.weight) / total <= limit {
+                let weight = current.weight + next.weight;
+                current.mean += (next.mean - current.mean) * next.weight / weight;
+                current.weight = weight;
+            } else {
+                before += current.weight;
+                merged.push(current);
+                limit = self.scale_inverse(self.scale(before / total) + 1.0);
+                current = next;
+            }
+        }
+        merged.push(current);
+        self.centroids = merged;
+    }
+
+    /// Add `values` to the distribution.
+    pub fn add(&mut self, values: &[f64]) {
+        for &value in values {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.count += values.len() as f64;
+        let centroids = values
+            .iter()
+            .map(|&mean| Centroid { mean, weight: 1.0 })
+            .collect();
+        self.compress(centroids);
+    }
+
+    /// Add the distribution summarized by `other`.
+    pub fn merge(&mut self, other: &TDigest) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.compress(other.centroids.clone());
+    }
+
+    /// Estimate the value below which a fraction `q` of the distribution falls, NaN if the
+    /// digest is empty.
+    pub fn quantile(&self, q: f64) -> f64 {
+        let (first, last) = match (self.centroids.first(), self.centroids.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return f64::NAN,
+        };
+        if q <= 0.0 {
+            return self.min;
+        }
+        if q >= 1.0 {
+            return self.max;
+        }
+
+        // each centroid sits at the middle of its weight, values interpolate in between
+        let target = q * self.count;
+        if target < first.weight / 2.0 {
+            return self.min + (first.mean - self.min) * target / (first.weight / 2.0);
+        }
+        let mut cumulative = first.weight / 2.0;
+        for pair in self.centroids.windows(2) {
+            let step = (pair[0].weight + pair[1].weight) / 2.0;
+            if cumulative + step > target {
+                let fraction = (target - cumulative) / step;
+                return pair[0].mean + (pair[1].mean - pair[0].mean) * fraction;
+            }
+            cumulative += step;
+        }
+        let fraction = ((target - cumulative) / (last.weight / 2.0)).min(1.0);
+        last.mean + (self.max - last.mean) * fraction
+    }
+
+    /// Estimate the fraction of the distribution at or below `value`, NaN if the digest is
+    /// empty.
+    pub fn cdf(&self, value: f64) -> f64 {
+        let (first, last) = match (self.centroids.first(), self.centroids.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return f64::NAN,
+        };
+        if value < self.min {
+            return 0.0;
+        }
+        if value >= self.max {
+            return 1.0;
+        }
+
+        if value < first.mean {
+            let span = first.mean - self.min;
+            let below = if span > 0.0 {
+                (value - self.min) / span
+            } else {
+                1.0
+            };
+            return below * first.weight / 2.0 / self.count;
+        }
+        let mut cumulative = first.weight / 2.0;
+        for pair in self.centroids.windows(2) {
+            let step = (pair[0].weight + pair[1].weight) / 2.0;
+            if value < pair[1].mean {
+                let span = pair[1].mean - pair[0].mean;
+                let fraction = if span > 0.0 {
+                    (value - pair[0].mean) / span
+                } else {
+                    1.0
+                };
+                return (cumulative + step * fraction) / self.count;
+            }
+            cumulative += step;
+        }
+        let span = self.max - last.mean;
+        let fraction = if span > 0.0 {
+            (value - last.mean) / span
+        } else {
+            1.0
+        };
+        (cumulative + last.weight / 2.0 * fraction) / self.count
+    }
+
+    /// Retrieve the smallest value added, NaN if the digest is empty.
+    pub fn min(&self) -> f64 {
+        if self.count > 0.0 {
+            self.min
+        } else {
+            f64::NAN
+        }
+    }
+
+    /// Retrieve the largest value added, NaN if the digest is empty.
+    pub fn max(&self) -> f64 {
+        if self.count > 0.0 {
+            self.max
+        } else {
+            f64::NAN
+        }
+    }
+
+    /// Retrieve an estimate of the memory used by the centroids, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.centroids.len() * std::mem::size_of::<Centroid>()
+    }
+}
+
+impl Cache {
+    /// Create an empty digest at `key`, failing if the key exists.
+    pub async fn tdigest_create(&self, key: String, compression: f64) -> Result<()> {
+        self.create_object(key, TDigest::new(compression).into())
+    }
+
+    /// Add `values` to the digest at `key`. Fails if the key doesn't exist.
+    pub async fn tdigest_add(&self, key: String, values: Vec<f64>) -> Result<()> {
+        self.write_object(
+            &key,
+            || None,
+            |object| {
+                object.as_tdigest_mut()?.add(&values);
+                Ok(())
+            },
+        )?
+        .ok_or_else(|| Error::msg("key does not exist"))
+    }
+
+    /// Run `f` against the digest at `key`, failing if the key doesn't exist.
+    pub async fn tdigest_read<T, F>(&self, key: String, f: F) -> Result<T>
+    where
+        F: FnOnce(&TDigest) -> T,
+    {
+        self.read_object(&key, |object| Ok(f(object.as_tdigest()?)))?
+            .ok_or_else(|| Error::msg("key does not exist"))
+    }
+
+    /// Store the union of the digests at `sources` in `destination`. An existing destination
+    /// is merged in too unless `replace` is set, and keeps its expiry. The compression is
+    /// the given one or the largest of the sources.
+    pub async fn tdigest_merge(
+        &self,
+        destination: String,
+        sources: Vec<String>,
+        compression: Option<f64>,
+        replace: bool,
+    ) -> Result<()> {
+        let mut store = self.store.write().unwrap();
+        self.evict_if_expired(&mut store, &destination);
+        for key in &sources {
+            self.evict_if_expired(&mut store, key);
+        }
+
+        let mut digests = Vec::with_capacity(sources.len() + 1);
+        for key in &sources {
+            match store.get(key) {
+                Some(entry) => digests.push(entry.value().as_tdigest()?),
+                None => return Err(Error::msg("key does not exist")),
+            }
+        }
+        if let Some(entry) = store.get(&destination) {
+            let existing = entry.value().as_tdigest()?;
+            if !replace {
+                digests.push(existing);
+            }
+        }
+
+        let compression = compression.unwrap_or_else(|| {
+            digests
+                .iter()
+                .map(|digest| digest.compression)
+                .fold(DEFAULT_COMPRESSION, f64::max)
+        });
+        let mut merged = TDigest::new(compression);
+        for digest in digests {
+            merged.merge(digest);
+        }
+
+        self.admit(&mut store, &destination)?;
+        let before = self.footprint(&store, &destination);
+        match store.get_mut(&destination) {
+            Some(entry) => *entry.value_mut() = merged.into(),
+            None => {
+                let entry = Entry::new(merged, self.default_expiry());
+                store.insert(destination.clone(), entry);
+            }
+        }
+        self.charge(self.footprint(&store, &destination).saturating_sub(before));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::quota::Quota;
+
+    fn uniform(range: std::ops::Range<u32>) -> TDigest {
+        let mut digest = TDigest::default();
+        let values = range.map(|n| n as f64).collect::<Vec<_>>();
+        for chunk in values.chunks(1000) {
+            digest.add(chunk);
+        }
+        digest
+    }
+
+    fn assert_close(estimate: f64, exact: f64, tolerance: f64) {
+        assert!(
+            (estimate - exact).abs() <= tolerance,
+            "estimate {} for {}",
+            estimate,
+            exact
+        );
+    }
+
+    #[test]
+    fn test_quantile() {
+        assert!(TDigest::default().quantile(0.5).is_nan());
+
+        let digest = uniform(0..100_000);
+        assert!(digest.centroids.len() < 200);
+        assert_close(digest.quantile(0.5), 50_000.0, 500.0);
+        assert_close(digest.quantile(0.99), 99_000.0, 100.0);
+        assert_close(digest.quantile(0.999), 99_900.0, 20.0);
+        assert_eq!(digest.quantile(0.0), 0.0);
+        assert_eq!(digest.quantile(1.0), 99_999.0);
+    }
+
+    #[test]
+    fn test_cdf() {
+        let digest = uniform(0..10_000);
+        assert_close(digest.cdf(2_500.0), 0.25, 0.01);
+        assert_eq!(digest.cdf(-1.0), 0.0);
+        assert_eq!(digest.cdf(10_000.0), 1.0);
+    }
+
+    #[test]
+    fn test_small() {
+        let mut digest = TDigest::default();
+        digest.add(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(digest.quantile(0.5), 3.0);
+        assert_eq!(digest.min(), 1.0);
+        assert_eq!(digest.max(), 5.0);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut digest = uniform(0..50_000);
+        digest.merge(&uniform(50_000..100_000));
+        assert_close(digest.quantile(0.5), 50_000.0, 500.0);
+        assert_eq!(digest.max(), 99_999.0);
+    }
+
+    #[tokio::test]
+    async fn test_tdigest_commands() -> Result<()> {
+        let cache = Cache::default();
+        assert!(cache
+            .tdigest_add("latency".to_string(), vec![1.0])
+            .await
+            .is_err());
+
+        cache
+            .tdigest_create("latency".to_string(), DEFAULT_COMPRESSION)
+            .await?;
+        cache
+            .tdigest_create("other".to_string(), DEFAULT_COMPRESSION)
+            .await?;
+        cache
+            .tdigest_add("latency".to_string(), vec![1.0, 2.0, 3.0])
+            .await?;
+        cache
+            .tdigest_add("other".to_string(), vec![4.0, 5.0])
+            .await?;
+
+        let sources = vec!["latency".to_string(), "other".to_string()];
+        cache
+            .tdigest_merge("all".to_string(), sources, None, false)
+            .await?;
+        let median = cache
+            .tdigest_read("all".to_string(), |digest| digest.quantile(0.5))
+            .await?;
+        assert_eq!(median, 3.0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_over_quota() -> Result<()> {
+        let cache = Cache::default();
+        for key in ["latency", "all"] {
+            cache
+                .tdigest_create(key.to_string(), DEFAULT_COMPRESSION)
+                .await?;
+        }
+        cache
+            .set_quota(Quota {
+                max_memory: Some(1),
+                ..Quota::default()
+            })
+            .await;
+
+        // an existing destination is replaced, which may grow it
+        let sources = vec!["latency".to_string()];
+        let error = cache
+            .tdigest_merge("all".to_string(), sources, None, false)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("OOM"));
+        Ok(())
+    }
+}
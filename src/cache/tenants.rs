@@ -0,0 +1,212 @@
+use crate::cache::quota::Quota;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+/// A named keyspace with its own limits.
+#[derive(Debug)]
+struct Tenant {
+    password: Option<String>,
+    cache: Arc<Cache>,
+}
+
+/// Information about a tenant, as reported by TENANT INFO.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TenantInfo {
+    pub keys: usize,
+    pub memory: usize,
+    pub quota: Quota,
+}
+
+/// The tenants of a server, shared by every connection.
+///
+/// Each tenant is a keyspace apart from the numbered databases, that connections bind to with
+/// AUTH or TENANT USE. Only clients authenticated with the admin password manage them, and
+/// none can without one. The keyspaces are swept by the monitor of the databases, deleting a
+/// tenant stops its sweeping while connections bound to it keep their keyspace until they
+/// bind again.
+#[derive(Clone, Debug)]
+pub struct Tenants {
+    tenants: Arc<RwLock<BTreeMap<String, Tenant>>>,
+    sample: usize,
+    threshold: f64,
+    /// The password clients authenticate with to manage the tenants.
+    admin_password: Option<String>,
+}
+
+impl Default for Tenants {
+    fn default() -> Self {
+        Tenants::new(25, 0.25)
+    }
+}
+
+fn no_tenant() -> Error {
+    Error::msg("no such tenant")
+}
+
+fn wrong_pass() -> Error {
+    Error::msg("WRONGPASS invalid username-password pair or user is disabled.")
+}
+
+impl Tenants {
+    /// Create an empty registry, whose keyspaces sample keys for expiry like `Cache::new`.
+    pub fn new(sample: usize, threshold: f64) -> Self {
+        Tenants {
+            tenants: Arc::new(RwLock::new(BTreeMap::new())),
+            sample,
+            threshold,
+            admin_password: None,
+        }
+    }
+
+    /// Let clients authenticated with `password` manage the tenants.
+    pub fn with_admin_password(mut self, password: Option<String>) -> Self {
+        self.admin_password = password;
+        self
+    }
+
+    /// Check `password` against the admin password, failing if there is none.
+    pub async fn authenticate_admin(&self, password: &str) -> Result<()> {
+        match &self.admin_password {
+            Some(admin) if admin == password => Ok(()),
+            Some(_) => Err(wrong_pass()),
+            None => Err(Error::msg(
+                "AUTH <password> called without any password configured for the default user",
+            )),
+        }
+    }
+
+    /// Create the tenant `name` with an empty keyspace limited by `quota`, only bound to with
+    /// `password` if one is given.
+    pub async fn create(&self, name: String, password: Option<String>, quota: Quota) -> Result<()> {
+        let cache = Arc::new(Cache::new(self.sample, self.threshold));
+        cache.set_quota(quota).await;
+
+        let mut tenants = self.tenants.write().unwrap();
+        if tenants.contains_key(&name) {
+            return Err(Error::msg("tenant already exists"));
+        }
+
+        log::debug!("creating tenant {}", name);
+        tenants.insert(name, Tenant { password, cache });
+        Ok(())
+    }
+
+    /// Replace the limits of the tenant `name`.
+    pub async fn set_quota(&self, name: &str, quota: Quota) -> Result<()> {
+        let cache = self.cache(name)?;
+        cache.set_quota(quota).await;
+        Ok(())
+    }
+
+    /// Delete the tenant `name`, freeing its keyspace once no connection is bound to it.
+    pub async fn delete(&self, name: &str) -> Result<()> {
+        let tenant = self.tenants.write().unwrap().remove(name);
+        tenant.map(drop).ok_or_else(no_tenant)
+    }
+
+    /// Retrieve the keyspace of the tenant `name`, checking `password` against its own.
+    pub async fn authenticate(&self, name: &str, password: &str) -> Result<Arc<Cache>> {
+        let tenants = self.tenants.read().unwrap();
+        match tenants.get(name) {
+            Some(tenant) if tenant.password.as_deref() == Some(password) => {
+                Ok(tenant.cache.clone())
+            }
+            _ => Err(wrong_pass()),
+        }
+    }
+
+    /// Retrieve the keyspace of the tenant `name`, which mustn't have a password.
+    pub async fn bind(&self, name: &str) -> Result<Arc<Cache>> {
+        let tenants = self.tenants.read().unwrap();
+        match tenants.get(name) {
+            Some(tenant) if tenant.password.is_none() => Ok(tenant.cache.clone()),
+            Some(_) => Err(Error::msg("tenant requires a password, use AUTH")),
+            None => Err(no_tenant()),
+        }
+    }
+
+    /// Retrieve the keyspace of every tenant, ordered by name.
+    pub fn all(&self) -> Vec<Arc<Cache>> {
+        let tenants = self.tenants.read().unwrap();
+        tenants
+            .values()
+            .map(|tenant| tenant.cache.clone())
+            .collect()
+    }
+
+    /// Retrieve the names of the tenants, in order.
+    pub async fn names(&self) -> Vec<String> {
+        self.tenants.read().unwrap().keys().cloned().collect()
+    }
+
+    /// Retrieve the usage and limits of the tenant `name`, measuring its memory.
+    pub async fn info(&self, name: &str) -> Result<TenantInfo> {
+        let cache = self.cache(name)?;
+        Ok(TenantInfo {
+            keys: cache.existing().await,
+            memory: cache.measure_memory().await,
+            quota: cache.quota().await,
+        })
+    }
+
+    fn cache(&self, name: &str) -> Result<Arc<Cache>> {
+        let tenants = self.tenants.read().unwrap();
+        tenants
+            .get(name)
+            .map(|tenant| tenant.cache.clone())
+            .ok_or_else(no_tenant)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tenants() -> Result<()> {
+        let tenants = Tenants::default();
+        let quota = Quota {
+            max_keys: Some(1),
+            ..Quota::default()
+        };
+        tenants
+            .create("team-a".to_string(), Some("secret".to_string()), quota)
+            .await?;
+        tenants
+            .create("team-b".to_string(), None, Quota::default())
+            .await?;
+        assert!(tenants
+            .create("team-b".to_string(), None, Quota::default())
+            .await
+            .is_err());
+        assert_eq!(tenants.names().await, vec!["team-a", "team-b"]);
+
+        assert!(tenants.authenticate("team-a", "wrong").await.is_err());
+        assert!(tenants.bind("team-a").await.is_err());
+        let team_a = tenants.authenticate("team-a", "secret").await?;
+        let team_b = tenants.bind("team-b").await?;
+
+        // keyspaces are apart, each with its own limits
+        team_a.set("key".to_string(), "a".to_string()).await?;
+        assert!(team_a
+            .set("other".to_string(), "a".to_string())
+            .await
+            .is_err());
+        team_b.set("other".to_string(), "b".to_string()).await?;
+        assert!(!team_b.exists("key".to_string()).await);
+
+        let info = tenants.info("team-a").await?;
+        assert_eq!(info.keys, 1);
+        assert_eq!(info.memory, 4);
+        assert_eq!(info.quota.max_keys, Some(1));
+
+        tenants.delete("team-a").await?;
+        assert!(tenants.delete("team-a").await.is_err());
+        assert!(tenants.authenticate("team-a", "secret").await.is_err());
+        assert!(Arc::ptr_eq(&tenants.all()[0], &team_b));
+        assert_eq!(tenants.all().len(), 1);
+        Ok(())
+    }
+}
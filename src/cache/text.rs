@@ -0,0 +1,556 @@
+use anyhow::{Error, Result};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The words left out of text indexes unless an index names its own.
+pub const DEFAULT_STOPWORDS: [&str; 33] = [
+    "a", "is", "the", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into",
+    "it", "no", "not", "of", "on", "or", "such", "that", "their", "then", "there", "these", "they",
+    "this", "to", "was", "will", "with",
+];
+
+// BM25 parameters, the usual ones.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// The runs of alphanumeric characters of `text` and their byte offsets.
+fn words(text: &str) -> impl Iterator<Item = (usize, &str)> + '_ {
+    let mut start = None;
+    text.char_indices()
+        .chain([(text.len(), ' ')])
+        .filter_map(move |(i, c)| match (c.is_alphanumeric(), start) {
+            (true, None) => {
+                start = Some(i);
+                None
+            }
+            (false, Some(from)) => {
+                start = None;
+                Some((from, &text[from..i]))
+            }
+            _ => None,
+        })
+}
+
+fn is_consonant(word: &[u8], i: usize) -> bool {
+    match word[i] {
+        b'a' | b'e' | b'i' | b'o' | b'u' => false,
+        b'y' => i == 0 || !is_consonant(word, i - 1),
+        _ => true,
+    }
+}
+
+/// The number of vowel-consonant sequences of `word`, the `m` of the Porter stemmer.
+fn measure(word: &[u8]) -> usize {
+    let mut i = 0;
+    let mut m = 0;
+    while i < word.len() && is_consonant(word, i) {
+        i += 1;
+    }
+    loop {
+        while i < word.len() && !is_consonant(word, i) {
+            i += 1;
+        }
+        if i == word.len() {
+            return m;
+        }
+        while i < word.len() && is_consonant(word, i) {
+            i += 1;
+        }
+        m += 1;
+    }
+}
+
+fn has_vowel(word: &[u8]) -> bool {
+    (0..word.len()).any(|i| !is_consonant(word, i))
+}
+
+fn ends_with_double_consonant(word: &[u8]) -> bool {
+    let n = word.len();
+    n >= 2 && word[n - 1] == word[n - 2] && is_consonant(word, n - 1)
+}
+
+/// Whether `word` ends consonant-vowel-consonant, the last not being w, x or y.
+fn ends_with_cvc(word: &[u8]) -> bool {
+    let n = word.len();
+    n >= 3
+        && is_consonant(word, n - 3)
+        && !is_consonant(word, n - 2)
+        && is_consonant(word, n - 1)
+        && !matches!(word[n - 1], b'w' | b'x' | b'y')
+}
+
+/// Replace the first of `rules` whose suffix ends `word`, if its stem measures more than `m`.
+fn replace_suffix(word: &mut Vec<u8>, rules: &[(&str, &str)], m: usize) {
+    if let Some((suffix, replacement)) = rules
+        .iter()
+        .find(|(suffix, _)| word.ends_with(suffix.as_bytes()))
+    {
+        let stem = word.len() - suffix.len();
+        if measure(&word[..stem]) > m {
+            word.truncate(stem);
+            word.extend_from_slice(replacement.as_bytes());
+        }
+    }
+}
+
+/// Reduce an English word to its stem with the Porter algorithm, so that "connected" and
+/// "connections" both become "connect". Words of other characters are left alone.
+pub fn stem(word: &str) -> String {
+    if word.len() <= 2 || !word.bytes().all(|b| b.is_ascii_lowercase()) {
+        return word.to_owned();
+    }
+    let mut w = word.as_bytes().to_vec();
+
+    // step 1a: plurals
+    if w.ends_with(b"sses") || w.ends_with(b"ies") {
+        w.truncate(w.len() - 2);
+    } else if w.ends_with(b"s") && !w.ends_with(b"ss") {
+        w.pop();
+    }
+
+    // step 1b: past participles and gerunds
+    if w.ends_with(b"eed") {
+        if measure(&w[..w.len() - 3]) > 0 {
+            w.pop();
+        }
+    } else if let Some(suffix) = [&b"ed"[..], b"ing"]
+        .into_iter()
+        .find(|suffix| w.ends_with(suffix) && has_vowel(&w[..w.len() - suffix.len()]))
+    {
+        w.truncate(w.len() - suffix.len());
+        if w.ends_with(b"at") || w.ends_with(b"bl") || w.ends_with(b"iz") {
+            w.push(b'e');
+        } else if ends_with_double_consonant(&w) && !matches!(w[w.len() - 1], b'l' | b's' | b'z') {
+            w.pop();
+        } else if measure(&w) == 1 && ends_with_cvc(&w) {
+            w.push(b'e');
+        }
+    }
+
+    // step 1c
+    if w.ends_with(b"y") && has_vowel(&w[..w.len() - 1]) {
+        *w.last_mut().unwrap() = b'i';
+    }
+
+    replace_suffix(
+        &mut w,
+        &[
+            ("ational", "ate"),
+            ("tional", "tion"),
+            ("enci", "ence"),
+            ("anci", "ance"),
+            ("izer", "ize"),
+            ("abli", "able"),
+            ("alli", "al"),
+            ("entli", "ent"),
+            ("eli", "e"),
+            ("ousli", "ous"),
+            ("ization", "ize"),
+            ("ation", "ate"),
+            ("ator", "ate"),
+            ("alism", "al"),
+            ("iveness", "ive"),
+            ("fulness", "ful"),
+            ("ousness", "ous"),
+            ("aliti", "al"),
+            ("iviti", "ive"),
+            ("biliti", "ble"),
+        ],
+        0,
+    );
+    replace_suffix(
+        &mut w,
+        &[
+            ("icate", "ic"),
+            ("ative", ""),
+            ("alize", "al"),
+            ("iciti", "ic"),
+            ("ical", "ic"),
+            ("ful", ""),
+            ("ness", ""),
+        ],
+        0,
+    );
+
+    // step 4: "ion" only goes after an s or a t
+    if w.ends_with(b"ion") && matches!(w.get(w.len().wrapping_sub(4)), Some(b's' | b't')) {
+        replace_suffix(&mut w, &[("ion", "")], 1);
+    } else {
+        replace_suffix(
+            &mut w,
+            &[
+                ("al", ""),
+                ("ance", ""),
+                ("ence", ""),
+                ("er", ""),
+                ("ic", ""),
+                ("able", ""),
+                ("ible", ""),
+                ("ant", ""),
+                ("ement", ""),
+                ("ment", ""),
+                ("ent", ""),
+                ("ou", ""),
+                ("ism", ""),
+                ("ate", ""),
+                ("iti", ""),
+                ("ous", ""),
+                ("ive", ""),
+                ("ize", ""),
+            ],
+            1,
+        );
+    }
+
+    // step 5
+    if w.ends_with(b"e") {
+        let m = measure(&w[..w.len() - 1]);
+        if m > 1 || (m == 1 && !ends_with_cvc(&w[..w.len() - 1])) {
+            w.pop();
+        }
+    }
+    if measure(&w) > 1 && ends_with_double_consonant(&w) && w.ends_with(b"l") {
+        w.pop();
+    }
+    String::from_utf8(w).unwrap()
+}
+
+/// An indexed word: its position among the words of the text and the term it is indexed
+/// under.
+#[derive(Clone, Debug, PartialEq)]
+struct Token {
+    position: usize,
+    term: String,
+}
+
+/// How text is split into the terms that are indexed and searched.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analyzer {
+    stopwords: BTreeSet<String>,
+    stemming: bool,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        let stopwords = DEFAULT_STOPWORDS.iter().map(|word| word.to_string());
+        Analyzer::new(stopwords.collect(), true)
+    }
+}
+
+impl Analyzer {
+    pub fn new(stopwords: Vec<String>, stemming: bool) -> Self {
+        Analyzer {
+            stopwords: stopwords.iter().map(|word| word.to_lowercase()).collect(),
+            stemming,
+        }
+    }
+
+    /// Split `text` into lowercase, possibly stemmed terms. Stopwords are skipped, but keep
+    /// their positions so that phrases match across them.
+    fn tokens(&self, text: &str) -> Vec<Token> {
+        words(text)
+            .enumerate()
+            .filter_map(|(position, (_, word))| {
+                let term = self.term(word)?;
+                Some(Token { position, term })
+            })
+            .collect()
+    }
+
+    /// The term `word` is indexed under, none for a stopword.
+    fn term(&self, word: &str) -> Option<String> {
+        let word = word.to_lowercase();
+        match (self.stopwords.contains(&word), self.stemming) {
+            (true, _) => None,
+            (false, true) => Some(stem(&word)),
+            (false, false) => Some(word),
+        }
+    }
+
+    /// Whether `term` is made of stopwords only, and so matches nothing to search for.
+    pub(super) fn ignores(&self, term: &Term) -> bool {
+        match term {
+            Term::Word(text) | Term::Phrase(text) => self.tokens(text).is_empty(),
+            Term::Prefix(_) => false,
+        }
+    }
+
+    /// Wrap the words of `text` indexed under one of `terms` in `open` and `close`.
+    pub fn highlight(
+        &self,
+        text: &str,
+        terms: &BTreeSet<String>,
+        open: &str,
+        close: &str,
+    ) -> String {
+        let mut highlighted = String::with_capacity(text.len());
+        let mut last = 0;
+        for (start, word) in words(text) {
+            if !self.term(word).is_some_and(|term| terms.contains(&term)) {
+                continue;
+            }
+            highlighted.push_str(&text[last..start]);
+            highlighted.push_str(open);
+            highlighted.push_str(word);
+            highlighted.push_str(close);
+            last = start + word.len();
+        }
+        highlighted.push_str(&text[last..]);
+        highlighted
+    }
+}
+
+/// A term of a text query, before analysis.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Term {
+    Word(String),
+    /// Any word starting with the prefix.
+    Prefix(String),
+    /// The words in a row, stopwords aside.
+    Phrase(String),
+}
+
+/// Parse the words, `prefix*` words and `"quoted phrases"` of a text query.
+pub fn parse_terms(text: &str) -> Result<Vec<Term>> {
+    if text.matches('"').count() % 2 == 1 {
+        return Err(Error::msg(format!("unbalanced quotes in {:?}", text)));
+    }
+    let mut terms = Vec::new();
+    for (i, part) in text.split('"').enumerate() {
+        if i % 2 == 1 {
+            terms.push(Term::Phrase(part.to_owned()));
+            continue;
+        }
+        for (start, word) in words(part) {
+            terms.push(match part[start + word.len()..].starts_with('*') {
+                true => Term::Prefix(word.to_lowercase()),
+                false => Term::Word(word.to_owned()),
+            });
+        }
+    }
+    Ok(terms)
+}
+
+/// How often a query term occurs in each value holding it.
+pub(super) type Frequencies = BTreeMap<String, usize>;
+
+/// The positions of the terms in the values of a text field, by term and key.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(super) struct TextIndex {
+    postings: BTreeMap<String, BTreeMap<String, Vec<usize>>>,
+    /// The number of terms in the value of each key.
+    lengths: BTreeMap<String, usize>,
+    total_length: usize,
+}
+
+impl TextIndex {
+    /// Index `value` under `key`, which must not be indexed already.
+    pub(super) fn add(&mut self, analyzer: &Analyzer, key: &str, value: &str) {
+        let tokens = analyzer.tokens(value);
+        for token in &tokens {
+            let keys = self.postings.entry(token.term.clone()).or_default();
+            keys.entry(key.to_owned()).or_default().push(token.position);
+        }
+        self.lengths.insert(key.to_owned(), tokens.len());
+        self.total_length += tokens.len();
+    }
+
+    /// Unindex `value`, indexed under `key` before.
+    pub(super) fn remove(&mut self, analyzer: &Analyzer, key: &str, value: &str) {
+        for token in analyzer.tokens(value) {
+            if let Some(keys) = self.postings.get_mut(&token.term) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.postings.remove(&token.term);
+                }
+            }
+        }
+        if let Some(length) = self.lengths.remove(key) {
+            self.total_length -= length;
+        }
+    }
+
+    /// The indexed terms starting with `prefix`.
+    fn prefixed<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = (&'a String, &'a BTreeMap<String, Vec<usize>>)> + 'a {
+        self.postings
+            .range(prefix.to_owned()..)
+            .take_while(move |(term, _)| term.starts_with(prefix))
+    }
+
+    /// How often the terms of `tokens` occur in a row, stopwords aside, in each value.
+    fn phrase(&self, tokens: &[Token]) -> Frequencies {
+        let Some((first, rest)) = tokens.split_first() else {
+            return Frequencies::new();
+        };
+        let Some(starts) = self.postings.get(&first.term) else {
+            return Frequencies::new();
+        };
+        let follows = |key: &str, start: usize| {
+            rest.iter().all(|token| {
+                let position = start + token.position - first.position;
+                self.postings
+                    .get(&token.term)
+                    .and_then(|keys| keys.get(key))
+                    .is_some_and(|positions| positions.binary_search(&position).is_ok())
+            })
+        };
+        starts
+            .iter()
+            .filter_map(|(key, positions)| {
+                let count = positions
+                    .iter()
+                    .filter(|&&start| follows(key, start))
+                    .count();
+                (count > 0).then(|| (key.clone(), count))
+            })
+            .collect()
+    }
+
+    /// The frequencies of `term`, once for every indexed term a prefix expands to.
+    pub(super) fn matches(&self, analyzer: &Analyzer, term: &Term) -> Vec<Frequencies> {
+        match term {
+            Term::Word(text) | Term::Phrase(text) => vec![self.phrase(&analyzer.tokens(text))],
+            Term::Prefix(prefix) => self
+                .prefixed(prefix)
+                .map(|(_, keys)| {
+                    keys.iter()
+                        .map(|(key, positions)| (key.clone(), positions.len()))
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// The indexed terms `term` stands for, to highlight them.
+    pub(super) fn terms(&self, analyzer: &Analyzer, term: &Term) -> Vec<String> {
+        match term {
+            Term::Word(text) | Term::Phrase(text) => analyzer
+                .tokens(text)
+                .into_iter()
+                .map(|token| token.term)
+                .collect(),
+            Term::Prefix(prefix) => self
+                .prefixed(prefix)
+                .map(|(term, _)| term.clone())
+                .collect(),
+        }
+    }
+
+    /// The BM25 relevance of the value of `key` to a term occurring as often as `frequencies`.
+    pub(super) fn score(&self, frequencies: &Frequencies, key: &str) -> f64 {
+        let Some(&frequency) = frequencies.get(key) else {
+            return 0.0;
+        };
+        let documents = self.lengths.len() as f64;
+        let matching = frequencies.len() as f64;
+        let idf = (1.0 + (documents - matching + 0.5) / (matching + 0.5)).ln();
+        let average_length = self.total_length as f64 / documents;
+        let length = self.lengths[key] as f64;
+        let frequency = frequency as f64;
+        idf * frequency * (K1 + 1.0) / (frequency + K1 * (1.0 - B + B * length / average_length))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stem() {
+        for (word, expected) in [
+            ("caresses", "caress"),
+            ("ponies", "poni"),
+            ("cats", "cat"),
+            ("feed", "feed"),
+            ("agreed", "agre"),
+            ("plastered", "plaster"),
+            ("motoring", "motor"),
+            ("sing", "sing"),
+            ("hopping", "hop"),
+            ("filing", "file"),
+            ("happy", "happi"),
+            ("relational", "relat"),
+            ("connections", "connect"),
+            ("connected", "connect"),
+            ("running", "run"),
+            ("generalizations", "gener"),
+            ("adoption", "adopt"),
+            ("controlling", "control"),
+            ("x11", "x11"),
+            ("café", "café"),
+        ] {
+            assert_eq!(stem(word), expected, "stem of {}", word);
+        }
+    }
+
+    #[test]
+    fn test_parse_terms() -> Result<()> {
+        assert_eq!(
+            parse_terms("Wireless lap* \"noise cancelling\"")?,
+            vec![
+                Term::Word("Wireless".to_string()),
+                Term::Prefix("lap".to_string()),
+                Term::Phrase("noise cancelling".to_string()),
+            ]
+        );
+        assert!(parse_terms("\"open").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_text_index() {
+        let analyzer = Analyzer::default();
+        let mut index = TextIndex::default();
+        index.add(
+            &analyzer,
+            "a",
+            "The quick brown fox jumps over the lazy dog",
+        );
+        index.add(&analyzer, "b", "Quick thinking: a fox, a fox and a dog");
+        index.add(&analyzer, "c", "Lazy afternoons");
+
+        let keys = |frequencies: &Frequencies| frequencies.keys().cloned().collect::<Vec<_>>();
+        let matches = index.matches(&analyzer, &Term::Word("FOXES".to_string()));
+        assert_eq!(
+            matches[0],
+            Frequencies::from([("a".to_string(), 1), ("b".to_string(), 2)])
+        );
+        assert!(index.score(&matches[0], "b") > index.score(&matches[0], "a"));
+        assert_eq!(index.score(&matches[0], "c"), 0.0);
+
+        let phrase = Term::Phrase("quick brown".to_string());
+        assert_eq!(keys(&index.matches(&analyzer, &phrase)[0]), vec!["a"]);
+        // stopwords keep their place in phrases
+        let phrase = Term::Phrase("jumps over a lazy dog".to_string());
+        assert_eq!(keys(&index.matches(&analyzer, &phrase)[0]), vec!["a"]);
+        let phrase = Term::Phrase("fox dog".to_string());
+        assert!(index.matches(&analyzer, &phrase)[0].is_empty());
+
+        let prefixed = index.matches(&analyzer, &Term::Prefix("la".to_string()));
+        assert_eq!(prefixed.len(), 1);
+        assert_eq!(keys(&prefixed[0]), vec!["a", "c"]);
+        assert!(analyzer.ignores(&Term::Word("the".to_string())));
+
+        index.remove(&analyzer, "b", "Quick thinking: a fox, a fox and a dog");
+        let matches = index.matches(&analyzer, &Term::Word("fox".to_string()));
+        assert_eq!(keys(&matches[0]), vec!["a"]);
+        assert!(!index.postings.contains_key("think"));
+    }
+
+    #[test]
+    fn test_highlight() {
+        let analyzer = Analyzer::default();
+        let terms = BTreeSet::from(["connect".to_string(), "wireless".to_string()]);
+        assert_eq!(
+            analyzer.highlight(
+                "Wireless earbuds, connected in seconds",
+                &terms,
+                "<b>",
+                "</b>"
+            ),
+            "<b>Wireless</b> earbuds, <b>connected</b> in seconds"
+        );
+    }
+}
@@ -0,0 +1,625 @@
+use crate::cache::entry::Entry;
+use crate::cache::object::Object;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use std::collections::BTreeMap;
+
+/// How the samples of a bucket are reduced to a single value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aggregation {
+    Avg,
+    Min,
+    Max,
+    Sum,
+    Count,
+}
+
+impl Aggregation {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "avg" => Ok(Aggregation::Avg),
+            "min" => Ok(Aggregation::Min),
+            "max" => Ok(Aggregation::Max),
+            "sum" => Ok(Aggregation::Sum),
+            "count" => Ok(Aggregation::Count),
+            _ => Err(Error::msg("unknown aggregation type")),
+        }
+    }
+}
+
+/// The running statistics of the samples of a bucket.
+#[derive(Clone, Debug, PartialEq)]
+struct Accumulator {
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Accumulator {
+    fn new(value: f64) -> Self {
+        Accumulator {
+            count: 1,
+            sum: value,
+            min: value,
+            max: value,
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    fn value(&self, aggregation: Aggregation) -> f64 {
+        match aggregation {
+            Aggregation::Avg => self.sum / self.count as f64,
+            Aggregation::Min => self.min,
+            Aggregation::Max => self.max,
+            Aggregation::Sum => self.sum,
+            Aggregation::Count => self.count as f64,
+        }
+    }
+}
+
+/// Retrieve the start of the bucket holding `timestamp`, buckets being aligned to the epoch.
+fn bucket_start(timestamp: u64, bucket: u64) -> u64 {
+    timestamp - timestamp % bucket
+}
+
+/// A compaction rule, downsampling the samples of a series into the series at `destination`.
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    destination: String,
+    aggregation: Aggregation,
+    bucket: u64,
+    /// The bucket being filled, written to the destination once a later sample arrives.
+    current: Option<(u64, Accumulator)>,
+}
+
+/// A sample written to a destination series by a compaction rule.
+type Compacted = (String, u64, f64);
+
+/// The settings of a new time series.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SeriesOptions {
+    /// How long samples are kept in milliseconds, zero keeping them forever.
+    pub retention: u64,
+    pub labels: Vec<(String, String)>,
+}
+
+/// Samples of floating point values ordered by their timestamp in milliseconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeSeries {
+    samples: BTreeMap<u64, f64>,
+    retention: u64,
+    labels: Vec<(String, String)>,
+    rules: Vec<Rule>,
+    /// The series this one is a compaction of.
+    source: Option<String>,
+}
+
+impl TimeSeries {
+    pub fn new(options: SeriesOptions) -> Self {
+        TimeSeries {
+            samples: BTreeMap::new(),
+            retention: options.retention,
+            labels: options.labels,
+            rules: Vec::new(),
+            source: None,
+        }
+    }
+
+    pub fn labels(&self) -> &[(String, String)] {
+        &self.labels
+    }
+
+    /// Add a sample, returning the samples its compaction rules completed. Samples older
+    /// than the retention allows, or at an existing timestamp, are refused.
+    fn add(&mut self, timestamp: u64, value: f64) -> Result<Vec<Compacted>> {
+        let last = self.samples.keys().next_back().copied();
+        if let Some(last) = last {
+            if self.retention > 0 && timestamp < last.saturating_sub(self.retention) {
+                return Err(Error::msg("timestamp is older than the retention period"));
+            }
+        }
+        if self.samples.contains_key(&timestamp) {
+            return Err(Error::msg("a sample already exists at this timestamp"));
+        }
+        self.samples.insert(timestamp, value);
+        self.trim(last.unwrap_or(0).max(timestamp));
+
+        let mut compacted = Vec::new();
+        for rule in &mut self.rules {
+            let start = bucket_start(timestamp, rule.bucket);
+            match &mut rule.current {
+                Some((current, accumulator)) if *current == start => accumulator.add(value),
+                Some((current, accumulator)) if *current < start => {
+                    compacted.push((
+                        rule.destination.clone(),
+                        *current,
+                        accumulator.value(rule.aggregation),
+                    ));
+                    rule.current = Some((start, Accumulator::new(value)));
+                }
+                // samples of completed buckets arrived too late to be compacted
+                Some(_) => {}
+                None => rule.current = Some((start, Accumulator::new(value))),
+            }
+        }
+        Ok(compacted)
+    }
+
+    /// Drop the samples that are older than the retention period at `now`.
+    fn trim(&mut self, now: u64) -> usize {
+        if self.retention == 0 {
+            return 0;
+        }
+        let kept = self.samples.split_off(&now.saturating_sub(self.retention));
+        let trimmed = self.samples.len();
+        self.samples = kept;
+        trimmed
+    }
+
+    /// Retrieve the samples between `from` and `to` inclusive, aggregated into buckets of
+    /// the given milliseconds if any.
+    pub fn range(
+        &self,
+        from: u64,
+        to: u64,
+        aggregation: Option<(Aggregation, u64)>,
+    ) -> Vec<(u64, f64)> {
+        if from > to {
+            return Vec::new();
+        }
+        let samples = self.samples.range(from..=to).map(|(t, v)| (*t, *v));
+        let (aggregation, bucket) = match aggregation {
+            Some(aggregation) => aggregation,
+            None => return samples.collect(),
+        };
+
+        let mut buckets: Vec<(u64, Accumulator)> = Vec::new();
+        for (timestamp, value) in samples {
+            let start = bucket_start(timestamp, bucket);
+            match buckets.last_mut() {
+                Some((current, accumulator)) if *current == start => accumulator.add(value),
+                _ => buckets.push((start, Accumulator::new(value))),
+            }
+        }
+        buckets
+            .into_iter()
+            .map(|(start, accumulator)| (start, accumulator.value(aggregation)))
+            .collect()
+    }
+
+    /// Check whether the samples are dropped once out of the retention period.
+    fn is_retained(&self) -> bool {
+        self.retention > 0
+    }
+
+    /// Check whether some samples are older than the retention period at `now`.
+    fn is_outdated(&self, now: u64) -> bool {
+        let first = self.samples.keys().next();
+        self.is_retained() && first.is_some_and(|first| *first < now.saturating_sub(self.retention))
+    }
+
+    /// Retrieve an estimate of the memory used by the samples and labels, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        let labels = self
+            .labels
+            .iter()
+            .map(|(label, value)| label.len() + value.len())
+            .sum::<usize>();
+        self.samples.len() * std::mem::size_of::<(u64, f64)>() + labels
+    }
+}
+
+/// Matches series by one of their labels: `label=value`, `label!=value`, `label=(a,b)` or
+/// `label!=(a,b)`. An empty value stands for a missing label, so `label=` matches series
+/// without the label and `label!=` series with it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabelFilter {
+    label: String,
+    values: Vec<String>,
+    negated: bool,
+}
+
+impl LabelFilter {
+    pub fn parse(s: &str) -> Result<Self> {
+        let invalid = || Error::msg(format!("invalid filter {:?}", s));
+        let (label, values, negated) = match s.split_once("!=") {
+            Some((label, values)) => (label, values, true),
+            None => {
+                let (label, values) = s.split_once('=').ok_or_else(invalid)?;
+                (label, values, false)
+            }
+        };
+        if label.is_empty() {
+            return Err(invalid());
+        }
+        let values = match values
+            .strip_prefix('(')
+            .and_then(|values| values.strip_suffix(')'))
+        {
+            Some(values) => values.split(',').map(|value| value.to_owned()).collect(),
+            None => vec![values.to_owned()],
+        };
+        Ok(LabelFilter {
+            label: label.to_owned(),
+            values,
+            negated,
+        })
+    }
+
+    fn matches(&self, labels: &[(String, String)]) -> bool {
+        let value = labels
+            .iter()
+            .find(|(label, _)| *label == self.label)
+            .map(|(_, value)| value.as_str())
+            .unwrap_or("");
+        self.values.iter().any(|v| v == value) != self.negated
+    }
+}
+
+/// The samples of a series matched by TS.MRANGE, with its key and labels.
+pub type SeriesRange = (String, Vec<(String, String)>, Vec<(u64, f64)>);
+
+fn no_series() -> Error {
+    Error::msg("the key does not exist")
+}
+
+impl Cache {
+    /// Create an empty time series at `key`, failing if the key exists.
+    pub async fn ts_create(&self, key: String, options: SeriesOptions) -> Result<()> {
+        let series = TimeSeries::new(options);
+        let retained = series.is_retained();
+        self.create_object(key.clone(), series.into())?;
+        if retained {
+            self.retained.lock().unwrap().insert(key);
+        }
+        Ok(())
+    }
+
+    /// Add a sample to the series at `key`, created with `options` if missing, and write the
+    /// samples its compaction rules completed to their destinations.
+    pub async fn ts_add(
+        &self,
+        key: String,
+        timestamp: u64,
+        value: f64,
+        options: SeriesOptions,
+    ) -> Result<u64> {
+        let mut store = self.store.write().unwrap();
+        self.evict_if_expired(&mut store, &key);
+        self.admit(&mut store, &key)?;
+        let created = !store.contains_key(&key);
+
+        let entry = store.entry(key.clone()).or_insert_with(|| {
+            Entry::new(
+                Object::from(TimeSeries::new(options)),
+                self.default_expiry(),
+            )
+        });
+        let series = entry.value_mut().as_time_series_mut()?;
+        let compacted = series.add(timestamp, value)?;
+        if created {
+            self.charge(key.len() + series.memory_usage());
+        } else {
+            self.charge(std::mem::size_of::<(u64, f64)>());
+        }
+        if series.is_retained() {
+            self.retained.lock().unwrap().insert(key);
+        }
+
+        for (destination, timestamp, value) in compacted {
+            self.evict_if_expired(&mut store, &destination);
+            let series = store
+                .get_mut(&destination)
+                .and_then(|entry| entry.value_mut().as_time_series_mut().ok());
+            if let Some(series) = series {
+                // destinations are never sources themselves, so nothing cascades
+                if let Err(e) = series.add(timestamp, value) {
+                    log::debug!("dropping compacted sample for {}: {}", destination, e);
+                }
+            }
+        }
+        Ok(timestamp)
+    }
+
+    /// Retrieve the samples of the series at `key` between `from` and `to` inclusive.
+    pub async fn ts_range(
+        &self,
+        key: String,
+        from: u64,
+        to: u64,
+        aggregation: Option<(Aggregation, u64)>,
+    ) -> Result<Vec<(u64, f64)>> {
+        self.read_object(&key, |object| {
+            Ok(object.as_time_series()?.range(from, to, aggregation))
+        })?
+        .ok_or_else(no_series)
+    }
+
+    /// Retrieve the samples between `from` and `to` of all the series matching `filters`,
+    /// ordered by key.
+    pub async fn ts_mrange(
+        &self,
+        filters: &[LabelFilter],
+        from: u64,
+        to: u64,
+        aggregation: Option<(Aggregation, u64)>,
+    ) -> Vec<SeriesRange> {
+        let store = self.store.read().unwrap();
+        store
+            .iter()
+            .filter(|(_, entry)| !entry.expiration().is_expired())
+            .filter_map(|(key, entry)| Some((key, entry.value().as_time_series().ok()?)))
+            .filter(|(_, series)| filters.iter().all(|filter| filter.matches(series.labels())))
+            .map(|(key, series)| {
+                (
+                    key.clone(),
+                    series.labels().to_vec(),
+                    series.range(from, to, aggregation),
+                )
+            })
+            .collect()
+    }
+
+    /// Downsample the new samples of the series at `source` into the one at `destination`,
+    /// aggregating them into buckets of `bucket` milliseconds.
+    pub async fn ts_create_rule(
+        &self,
+        source: String,
+        destination: String,
+        aggregation: Aggregation,
+        bucket: u64,
+    ) -> Result<()> {
+        if source == destination {
+            return Err(Error::msg("the source and destination key must differ"));
+        }
+        let mut store = self.store.write().unwrap();
+        self.evict_if_expired(&mut store, &source);
+        self.evict_if_expired(&mut store, &destination);
+
+        let series = |store: &BTreeMap<String, Entry>, key: &str| -> Result<TimeSeries> {
+            let entry = store.get(key).ok_or_else(no_series)?;
+            Ok(entry.value().as_time_series()?.clone())
+        };
+        let (from, to) = (series(&store, &source)?, series(&store, &destination)?);
+        if from.source.is_some() {
+            return Err(Error::msg("the source key is a compaction destination"));
+        }
+        if to.source.is_some() || !to.rules.is_empty() {
+            return Err(Error::msg(
+                "the destination key already has a source or rules",
+            ));
+        }
+
+        let to = store.get_mut(&destination).unwrap().value_mut();
+        to.as_time_series_mut()?.source = Some(source.clone());
+        let from = store.get_mut(&source).unwrap().value_mut();
+        from.as_time_series_mut()?.rules.push(Rule {
+            destination,
+            aggregation,
+            bucket,
+            current: None,
+        });
+        Ok(())
+    }
+
+    /// Remove the compaction rule from `source` to `destination`.
+    pub async fn ts_delete_rule(&self, source: String, destination: String) -> Result<()> {
+        let mut store = self.store.write().unwrap();
+        self.evict_if_expired(&mut store, &source);
+
+        let from = store
+            .get_mut(&source)
+            .ok_or_else(no_series)?
+            .value_mut()
+            .as_time_series_mut()?;
+        let before = from.rules.len();
+        from.rules.retain(|rule| rule.destination != destination);
+        if from.rules.len() == before {
+            return Err(Error::msg("compaction rule does not exist"));
+        }
+
+        if let Some(entry) = store.get_mut(&destination) {
+            if let Ok(to) = entry.value_mut().as_time_series_mut() {
+                to.source = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Have the sweeper trim the series placed under `key` if it has a retention period.
+    pub(super) fn retain_placed(&self, key: &str, object: &Object) {
+        if object.as_time_series().is_ok_and(TimeSeries::is_retained) {
+            self.retained.lock().unwrap().insert(key.to_owned());
+        }
+    }
+
+    /// Drop the samples older than the retention period of their series at `now`.
+    pub async fn trim_time_series(&self, now: u64) {
+        if self.retained.lock().unwrap().is_empty() {
+            return;
+        }
+        let mut store = self.store.write().unwrap();
+        let mut retained = self.retained.lock().unwrap();
+        let mut trimmed = 0;
+        retained.retain(|key| {
+            let Some(entry) = store.get_mut(key) else {
+                return false;
+            };
+            match entry.value().as_time_series() {
+                Ok(series) if series.is_retained() => {
+                    // only series that lose samples count as written, for WATCH
+                    if series.is_outdated(now) {
+                        let series = entry.value_mut().as_time_series_mut().unwrap();
+                        trimmed += series.trim(now);
+                    }
+                    true
+                }
+                _ => false,
+            }
+        });
+        log::debug!("trimmed {} samples out of their retention period", trimmed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(label, value)| (label.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_range_aggregation() {
+        let mut series = TimeSeries::new(SeriesOptions::default());
+        for (timestamp, value) in [(0, 1.0), (5, 3.0), (10, 2.0), (12, 4.0), (25, 8.0)] {
+            series.add(timestamp, value).unwrap();
+        }
+        assert!(series.add(5, 0.0).is_err());
+
+        assert_eq!(
+            series.range(5, 12, None),
+            vec![(5, 3.0), (10, 2.0), (12, 4.0)]
+        );
+        let ranges = [
+            (Aggregation::Avg, vec![(0, 2.0), (10, 3.0), (20, 8.0)]),
+            (Aggregation::Min, vec![(0, 1.0), (10, 2.0), (20, 8.0)]),
+            (Aggregation::Max, vec![(0, 3.0), (10, 4.0), (20, 8.0)]),
+            (Aggregation::Sum, vec![(0, 4.0), (10, 6.0), (20, 8.0)]),
+            (Aggregation::Count, vec![(0, 2.0), (10, 2.0), (20, 1.0)]),
+        ];
+        for (aggregation, expected) in ranges {
+            assert_eq!(series.range(0, u64::MAX, Some((aggregation, 10))), expected);
+        }
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut series = TimeSeries::new(SeriesOptions {
+            retention: 100,
+            labels: Vec::new(),
+        });
+        series.add(1000, 1.0).unwrap();
+        series.add(1050, 2.0).unwrap();
+        assert!(series.add(800, 0.0).is_err());
+
+        series.add(1120, 3.0).unwrap();
+        assert_eq!(
+            series.range(0, u64::MAX, None),
+            vec![(1050, 2.0), (1120, 3.0)]
+        );
+        assert_eq!(series.trim(1200), 1);
+        assert_eq!(series.range(0, u64::MAX, None), vec![(1120, 3.0)]);
+    }
+
+    #[test]
+    fn test_label_filter() {
+        let series = labels(&[("host", "a"), ("region", "eu")]);
+        let matches = |filter: &str| LabelFilter::parse(filter).unwrap().matches(&series);
+        assert!(matches("host=a"));
+        assert!(!matches("host!=a"));
+        assert!(matches("host=(a,b)"));
+        assert!(!matches("host!=(a,b)"));
+        assert!(matches("zone="));
+        assert!(!matches("region="));
+        assert!(matches("region!="));
+        assert!(LabelFilter::parse("host").is_err());
+        assert!(LabelFilter::parse("=a").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_compaction() -> Result<()> {
+        let cache = Cache::default();
+        let key = |key: &str| key.to_string();
+        cache
+            .ts_create(key("raw"), SeriesOptions::default())
+            .await?;
+        cache
+            .ts_create(key("avg"), SeriesOptions::default())
+            .await?;
+        assert!(cache
+            .ts_create_rule(key("raw"), key("missing"), Aggregation::Avg, 10)
+            .await
+            .is_err());
+        cache
+            .ts_create_rule(key("raw"), key("avg"), Aggregation::Avg, 10)
+            .await?;
+        assert!(cache
+            .ts_create_rule(key("avg"), key("raw"), Aggregation::Avg, 10)
+            .await
+            .is_err());
+
+        for (timestamp, value) in [(1, 1.0), (2, 3.0), (11, 5.0), (25, 7.0)] {
+            cache
+                .ts_add(key("raw"), timestamp, value, SeriesOptions::default())
+                .await?;
+        }
+        let compacted = cache.ts_range(key("avg"), 0, u64::MAX, None).await?;
+        assert_eq!(compacted, vec![(0, 2.0), (10, 5.0)]);
+
+        cache.ts_delete_rule(key("raw"), key("avg")).await?;
+        cache
+            .ts_add(key("raw"), 40, 1.0, SeriesOptions::default())
+            .await?;
+        let compacted = cache.ts_range(key("avg"), 0, u64::MAX, None).await?;
+        assert_eq!(compacted.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mrange_and_trim() -> Result<()> {
+        let cache = Cache::default();
+        for (key, host) in [("cpu:a", "a"), ("cpu:b", "b")] {
+            let options = SeriesOptions {
+                retention: 1000,
+                labels: labels(&[("metric", "cpu"), ("host", host)]),
+            };
+            cache.ts_add(key.to_string(), 5000, 1.0, options).await?;
+        }
+        cache.set("plain".to_string(), "value".to_string()).await?;
+
+        let filters = [LabelFilter::parse("metric=cpu")?];
+        let ranges = cache.ts_mrange(&filters, 0, u64::MAX, None).await;
+        let keys = ranges
+            .iter()
+            .map(|(key, _, _)| key.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec!["cpu:a", "cpu:b"]);
+        let filters = [
+            LabelFilter::parse("metric=cpu")?,
+            LabelFilter::parse("host!=a")?,
+        ];
+        let ranges = cache.ts_mrange(&filters, 0, u64::MAX, None).await;
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].2, vec![(5000, 1.0)]);
+
+        cache.trim_time_series(7000).await;
+        let samples = cache
+            .ts_range("cpu:a".to_string(), 0, u64::MAX, None)
+            .await?;
+        assert!(samples.is_empty());
+
+        // only the series with a retention period are trimmed, under their current key
+        cache
+            .ts_create("forever".to_string(), SeriesOptions::default())
+            .await?;
+        cache
+            .rename("cpu:a".to_string(), "cpu:c".to_string(), true)
+            .await?;
+        cache.set("cpu:b".to_string(), "value".to_string()).await?;
+        cache.trim_time_series(7000).await;
+        let retained = cache.retained.lock().unwrap().clone();
+        assert_eq!(retained.into_iter().collect::<Vec<_>>(), vec!["cpu:c"]);
+        Ok(())
+    }
+}
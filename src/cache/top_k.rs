@@ -0,0 +1,317 @@
+use crate::cache::bloom::{allocate, hash_pair};
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use rand::Rng;
+
+/// The defaults of TOPK.RESERVE, matching RedisBloom.
+pub const DEFAULT_WIDTH: usize = 8;
+pub const DEFAULT_DEPTH: usize = 7;
+pub const DEFAULT_DECAY: f64 = 0.9;
+/// The largest dimensions of a sketch, whose buckets must also fit in the largest allocation.
+pub const MAX_K: usize = 1 << 16;
+pub const MAX_WIDTH: usize = 1 << 24;
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Bucket {
+    fingerprint: u64,
+    count: u64,
+}
+
+/// The heavy hitters of a stream of items, tracked with the HeavyKeeper algorithm: items
+/// fight over the buckets of a table where counts of other items decay exponentially, so
+/// only frequent items keep high counts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TopK {
+    k: usize,
+    width: usize,
+    depth: usize,
+    decay: f64,
+    buckets: Vec<Bucket>,
+    /// The current top items with their estimated counts, unordered.
+    heap: Vec<(String, u64)>,
+}
+
+impl TopK {
+    /// Create a sketch tracking `k` items in `width` by `depth` buckets, failing if it would
+    /// be too large.
+    pub fn new(k: usize, width: usize, depth: usize, decay: f64) -> Result<Self> {
+        if k > MAX_K || width > MAX_WIDTH || depth > MAX_DEPTH {
+            return Err(Error::msg(format!(
+                "topk must be at most {}, width at most {} and depth at most {}",
+                MAX_K, MAX_WIDTH, MAX_DEPTH
+            )));
+        }
+        let cells = width
+            .checked_mul(depth)
+            .ok_or_else(|| Error::msg("the requested size is too large"))?;
+        Ok(TopK {
+            k,
+            width,
+            depth,
+            decay,
+            buckets: allocate(cells, Bucket::default())?,
+            heap: Vec::with_capacity(k),
+        })
+    }
+
+    /// Count `item` `by` more times, returning the item it expelled from the top list if
+    /// any.
+    pub fn add(&mut self, item: &str, by: u64) -> Option<String> {
+        let (fingerprint, step) = hash_pair(item.as_bytes());
+        let mut rng = rand::thread_rng();
+        let mut estimate = 0;
+        for row in 0..self.depth as u64 {
+            let column = fingerprint.wrapping_add(row.wrapping_mul(step)) % self.width as u64;
+            let bucket = &mut self.buckets[row as usize * self.width + column as usize];
+
+            if bucket.count == 0 {
+                bucket.fingerprint = fingerprint;
+            }
+            let mut remaining = by;
+            while bucket.fingerprint != fingerprint && remaining > 0 {
+                // a held bucket decays with a probability shrinking as its count grows
+                if rng.gen::<f64>() < self.decay.powf(bucket.count as f64) {
+                    bucket.count -= 1;
+                    if bucket.count == 0 {
+                        bucket.fingerprint = fingerprint;
+                    }
+                }
+                remaining -= 1;
+            }
+            if bucket.fingerprint == fingerprint {
+                bucket.count = bucket.count.saturating_add(remaining);
+                estimate = estimate.max(bucket.count);
+            }
+        }
+
+        if let Some(entry) = self.heap.iter_mut().find(|(member, _)| member == item) {
+            entry.1 = estimate;
+            return None;
+        }
+        if estimate == 0 {
+            return None;
+        }
+        if self.heap.len() < self.k {
+            self.heap.push((item.to_owned(), estimate));
+            return None;
+        }
+        let (min, _) = self
+            .heap
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, (_, count))| *count)?;
+        if self.heap[min].1 < estimate {
+            let expelled = std::mem::replace(&mut self.heap[min], (item.to_owned(), estimate));
+            return Some(expelled.0);
+        }
+        None
+    }
+
+    /// Retrieve whether `item` is in the top list.
+    pub fn contains(&self, item: &str) -> bool {
+        self.heap.iter().any(|(member, _)| member == item)
+    }
+
+    /// Retrieve the top items with their estimated counts, the most frequent first.
+    pub fn list(&self) -> Vec<(String, u64)> {
+        let mut list = self.heap.clone();
+        list.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        list
+    }
+
+    /// Count the top items of `other` in this list. The counts of items only other knew
+    /// about are approximations, like those HeavyKeeper estimates in the first place.
+    pub fn merge(&mut self, other: &TopK) {
+        for (item, count) in other.list() {
+            self.add(&item, count);
+        }
+    }
+
+    /// Retrieve an estimate of the memory used by the buckets and top items, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        let heap = self
+            .heap
+            .iter()
+            .map(|(item, _)| item.len() + std::mem::size_of::<(String, u64)>())
+            .sum::<usize>();
+        self.buckets.len() * std::mem::size_of::<Bucket>() + heap
+    }
+}
+
+impl Cache {
+    /// Create an empty top list at `key`, failing if the key exists.
+    pub async fn topk_reserve(&self, key: String, top_k: TopK) -> Result<()> {
+        self.create_object(key, top_k.into())
+    }
+
+    /// Count `items` in the top list at `key`, returning the items expelled by each.
+    /// Fails if the key doesn't exist.
+    pub async fn topk_add(
+        &self,
+        key: String,
+        items: Vec<(String, u64)>,
+    ) -> Result<Vec<Option<String>>> {
+        self.write_object(
+            &key,
+            || None,
+            |object| {
+                let top_k = object.as_top_k_mut()?;
+                Ok(items
+                    .iter()
+                    .map(|(item, by)| top_k.add(item, *by))
+                    .collect())
+            },
+        )?
+        .ok_or_else(|| Error::msg("key does not exist"))
+    }
+
+    /// Retrieve whether each of `items` is in the top list at `key`.
+    pub async fn topk_query(&self, key: String, items: Vec<String>) -> Result<Vec<bool>> {
+        self.read_object(&key, |object| {
+            let top_k = object.as_top_k()?;
+            Ok(items.iter().map(|item| top_k.contains(item)).collect())
+        })?
+        .ok_or_else(|| Error::msg("key does not exist"))
+    }
+
+    /// Retrieve the items of the top list at `key`, the most frequent first.
+    pub async fn topk_list(&self, key: String) -> Result<Vec<(String, u64)>> {
+        self.read_object(&key, |object| Ok(object.as_top_k()?.list()))?
+            .ok_or_else(|| Error::msg("key does not exist"))
+    }
+
+    /// Count the top items of the lists at `sources` in the one at `destination`, which
+    /// must exist.
+    pub async fn topk_merge(&self, destination: String, sources: Vec<String>) -> Result<()> {
+        let mut store = self.store.write().unwrap();
+        self.evict_if_expired(&mut store, &destination);
+
+        let mut lists = Vec::with_capacity(sources.len());
+        for key in &sources {
+            match store.get(key) {
+                Some(entry) if !entry.expiration().is_expired() => {
+                    lists.push(entry.value().as_top_k()?.clone())
+                }
+                _ => return Err(Error::msg("key does not exist")),
+            }
+        }
+
+        self.admit(&mut store, &destination)?;
+        let before = self.footprint(&store, &destination);
+        let top_k = store
+            .get_mut(&destination)
+            .ok_or_else(|| Error::msg("key does not exist"))?
+            .value_mut()
+            .as_top_k_mut()?;
+        for list in &lists {
+            top_k.merge(list);
+        }
+        self.charge(self.footprint(&store, &destination).saturating_sub(before));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::quota::Quota;
+
+    #[test]
+    fn test_heavy_hitters() {
+        let mut top_k = TopK::new(3, 50, 5, DEFAULT_DECAY).unwrap();
+        for round in 0..100 {
+            top_k.add("popular", 5);
+            top_k.add("common", 3);
+            top_k.add("regular", 2);
+            top_k.add(&format!("rare{}", round), 1);
+        }
+
+        let list = top_k.list();
+        let items = list
+            .iter()
+            .map(|(item, _)| item.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(items, vec!["popular", "common", "regular"]);
+        assert!(list[0].1 <= 500 && list[0].1 > 400);
+        assert!(top_k.contains("common"));
+        assert!(!top_k.contains("rare0"));
+    }
+
+    #[test]
+    fn test_expelled() {
+        let mut top_k = TopK::new(1, 8, 7, DEFAULT_DECAY).unwrap();
+        assert_eq!(top_k.add("a", 1), None);
+        assert_eq!(top_k.add("b", 10), Some("a".to_string()));
+        assert_eq!(top_k.add("b", 1), None);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut first = TopK::new(2, 8, 7, DEFAULT_DECAY).unwrap();
+        first.add("a", 10);
+        let mut second = TopK::new(2, 8, 7, DEFAULT_DECAY).unwrap();
+        second.add("b", 20);
+        second.add("a", 5);
+
+        first.merge(&second);
+        let list = first.list();
+        assert_eq!(list[0].0, "b");
+        assert_eq!(list[1], ("a".to_string(), 15));
+    }
+
+    #[tokio::test]
+    async fn test_topk_commands() -> Result<()> {
+        let cache = Cache::default();
+        let top_k = TopK::new(2, DEFAULT_WIDTH, DEFAULT_DEPTH, DEFAULT_DECAY).unwrap();
+        cache
+            .topk_reserve("pages".to_string(), top_k.clone())
+            .await?;
+        assert!(cache
+            .topk_reserve("pages".to_string(), top_k)
+            .await
+            .is_err());
+
+        let items = vec![
+            ("home".to_string(), 5),
+            ("about".to_string(), 2),
+            ("blog".to_string(), 3),
+        ];
+        let expelled = cache.topk_add("pages".to_string(), items).await?;
+        assert_eq!(expelled, vec![None, None, Some("about".to_string())]);
+
+        let found = cache
+            .topk_query(
+                "pages".to_string(),
+                vec!["home".to_string(), "about".to_string()],
+            )
+            .await?;
+        assert_eq!(found, vec![true, false]);
+        let list = cache.topk_list("pages".to_string()).await?;
+        assert_eq!(list, vec![("home".to_string(), 5), ("blog".to_string(), 3)]);
+        assert!(cache.topk_list("missing".to_string()).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_over_quota() -> Result<()> {
+        let cache = Cache::default();
+        let top_k = TopK::new(2, DEFAULT_WIDTH, DEFAULT_DEPTH, DEFAULT_DECAY).unwrap();
+        for key in ["today", "week"] {
+            cache.topk_reserve(key.to_string(), top_k.clone()).await?;
+        }
+        cache
+            .set_quota(Quota {
+                max_memory: Some(1),
+                ..Quota::default()
+            })
+            .await;
+
+        let error = cache
+            .topk_merge("week".to_string(), vec!["today".to_string()])
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("OOM"));
+        Ok(())
+    }
+}
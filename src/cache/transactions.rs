@@ -0,0 +1,158 @@
+use crate::cache::entry::Entry;
+use crate::cache::Cache;
+use std::sync::atomic::Ordering;
+use tokio::sync::{RwLockReadGuard, RwLockWriteGuard};
+
+impl Cache {
+    /// Wait for the transaction running if any, then let a command run alongside the others
+    /// until the guard is dropped.
+    pub async fn enter(&self) -> RwLockReadGuard<'_, ()> {
+        self.gate.read().await
+    }
+
+    /// Wait for the commands running to finish, then hold the others back until the guard is
+    /// dropped, so that the commands of a transaction run as one.
+    pub async fn enter_exclusive(&self) -> RwLockWriteGuard<'_, ()> {
+        self.gate.write().await
+    }
+
+    /// Retrieve the version of the entry under `key`, which changes with every write to it,
+    /// `None` once removed or expired. An expired entry is removed on the way, for a watch to
+    /// count it.
+    pub async fn version(&self, key: &str) -> Option<u64> {
+        let mut store = self.store.write().unwrap();
+        self.evict_if_expired(&mut store, key);
+        store.get(key).map(|entry| entry.version())
+    }
+
+    /// Start counting the removals of `key` for a transaction watching it, until as many
+    /// calls to `unwatch` as to this one. A key created and removed again between WATCH and
+    /// EXEC has no version either time, but its removal is counted.
+    pub fn watch(&self, key: &str) {
+        let mut watched = self.watched.lock().unwrap();
+        watched.entry(key.to_owned()).or_insert((0, 0)).0 += 1;
+    }
+
+    /// Stop counting the removals of `key` for one of the transactions watching it.
+    pub fn unwatch(&self, key: &str) {
+        let mut watched = self.watched.lock().unwrap();
+        if let Some((watchers, _)) = watched.get_mut(key) {
+            *watchers -= 1;
+            if *watchers == 0 {
+                watched.remove(key);
+            }
+        }
+    }
+
+    /// Retrieve how many times `key` was removed, deleted or expired, since it was first
+    /// watched.
+    pub fn removals(&self, key: &str) -> u64 {
+        let watched = self.watched.lock().unwrap();
+        watched.get(key).map_or(0, |(_, removals)| *removals)
+    }
+
+    /// Count the removal of the watched keys among the entries removed from the store.
+    pub(super) fn count_removed(&self, removed: &[(String, Entry)]) {
+        let mut watched = self.watched.lock().unwrap();
+        if watched.is_empty() {
+            return;
+        }
+        for (key, _) in removed {
+            if let Some((_, removals)) = watched.get_mut(key) {
+                *removals += 1;
+            }
+        }
+    }
+
+    /// Retrieve the epoch of the keyspace, which changes when it is flushed or swapped with
+    /// another database as every key changes at once.
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Relaxed)
+    }
+
+    /// Start a new epoch, for the transactions watching keys here to abort.
+    pub(super) fn next_epoch(&self) {
+        self.epoch.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_version() -> Result<()> {
+        let cache = Cache::default();
+        assert_eq!(cache.version("a").await, None);
+        cache.set("a".to_string(), "1".to_string()).await?;
+        let version = cache.version("a").await;
+        assert!(version.is_some());
+
+        cache
+            .hset("h".to_string(), vec![("f".into(), "v".into())])
+            .await?;
+        assert_eq!(cache.version("a").await, version);
+        cache.set("a".to_string(), "1".to_string()).await?;
+        assert_ne!(cache.version("a").await, version);
+
+        let version = cache.version("a").await;
+        cache.rename("a".to_string(), "b".to_string(), true).await?;
+        assert_eq!(cache.version("a").await, None);
+        assert_ne!(cache.version("b").await, version);
+
+        // expiring counts as a change
+        cache
+            .set_with_expiry("c".to_string(), "1".to_string(), Duration::from_millis(10))
+            .await?;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.version("c").await, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_removals() -> Result<()> {
+        let cache = Cache::default();
+        cache.watch("a");
+        cache.set("a".to_string(), "1".to_string()).await?;
+        cache.remove("a".to_string()).await?;
+        assert_eq!(cache.version("a").await, None);
+        assert_eq!(cache.removals("a"), 1);
+
+        // an expired key counts once found
+        cache
+            .set_with_expiry("a".to_string(), "1".to_string(), Duration::from_millis(10))
+            .await?;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.removals("a"), 1);
+        assert_eq!(cache.version("a").await, None);
+        assert_eq!(cache.removals("a"), 2);
+
+        // keys nobody watches aren't counted
+        cache.unwatch("a");
+        cache.set("a".to_string(), "1".to_string()).await?;
+        cache.remove("a".to_string()).await?;
+        assert_eq!(cache.removals("a"), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_exclusive_gate() -> Result<()> {
+        let cache = Arc::new(Cache::default());
+        let gate = cache.enter_exclusive().await;
+        let clone = cache.clone();
+        let command = tokio::spawn(async move {
+            let _gate = clone.enter().await;
+            clone.set("a".to_string(), "1".to_string()).await
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!command.is_finished());
+
+        drop(gate);
+        command.await??;
+        assert_eq!(cache.get("a".to_string()).await, Some("1".to_string()));
+        Ok(())
+    }
+}
@@ -0,0 +1,699 @@
+use crate::cache::entry::Entry;
+use crate::cache::object::Object;
+use crate::cache::Cache;
+use anyhow::{Error, Result};
+use rand::Rng;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+
+/// The defaults of the HNSW parameters.
+pub const DEFAULT_M: usize = 16;
+pub const DEFAULT_EF_CONSTRUCTION: usize = 200;
+pub const DEFAULT_EF_RUNTIME: usize = 10;
+
+/// How the distance between two vectors is measured, smaller being nearer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    /// One minus the cosine of the angle between the vectors.
+    Cosine,
+    /// The euclidean distance.
+    L2,
+    /// One minus the inner product of the vectors.
+    InnerProduct,
+}
+
+impl Metric {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "cosine" => Ok(Metric::Cosine),
+            "l2" => Ok(Metric::L2),
+            "ip" => Ok(Metric::InnerProduct),
+            _ => Err(Error::msg("unknown distance metric")),
+        }
+    }
+
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        let dot = || a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+        match self {
+            Metric::Cosine => {
+                let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+                let norms = norm(a) * norm(b);
+                if norms == 0.0 {
+                    1.0
+                } else {
+                    1.0 - dot() / norms
+                }
+            }
+            Metric::L2 => a
+                .iter()
+                .zip(b)
+                .map(|(x, y)| (x - y) * (x - y))
+                .sum::<f32>()
+                .sqrt(),
+            Metric::InnerProduct => 1.0 - dot(),
+        }
+    }
+}
+
+/// A node reached while searching the graph, ordered by its distance to the query.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Candidate {
+    distance: f32,
+    node: usize,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then_with(|| self.node.cmp(&other.node))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Node {
+    key: String,
+    vector: Vec<f32>,
+    /// The neighbours of the node on each of its layers, the lowest first.
+    links: Vec<Vec<usize>>,
+    /// Removed nodes still route searches until the graph is rebuilt.
+    removed: bool,
+}
+
+/// The HNSW parameters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HnswOptions {
+    /// The number of neighbours linked on the upper layers, twice as many on the lowest.
+    pub m: usize,
+    pub ef_construction: usize,
+    /// The number of candidates searches keep by default.
+    pub ef_runtime: usize,
+}
+
+impl Default for HnswOptions {
+    fn default() -> Self {
+        HnswOptions {
+            m: DEFAULT_M,
+            ef_construction: DEFAULT_EF_CONSTRUCTION,
+            ef_runtime: DEFAULT_EF_RUNTIME,
+        }
+    }
+}
+
+/// A hierarchical navigable small world graph: layers of proximity graphs, each sparser
+/// than the one below, that searches descend greedily towards the query.
+#[derive(Clone, Debug, PartialEq)]
+struct Hnsw {
+    options: HnswOptions,
+    metric: Metric,
+    nodes: Vec<Node>,
+    ids: HashMap<String, usize>,
+    entry: Option<usize>,
+    removed: usize,
+}
+
+impl Hnsw {
+    fn new(options: HnswOptions, metric: Metric) -> Self {
+        Hnsw {
+            options,
+            metric,
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+            entry: None,
+            removed: 0,
+        }
+    }
+
+    fn distance(&self, query: &[f32], node: usize) -> f32 {
+        self.metric.distance(query, &self.nodes[node].vector)
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.options.m * 2
+        } else {
+            self.options.m
+        }
+    }
+
+    /// Retrieve the `ef` nodes nearest to `query` on `layer` reachable from `entries`,
+    /// the nearest first.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entries: &[usize],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited = entries.iter().copied().collect::<HashSet<_>>();
+        let mut candidates = BinaryHeap::new();
+        let mut nearest = BinaryHeap::new();
+        for &node in entries {
+            let candidate = Candidate {
+                distance: self.distance(query, node),
+                node,
+            };
+            candidates.push(Reverse(candidate));
+            nearest.push(candidate);
+        }
+
+        while let Some(Reverse(candidate)) = candidates.pop() {
+            let furthest = nearest.peek().map(|c: &Candidate| c.distance);
+            if nearest.len() >= ef && Some(candidate.distance) > furthest {
+                break;
+            }
+            for &neighbour in &self.nodes[candidate.node].links[layer] {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+                let distance = self.distance(query, neighbour);
+                let furthest = nearest.peek().map(|c: &Candidate| c.distance);
+                if nearest.len() < ef || Some(distance) < furthest {
+                    let neighbour = Candidate {
+                        distance,
+                        node: neighbour,
+                    };
+                    candidates.push(Reverse(neighbour));
+                    nearest.push(neighbour);
+                    if nearest.len() > ef {
+                        nearest.pop();
+                    }
+                }
+            }
+        }
+        nearest.into_sorted_vec()
+    }
+
+    fn insert(&mut self, key: String, vector: Vec<f32>) {
+        self.remove(&key);
+
+        // each layer holds about 1/m of the nodes of the one below
+        let ml = 1.0 / (self.options.m.max(2) as f64).ln();
+        let level = (-rand::thread_rng().gen::<f64>().max(f64::MIN_POSITIVE).ln() * ml) as usize;
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            key: key.clone(),
+            vector,
+            links: vec![Vec::new(); level + 1],
+            removed: false,
+        });
+        self.ids.insert(key, id);
+
+        let entry = match self.entry {
+            Some(entry) => entry,
+            None => {
+                self.entry = Some(id);
+                return;
+            }
+        };
+        let top = self.nodes[entry].links.len() - 1;
+        let query = self.nodes[id].vector.clone();
+
+        let mut entries = vec![entry];
+        for layer in (level + 1..=top).rev() {
+            entries = vec![self.search_layer(&query, &entries, 1, layer)[0].node];
+        }
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&query, &entries, self.options.ef_construction, layer);
+            let neighbours = found
+                .iter()
+                .take(self.options.m)
+                .map(|candidate| candidate.node)
+                .collect::<Vec<_>>();
+            for &neighbour in &neighbours {
+                self.nodes[neighbour].links[layer].push(id);
+                self.prune(neighbour, layer);
+            }
+            self.nodes[id].links[layer] = neighbours;
+            entries = found.iter().map(|candidate| candidate.node).collect();
+        }
+        if level > top {
+            self.entry = Some(id);
+        }
+    }
+
+    /// Keep the nearest neighbours of `node` on `layer` once it has too many.
+    fn prune(&mut self, node: usize, layer: usize) {
+        let max = self.max_links(layer);
+        if self.nodes[node].links[layer].len() <= max {
+            return;
+        }
+        let vector = &self.nodes[node].vector;
+        let mut links = self.nodes[node].links[layer]
+            .iter()
+            .map(|&neighbour| Candidate {
+                distance: self.metric.distance(vector, &self.nodes[neighbour].vector),
+                node: neighbour,
+            })
+            .collect::<Vec<_>>();
+        links.sort();
+        self.nodes[node].links[layer] = links
+            .into_iter()
+            .take(max)
+            .map(|candidate| candidate.node)
+            .collect();
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(id) = self.ids.remove(key) {
+            self.nodes[id].removed = true;
+            self.removed += 1;
+        }
+        // rebuild once removed nodes make up most of the graph
+        if self.removed > 0 && self.removed * 2 >= self.nodes.len() {
+            let nodes = std::mem::take(&mut self.nodes);
+            *self = Hnsw::new(self.options, self.metric);
+            for node in nodes.into_iter().filter(|node| !node.removed) {
+                self.insert(node.key, node.vector);
+            }
+        }
+    }
+
+    /// Retrieve the nodes nearest to `query` found while keeping `ef` candidates.
+    fn search(&self, query: &[f32], ef: usize) -> Vec<Candidate> {
+        let entry = match self.entry {
+            Some(entry) => entry,
+            None => return Vec::new(),
+        };
+        let mut entries = vec![entry];
+        for layer in (1..self.nodes[entry].links.len()).rev() {
+            entries = vec![self.search_layer(query, &entries, 1, layer)[0].node];
+        }
+        self.search_layer(query, &entries, ef, 0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Algorithm {
+    /// Exact search, comparing the query with every vector.
+    Flat(BTreeMap<String, Vec<f32>>),
+    /// Approximate search through a HNSW graph.
+    Hnsw(Hnsw),
+}
+
+/// An index of the vectors of the keys added to it, searched for the nearest neighbours of
+/// a query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VectorIndex {
+    dimension: usize,
+    metric: Metric,
+    algorithm: Algorithm,
+}
+
+impl VectorIndex {
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        match &self.algorithm {
+            Algorithm::Flat(vectors) => vectors.len(),
+            Algorithm::Hnsw(graph) => graph.ids.len(),
+        }
+    }
+
+    /// Create an index searched exactly.
+    pub fn flat(dimension: usize, metric: Metric) -> Self {
+        VectorIndex {
+            dimension,
+            metric,
+            algorithm: Algorithm::Flat(BTreeMap::new()),
+        }
+    }
+
+    /// Create an index searched approximately through a HNSW graph.
+    pub fn hnsw(dimension: usize, metric: Metric, options: HnswOptions) -> Self {
+        VectorIndex {
+            dimension,
+            metric,
+            algorithm: Algorithm::Hnsw(Hnsw::new(options, metric)),
+        }
+    }
+
+    fn check_dimension(&self, vector: &[f32]) -> Result<()> {
+        if vector.len() != self.dimension {
+            return Err(Error::msg(format!(
+                "vector has {} dimensions, the index expects {}",
+                vector.len(),
+                self.dimension
+            )));
+        }
+        Ok(())
+    }
+
+    /// Add the vector of `key`, replacing its previous one.
+    fn add(&mut self, key: String, vector: Vec<f32>) {
+        match &mut self.algorithm {
+            Algorithm::Flat(vectors) => {
+                vectors.insert(key, vector);
+            }
+            Algorithm::Hnsw(graph) => graph.insert(key, vector),
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        match &mut self.algorithm {
+            Algorithm::Flat(vectors) => {
+                vectors.remove(key);
+            }
+            Algorithm::Hnsw(graph) => graph.remove(key),
+        }
+    }
+
+    /// Retrieve the `k` keys nearest to `query` that `accept` keeps, with their distance.
+    /// Approximate searches keep `ef` candidates and fall back to an exact search when too
+    /// few of them are accepted.
+    fn search<F>(&self, query: &[f32], k: usize, ef: Option<usize>, accept: F) -> Vec<(String, f32)>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let exact = |vectors: &mut dyn Iterator<Item = (&String, &Vec<f32>)>| {
+            let mut nearest = vectors
+                .filter(|(key, _)| accept(key))
+                .map(|(key, vector)| (key.clone(), self.metric.distance(query, vector)))
+                .collect::<Vec<_>>();
+            nearest.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+            nearest.truncate(k);
+            nearest
+        };
+
+        match &self.algorithm {
+            Algorithm::Flat(vectors) => exact(&mut vectors.iter()),
+            Algorithm::Hnsw(graph) => {
+                let ef = ef.unwrap_or(graph.options.ef_runtime).max(k);
+                let nearest = graph
+                    .search(query, ef)
+                    .into_iter()
+                    .map(|candidate| (&graph.nodes[candidate.node], candidate.distance))
+                    .filter(|(node, _)| !node.removed && accept(&node.key))
+                    .take(k)
+                    .map(|(node, distance)| (node.key.clone(), distance))
+                    .collect::<Vec<_>>();
+                if nearest.len() == k {
+                    return nearest;
+                }
+                let mut live = graph
+                    .nodes
+                    .iter()
+                    .filter(|node| !node.removed)
+                    .map(|node| (&node.key, &node.vector));
+                exact(&mut live)
+            }
+        }
+    }
+}
+
+/// A vector stored under a key, indexed by the index it names.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Embedding {
+    pub index: String,
+    pub vector: Vec<f32>,
+    pub metadata: Vec<(String, String)>,
+}
+
+/// A key found by a vector search with its distance to the query and metadata.
+pub type Neighbour = (String, f32, Vec<(String, String)>);
+
+fn no_index() -> Error {
+    Error::msg("no such index")
+}
+
+impl Cache {
+    /// Create the vector index `name`, failing if it exists.
+    pub async fn vec_create(&self, name: String, index: VectorIndex) -> Result<()> {
+        let mut indexes = self.vectors.write().unwrap();
+        if indexes.contains_key(&name) {
+            return Err(Error::msg("index already exists"));
+        }
+        indexes.insert(name, index);
+        Ok(())
+    }
+
+    /// Store `embedding` under `key`, replacing its value and expiration like SET, and add it
+    /// to its index.
+    pub async fn vec_set(&self, key: String, embedding: Embedding) -> Result<()> {
+        let mut store = self.store.write().unwrap();
+        {
+            let indexes = self.vectors.read().unwrap();
+            indexes
+                .get(&embedding.index)
+                .ok_or_else(no_index)?
+                .check_dimension(&embedding.vector)?;
+        }
+        self.admit(&mut store, &key)?;
+
+        // placing the entry unindexes the one it replaces and indexes the embedding
+        let entry = Entry::new(Object::from(embedding), self.default_expiry());
+        self.charge(key.len() + entry.value().memory_usage());
+        self.place(&mut store, key, entry);
+        Ok(())
+    }
+
+    /// Retrieve the embedding stored under `key`.
+    pub async fn vec_get(&self, key: String) -> Result<Option<Embedding>> {
+        self.read_object(&key, |object| Ok(object.as_embedding()?.clone()))
+    }
+
+    /// Retrieve the `k` keys of the index `name` nearest to `query`, restricted to the keys
+    /// starting with `prefix` if any.
+    pub async fn vec_search(
+        &self,
+        name: String,
+        query: Vec<f32>,
+        k: usize,
+        ef: Option<usize>,
+        prefix: Option<String>,
+    ) -> Result<Vec<Neighbour>> {
+        let store = self.store.read().unwrap();
+        let indexes = self.vectors.read().unwrap();
+        let index = indexes.get(&name).ok_or_else(no_index)?;
+        index.check_dimension(&query)?;
+
+        // keys deleted or overwritten since they were indexed are skipped here
+        let embedding = |key: &str| match store.get(key) {
+            Some(entry) if !entry.expiration().is_expired() => match entry.value() {
+                Object::Embedding(embedding) if embedding.index == name => Some(embedding),
+                _ => None,
+            },
+            _ => None,
+        };
+        let prefix = prefix.unwrap_or_default();
+        let accept = |key: &str| key.starts_with(&prefix) && embedding(key).is_some();
+
+        Ok(index
+            .search(&query, k, ef, accept)
+            .into_iter()
+            .map(|(key, distance)| {
+                let metadata = embedding(&key).unwrap().metadata.clone();
+                (key, distance, metadata)
+            })
+            .collect())
+    }
+
+    /// Remove `key` from the index of the embedding it held.
+    fn unindex(indexes: &mut BTreeMap<String, VectorIndex>, key: &str, object: &Object) {
+        if let Object::Embedding(embedding) = object {
+            if let Some(index) = indexes.get_mut(&embedding.index) {
+                index.remove(key);
+            }
+        }
+    }
+
+    /// Add the embedding placed under `key` by another command than VEC.SET to its index.
+    pub(super) fn index_placed(&self, key: &str, object: &Object) {
+        if let Object::Embedding(embedding) = object {
+            let mut indexes = self.vectors.write().unwrap();
+            if let Some(index) = indexes.get_mut(&embedding.index) {
+                index.add(key.to_owned(), embedding.vector.clone());
+            }
+        }
+    }
+
+    /// Remove the keys removed from the store from the index of the embedding they held.
+    pub(super) fn unindex_removed(&self, removed: &[(String, Entry)]) {
+        if !removed
+            .iter()
+            .any(|(_, entry)| matches!(entry.value(), Object::Embedding(_)))
+        {
+            return;
+        }
+        let mut indexes = self.vectors.write().unwrap();
+        for (key, entry) in removed {
+            Self::unindex(&mut indexes, key, entry.value());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::expiry::Expiry;
+    use std::time::Duration;
+
+    fn random_vectors(count: usize, dimension: usize) -> Vec<Vec<f32>> {
+        let mut rng = rand::thread_rng();
+        (0..count)
+            .map(|_| (0..dimension).map(|_| rng.gen::<f32>()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_metrics() {
+        let (a, b) = ([1.0, 0.0], [0.0, 2.0]);
+        assert_eq!(Metric::Cosine.distance(&a, &b), 1.0);
+        assert_eq!(Metric::Cosine.distance(&a, &[3.0, 0.0]), 0.0);
+        assert_eq!(Metric::L2.distance(&[0.0, 3.0], &[4.0, 0.0]), 5.0);
+        assert_eq!(Metric::InnerProduct.distance(&[0.5, 0.5], &[1.0, 0.0]), 0.5);
+        assert!(Metric::parse("hamming").is_err());
+    }
+
+    #[test]
+    fn test_hnsw_recall() {
+        let vectors = random_vectors(500, 8);
+        let mut flat = VectorIndex::flat(8, Metric::L2);
+        let mut hnsw = VectorIndex::hnsw(8, Metric::L2, HnswOptions::default());
+        for (i, vector) in vectors.iter().enumerate() {
+            flat.add(format!("v{}", i), vector.clone());
+            hnsw.add(format!("v{}", i), vector.clone());
+        }
+
+        let mut found = 0;
+        for query in random_vectors(20, 8) {
+            let exact = flat.search(&query, 10, None, |_| true);
+            let approximate = hnsw.search(&query, 10, Some(50), |_| true);
+            found += approximate
+                .iter()
+                .filter(|neighbour| exact.contains(neighbour))
+                .count();
+        }
+        assert!(found >= 180, "recall too low: {} / 200", found);
+    }
+
+    #[test]
+    fn test_hnsw_remove() {
+        let mut hnsw = VectorIndex::hnsw(2, Metric::L2, HnswOptions::default());
+        for i in 0..10 {
+            hnsw.add(format!("v{}", i), vec![i as f32, 0.0]);
+        }
+        hnsw.remove("v0");
+        let nearest = hnsw.search(&[0.0, 0.0], 1, None, |_| true);
+        assert_eq!(nearest, vec![("v1".to_string(), 1.0)]);
+
+        // removing most nodes rebuilds the graph from the others
+        for i in 1..9 {
+            hnsw.remove(&format!("v{}", i));
+        }
+        let nearest = hnsw.search(&[0.0, 0.0], 5, None, |_| true);
+        assert_eq!(nearest, vec![("v9".to_string(), 9.0)]);
+    }
+
+    #[tokio::test]
+    async fn test_vector_commands() -> Result<()> {
+        let cache = Cache::default();
+        let embedding = |vector: Vec<f32>, answer: &str| Embedding {
+            index: "answers".to_string(),
+            vector,
+            metadata: vec![("answer".to_string(), answer.to_string())],
+        };
+        assert!(cache
+            .vec_set("q:1".to_string(), embedding(vec![1.0, 0.0], "one"))
+            .await
+            .is_err());
+
+        let index = VectorIndex::hnsw(2, Metric::Cosine, HnswOptions::default());
+        cache
+            .vec_create("answers".to_string(), index.clone())
+            .await?;
+        assert!(cache
+            .vec_create("answers".to_string(), index)
+            .await
+            .is_err());
+        assert!(cache
+            .vec_set("q:1".to_string(), embedding(vec![1.0, 0.0, 0.0], "one"))
+            .await
+            .is_err());
+        cache
+            .vec_set("q:1".to_string(), embedding(vec![1.0, 0.0], "one"))
+            .await?;
+        cache
+            .vec_set("q:2".to_string(), embedding(vec![0.0, 1.0], "two"))
+            .await?;
+        cache
+            .vec_set("other:3".to_string(), embedding(vec![1.0, 0.1], "three"))
+            .await?;
+
+        let search = |prefix: Option<&str>| {
+            cache.vec_search(
+                "answers".to_string(),
+                vec![1.0, 0.0],
+                2,
+                None,
+                prefix.map(|prefix| prefix.to_string()),
+            )
+        };
+        let keys = |neighbours: Vec<Neighbour>| {
+            neighbours
+                .into_iter()
+                .map(|(key, _, _)| key)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys(search(None).await?), vec!["q:1", "other:3"]);
+        assert_eq!(keys(search(Some("q:")).await?), vec!["q:1", "q:2"]);
+        let nearest = search(None).await?;
+        assert_eq!(
+            nearest[0].2,
+            vec![("answer".to_string(), "one".to_string())]
+        );
+
+        // overwritten and expired keys leave the index
+        cache.set("q:2".to_string(), "plain".to_string()).await?;
+        cache
+            .expire("q:1".to_string(), Expiry::after(Duration::from_millis(1))?)
+            .await;
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(keys(search(Some("q:")).await?), Vec::<String>::new());
+        cache.purge().await;
+        assert_eq!(keys(search(None).await?), vec!["other:3"]);
+        assert_eq!(cache.vec_get("q:1".to_string()).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replaced_keys_leave_the_index() -> Result<()> {
+        let cache = Cache::default();
+        let index = VectorIndex::flat(2, Metric::L2);
+        cache.vec_create("answers".to_string(), index).await?;
+        for key in ["e:1", "e:2", "e:3"] {
+            let embedding = Embedding {
+                index: "answers".to_string(),
+                vector: vec![1.0, 0.0],
+                metadata: Vec::new(),
+            };
+            cache.vec_set(key.to_string(), embedding).await?;
+        }
+        let indexed = || cache.vectors.read().unwrap()["answers"].len();
+        assert_eq!(indexed(), 3);
+
+        // whether evicted once expired or overwritten by another write
+        cache
+            .expire("e:1".to_string(), Expiry::after(Duration::from_millis(1))?)
+            .await;
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(cache.get_bytes("e:1".to_string()).await?, None);
+        cache.set("e:2".to_string(), "plain".to_string()).await?;
+        cache
+            .set_with_expiry(
+                "e:3".to_string(),
+                "plain".to_string(),
+                Duration::from_secs(5),
+            )
+            .await?;
+        assert_eq!(indexed(), 0);
+        Ok(())
+    }
+}
@@ -2,31 +2,63 @@ mod cache;
 mod resp;
 mod server;
 
-use std::sync::Arc;
 use std::time::Duration;
 use tokio::{net::TcpListener, signal};
 
-use crate::cache::Cache;
+use crate::cache::databases::Databases;
+use crate::cache::notifications::Notifications;
+use crate::cache::pubsub::PubSub;
+use crate::cache::scripts::Scripts;
+use crate::cache::sweeper;
+use crate::cache::tenants::Tenants;
 use crate::server::Server;
 
-pub async fn run_server(socket_addr: &str, sample: usize, threshold: f64, frequency: Duration) {
+pub async fn run_server(
+    socket_addr: &str,
+    databases: usize,
+    sample: usize,
+    threshold: f64,
+    frequency: Duration,
+    admin_password: Option<String>,
+) {
     // Bind a tcp listener
     let listener = TcpListener::bind(socket_addr).await.unwrap();
 
     // Create the shutdown signal which will shutdown when we hit ctrl_c
     let shutdown = signal::ctrl_c();
 
-    // Create the main_cache arc that we clone in every connection. We only clone a ref to the store
+    // Create the databases that we clone in every connection. We only clone a ref to the stores
     // which makes it inexpensive
-    let main_cache = Arc::new(Cache::new(sample, threshold, frequency));
+    let databases = Databases::new(databases, sample, threshold);
 
-    // Create a a cache clone to spawn the cache monitor_for_expiry
-    let clone = main_cache.clone();
+    // The databases publish their keyspace notifications on the channels clients subscribe
+    // to, once configured
+    let notifications = Notifications::new(PubSub::default());
+    databases.notify_through(&notifications);
 
-    let task = tokio::spawn(async move { clone.monitor_for_expiry().await });
+    // Tenants get keyspaces of their own, managed by clients knowing the admin password
+    let tenants = Tenants::new(sample, threshold).with_admin_password(admin_password);
+
+    // A single monitor walks the databases and the tenants, sampling the keys of each for
+    // expiry and trimming its time series out of their retention period
+    let monitor = tokio::spawn(sweeper::monitor(
+        databases.clone(),
+        tenants.clone(),
+        frequency,
+    ));
+
+    // Scripts loaded by any client can be run by the others
+    let scripts = Scripts::default();
 
     // Create the server instance
-    let server = Server::new(socket_addr, main_cache, listener);
+    let server = Server::new(
+        socket_addr,
+        databases,
+        tenants,
+        notifications,
+        scripts,
+        listener,
+    );
 
     log::info!("{:?}", "Server is created");
 
@@ -41,8 +73,8 @@ pub async fn run_server(socket_addr: &str, sample: usize, threshold: f64, freque
         }
     }
 
-    // closing background monitor
-    task.abort();
+    // closing the background monitor
+    monitor.abort();
 
     log::info!("{:?}", "Server is closed");
 }
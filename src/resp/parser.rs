@@ -1,106 +1,113 @@
 use crate::resp::value::Value;
 use anyhow::{Error, Result};
-use bytes::BytesMut;
+use thiserror::Error;
 
 const CARRIAGE_RETURN: u8 = b'\r';
 const NEWLINE: u8 = b'\n';
+/// The longest bulk string accepted, like the default `proto-max-bulk-len` of Redis.
+const MAX_BULK_LENGTH: i64 = 512 * 1024 * 1024;
+
+/// A message cut short, which parses once more of it is read.
+#[derive(Debug, Error)]
+#[error("incomplete message")]
+pub struct Incomplete;
+
+/// A malformed message, which no amount of reading would let parse.
+#[derive(Debug, Error)]
+#[error("Protocol error: {0}")]
+pub struct ProtocolError(&'static str);
 
 #[derive(Eq, PartialEq, Clone, Debug)]
 pub struct Parser;
 
 impl Parser {
-    pub fn parse_message(buffer: &BytesMut) -> Result<(Value, usize)> {
-        match buffer[0] as char {
-            '+' => Self::decode_simple_string(buffer),
-            ':' => Self::decode_integer(buffer),
-            '*' => Self::decode_array(buffer),
-            '$' => Self::decode_bulk_string(buffer),
-            _ => Err(Error::msg("unrecognised message type")),
+    /// Parse the message at the start of `buffer`, along with the number of bytes it takes.
+    /// Fails with `Incomplete` while the message is cut short, and with `ProtocolError` once
+    /// it can't be parsed.
+    pub fn parse_message(buffer: &[u8]) -> Result<(Value, usize)> {
+        match buffer.first().map(|&byte| byte as char) {
+            Some('+') => Self::decode_simple_string(buffer),
+            Some(':') => Self::decode_integer(buffer),
+            Some('*') => Self::decode_array(buffer),
+            Some('$') => Self::decode_bulk_string(buffer),
+            Some(_) => Err(ProtocolError("unrecognised message type").into()),
+            None => Err(Incomplete.into()),
         }
     }
 
-    fn decode_simple_string(buffer: &BytesMut) -> Result<(Value, usize)> {
-        if let Some((line, len)) = Self::read_until_crlf(&buffer[1..]) {
-            let str = Self::parse_string(line)?;
-            return Ok((Value::SimpleString(str), len + 1));
-        }
-        return Err(Error::msg("Error in decoding simple string"));
+    fn decode_simple_string(buffer: &[u8]) -> Result<(Value, usize)> {
+        let (line, len) = Self::read_until_crlf(&buffer[1..])?;
+        let str = Self::parse_string(line)?;
+        Ok((Value::SimpleString(str), len + 1))
     }
 
-    fn decode_integer(buffer: &BytesMut) -> Result<(Value, usize)> {
-        if let Some((line, len)) = Self::read_until_crlf(&buffer[1..]) {
-            let str = Self::parse_string(line)?;
-            return Ok((Value::Integer(str), len + 1));
-        }
-        return Err(Error::msg("Error in decoding simple string"));
+    fn decode_integer(buffer: &[u8]) -> Result<(Value, usize)> {
+        let (line, len) = Self::read_until_crlf(&buffer[1..])?;
+        let integer = Self::parse_integer(line)?;
+        Ok((Value::Integer(integer.to_string()), len + 1))
     }
 
-    fn decode_array(buffer: &BytesMut) -> Result<(Value, usize)> {
-        let (array_length, mut bytes_consumed) =
-            if let Some((line, len)) = Self::read_until_crlf(&buffer[1..]) {
-                let array_length = Self::parse_integer(line)?;
-                (array_length, len + 1)
-            } else {
-                return Err(Error::msg("Error in decoding simple array"));
-            };
+    fn decode_array(buffer: &[u8]) -> Result<(Value, usize)> {
+        let (line, len) = Self::read_until_crlf(&buffer[1..])?;
+        let array_length = Self::parse_integer(line)?;
+        if array_length < 0 {
+            return Err(ProtocolError("invalid multibulk length").into());
+        }
 
+        let mut bytes_consumed = len + 1;
         let mut items: Vec<Value> = Vec::new();
         for _ in 0..array_length {
-            match Self::parse_message(&BytesMut::from(&buffer[bytes_consumed..])) {
-                Ok((v, len)) => {
-                    items.push(v);
-                    bytes_consumed += len;
-                }
-                Err(_e) => {
-                    return Err(Error::msg("Error in decoding simple array"));
-                }
-            }
+            let (v, len) = Self::parse_message(&buffer[bytes_consumed..])?;
+            items.push(v);
+            bytes_consumed += len;
         }
-        return Ok((Value::Array(items), bytes_consumed));
-    }
-
-    fn decode_bulk_string(buffer: &BytesMut) -> Result<(Value, usize)> {
-        let (bulk_length, bytes_consumed) =
-            if let Some((line, len)) = Parser::read_until_crlf(&buffer[1..]) {
-                let bulk_length = Parser::parse_integer(line)?;
-                (bulk_length, len + 1)
-            } else {
-                return Err(Error::msg("Error in decoding simple array"));
-            };
-        let end_of_bulk = bytes_consumed + (bulk_length as usize);
-        let end_of_bulk_line = end_of_bulk + 2;
-        return if end_of_bulk_line <= buffer.len() {
-            Ok((
-                Value::BulkString(Self::parse_string(&buffer[bytes_consumed..end_of_bulk])?),
-                end_of_bulk_line,
-            ))
-        } else {
-            return Err(Error::msg("Error in decoding simple array"));
+        Ok((Value::Array(items), bytes_consumed))
+    }
+
+    fn decode_bulk_string(buffer: &[u8]) -> Result<(Value, usize)> {
+        let (line, len) = Self::read_until_crlf(&buffer[1..])?;
+        let bytes_consumed = len + 1;
+        let bulk_length = match Self::parse_integer(line)? {
+            -1 => return Ok((Value::Null, bytes_consumed)),
+            length @ 0..=MAX_BULK_LENGTH => length as usize,
+            _ => return Err(ProtocolError("invalid bulk length").into()),
         };
+        let end_of_bulk = bytes_consumed + bulk_length;
+        let end_of_bulk_line = end_of_bulk + 2;
+        if buffer.len() < end_of_bulk_line {
+            return Err(Incomplete.into());
+        }
+        if buffer[end_of_bulk..end_of_bulk_line] != [CARRIAGE_RETURN, NEWLINE] {
+            return Err(ProtocolError("bulk string longer than its length").into());
+        }
+        // bulk strings are binary safe, only held as a `String` when they are UTF-8
+        let bytes = buffer[bytes_consumed..end_of_bulk].to_vec();
+        Ok((Value::from(bytes), end_of_bulk_line))
     }
 
-    fn read_until_crlf(buffer: &[u8]) -> Option<(&[u8], usize)> {
+    fn read_until_crlf(buffer: &[u8]) -> Result<(&[u8], usize)> {
         for i in 1..buffer.len() {
             if buffer[i - 1] == CARRIAGE_RETURN && buffer[i] == NEWLINE {
-                return Some((&buffer[0..(i - 1)], i + 1));
+                return Ok((&buffer[0..(i - 1)], i + 1));
             }
         }
-        None
+        Err(Incomplete.into())
     }
 
     fn parse_string(bytes: &[u8]) -> Result<String> {
-        String::from_utf8(bytes.to_vec()).map_err(|_| Error::msg("Could not parse string"))
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| Error::from(ProtocolError("could not parse string")))
     }
 
     fn parse_integer(bytes: &[u8]) -> Result<i64> {
         let str_integer = Parser::parse_string(bytes)?;
-        (str_integer.parse::<i64>()).map_err(|_| Error::msg("Could not parse integer"))
+        (str_integer.parse::<i64>()).map_err(|_| ProtocolError("could not parse integer").into())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::resp::parser::Parser;
+    use crate::resp::parser::{Incomplete, Parser, ProtocolError};
     use crate::resp::value::Value;
     use bytes::{BufMut, BytesMut};
 
@@ -150,6 +157,50 @@ mod tests {
     fn test_parse_unknown_input() {
         let mut bytes = BytesMut::new();
         bytes.put_slice(b"hello world");
-        assert_eq!(Parser::parse_message(&bytes).is_err(), true);
+        assert!(Parser::parse_message(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_parse_split_messages() {
+        let messages: [&[u8]; 5] = [
+            b"+OK\r\n",
+            b":-12\r\n",
+            b"$-1\r\n",
+            b"$4\r\nbu\r\n\r\n",
+            b"*3\r\n$4\r\nECHO\r\n*1\r\n:1\r\n$0\r\n\r\n",
+        ];
+        for message in messages {
+            for offset in 0..message.len() {
+                let error = Parser::parse_message(&message[..offset]).unwrap_err();
+                assert!(error.is::<Incomplete>(), "{:?} cut at {}", message, offset);
+            }
+            let (_, len) = Parser::parse_message(message).unwrap();
+            assert_eq!(len, message.len());
+        }
+    }
+
+    #[test]
+    fn test_parse_binary_bulk_string() {
+        let (v, s) = Parser::parse_message(b"$4\r\n\xff\r\n\x00\r\n").unwrap();
+        assert_eq!(s, 10);
+        assert_eq!(v, Value::BulkBytes(vec![0xff, b'\r', b'\n', 0]));
+    }
+
+    #[test]
+    fn test_parse_malformed_messages() {
+        let messages: [&[u8]; 8] = [
+            b"+\xff\xfe\r\n",
+            b"hello world",
+            b"*1\r\n$x\r\n",
+            b"*-2\r\n",
+            b"*2\r\n$4\r\nECHO\r\n!",
+            b"$-2\r\n",
+            b"$2\r\nhello\r\n",
+            b":1.5\r\n",
+        ];
+        for message in messages {
+            let error = Parser::parse_message(message).unwrap_err();
+            assert!(error.is::<ProtocolError>(), "{:?}", message);
+        }
     }
 }
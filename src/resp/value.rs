@@ -9,18 +9,20 @@ pub enum Value {
     Integer(String),
     Error(String),
     BulkString(String),
+    /// A bulk string of raw bytes that aren't UTF-8, like the binary safe values of strings.
+    BulkBytes(Vec<u8>),
     Array(Vec<Value>),
 }
 
 impl Value {
     pub fn to_command(&self) -> Result<(String, Vec<Value>)> {
         match self {
-            Value::Array(items) => {
-                return Ok((
-                    items.first().unwrap().unwrap_bulk(),
-                    items.clone().into_iter().skip(1).collect(),
-                ));
-            }
+            Value::Array(items) => match items.first() {
+                Some(name @ (Value::BulkString(_) | Value::BulkBytes(_))) => {
+                    Ok((name.unwrap_bulk(), items[1..].to_vec()))
+                }
+                _ => Err(Error::msg("not a command")),
+            },
             _ => Err(Error::msg("not an array")),
         }
     }
@@ -28,18 +30,41 @@ impl Value {
     fn unwrap_bulk(&self) -> String {
         match self {
             Value::BulkString(str) => str.clone(),
+            Value::BulkBytes(bytes) => String::from_utf8_lossy(bytes).into_owned(),
             _ => panic!("not a bulk string"),
         }
     }
 
-    pub fn encode(self) -> String {
+    pub fn encode(self) -> Vec<u8> {
         match self {
-            Value::Null => "$-1\r\n".to_string(),
-            Value::SimpleString(s) => format!("+{}\r\n", s),
-            Value::Integer(s) => format!(":{}\r\n", s),
-            Value::Error(msg) => format!("-{}\r\n", msg),
-            Value::BulkString(s) => format!("${}\r\n{}\r\n", s.chars().count(), s),
-            _ => panic!("value encode not implemented for: {:?}", self),
+            Value::Null => b"$-1\r\n".to_vec(),
+            Value::SimpleString(s) => format!("+{}\r\n", s).into_bytes(),
+            Value::Integer(s) => format!(":{}\r\n", s).into_bytes(),
+            Value::Error(msg) => format!("-{}\r\n", msg).into_bytes(),
+            Value::BulkString(s) => Value::BulkBytes(s.into_bytes()).encode(),
+            Value::BulkBytes(bytes) => {
+                let mut encoded = format!("${}\r\n", bytes.len()).into_bytes();
+                encoded.extend_from_slice(&bytes);
+                encoded.extend_from_slice(b"\r\n");
+                encoded
+            }
+            Value::Array(items) => {
+                let mut encoded = format!("*{}\r\n", items.len()).into_bytes();
+                for item in items {
+                    encoded.extend(item.encode());
+                }
+                encoded
+            }
+        }
+    }
+}
+
+/// A bulk string of `bytes`, held as a `String` when they are UTF-8.
+impl From<Vec<u8>> for Value {
+    fn from(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(s) => Value::BulkString(s),
+            Err(e) => Value::BulkBytes(e.into_bytes()),
         }
     }
 }
@@ -83,7 +108,10 @@ mod tests {
     #[test]
     fn test_to_command_error() {
         let v = Value::BulkString("set".to_string());
-        assert_eq!(v.to_command().is_err(), true);
+        assert!(v.to_command().is_err());
+        assert!(Value::Array(vec![]).to_command().is_err());
+        let v = Value::Array(vec![Value::Integer("1".to_string())]);
+        assert!(v.to_command().is_err());
     }
 
     #[test]
@@ -97,42 +125,56 @@ mod tests {
     #[test]
     fn test_encode_null_value() {
         let value = Value::Null;
-        assert_eq!("$-1\r\n".to_string(), value.encode());
+        assert_eq!("$-1\r\n".as_bytes(), value.encode());
     }
 
     #[test]
     fn test_encode_simple_string_value() {
         let value = Value::SimpleString("m".to_string());
-        assert_eq!("+m\r\n".to_string(), value.encode());
+        assert_eq!("+m\r\n".as_bytes(), value.encode());
     }
 
     #[test]
     fn test_encode_integer_value() {
         let value = Value::Integer("5".to_string());
-        assert_eq!(":5\r\n".to_string(), value.encode());
+        assert_eq!(":5\r\n".as_bytes(), value.encode());
     }
 
     #[test]
     fn test_encode_error_value() {
         let value = Value::Error("error".to_string());
-        assert_eq!("-error\r\n".to_string(), value.encode());
+        assert_eq!("-error\r\n".as_bytes(), value.encode());
     }
 
     #[test]
     fn test_encode_bulk_string_value() {
         let value = Value::BulkString("bulk_string".to_string());
-        assert_eq!("$11\r\nbulk_string\r\n".to_string(), value.encode());
+        assert_eq!("$11\r\nbulk_string\r\n".as_bytes(), value.encode());
     }
 
     #[test]
-    #[should_panic]
-    fn test_encode_array_value_cause_panic() {
+    fn test_encode_array_value() {
         let v = vec![
             Value::BulkString("set".to_string()),
-            Value::BulkString("country egypt".to_string()),
+            Value::Array(vec![Value::Integer("1".to_string()), Value::Null]),
         ];
         let v = Value::Array(v);
-        v.encode();
+        assert_eq!(
+            "*2\r\n$3\r\nset\r\n*2\r\n:1\r\n$-1\r\n".as_bytes(),
+            v.encode()
+        );
+    }
+
+    #[test]
+    fn test_encode_bulk_string_counts_bytes() {
+        let value = Value::BulkString("بادر".to_string());
+        assert_eq!("$8\r\nبادر\r\n".as_bytes(), value.encode());
+    }
+
+    #[test]
+    fn test_encode_bulk_bytes_value() {
+        let value = Value::BulkBytes(vec![0xff, b'\r', b'\n']);
+        assert_eq!(b"$3\r\n\xff\r\n\r\n".to_vec(), value.encode());
     }
 
     #[test]
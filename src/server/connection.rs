@@ -1,8 +1,9 @@
 use anyhow::Result;
-use bytes::BytesMut;
+use bytes::{Buf, BytesMut};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 
+use crate::resp::parser::{Incomplete, Parser};
 use crate::resp::value::Value;
 
 #[derive(Debug)]
@@ -19,19 +20,101 @@ impl Connection {
         }
     }
 
+    /// Read the next value, `None` once the connection is closed. A malformed value fails
+    /// with a `ProtocolError`, dropping whatever was buffered with it.
     pub async fn read_value(&mut self) -> Result<Option<Value>> {
-        let bytes_read = self.stream.read_buf(&mut self.buffer).await?;
-
-        // Connection closed
-        if bytes_read == 0 {
-            Ok(None)
-        } else {
-            let value = Value::from(&mut self.buffer);
-            Ok(Some(value))
+        loop {
+            // serve pipelined commands already buffered before reading more
+            match Parser::parse_message(&self.buffer) {
+                Ok((value, len)) => {
+                    self.buffer.advance(len);
+                    return Ok(Some(value));
+                }
+                Err(e) if e.is::<Incomplete>() => {}
+                Err(e) => {
+                    self.buffer.clear();
+                    return Err(e);
+                }
+            }
+
+            let bytes_read = self.stream.read_buf(&mut self.buffer).await?;
+
+            // Connection closed
+            if bytes_read == 0 {
+                return Ok(None);
+            }
         }
     }
 
     pub async fn write_value(&mut self, value: Value) {
-        _ = self.stream.write(value.encode().as_bytes()).await;
+        _ = self.stream.write(&value.encode()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::Cache;
+    use crate::resp::parser::ProtocolError;
+    use crate::server::handler::Handler;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    /// Connect a client to a connection of the server side.
+    async fn connect() -> Result<(TcpStream, Connection)> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let client = TcpStream::connect(listener.local_addr()?).await?;
+        let (socket, _) = listener.accept().await?;
+        Ok((client, Connection::new(socket)))
+    }
+
+    #[tokio::test]
+    async fn test_read_split_value() -> Result<()> {
+        let (mut client, mut connection) = connect().await?;
+        let echo = b"*2\r\n$4\r\nECHO\r\n$2\r\nhi\r\n";
+        for offset in 1..echo.len() {
+            client.write_all(&echo[..offset]).await?;
+            let (value, _) = tokio::join!(connection.read_value(), async {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                client.write_all(&echo[offset..]).await
+            });
+            let (name, args) = value?.unwrap().to_command()?;
+            assert_eq!(name, "ECHO");
+            assert_eq!(args, vec![Value::BulkString("hi".to_string())]);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_malformed_value() -> Result<()> {
+        let (mut client, mut connection) = connect().await?;
+        client
+            .write_all(b"*1\r\n$x\r\n*1\r\n$4\r\nPING\r\n")
+            .await?;
+        let error = connection.read_value().await.unwrap_err();
+        assert!(error.is::<ProtocolError>());
+
+        // the rest of the buffer is dropped with the malformed value
+        client.write_all(b"*1\r\n$4\r\nPING\r\n").await?;
+        let (name, _) = connection.read_value().await?.unwrap().to_command()?;
+        assert_eq!(name, "PING");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reply_to_malformed_value() -> Result<()> {
+        let (mut client, connection) = connect().await?;
+        let mut handler = Handler::new(Arc::new(Cache::default()), Some(connection));
+        tokio::spawn(async move { handler.handle_connection().await });
+
+        client.write_all(b"*1\r\n$x\r\n").await?;
+        let mut reply = vec![0; 64];
+        let len = client.read(&mut reply).await?;
+        assert!(reply[..len].starts_with(b"-ERR Protocol error"));
+        client.write_all(b"*1\r\n$4\r\nPING\r\n").await?;
+        let len = client.read(&mut reply).await?;
+        assert_eq!(&reply[..len], b"+PONG\r\n");
+        Ok(())
     }
 }
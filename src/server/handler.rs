@@ -1,36 +1,134 @@
-use crate::cache::expiry::ExpiryFormat;
+mod bitmap;
+mod bloom;
+mod config;
+mod count_min;
+mod cuckoo;
+mod databases;
+mod geo;
+mod graph;
+mod hash;
+mod hyperloglog;
+mod json;
+mod keys;
+mod keyspace;
+mod list;
+mod pubsub;
+mod scripting;
+mod search;
+mod set;
+mod sorted_set;
+mod stream;
+mod suggestion;
+mod tdigest;
+mod tenants;
+#[cfg(test)]
+mod test_helpers;
+mod time_series;
+mod top_k;
+mod transactions;
+mod vector;
+
+use crate::cache::databases::Databases;
+use crate::cache::expiry::{Expiry, ExpiryFormat};
+use crate::cache::list::End;
+use crate::cache::notifications::Notifications;
+use crate::cache::pubsub::{PubSub, Subscriber};
+use crate::cache::scripts::Scripts;
+use crate::cache::tenants::Tenants;
 use crate::cache::Cache;
+use crate::resp::parser::ProtocolError;
 use crate::resp::value::Value;
 use crate::server::connection::Connection;
-use anyhow::Result;
+use anyhow::{Error, Result};
+use pubsub::message_value;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use transactions::{Transaction, Watch};
 
 #[derive(Debug)]
 pub struct Handler {
+    databases: Databases,
+    /// The number of the database selected by the client.
+    db: usize,
+    /// The selected database, resolved again on every request as it may have been swapped.
     client_store: Arc<Cache>,
+    tenants: Tenants,
+    /// The tenant the client is bound to, whose keyspace replaces the databases.
+    tenant: Option<String>,
+    /// Whether the client authenticated with the admin password, to manage the tenants.
+    admin: bool,
+    pubsub: PubSub,
+    /// The keyspace notifications of the server, configured with CONFIG.
+    notifications: Notifications,
+    /// The subscriptions of the client, which is in subscriber mode while it has any.
+    subscriber: Option<Subscriber>,
+    /// Replies written after the response, for commands like SUBSCRIBE replying once per
+    /// argument.
+    replies: Vec<Value>,
+    /// The scripts loaded on the server, shared by every client.
+    scripts: Scripts,
+    /// The commands queued since MULTI, while the client is in a transaction.
+    transaction: Option<Transaction>,
+    /// The keys watched for changes, aborting the next transaction if any changed.
+    watched: Vec<Watch>,
     connection: Option<Connection>,
 }
 
 impl Handler {
-    pub fn new(client_store: Arc<Cache>, connection: Option<Connection>) -> Self {
-        return Self {
+    pub fn new(databases: impl Into<Databases>, connection: Option<Connection>) -> Self {
+        let databases = databases.into();
+        let client_store = databases.get(0).unwrap();
+        let pubsub = PubSub::default();
+        Self {
+            databases,
+            db: 0,
             client_store,
+            tenants: Tenants::default(),
+            tenant: None,
+            admin: false,
+            notifications: Notifications::new(pubsub.clone()),
+            pubsub,
+            subscriber: None,
+            replies: Vec::new(),
+            scripts: Scripts::default(),
+            transaction: None,
+            watched: Vec::new(),
             connection,
-        };
+        }
+    }
+
+    /// Let the client bind to one of `tenants`.
+    pub fn with_tenants(mut self, tenants: Tenants) -> Self {
+        self.tenants = tenants;
+        self
+    }
+
+    /// Let the client configure `notifications`, and subscribe to the channels they are
+    /// published on.
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.pubsub = notifications.pubsub().clone();
+        self.notifications = notifications;
+        self
+    }
+
+    /// Let the client run and load the scripts of `scripts`.
+    pub fn with_scripts(mut self, scripts: Scripts) -> Self {
+        self.scripts = scripts;
+        self
     }
 
     pub async fn handle_connection(&mut self) {
         loop {
-            match self.connection.as_mut().unwrap().read_value().await {
+            match self.read_request().await {
                 Ok(value) => {
                     if let Some(v) = value {
                         match self.handle_request(v).await {
                             Ok(response) => {
-                                self.connection
-                                    .as_mut()
-                                    .unwrap()
-                                    .write_value(response)
-                                    .await;
+                                let connection = self.connection.as_mut().unwrap();
+                                connection.write_value(response).await;
+                                for reply in self.replies.drain(..) {
+                                    connection.write_value(reply).await;
+                                }
                             }
                             Err(e) => {
                                 log::error!("error: {:?}", e);
@@ -41,6 +139,12 @@ impl Handler {
                         break;
                     }
                 }
+                Err(e) if e.is::<ProtocolError>() => {
+                    let connection = self.connection.as_mut().unwrap();
+                    connection
+                        .write_value(Value::Error(format!("ERR {}", e)))
+                        .await;
+                }
                 Err(e) => {
                     log::error!("error: {:?}", e);
                     break;
@@ -49,27 +153,248 @@ impl Handler {
         }
     }
 
+    /// Read the next request, writing the messages published to the client in the meantime
+    /// when it is in subscriber mode.
+    async fn read_request(&mut self) -> Result<Option<Value>> {
+        loop {
+            let connection = self.connection.as_mut().unwrap();
+            let subscriber = match self.subscriber.as_mut() {
+                Some(subscriber) => subscriber,
+                None => return connection.read_value().await,
+            };
+            let message = tokio::select! {
+                value = connection.read_value() => return value,
+                message = subscriber.recv() => message,
+            };
+            match message {
+                Some(message) => connection.write_value(message_value(message)).await,
+                None => return Err(Error::msg("subscriber dropped for falling behind")),
+            }
+        }
+    }
+
     pub async fn handle_request(&mut self, value: Value) -> Result<Value> {
         let (first_arg, args) = value.to_command()?;
-        let command = first_arg.to_ascii_lowercase().as_str().into();
-        let response = match command {
-            Command::PING => Value::SimpleString("PONG".to_string()),
-            Command::ECHO => args.first().unwrap().clone(),
-            Command::Get => self.handle_get(&args).await,
-            Command::SET => self.handle_set(&args).await,
-            Command::DELETE => self.handle_delete(&args).await,
-            Command::EXISTS => self.handle_exists(&args).await,
-            _ => Value::Error(format!("command not implemented: {}", first_arg)),
-        };
-        Ok(response)
+        let command: Command = first_arg.to_ascii_lowercase().as_str().into();
+        self.client_store = self.databases.get(self.db)?;
+        if self.subscriber.is_some() && !command.is_allowed_when_subscribed() {
+            return Ok(Value::Error(format!(
+                "Can't execute '{}': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING are allowed in this context",
+                first_arg.to_ascii_lowercase()
+            )));
+        }
+        if self.transaction.is_some() && !command.is_transaction_control() {
+            return Ok(self.queue(command, first_arg, args));
+        }
+        match command {
+            Command::Multi => Ok(self.handle_multi(&args).await),
+            Command::Exec => self.handle_exec(&args).await,
+            Command::Discard => Ok(self.handle_discard(&args).await),
+            Command::Watch => Ok(self.handle_watch(&args).await),
+            Command::Unwatch => Ok(self.handle_unwatch(&args).await),
+            Command::Eval => Ok(self.handle_eval(&args, false).await),
+            Command::EvalSha => Ok(self.handle_eval(&args, true).await),
+            // blocking commands only hold transactions back while trying to serve themselves
+            _ if command.is_blocking() => Ok(self.dispatch(command, &first_arg, &args).await),
+            _ => {
+                let store = self.client_store.clone();
+                let _gate = store.enter().await;
+                Ok(self.dispatch(command, &first_arg, &args).await)
+            }
+        }
+    }
+
+    /// Run `command`, named `name` by the client, with `args`.
+    async fn dispatch(&mut self, command: Command, name: &str, args: &[Value]) -> Value {
+        match command {
+            Command::Ping if self.subscriber.is_some() => Value::Array(vec![
+                Value::BulkString("pong".to_string()),
+                Value::BulkString(String::new()),
+            ]),
+            Command::Ping => Value::SimpleString("PONG".to_string()),
+            Command::Echo => args.first().unwrap().clone(),
+            Command::Get => self.handle_get(args).await,
+            Command::Set => self.handle_set(args).await,
+            Command::Delete => self.handle_delete(args).await,
+            Command::Exists => self.handle_exists(args).await,
+            Command::Expire => self.handle_expire(args, false).await,
+            Command::PExpire => self.handle_expire(args, true).await,
+            Command::Ttl => self.handle_ttl(args, false).await,
+            Command::PTtl => self.handle_ttl(args, true).await,
+            Command::PfAdd => self.handle_pfadd(args).await,
+            Command::PfCount => self.handle_pfcount(args).await,
+            Command::PfMerge => self.handle_pfmerge(args).await,
+            Command::BfReserve => self.handle_bf_reserve(args).await,
+            Command::BfAdd => self.handle_bf_add(args, false).await,
+            Command::BfMAdd => self.handle_bf_add(args, true).await,
+            Command::BfExists => self.handle_bf_exists(args, false).await,
+            Command::BfMExists => self.handle_bf_exists(args, true).await,
+            Command::BfInfo => self.handle_bf_info(args).await,
+            Command::CfReserve => self.handle_cf_reserve(args).await,
+            Command::CfAdd => self.handle_cf_add(args, false).await,
+            Command::CfAddNx => self.handle_cf_add(args, true).await,
+            Command::CfExists => self.handle_cf_exists(args, false).await,
+            Command::CfMExists => self.handle_cf_exists(args, true).await,
+            Command::CfCount => self.handle_cf_count(args).await,
+            Command::CfDel => self.handle_cf_del(args).await,
+            Command::CmsInitByDim => self.handle_cms_init(args, false).await,
+            Command::CmsInitByProb => self.handle_cms_init(args, true).await,
+            Command::CmsIncrBy => self.handle_cms_incrby(args).await,
+            Command::CmsQuery => self.handle_cms_query(args).await,
+            Command::CmsMerge => self.handle_cms_merge(args).await,
+            Command::CmsInfo => self.handle_cms_info(args).await,
+            Command::TopKReserve => self.handle_topk_reserve(args).await,
+            Command::TopKAdd => self.handle_topk_add(args).await,
+            Command::TopKIncrBy => self.handle_topk_incrby(args).await,
+            Command::TopKQuery => self.handle_topk_query(args).await,
+            Command::TopKList => self.handle_topk_list(args).await,
+            Command::TopKMerge => self.handle_topk_merge(args).await,
+            Command::TDigestCreate => self.handle_tdigest_create(args).await,
+            Command::TDigestAdd => self.handle_tdigest_add(args).await,
+            Command::TDigestQuantile => self.handle_tdigest_estimate(args, false).await,
+            Command::TDigestCdf => self.handle_tdigest_estimate(args, true).await,
+            Command::TDigestMin => self.handle_tdigest_bound(args, true).await,
+            Command::TDigestMax => self.handle_tdigest_bound(args, false).await,
+            Command::TDigestMerge => self.handle_tdigest_merge(args).await,
+            Command::JsonSet => self.handle_json_set(args).await,
+            Command::JsonGet => self.handle_json_get(args).await,
+            Command::JsonDel => self.handle_json_del(args).await,
+            Command::JsonNumIncrBy => self.handle_json_numincrby(args).await,
+            Command::JsonArrAppend => self.handle_json_arrappend(args).await,
+            Command::JsonType => self.handle_json_type(args).await,
+            Command::TsCreate => self.handle_ts_create(args).await,
+            Command::TsAdd => self.handle_ts_add(args).await,
+            Command::TsRange => self.handle_ts_range(args).await,
+            Command::TsMRange => self.handle_ts_mrange(args).await,
+            Command::TsCreateRule => self.handle_ts_createrule(args).await,
+            Command::TsDeleteRule => self.handle_ts_deleterule(args).await,
+            Command::VecCreate => self.handle_vec_create(args).await,
+            Command::VecSet => self.handle_vec_set(args).await,
+            Command::VecGet => self.handle_vec_get(args).await,
+            Command::VecSearch => self.handle_vec_search(args).await,
+            Command::HSet => self.handle_hset(args).await,
+            Command::HGet => self.handle_hmget(args, true).await,
+            Command::HMGet => self.handle_hmget(args, false).await,
+            Command::HDel => self.handle_hdel(args).await,
+            Command::HGetAll => self.handle_hgetall(args).await,
+            Command::HLen => self.handle_hlen(args).await,
+            Command::HExpire => self.handle_hexpire(args, false).await,
+            Command::HPExpire => self.handle_hexpire(args, true).await,
+            Command::HTtl => self.handle_httl(args, false).await,
+            Command::HPTtl => self.handle_httl(args, true).await,
+            Command::HPersist => self.handle_hpersist(args).await,
+            Command::SAdd => self.handle_sadd(args).await,
+            Command::SRem => self.handle_srem(args).await,
+            Command::SMembers => self.handle_smembers(args).await,
+            Command::SIsMember => self.handle_sismember(args).await,
+            Command::SCard => self.handle_scard(args).await,
+            Command::SExpire => self.handle_sexpire(args, false).await,
+            Command::SPExpire => self.handle_sexpire(args, true).await,
+            Command::STtl => self.handle_sttl(args, false).await,
+            Command::SPTtl => self.handle_sttl(args, true).await,
+            Command::SPersist => self.handle_spersist(args).await,
+            Command::FtCreate => self.handle_ft_create(args).await,
+            Command::FtSearch => self.handle_ft_search(args).await,
+            Command::FtDropIndex => self.handle_ft_dropindex(args).await,
+            Command::FtSugAdd => self.handle_ft_sugadd(args).await,
+            Command::FtSugGet => self.handle_ft_sugget(args).await,
+            Command::FtSugDel => self.handle_ft_sugdel(args).await,
+            Command::FtSugLen => self.handle_ft_suglen(args).await,
+            Command::GraphAddNode => self.handle_graph_addnode(args).await,
+            Command::GraphAddEdge => self.handle_graph_addedge(args).await,
+            Command::GraphDelNode => self.handle_graph_delnode(args).await,
+            Command::GraphDelEdge => self.handle_graph_deledge(args).await,
+            Command::GraphGetNode => self.handle_graph_getnode(args).await,
+            Command::GraphNeighbours => self.handle_graph_neighbours(args).await,
+            Command::GraphTraverse => self.handle_graph_traverse(args).await,
+            Command::GraphShortestPath => self.handle_graph_shortestpath(args).await,
+            Command::Rename => self.handle_rename(args, true).await,
+            Command::RenameNx => self.handle_rename(args, false).await,
+            Command::Copy => self.handle_copy(args).await,
+            Command::Move => self.handle_move(args).await,
+            Command::Touch => self.handle_touch(args).await,
+            Command::Unlink => self.handle_unlink(args).await,
+            Command::Scan => self.handle_scan(args).await,
+            Command::Keys => self.handle_keys(args).await,
+            Command::Type => self.handle_type(args).await,
+            Command::RandomKey => self.handle_randomkey(args).await,
+            Command::KRange => self.handle_krange(args).await,
+            Command::PrefixCount => self.handle_prefixcount(args).await,
+            Command::PrefixDel => self.handle_prefixdel(args).await,
+            Command::PrefixExpire => self.handle_prefixexpire(args, false).await,
+            Command::PrefixPExpire => self.handle_prefixexpire(args, true).await,
+            Command::Select => self.handle_select(args).await,
+            Command::SwapDb => self.handle_swapdb(args).await,
+            Command::FlushDb => self.handle_flushdb(args).await,
+            Command::FlushAll => self.handle_flushall(args).await,
+            Command::DbSize => self.handle_dbsize(args).await,
+            Command::Auth => self.handle_auth(args).await,
+            Command::Tenant => self.handle_tenant(args).await,
+            Command::Subscribe => self.handle_subscribe(args, false).await,
+            Command::PSubscribe => self.handle_subscribe(args, true).await,
+            Command::Unsubscribe => self.handle_unsubscribe(args, false).await,
+            Command::PUnsubscribe => self.handle_unsubscribe(args, true).await,
+            Command::Publish => self.handle_publish(args).await,
+            Command::PubSub => self.handle_pubsub(args).await,
+            Command::Config => self.handle_config(args).await,
+            Command::Script => self.handle_script(args).await,
+            Command::SetBit => self.handle_setbit(args).await,
+            Command::GetBit => self.handle_getbit(args).await,
+            Command::BitCount => self.handle_bitcount(args).await,
+            Command::BitPos => self.handle_bitpos(args).await,
+            Command::BitOp => self.handle_bitop(args).await,
+            Command::BitField => self.handle_bitfield(args, false).await,
+            Command::BitFieldRo => self.handle_bitfield(args, true).await,
+            Command::LPush => self.handle_push(args, End::Left, true).await,
+            Command::RPush => self.handle_push(args, End::Right, true).await,
+            Command::LPushX => self.handle_push(args, End::Left, false).await,
+            Command::RPushX => self.handle_push(args, End::Right, false).await,
+            Command::LPop => self.handle_pop(args, End::Left).await,
+            Command::RPop => self.handle_pop(args, End::Right).await,
+            Command::LLen => self.handle_llen(args).await,
+            Command::LRange => self.handle_lrange(args).await,
+            Command::LMove => self.handle_lmove(args).await,
+            Command::BLPop => self.handle_blocking_pop(args, End::Left).await,
+            Command::BRPop => self.handle_blocking_pop(args, End::Right).await,
+            Command::BLMove => self.handle_blmove(args).await,
+            Command::ZAdd => self.handle_zadd(args).await,
+            Command::ZCard => self.handle_zcard(args).await,
+            Command::ZScore => self.handle_zscore(args).await,
+            Command::ZRank => self.handle_zrank(args).await,
+            Command::ZRange => self.handle_zrange(args).await,
+            Command::ZRem => self.handle_zrem(args).await,
+            Command::ZRemRangeByScore => self.handle_zremrangebyscore(args).await,
+            Command::ZPopMin => self.handle_zpop(args, false).await,
+            Command::ZPopMax => self.handle_zpop(args, true).await,
+            Command::BZPopMin => self.handle_bzpop(args, false).await,
+            Command::BZPopMax => self.handle_bzpop(args, true).await,
+            Command::ZUnionStore => self.handle_zstore(args, false).await,
+            Command::ZInterStore => self.handle_zstore(args, true).await,
+            Command::GeoAdd => self.handle_geoadd(args).await,
+            Command::GeoPos => self.handle_geopos(args).await,
+            Command::GeoDist => self.handle_geodist(args).await,
+            Command::GeoSearch => self.handle_geosearch(args).await,
+            Command::XAdd => self.handle_xadd(args).await,
+            Command::XLen => self.handle_xlen(args).await,
+            Command::XRange => self.handle_xrange(args, false).await,
+            Command::XRevRange => self.handle_xrange(args, true).await,
+            Command::XTrim => self.handle_xtrim(args).await,
+            Command::XRead => self.handle_xread(args).await,
+            Command::XGroup => self.handle_xgroup(args).await,
+            Command::XReadGroup => self.handle_xreadgroup(args).await,
+            Command::XAck => self.handle_xack(args).await,
+            Command::XPending => self.handle_xpending(args).await,
+            Command::XClaim => self.handle_xclaim(args).await,
+            _ => Value::Error(format!("command not implemented: {}", name)),
+        }
     }
 
     async fn handle_get(&mut self, args: &[Value]) -> Value {
-        if let Some(Value::BulkString(key)) = args.get(0) {
-            if let Some(value) = self.client_store.get(key.clone()).await {
-                Value::SimpleString(value)
-            } else {
-                Value::Null
+        if let Some(Value::BulkString(key)) = args.first() {
+            match self.client_store.get_bytes(key.clone()).await {
+                Ok(Some(value)) => Value::from(value),
+                Ok(None) => Value::Null,
+                Err(e) => Value::Error(e.to_string()),
             }
         } else {
             Value::Error("GET requires one argument".to_string())
@@ -77,8 +402,8 @@ impl Handler {
     }
 
     async fn handle_set(&mut self, args: &[Value]) -> Value {
-        if let (Some(Value::BulkString(key)), Some(Value::BulkString(value))) =
-            (args.get(0), args.get(1))
+        if let (Some(Value::BulkString(key)), Some(value)) =
+            (args.first(), args.get(1).and_then(bulk_bytes))
         {
             if let (Some(Value::BulkString(expiry_format)), Some(Value::BulkString(amount))) =
                 (args.get(2), args.get(3))
@@ -91,8 +416,10 @@ impl Handler {
                     self.handle_set_with_expiry(key, value, amount, None).await
                 }
             } else {
-                self.client_store.set(key.clone(), value.clone()).await;
-                Value::SimpleString("OK".to_string())
+                match self.client_store.set(key.clone(), value).await {
+                    Ok(()) => Value::SimpleString("OK".to_string()),
+                    Err(e) => Value::Error(e.to_string()),
+                }
             }
         } else {
             Value::Error("SET requires two or four arguments".to_string())
@@ -102,31 +429,34 @@ impl Handler {
     async fn handle_set_with_expiry(
         &self,
         key: &str,
-        value: &str,
+        value: Vec<u8>,
         amount: &str,
         expiry_format: Option<&String>,
     ) -> Value {
         if let Ok(amount) = amount.parse::<u64>() {
-            match expiry_format {
+            let set = match expiry_format {
                 Some(e) => {
                     self.client_store
-                        .set_with_expiry(key.to_owned(), value.to_owned(), (amount, e))
-                        .await;
+                        .set_with_expiry(key.to_owned(), value, (amount, e))
+                        .await
                 }
                 _ => {
                     self.client_store
-                        .set_with_expiry(key.to_owned(), value.to_owned(), amount)
-                        .await;
+                        .set_with_expiry(key.to_owned(), value, amount)
+                        .await
                 }
+            };
+            match set {
+                Ok(()) => Value::SimpleString("OK".to_string()),
+                Err(e) => Value::Error(e.to_string()),
             }
-            Value::SimpleString("OK".to_string())
         } else {
             Value::Error("Unsupported expiry format".to_string())
         }
     }
 
     async fn handle_delete(&self, args: &[Value]) -> Value {
-        if let Some(Value::BulkString(key)) = args.get(0) {
+        if let Some(Value::BulkString(key)) = args.first() {
             match self.client_store.remove(key.clone()).await {
                 Ok(_) => Value::SimpleString("OK".to_string()),
                 Err(e) => Value::Error(format!("Error while deleting: {:?}", e)),
@@ -137,7 +467,7 @@ impl Handler {
     }
 
     async fn handle_exists(&self, args: &[Value]) -> Value {
-        if let Some(Value::BulkString(key)) = args.get(0) {
+        if let Some(Value::BulkString(key)) = args.first() {
             match self.client_store.exists(key.clone()).await {
                 true => Value::SimpleString("true".to_string()),
                 false => Value::SimpleString("false".to_string()),
@@ -146,28 +476,592 @@ impl Handler {
             Value::Error("EXISTS requires one argument".to_string())
         }
     }
+
+    async fn handle_expire(&self, args: &[Value], millis: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => return Value::Error("EXPIRE and PEXPIRE require a key and a timeout".to_string()),
+        };
+        let expiry = match parse_ttl(&args[1], millis).and_then(Expiry::after) {
+            Ok(expiry) => expiry,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        integer(self.client_store.expire(args[0].clone(), expiry).await as u8)
+    }
+
+    async fn handle_ttl(&self, args: &[Value], millis: bool) -> Value {
+        if let Some(Value::BulkString(key)) = args.first() {
+            match self.client_store.ttl(key.clone()).await {
+                Some(Some(remaining)) if millis => integer(remaining.as_millis()),
+                Some(Some(remaining)) => integer(remaining.as_secs_f64().round() as u64),
+                Some(None) => integer(-1),
+                None => integer(-2),
+            }
+        } else {
+            Value::Error("TTL and PTTL require one argument".to_string())
+        }
+    }
+}
+
+/// Collect the arguments of a command as strings, or `None` if any isn't a bulk string.
+fn bulk_strings(args: &[Value]) -> Option<Vec<String>> {
+    args.iter()
+        .map(|arg| match arg {
+            Value::BulkString(s) => Some(s.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Retrieve the bytes of a bulk string argument, UTF-8 or not, like the value of a string.
+fn bulk_bytes(arg: &Value) -> Option<Vec<u8>> {
+    match arg {
+        Value::BulkString(s) => Some(s.clone().into_bytes()),
+        Value::BulkBytes(bytes) => Some(bytes.clone()),
+        _ => None,
+    }
+}
+
+fn integer<N: ToString>(n: N) -> Value {
+    Value::Integer(n.to_string())
+}
+
+fn syntax_error() -> Error {
+    Error::msg("syntax error")
+}
+
+fn parse_count(s: &str) -> Result<usize> {
+    s.parse::<usize>()
+        .map_err(|_| Error::msg("value is out of range, must be positive"))
+}
+
+fn parse_integer(s: &str) -> Result<i64> {
+    s.parse::<i64>()
+        .map_err(|_| Error::msg("value is not an integer or out of range"))
+}
+
+/// Parse `numkeys key [key ...]`, returning the keys and the arguments following them.
+fn parse_keys(args: &[String]) -> Result<(Vec<String>, &[String])> {
+    let count = parse_integer(args.first().ok_or_else(syntax_error)?)?;
+    if count <= 0 {
+        return Err(Error::msg("at least 1 input key is needed"));
+    }
+    let count = count as usize;
+    if args.len() < count + 1 {
+        return Err(syntax_error());
+    }
+    Ok((args[1..=count].to_vec(), &args[count + 1..]))
+}
+
+/// Parse `keyword count member [member ...]`, as in `FIELDS 2 name age`.
+fn parse_members(args: &[String], keyword: &str) -> Result<Vec<String>> {
+    match args {
+        [option, count, members @ ..] if option.eq_ignore_ascii_case(keyword) => {
+            let count = parse_count(count)?;
+            if count == 0 || count != members.len() {
+                return Err(Error::msg(format!(
+                    "the number of {} must match the count",
+                    keyword.to_ascii_lowercase()
+                )));
+            }
+            Ok(members.to_vec())
+        }
+        _ => Err(syntax_error()),
+    }
+}
+
+/// Parse a time to live in seconds, or milliseconds if `millis` is set, negative ones having
+/// already passed.
+fn parse_ttl(s: &str, millis: bool) -> Result<Duration> {
+    let amount = parse_integer(s)?.max(0) as u64;
+    Ok(match millis {
+        true => Duration::from_millis(amount),
+        false => Duration::from_secs(amount),
+    })
+}
+
+/// Parse a timeout in seconds, or milliseconds if `millis` is set, into the instant it ends.
+fn parse_deadline(s: &str, millis: bool) -> Result<Instant> {
+    let timeout = parse_ttl(s, millis)?;
+    Instant::now()
+        .checked_add(timeout)
+        .ok_or_else(|| Error::msg("invalid expire time"))
+}
+
+/// Reply to a member expiry with -2 for missing members, 2 for members removed right away as
+/// their expiry has passed, and 1 otherwise.
+fn expire_reply(expired: Vec<Option<bool>>) -> Value {
+    Value::Array(
+        expired
+            .into_iter()
+            .map(|expired| match expired {
+                Some(true) => integer(1),
+                Some(false) => integer(2),
+                None => integer(-2),
+            })
+            .collect(),
+    )
+}
+
+/// Reply with the time left before members expire, like TTL and PTTL do for keys.
+fn ttl_reply(remaining: Vec<Option<Option<Duration>>>, millis: bool) -> Value {
+    Value::Array(
+        remaining
+            .into_iter()
+            .map(|remaining| match remaining {
+                Some(Some(remaining)) if millis => integer(remaining.as_millis()),
+                Some(Some(remaining)) => integer(remaining.as_secs_f64().round() as u64),
+                Some(None) => integer(-1),
+                None => integer(-2),
+            })
+            .collect(),
+    )
+}
+
+/// Reply to dropping member expiries with -2 for missing members, -1 for members without an
+/// expiry, and 1 otherwise.
+fn persist_reply(persisted: Vec<Option<bool>>) -> Value {
+    Value::Array(
+        persisted
+            .into_iter()
+            .map(|persisted| match persisted {
+                Some(true) => integer(1),
+                Some(false) => integer(-1),
+                None => integer(-2),
+            })
+            .collect(),
+    )
+}
+
+fn parse_float(s: &str) -> Result<f64> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+        .ok_or_else(|| Error::msg("value is not a valid float"))
+}
+
+/// Parse the timeout of a blocking command, in seconds. Zero blocks forever.
+fn parse_timeout(s: &str) -> Result<Option<Duration>> {
+    let timeout = s
+        .parse::<f64>()
+        .ok()
+        .filter(|timeout| timeout.is_finite())
+        .ok_or_else(|| Error::msg("timeout is not a float or out of range"))?;
+    if timeout < 0.0 {
+        return Err(Error::msg("timeout is negative"));
+    }
+    match Duration::try_from_secs_f64(timeout) {
+        Ok(timeout) => Ok((!timeout.is_zero()).then_some(timeout)),
+        Err(_) => Err(Error::msg("timeout is out of range")),
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Command {
-    PING,
-    ECHO,
+    Ping,
+    Echo,
     Get,
-    SET,
-    DELETE,
-    EXISTS,
+    Set,
+    Delete,
+    Exists,
+    Expire,
+    PExpire,
+    Ttl,
+    PTtl,
+    PfAdd,
+    PfCount,
+    PfMerge,
+    BfReserve,
+    BfAdd,
+    BfMAdd,
+    BfExists,
+    BfMExists,
+    BfInfo,
+    CfReserve,
+    CfAdd,
+    CfAddNx,
+    CfExists,
+    CfMExists,
+    CfCount,
+    CfDel,
+    CmsInitByDim,
+    CmsInitByProb,
+    CmsIncrBy,
+    CmsQuery,
+    CmsMerge,
+    CmsInfo,
+    TopKReserve,
+    TopKAdd,
+    TopKIncrBy,
+    TopKQuery,
+    TopKList,
+    TopKMerge,
+    TDigestCreate,
+    TDigestAdd,
+    TDigestQuantile,
+    TDigestCdf,
+    TDigestMin,
+    TDigestMax,
+    TDigestMerge,
+    JsonSet,
+    JsonGet,
+    JsonDel,
+    JsonNumIncrBy,
+    JsonArrAppend,
+    JsonType,
+    TsCreate,
+    TsAdd,
+    TsRange,
+    TsMRange,
+    TsCreateRule,
+    TsDeleteRule,
+    VecCreate,
+    VecSet,
+    VecGet,
+    VecSearch,
+    HSet,
+    HGet,
+    HMGet,
+    HDel,
+    HGetAll,
+    HLen,
+    HExpire,
+    HPExpire,
+    HTtl,
+    HPTtl,
+    HPersist,
+    SAdd,
+    SRem,
+    SMembers,
+    SIsMember,
+    SCard,
+    SExpire,
+    SPExpire,
+    STtl,
+    SPTtl,
+    SPersist,
+    FtCreate,
+    FtSearch,
+    FtDropIndex,
+    FtSugAdd,
+    FtSugGet,
+    FtSugDel,
+    FtSugLen,
+    GraphAddNode,
+    GraphAddEdge,
+    GraphDelNode,
+    GraphDelEdge,
+    GraphGetNode,
+    GraphNeighbours,
+    GraphTraverse,
+    GraphShortestPath,
+    Rename,
+    RenameNx,
+    Copy,
+    Move,
+    Touch,
+    Unlink,
+    Scan,
+    Keys,
+    Type,
+    RandomKey,
+    KRange,
+    PrefixCount,
+    PrefixDel,
+    PrefixExpire,
+    PrefixPExpire,
+    Select,
+    SwapDb,
+    FlushDb,
+    FlushAll,
+    DbSize,
+    Auth,
+    Tenant,
+    Subscribe,
+    PSubscribe,
+    Unsubscribe,
+    PUnsubscribe,
+    Publish,
+    PubSub,
+    Config,
+    Multi,
+    Exec,
+    Discard,
+    Watch,
+    Unwatch,
+    Eval,
+    EvalSha,
+    Script,
+    SetBit,
+    GetBit,
+    BitCount,
+    BitPos,
+    BitOp,
+    BitField,
+    BitFieldRo,
+    LPush,
+    RPush,
+    LPushX,
+    RPushX,
+    LPop,
+    RPop,
+    LLen,
+    LRange,
+    LMove,
+    BLPop,
+    BRPop,
+    BLMove,
+    ZAdd,
+    ZCard,
+    ZScore,
+    ZRank,
+    ZRange,
+    ZRem,
+    ZRemRangeByScore,
+    ZPopMin,
+    ZPopMax,
+    BZPopMin,
+    BZPopMax,
+    ZUnionStore,
+    ZInterStore,
+    GeoAdd,
+    GeoPos,
+    GeoDist,
+    GeoSearch,
+    XAdd,
+    XLen,
+    XRange,
+    XRevRange,
+    XTrim,
+    XRead,
+    XGroup,
+    XReadGroup,
+    XAck,
+    XPending,
+    XClaim,
     Uninitialized,
 }
 
+impl Command {
+    /// Retrieve whether a client in subscriber mode may run this command.
+    fn is_allowed_when_subscribed(&self) -> bool {
+        matches!(
+            self,
+            Command::Ping
+                | Command::Subscribe
+                | Command::PSubscribe
+                | Command::Unsubscribe
+                | Command::PUnsubscribe
+        )
+    }
+
+    /// Retrieve whether this command controls transactions, and so runs right away rather
+    /// than being queued.
+    fn is_transaction_control(&self) -> bool {
+        matches!(
+            self,
+            Command::Multi | Command::Exec | Command::Discard | Command::Watch | Command::Unwatch
+        )
+    }
+
+    /// Retrieve whether this command may block the client, which can't happen in a
+    /// transaction.
+    fn is_blocking(&self) -> bool {
+        matches!(
+            self,
+            Command::BLPop
+                | Command::BRPop
+                | Command::BLMove
+                | Command::BZPopMin
+                | Command::BZPopMax
+        )
+    }
+}
+
 impl From<&str> for Command {
     fn from(s: &str) -> Self {
         match s {
-            "ping" => Command::PING,
-            "echo" => Command::ECHO,
+            "ping" => Command::Ping,
+            "echo" => Command::Echo,
             "get" => Command::Get,
-            "set" => Command::SET,
-            "del" => Command::DELETE,
-            "exists" => Command::EXISTS,
+            "set" => Command::Set,
+            "del" => Command::Delete,
+            "exists" => Command::Exists,
+            "expire" => Command::Expire,
+            "pexpire" => Command::PExpire,
+            "ttl" => Command::Ttl,
+            "pttl" => Command::PTtl,
+            "pfadd" => Command::PfAdd,
+            "pfcount" => Command::PfCount,
+            "pfmerge" => Command::PfMerge,
+            "bf.reserve" => Command::BfReserve,
+            "bf.add" => Command::BfAdd,
+            "bf.madd" => Command::BfMAdd,
+            "bf.exists" => Command::BfExists,
+            "bf.mexists" => Command::BfMExists,
+            "bf.info" => Command::BfInfo,
+            "cf.reserve" => Command::CfReserve,
+            "cf.add" => Command::CfAdd,
+            "cf.addnx" => Command::CfAddNx,
+            "cf.exists" => Command::CfExists,
+            "cf.mexists" => Command::CfMExists,
+            "cf.count" => Command::CfCount,
+            "cf.del" => Command::CfDel,
+            "cms.initbydim" => Command::CmsInitByDim,
+            "cms.initbyprob" => Command::CmsInitByProb,
+            "cms.incrby" => Command::CmsIncrBy,
+            "cms.query" => Command::CmsQuery,
+            "cms.merge" => Command::CmsMerge,
+            "cms.info" => Command::CmsInfo,
+            "topk.reserve" => Command::TopKReserve,
+            "topk.add" => Command::TopKAdd,
+            "topk.incrby" => Command::TopKIncrBy,
+            "topk.query" => Command::TopKQuery,
+            "topk.list" => Command::TopKList,
+            "topk.merge" => Command::TopKMerge,
+            "tdigest.create" => Command::TDigestCreate,
+            "tdigest.add" => Command::TDigestAdd,
+            "tdigest.quantile" => Command::TDigestQuantile,
+            "tdigest.cdf" => Command::TDigestCdf,
+            "tdigest.min" => Command::TDigestMin,
+            "tdigest.max" => Command::TDigestMax,
+            "tdigest.merge" => Command::TDigestMerge,
+            "json.set" => Command::JsonSet,
+            "json.get" => Command::JsonGet,
+            "json.del" => Command::JsonDel,
+            "json.numincrby" => Command::JsonNumIncrBy,
+            "json.arrappend" => Command::JsonArrAppend,
+            "json.type" => Command::JsonType,
+            "ts.create" => Command::TsCreate,
+            "ts.add" => Command::TsAdd,
+            "ts.range" => Command::TsRange,
+            "ts.mrange" => Command::TsMRange,
+            "ts.createrule" => Command::TsCreateRule,
+            "ts.deleterule" => Command::TsDeleteRule,
+            "vec.create" => Command::VecCreate,
+            "vec.set" => Command::VecSet,
+            "vec.get" => Command::VecGet,
+            "vec.search" => Command::VecSearch,
+            "hset" => Command::HSet,
+            "hget" => Command::HGet,
+            "hmget" => Command::HMGet,
+            "hdel" => Command::HDel,
+            "hgetall" => Command::HGetAll,
+            "hlen" => Command::HLen,
+            "hexpire" => Command::HExpire,
+            "hpexpire" => Command::HPExpire,
+            "httl" => Command::HTtl,
+            "hpttl" => Command::HPTtl,
+            "hpersist" => Command::HPersist,
+            "sadd" => Command::SAdd,
+            "srem" => Command::SRem,
+            "smembers" => Command::SMembers,
+            "sismember" => Command::SIsMember,
+            "scard" => Command::SCard,
+            "sexpire" => Command::SExpire,
+            "spexpire" => Command::SPExpire,
+            "sttl" => Command::STtl,
+            "spttl" => Command::SPTtl,
+            "spersist" => Command::SPersist,
+            "ft.create" => Command::FtCreate,
+            "ft.search" => Command::FtSearch,
+            "ft.dropindex" => Command::FtDropIndex,
+            "ft.sugadd" => Command::FtSugAdd,
+            "ft.sugget" => Command::FtSugGet,
+            "ft.sugdel" => Command::FtSugDel,
+            "ft.suglen" => Command::FtSugLen,
+            "graph.addnode" => Command::GraphAddNode,
+            "graph.addedge" => Command::GraphAddEdge,
+            "graph.delnode" => Command::GraphDelNode,
+            "graph.deledge" => Command::GraphDelEdge,
+            "graph.getnode" => Command::GraphGetNode,
+            "graph.neighbours" => Command::GraphNeighbours,
+            "graph.traverse" => Command::GraphTraverse,
+            "graph.shortestpath" => Command::GraphShortestPath,
+            "rename" => Command::Rename,
+            "renamenx" => Command::RenameNx,
+            "copy" => Command::Copy,
+            "move" => Command::Move,
+            "touch" => Command::Touch,
+            "unlink" => Command::Unlink,
+            "scan" => Command::Scan,
+            "keys" => Command::Keys,
+            "type" => Command::Type,
+            "randomkey" => Command::RandomKey,
+            "krange" => Command::KRange,
+            "prefixcount" => Command::PrefixCount,
+            "prefixdel" => Command::PrefixDel,
+            "prefixexpire" => Command::PrefixExpire,
+            "prefixpexpire" => Command::PrefixPExpire,
+            "select" => Command::Select,
+            "swapdb" => Command::SwapDb,
+            "flushdb" => Command::FlushDb,
+            "flushall" => Command::FlushAll,
+            "dbsize" => Command::DbSize,
+            "auth" => Command::Auth,
+            "tenant" => Command::Tenant,
+            "subscribe" => Command::Subscribe,
+            "psubscribe" => Command::PSubscribe,
+            "unsubscribe" => Command::Unsubscribe,
+            "punsubscribe" => Command::PUnsubscribe,
+            "publish" => Command::Publish,
+            "pubsub" => Command::PubSub,
+            "config" => Command::Config,
+            "multi" => Command::Multi,
+            "exec" => Command::Exec,
+            "discard" => Command::Discard,
+            "watch" => Command::Watch,
+            "unwatch" => Command::Unwatch,
+            "eval" => Command::Eval,
+            "evalsha" => Command::EvalSha,
+            "script" => Command::Script,
+            "setbit" => Command::SetBit,
+            "getbit" => Command::GetBit,
+            "bitcount" => Command::BitCount,
+            "bitpos" => Command::BitPos,
+            "bitop" => Command::BitOp,
+            "bitfield" => Command::BitField,
+            "bitfield_ro" => Command::BitFieldRo,
+            "lpush" => Command::LPush,
+            "rpush" => Command::RPush,
+            "lpushx" => Command::LPushX,
+            "rpushx" => Command::RPushX,
+            "lpop" => Command::LPop,
+            "rpop" => Command::RPop,
+            "llen" => Command::LLen,
+            "lrange" => Command::LRange,
+            "lmove" => Command::LMove,
+            "blpop" => Command::BLPop,
+            "brpop" => Command::BRPop,
+            "blmove" => Command::BLMove,
+            "zadd" => Command::ZAdd,
+            "zcard" => Command::ZCard,
+            "zscore" => Command::ZScore,
+            "zrank" => Command::ZRank,
+            "zrange" => Command::ZRange,
+            "zrem" => Command::ZRem,
+            "zremrangebyscore" => Command::ZRemRangeByScore,
+            "zpopmin" => Command::ZPopMin,
+            "zpopmax" => Command::ZPopMax,
+            "bzpopmin" => Command::BZPopMin,
+            "bzpopmax" => Command::BZPopMax,
+            "zunionstore" => Command::ZUnionStore,
+            "zinterstore" => Command::ZInterStore,
+            "geoadd" => Command::GeoAdd,
+            "geopos" => Command::GeoPos,
+            "geodist" => Command::GeoDist,
+            "geosearch" => Command::GeoSearch,
+            "xadd" => Command::XAdd,
+            "xlen" => Command::XLen,
+            "xrange" => Command::XRange,
+            "xrevrange" => Command::XRevRange,
+            "xtrim" => Command::XTrim,
+            "xread" => Command::XRead,
+            "xgroup" => Command::XGroup,
+            "xreadgroup" => Command::XReadGroup,
+            "xack" => Command::XAck,
+            "xpending" => Command::XPending,
+            "xclaim" => Command::XClaim,
             _ => Command::Uninitialized,
         }
     }
@@ -178,6 +1072,7 @@ mod tests {
     use super::*;
     use crate::cache::Cache;
     use crate::resp::value::Value::Null;
+    use crate::server::handler::test_helpers::command;
 
     #[tokio::test]
     async fn test_ping_command() -> Result<()> {
@@ -213,9 +1108,9 @@ mod tests {
         let response = handler.handle_request(value.clone()).await?;
         assert_eq!(response, Value::Null);
 
-        cache.set("key".to_string(), "value".to_string()).await;
+        cache.set("key".to_string(), "value".to_string()).await?;
         let response = handler.handle_request(value.clone()).await?;
-        assert_eq!(response, Value::SimpleString("value".to_string()));
+        assert_eq!(response, Value::BulkString("value".to_string()));
 
         Ok(())
     }
@@ -279,10 +1174,54 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_expire_ttl_command() -> Result<()> {
+        let cache = Arc::new(Cache::default());
+        cache.set("key".to_string(), "value".to_string()).await?;
+        let mut handler = Handler::new(cache.clone(), None);
+
+        let ttl = Value::Array(vec![
+            Value::BulkString("TTL".to_string()),
+            Value::BulkString("key".to_string()),
+        ]);
+        let response = handler.handle_request(ttl.clone()).await?;
+        assert_eq!(response, Value::Integer("-1".to_string()));
+
+        let value = Value::Array(vec![
+            Value::BulkString("EXPIRE".to_string()),
+            Value::BulkString("key".to_string()),
+            Value::BulkString("100".to_string()),
+        ]);
+        let response = handler.handle_request(value.clone()).await?;
+        assert_eq!(response, Value::Integer("1".to_string()));
+        let response = handler.handle_request(ttl.clone()).await?;
+        assert_eq!(response, Value::Integer("100".to_string()));
+
+        let invalid = Value::Error("invalid expire time".to_string());
+        for request in [
+            command(&["EXPIRE", "key", "9223372036854775807"]),
+            command(&["SET", "key", "value", "ex", "18446744073709551615"]),
+        ] {
+            let response = handler.handle_request(request).await?;
+            assert_eq!(response, invalid);
+        }
+        let response = handler.handle_request(ttl.clone()).await?;
+        assert_eq!(response, Value::Integer("100".to_string()));
+
+        let value = Value::Array(vec![
+            Value::BulkString("PTTL".to_string()),
+            Value::BulkString("missing".to_string()),
+        ]);
+        let response = handler.handle_request(value.clone()).await?;
+        assert_eq!(response, Value::Integer("-2".to_string()));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_del_command() -> Result<()> {
         let cache = Arc::new(Cache::default());
-        cache.set("key".to_string(), "value".to_string()).await;
+        cache.set("key".to_string(), "value".to_string()).await?;
         let value = Value::Array(vec![
             Value::BulkString("get".to_string()),
             Value::BulkString("key".to_string()),
@@ -290,7 +1229,7 @@ mod tests {
         let mut handler = Handler::new(cache, None);
 
         let response = handler.handle_request(value.clone()).await?;
-        assert_eq!(response, Value::SimpleString("value".to_string()));
+        assert_eq!(response, Value::BulkString("value".to_string()));
 
         let value = Value::Array(vec![
             Value::BulkString("del".to_string()),
@@ -314,7 +1253,7 @@ mod tests {
     #[tokio::test]
     async fn test_exists_command() -> Result<()> {
         let cache = Arc::new(Cache::default());
-        cache.set("key".to_string(), "value".to_string()).await;
+        cache.set("key".to_string(), "value".to_string()).await?;
 
         let value = Value::Array(vec![
             Value::BulkString("exists".to_string()),
@@ -339,12 +1278,15 @@ mod tests {
 
     #[test]
     fn test_command_from_str() {
-        assert_eq!(Command::from("ping"), Command::PING);
-        assert_eq!(Command::from("echo"), Command::ECHO);
+        assert_eq!(Command::from("ping"), Command::Ping);
+        assert_eq!(Command::from("echo"), Command::Echo);
         assert_eq!(Command::from("get"), Command::Get);
-        assert_eq!(Command::from("set"), Command::SET);
-        assert_eq!(Command::from("del"), Command::DELETE);
-        assert_eq!(Command::from("exists"), Command::EXISTS);
+        assert_eq!(Command::from("set"), Command::Set);
+        assert_eq!(Command::from("del"), Command::Delete);
+        assert_eq!(Command::from("exists"), Command::Exists);
+        assert_eq!(Command::from("expire"), Command::Expire);
+        assert_eq!(Command::from("zadd"), Command::ZAdd);
+        assert_eq!(Command::from("zremrangebyscore"), Command::ZRemRangeByScore);
         assert_eq!(Command::from("unknown"), Command::Uninitialized);
     }
 }
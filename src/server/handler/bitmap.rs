@@ -0,0 +1,273 @@
+use crate::cache::bitmap::{
+    parse_offset, BitOperation, BitRange, FieldOp, FieldType, Overflow, Unit,
+};
+use crate::resp::value::Value;
+use crate::server::handler::{bulk_strings, integer, parse_integer, syntax_error, Handler};
+use anyhow::{Error, Result};
+
+fn parse_bit(s: &str) -> Result<bool> {
+    match s {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(Error::msg("The bit argument must be 1 or 0.")),
+    }
+}
+
+/// Parse `[start [end [BYTE|BIT]]]`, an end being required by BITCOUNT when a start is given.
+fn parse_range(args: &[String], end_required: bool) -> Result<Option<BitRange>> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+    if args.len() > 3 || (end_required && args.len() == 1) {
+        return Err(syntax_error());
+    }
+    let start = parse_integer(&args[0])?;
+    let end = args.get(1).map(|end| parse_integer(end)).transpose()?;
+    let unit = match args.get(2) {
+        Some(unit) => Unit::parse(unit)?,
+        None => Unit::default(),
+    };
+    Ok(Some(BitRange { start, end, unit }))
+}
+
+/// Parse the subcommands of `BITFIELD key [GET type offset] [SET type offset value]
+/// [INCRBY type offset increment] [OVERFLOW WRAP|SAT|FAIL] ...`.
+fn parse_bitfield(args: &[String], read_only: bool) -> Result<Vec<FieldOp>> {
+    let mut ops = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        let subcommand = args[i].to_ascii_lowercase();
+        let arg = |offset: usize| args.get(i + offset).ok_or_else(syntax_error);
+        let (op, width) = match subcommand.as_str() {
+            "overflow" if !read_only => (FieldOp::Overflow(Overflow::parse(arg(1)?)?), 2),
+            "get" => {
+                let field = FieldType::parse(arg(1)?)?;
+                let offset = field.parse_offset(arg(2)?)?;
+                (FieldOp::Get(field, offset), 3)
+            }
+            "set" | "incrby" if !read_only => {
+                let field = FieldType::parse(arg(1)?)?;
+                let offset = field.parse_offset(arg(2)?)?;
+                let value = parse_integer(arg(3)?)?;
+                if subcommand == "set" {
+                    (FieldOp::Set(field, offset, value), 4)
+                } else {
+                    (FieldOp::IncrBy(field, offset, value), 4)
+                }
+            }
+            _ if read_only => {
+                return Err(Error::msg("BITFIELD_RO only supports the GET subcommand"))
+            }
+            _ => return Err(syntax_error()),
+        };
+        ops.push(op);
+        i += width;
+    }
+    Ok(ops)
+}
+
+impl Handler {
+    pub(super) async fn handle_setbit(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 3 => args,
+            _ => return Value::Error("SETBIT requires a key, an offset and a bit".to_string()),
+        };
+        let (offset, bit) = match (parse_offset(&args[1]), parse_bit(&args[2])) {
+            (Ok(offset), Ok(bit)) => (offset, bit),
+            (Err(e), _) | (_, Err(e)) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.setbit(args[0].clone(), offset, bit).await {
+            Ok(previous) => integer(previous as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_getbit(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => return Value::Error("GETBIT requires a key and an offset".to_string()),
+        };
+        let offset = match parse_offset(&args[1]) {
+            Ok(offset) => offset,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.getbit(args[0].clone(), offset).await {
+            Ok(bit) => integer(bit as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_bitcount(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("BITCOUNT requires a key".to_string()),
+        };
+        let range = match parse_range(&args[1..], true) {
+            Ok(range) => range,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.bitcount(args[0].clone(), range).await {
+            Ok(count) => integer(count),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_bitpos(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("BITPOS requires a key and a bit".to_string()),
+        };
+        let (bit, range) = match (parse_bit(&args[1]), parse_range(&args[2..], false)) {
+            (Ok(bit), Ok(range)) => (bit, range),
+            (Err(e), _) | (_, Err(e)) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.bitpos(args[0].clone(), bit, range).await {
+            Ok(position) => integer(position),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_bitop(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => {
+                return Value::Error(
+                    "BITOP requires an operation, a destination and keys".to_string(),
+                )
+            }
+        };
+        let operation = match BitOperation::parse(&args[0]) {
+            Ok(operation) => operation,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        let keys = args[2..].to_vec();
+        match self
+            .client_store
+            .bitop(operation, args[1].clone(), keys)
+            .await
+        {
+            Ok(len) => integer(len),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_bitfield(&self, args: &[Value], read_only: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("BITFIELD requires a key".to_string()),
+        };
+        let ops = match parse_bitfield(&args[1..], read_only) {
+            Ok(ops) => ops,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.bitfield(args[0].clone(), ops).await {
+            Ok(replies) => Value::Array(
+                replies
+                    .into_iter()
+                    .map(|reply| reply.map(integer).unwrap_or(Value::Null))
+                    .collect(),
+            ),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::parser::Parser;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{command, int, ok};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_bit_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["SETBIT", "visits", "10", "1"]))
+            .await?;
+        assert_eq!(response, int(0));
+        let response = handler
+            .handle_request(command(&["SETBIT", "visits", "10", "2"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = handler
+            .handle_request(command(&["GETBIT", "visits", "10"]))
+            .await?;
+        assert_eq!(response, int(1));
+
+        let response = handler
+            .handle_request(command(&["BITCOUNT", "visits", "0", "-1", "BIT"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["BITCOUNT", "visits", "0"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&["BITPOS", "visits", "1", "1"]))
+            .await?;
+        assert_eq!(response, int(10));
+
+        let response = handler
+            .handle_request(command(&["BITOP", "NOT", "inverted", "visits"]))
+            .await?;
+        assert_eq!(response, int(2));
+        let response = handler
+            .handle_request(command(&["BITCOUNT", "inverted"]))
+            .await?;
+        assert_eq!(response, int(15));
+
+        // bitmaps are read back as their raw bytes
+        let response = handler
+            .handle_request(command(&["GET", "inverted"]))
+            .await?;
+        assert_eq!(response, Value::BulkBytes(vec![0xff, 0xdf]));
+        assert_eq!(response.encode(), b"$2\r\n\xff\xdf\r\n");
+
+        // and written back as they are
+        let request = b"*3\r\n$3\r\nSET\r\n$4\r\ncopy\r\n$2\r\n\xff\xdf\r\n";
+        let (request, _) = Parser::parse_message(request)?;
+        assert_eq!(handler.handle_request(request).await?, ok());
+        let response = handler.handle_request(command(&["GET", "copy"])).await?;
+        assert_eq!(response, Value::BulkBytes(vec![0xff, 0xdf]));
+        let response = handler
+            .handle_request(command(&["BITCOUNT", "copy"]))
+            .await?;
+        assert_eq!(response, int(15));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bitfield_command() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&[
+                "BITFIELD", "counters", "INCRBY", "u2", "#1", "3", "OVERFLOW", "FAIL", "INCRBY",
+                "u2", "#1", "1", "GET", "u4", "0",
+            ]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(3), Value::Null, int(3)]));
+
+        let response = handler
+            .handle_request(command(&["BITFIELD_RO", "counters", "GET", "i2", "2"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(-1)]));
+        let response = handler
+            .handle_request(command(&["BITFIELD_RO", "counters", "SET", "u2", "0", "1"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        Ok(())
+    }
+}
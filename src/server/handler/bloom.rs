@@ -0,0 +1,220 @@
+use crate::cache::bloom::{DEFAULT_EXPANSION, MAX_CAPACITY};
+use crate::resp::value::Value;
+use crate::server::handler::{
+    bulk_strings, integer, parse_count, parse_float, syntax_error, Handler,
+};
+use anyhow::{Error, Result};
+
+/// Parse `BF.RESERVE key error_rate capacity [EXPANSION expansion] [NONSCALING]`.
+fn parse_reserve(args: &[String]) -> Result<(f64, usize, Option<u32>)> {
+    let error_rate = parse_float(&args[1])?;
+    if error_rate <= 0.0 || error_rate >= 1.0 {
+        return Err(Error::msg("error rate must be between 0 and 1, exclusive"));
+    }
+    let capacity = parse_count(&args[2])?;
+    if capacity == 0 {
+        return Err(Error::msg("capacity must be larger than 0"));
+    }
+    if capacity > MAX_CAPACITY {
+        return Err(Error::msg(format!(
+            "capacity must be at most {}",
+            MAX_CAPACITY
+        )));
+    }
+
+    let mut expansion = None;
+    let mut scaling = true;
+    let mut i = 3;
+    while i < args.len() {
+        match args[i].to_ascii_lowercase().as_str() {
+            "expansion" => {
+                let parsed = parse_count(args.get(i + 1).ok_or_else(syntax_error)?)?;
+                if parsed == 0 || parsed > u32::MAX as usize {
+                    return Err(Error::msg("expansion must be a positive integer"));
+                }
+                expansion = Some(parsed as u32);
+                i += 2;
+            }
+            "nonscaling" => {
+                scaling = false;
+                i += 1;
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+
+    match (scaling, expansion) {
+        (true, expansion) => Ok((
+            error_rate,
+            capacity,
+            Some(expansion.unwrap_or(DEFAULT_EXPANSION)),
+        )),
+        (false, None) => Ok((error_rate, capacity, None)),
+        (false, Some(_)) => Err(Error::msg("nonscaling filters cannot expand")),
+    }
+}
+
+impl Handler {
+    pub(super) async fn handle_bf_reserve(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => {
+                return Value::Error(
+                    "BF.RESERVE requires a key, an error rate and a capacity".to_string(),
+                )
+            }
+        };
+        let (error_rate, capacity, expansion) = match parse_reserve(&args) {
+            Ok(parsed) => parsed,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .bf_reserve(args[0].clone(), error_rate, capacity, expansion)
+            .await
+        {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    /// Handle BF.ADD, or BF.MADD when `multiple` items are given.
+    pub(super) async fn handle_bf_add(&self, args: &[Value], multiple: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 || (multiple && args.len() > 2) => args,
+            _ if multiple => return Value::Error("BF.MADD requires a key and items".to_string()),
+            _ => return Value::Error("BF.ADD requires a key and an item".to_string()),
+        };
+
+        let items = args[1..].to_vec();
+        let mut replies = match self.client_store.bf_add(args[0].clone(), items).await {
+            Ok(added) => added
+                .into_iter()
+                .map(|added| match added {
+                    Ok(added) => integer(added as u8),
+                    Err(e) => Value::Error(e.to_string()),
+                })
+                .collect::<Vec<_>>(),
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        if multiple {
+            Value::Array(replies)
+        } else {
+            replies.remove(0)
+        }
+    }
+
+    /// Handle BF.EXISTS, or BF.MEXISTS when `multiple` items are given.
+    pub(super) async fn handle_bf_exists(&self, args: &[Value], multiple: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 || (multiple && args.len() > 2) => args,
+            _ if multiple => {
+                return Value::Error("BF.MEXISTS requires a key and items".to_string())
+            }
+            _ => return Value::Error("BF.EXISTS requires a key and an item".to_string()),
+        };
+
+        let items = args[1..].to_vec();
+        match self.client_store.bf_exists(args[0].clone(), items).await {
+            Ok(found) if multiple => Value::Array(
+                found
+                    .into_iter()
+                    .map(|found| integer(found as u8))
+                    .collect(),
+            ),
+            Ok(found) => integer(found[0] as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_bf_info(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("BF.INFO requires a key".to_string()),
+        };
+
+        match self.client_store.bf_info(args[0].clone()).await {
+            Ok(info) => {
+                let field = |name: &str| Value::SimpleString(name.to_string());
+                Value::Array(vec![
+                    field("Capacity"),
+                    integer(info.capacity),
+                    field("Size"),
+                    integer(info.size),
+                    field("Number of filters"),
+                    integer(info.filters),
+                    field("Number of items inserted"),
+                    integer(info.items),
+                    field("Expansion rate"),
+                    info.expansion.map(integer).unwrap_or(Value::Null),
+                ])
+            }
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_bf_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["BF.RESERVE", "users", "0.01", "2", "NONSCALING"]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        let response = handler
+            .handle_request(command(&["BF.RESERVE", "other", "1.5", "100"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        // sizes too large are refused rather than allocated
+        for args in [["1099511627776", "0.01"], ["100000000", "1e-300"]] {
+            let response = handler
+                .handle_request(command(&["BF.RESERVE", "other", args[1], args[0]]))
+                .await?;
+            assert!(matches!(response, Value::Error(_)));
+        }
+
+        let response = handler
+            .handle_request(command(&["BF.ADD", "users", "alice"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["BF.MADD", "users", "alice", "bob", "carol"]))
+            .await?;
+        match response {
+            Value::Array(replies) => {
+                assert_eq!(replies[..2], [int(0), int(1)]);
+                assert!(matches!(replies[2], Value::Error(_)));
+            }
+            _ => panic!("unexpected response {:?}", response),
+        }
+
+        let response = handler
+            .handle_request(command(&["BF.EXISTS", "users", "bob"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["BF.MEXISTS", "users", "alice", "dave"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(1), int(0)]));
+
+        let response = handler
+            .handle_request(command(&["BF.INFO", "users"]))
+            .await?;
+        match response {
+            Value::Array(info) => assert_eq!(info[7], int(2)),
+            _ => panic!("unexpected response {:?}", response),
+        }
+        Ok(())
+    }
+}
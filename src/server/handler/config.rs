@@ -0,0 +1,121 @@
+use crate::cache::glob;
+use crate::resp::value::Value;
+use crate::server::handler::{bulk_strings, Handler};
+
+/// The only parameter there is, configuring the keyspace notifications.
+const NOTIFY_KEYSPACE_EVENTS: &str = "notify-keyspace-events";
+
+impl Handler {
+    /// Handle `CONFIG GET pattern` and `CONFIG SET parameter value`. The configuration is the
+    /// server's, which clients bound to a tenant can't change.
+    pub(super) async fn handle_config(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("CONFIG requires a subcommand".to_string()),
+        };
+
+        match (args[0].to_ascii_lowercase().as_str(), &args[1..]) {
+            ("get", [pattern]) => {
+                match glob::matches(&pattern.to_ascii_lowercase(), NOTIFY_KEYSPACE_EVENTS) {
+                    true => Value::Array(vec![
+                        Value::BulkString(NOTIFY_KEYSPACE_EVENTS.to_string()),
+                        Value::BulkString(self.notifications.flags()),
+                    ]),
+                    false => Value::Array(vec![]),
+                }
+            }
+            ("set", [..]) if self.tenant.is_some() => Value::Error(
+                "NOPERM clients bound to a tenant can't change the configuration".to_string(),
+            ),
+            ("set", [parameter, value])
+                if parameter.eq_ignore_ascii_case(NOTIFY_KEYSPACE_EVENTS) =>
+            {
+                match self.notifications.configure(value) {
+                    Ok(()) => Value::SimpleString("OK".to_string()),
+                    Err(e) => Value::Error(e.to_string()),
+                }
+            }
+            ("set", [parameter, _]) => Value::Error(format!(
+                "Unknown option or number of arguments for CONFIG SET - '{}'",
+                parameter
+            )),
+            _ => Value::Error(format!(
+                "unknown or malformed CONFIG subcommand {}",
+                args[0]
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::databases::Databases;
+    use crate::cache::notifications::Notifications;
+    use crate::cache::pubsub::PubSub;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, ok};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_keyspace_notifications() -> Result<()> {
+        let databases = Databases::new(2, 25, 0.25);
+        let notifications = Notifications::new(PubSub::default());
+        databases.notify_through(&notifications);
+        let mut client =
+            Handler::new(databases.clone(), None).with_notifications(notifications.clone());
+        let mut subscriber = Handler::new(databases, None).with_notifications(notifications);
+
+        let response = client
+            .handle_request(command(&["CONFIG", "SET", "notify-keyspace-events", "Eg$"]))
+            .await?;
+        assert_eq!(response, ok());
+        let response = client
+            .handle_request(command(&["CONFIG", "GET", "notify-*"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![bulk("notify-keyspace-events"), bulk("g$E")])
+        );
+        let response = client
+            .handle_request(command(&["CONFIG", "SET", "maxmemory", "1"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        subscriber
+            .handle_request(command(&["PSUBSCRIBE", "__keyevent@1__:*"]))
+            .await?;
+        client.handle_request(command(&["SELECT", "1"])).await?;
+        client.handle_request(command(&["SET", "a", "1"])).await?;
+        client.handle_request(command(&["DEL", "a"])).await?;
+        // the swapped database is numbered 0 in its events from then on
+        client
+            .handle_request(command(&["SWAPDB", "0", "1"]))
+            .await?;
+        client.handle_request(command(&["SELECT", "0"])).await?;
+        client.handle_request(command(&["SET", "b", "1"])).await?;
+        client.handle_request(command(&["SELECT", "1"])).await?;
+        client.handle_request(command(&["SET", "c", "1"])).await?;
+
+        let mut events = Vec::new();
+        for _ in 0..3 {
+            let message = subscriber
+                .subscriber
+                .as_mut()
+                .unwrap()
+                .recv()
+                .await
+                .unwrap();
+            events.push((message.channel, message.payload));
+        }
+        assert_eq!(
+            events,
+            vec![
+                ("__keyevent@1__:set".to_string(), "a".to_string()),
+                ("__keyevent@1__:del".to_string(), "a".to_string()),
+                ("__keyevent@1__:set".to_string(), "c".to_string()),
+            ]
+        );
+        Ok(())
+    }
+}
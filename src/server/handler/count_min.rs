@@ -0,0 +1,229 @@
+use crate::cache::count_min::CountMinSketch;
+use crate::resp::value::Value;
+use crate::server::handler::{
+    bulk_strings, integer, parse_count, parse_float, parse_keys, syntax_error, Handler,
+};
+use anyhow::Result;
+
+/// Parse `item increment [item increment ...]`.
+fn parse_increments(args: &[String]) -> Result<Vec<(String, u64)>> {
+    if args.is_empty() || !args.len().is_multiple_of(2) {
+        return Err(syntax_error());
+    }
+    args.chunks(2)
+        .map(|pair| {
+            let by = parse_count(&pair[1])?;
+            Ok((pair[0].clone(), by as u64))
+        })
+        .collect()
+}
+
+/// Parse `numkeys source [source ...] [WEIGHTS weight [weight ...]]`.
+fn parse_merge(args: &[String]) -> Result<Vec<(String, u64)>> {
+    let (keys, rest) = parse_keys(args)?;
+    let weights = match rest {
+        [] => vec![1; keys.len()],
+        [weights, rest @ ..] if weights.eq_ignore_ascii_case("weights") => {
+            if rest.len() != keys.len() {
+                return Err(syntax_error());
+            }
+            rest.iter()
+                .map(|weight| parse_count(weight).map(|weight| weight as u64))
+                .collect::<Result<Vec<_>>>()?
+        }
+        _ => return Err(syntax_error()),
+    };
+    Ok(keys.into_iter().zip(weights).collect())
+}
+
+impl Handler {
+    /// Handle CMS.INITBYDIM, or CMS.INITBYPROB when `by_probability` is set.
+    pub(super) async fn handle_cms_init(&self, args: &[Value], by_probability: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 3 => args,
+            _ if by_probability => {
+                return Value::Error(
+                    "CMS.INITBYPROB requires a key, an error and a probability".to_string(),
+                )
+            }
+            _ => {
+                return Value::Error(
+                    "CMS.INITBYDIM requires a key, a width and a depth".to_string(),
+                )
+            }
+        };
+        let sketch = if by_probability {
+            match (parse_float(&args[1]), parse_float(&args[2])) {
+                (Ok(error), Ok(probability))
+                    if error > 0.0 && error < 1.0 && probability > 0.0 && probability < 1.0 =>
+                {
+                    CountMinSketch::with_error(error, probability)
+                }
+                (Err(e), _) | (_, Err(e)) => return Value::Error(e.to_string()),
+                _ => {
+                    return Value::Error(
+                        "error and probability must be between 0 and 1, exclusive".to_string(),
+                    )
+                }
+            }
+        } else {
+            match (parse_count(&args[1]), parse_count(&args[2])) {
+                (Ok(width), Ok(depth)) if width > 0 && depth > 0 => {
+                    CountMinSketch::new(width, depth)
+                }
+                (Err(e), _) | (_, Err(e)) => return Value::Error(e.to_string()),
+                _ => return Value::Error("width and depth must be positive".to_string()),
+            }
+        };
+        let sketch = match sketch {
+            Ok(sketch) => sketch,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.cms_init(args[0].clone(), sketch).await {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_cms_incrby(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => {
+                return Value::Error(
+                    "CMS.INCRBY requires a key and item increment pairs".to_string(),
+                )
+            }
+        };
+        let items = match parse_increments(&args[1..]) {
+            Ok(items) => items,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.cms_incrby(args[0].clone(), items).await {
+            Ok(counts) => Value::Array(counts.into_iter().map(integer).collect()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_cms_query(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("CMS.QUERY requires a key and items".to_string()),
+        };
+
+        let items = args[1..].to_vec();
+        match self.client_store.cms_query(args[0].clone(), items).await {
+            Ok(counts) => Value::Array(counts.into_iter().map(integer).collect()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_cms_merge(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => {
+                return Value::Error(
+                    "CMS.MERGE requires a destination, numkeys and sources".to_string(),
+                )
+            }
+        };
+        let sources = match parse_merge(&args[1..]) {
+            Ok(sources) => sources,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.cms_merge(args[0].clone(), sources).await {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_cms_info(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("CMS.INFO requires a key".to_string()),
+        };
+
+        match self.client_store.cms_info(args[0].clone()).await {
+            Ok((width, depth, count)) => {
+                let field = |name: &str| Value::SimpleString(name.to_string());
+                Value::Array(vec![
+                    field("width"),
+                    integer(width),
+                    field("depth"),
+                    integer(depth),
+                    field("count"),
+                    integer(count),
+                ])
+            }
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_cms_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["CMS.INCRBY", "hits", "home", "1"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = handler
+            .handle_request(command(&["CMS.INITBYDIM", "hits", "1000", "5"]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        handler
+            .handle_request(command(&["CMS.INITBYPROB", "other", "0.001", "0.01"]))
+            .await?;
+        // sizes too large are refused rather than allocated
+        for args in [
+            ["CMS.INITBYDIM", "huge", "4294967296", "4294967296"],
+            ["CMS.INITBYDIM", "huge", "16777216", "64"],
+            ["CMS.INITBYPROB", "huge", "1e-300", "0.01"],
+        ] {
+            let response = handler.handle_request(command(&args)).await?;
+            assert!(matches!(response, Value::Error(_)));
+        }
+
+        let response = handler
+            .handle_request(command(&["CMS.INCRBY", "hits", "home", "3", "blog", "1"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(3), int(1)]));
+        let response = handler
+            .handle_request(command(&["CMS.QUERY", "hits", "home", "about"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(3), int(0)]));
+
+        let response = handler
+            .handle_request(command(&[
+                "CMS.MERGE",
+                "twice",
+                "1",
+                "hits",
+                "WEIGHTS",
+                "2",
+            ]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        let response = handler
+            .handle_request(command(&["CMS.QUERY", "twice", "home"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(6)]));
+        let response = handler
+            .handle_request(command(&["CMS.MERGE", "twice", "2", "hits", "other"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use crate::cache::cuckoo::{CuckooOptions, MAX_BUCKET_SIZE, MAX_CAPACITY};
+use crate::resp::value::Value;
+use crate::server::handler::{bulk_strings, integer, parse_count, syntax_error, Handler};
+use anyhow::{Error, Result};
+
+/// Parse `CF.RESERVE key capacity [BUCKETSIZE size] [MAXITERATIONS iterations]
+/// [EXPANSION expansion]`.
+fn parse_reserve(args: &[String]) -> Result<CuckooOptions> {
+    let mut options = CuckooOptions {
+        capacity: parse_count(&args[1])?,
+        ..CuckooOptions::default()
+    };
+    if options.capacity == 0 {
+        return Err(Error::msg("capacity must be larger than 0"));
+    }
+    if options.capacity > MAX_CAPACITY {
+        return Err(Error::msg(format!(
+            "capacity must be at most {}",
+            MAX_CAPACITY
+        )));
+    }
+
+    let mut i = 2;
+    while i < args.len() {
+        let value = parse_count(args.get(i + 1).ok_or_else(syntax_error)?)?;
+        match args[i].to_ascii_lowercase().as_str() {
+            "bucketsize" if (1..=MAX_BUCKET_SIZE).contains(&value) => options.bucket_size = value,
+            "bucketsize" => {
+                return Err(Error::msg(format!(
+                    "bucket size must be between 1 and {}",
+                    MAX_BUCKET_SIZE
+                )))
+            }
+            "maxiterations" if value > 0 => options.max_iterations = value,
+            "maxiterations" => return Err(Error::msg("max iterations must be larger than 0")),
+            "expansion" if value <= 32768 => options.expansion = value,
+            "expansion" => return Err(Error::msg("expansion must be between 0 and 32768")),
+            _ => return Err(syntax_error()),
+        }
+        i += 2;
+    }
+    Ok(options)
+}
+
+impl Handler {
+    pub(super) async fn handle_cf_reserve(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("CF.RESERVE requires a key and a capacity".to_string()),
+        };
+        let options = match parse_reserve(&args) {
+            Ok(options) => options,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.cf_reserve(args[0].clone(), options).await {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    /// Handle CF.ADD, or CF.ADDNX when `nx` is set.
+    pub(super) async fn handle_cf_add(&self, args: &[Value], nx: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => return Value::Error("CF.ADD requires a key and an item".to_string()),
+        };
+
+        match self
+            .client_store
+            .cf_add(args[0].clone(), args[1].clone(), nx)
+            .await
+        {
+            Ok(added) => integer(added as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    /// Handle CF.EXISTS, or CF.MEXISTS when `multiple` items are given.
+    pub(super) async fn handle_cf_exists(&self, args: &[Value], multiple: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 || (multiple && args.len() > 2) => args,
+            _ if multiple => {
+                return Value::Error("CF.MEXISTS requires a key and items".to_string())
+            }
+            _ => return Value::Error("CF.EXISTS requires a key and an item".to_string()),
+        };
+
+        let items = args[1..].to_vec();
+        match self.client_store.cf_exists(args[0].clone(), items).await {
+            Ok(found) if multiple => Value::Array(
+                found
+                    .into_iter()
+                    .map(|found| integer(found as u8))
+                    .collect(),
+            ),
+            Ok(found) => integer(found[0] as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_cf_count(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => return Value::Error("CF.COUNT requires a key and an item".to_string()),
+        };
+
+        match self
+            .client_store
+            .cf_count(args[0].clone(), args[1].clone())
+            .await
+        {
+            Ok(count) => integer(count),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_cf_del(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => return Value::Error("CF.DEL requires a key and an item".to_string()),
+        };
+
+        match self
+            .client_store
+            .cf_del(args[0].clone(), args[1].clone())
+            .await
+        {
+            Ok(removed) => integer(removed as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_cf_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&[
+                "CF.RESERVE",
+                "emails",
+                "1000",
+                "BUCKETSIZE",
+                "4",
+            ]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        let response = handler
+            .handle_request(command(&["CF.RESERVE", "other", "1000", "BUCKETSIZE", "0"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        // sizes too large are refused rather than allocated
+        let response = handler
+            .handle_request(command(&["CF.RESERVE", "other", "1099511627776"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&["CF.ADD", "emails", "alice"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["CF.ADDNX", "emails", "alice"]))
+            .await?;
+        assert_eq!(response, int(0));
+        let response = handler
+            .handle_request(command(&["CF.ADD", "emails", "alice"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["CF.COUNT", "emails", "alice"]))
+            .await?;
+        assert_eq!(response, int(2));
+
+        let response = handler
+            .handle_request(command(&["CF.DEL", "emails", "alice"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["CF.MEXISTS", "emails", "alice", "bob"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(1), int(0)]));
+        let response = handler
+            .handle_request(command(&["CF.DEL", "missing", "alice"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use crate::cache::databases::out_of_range;
+use crate::resp::value::Value;
+use crate::server::handler::{bulk_strings, integer, parse_integer, syntax_error, Handler};
+use anyhow::Result;
+
+impl Handler {
+    /// Parse the number of an existing database.
+    pub(super) fn parse_database(&self, s: &str) -> Result<usize> {
+        let index = parse_integer(s)?;
+        if index < 0 || index as usize >= self.databases.count() {
+            return Err(out_of_range());
+        }
+        Ok(index as usize)
+    }
+
+    pub(super) async fn handle_select(&mut self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("SELECT requires a database".to_string()),
+        };
+
+        match self.parse_database(&args[0]) {
+            Ok(db) => {
+                self.db = db;
+                Value::SimpleString("OK".to_string())
+            }
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_swapdb(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => return Value::Error("SWAPDB requires two databases".to_string()),
+        };
+        let swapped = self.parse_database(&args[0]).and_then(|first| {
+            let second = self.parse_database(&args[1])?;
+            self.databases.swap(first, second)
+        });
+
+        match swapped {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    /// Handle FLUSHDB. Values are always freed in the background, so ASYNC and SYNC are
+    /// accepted for compatibility but change nothing.
+    pub(super) async fn handle_flushdb(&self, args: &[Value]) -> Value {
+        if !is_flush_mode(args) {
+            return Value::Error(syntax_error().to_string());
+        }
+
+        self.client_store.flush().await;
+        Value::SimpleString("OK".to_string())
+    }
+
+    pub(super) async fn handle_flushall(&self, args: &[Value]) -> Value {
+        if !is_flush_mode(args) {
+            return Value::Error(syntax_error().to_string());
+        }
+
+        for cache in self.databases.all() {
+            cache.flush().await;
+        }
+        Value::SimpleString("OK".to_string())
+    }
+
+    pub(super) async fn handle_dbsize(&self, args: &[Value]) -> Value {
+        if !args.is_empty() {
+            return Value::Error("DBSIZE takes no arguments".to_string());
+        }
+
+        integer(self.client_store.existing().await)
+    }
+}
+
+/// Retrieve whether `args` are empty or the `ASYNC` or `SYNC` flag of FLUSHDB and FLUSHALL.
+fn is_flush_mode(args: &[Value]) -> bool {
+    match bulk_strings(args) {
+        Some(args) if args.is_empty() => true,
+        Some(args) if args.len() == 1 => {
+            args[0].eq_ignore_ascii_case("async") || args[0].eq_ignore_ascii_case("sync")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::databases::Databases;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{command, int, ok};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_database_commands() -> Result<()> {
+        let databases = Databases::new(4, 25, 0.25);
+        let mut handler = Handler::new(databases.clone(), None);
+        let mut other = Handler::new(databases, None);
+        handler
+            .handle_request(command(&["SET", "config", "live"]))
+            .await?;
+
+        let response = handler.handle_request(command(&["SELECT", "1"])).await?;
+        assert_eq!(response, ok());
+        let response = handler.handle_request(command(&["SELECT", "4"])).await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = handler.handle_request(command(&["GET", "config"])).await?;
+        assert_eq!(response, Value::Null);
+        handler
+            .handle_request(command(&["SET", "config", "staged"]))
+            .await?;
+        handler
+            .handle_request(command(&["SET", "other", "staged"]))
+            .await?;
+        let response = handler.handle_request(command(&["DBSIZE"])).await?;
+        assert_eq!(response, int(2));
+
+        // the swap is seen by every connection
+        let response = handler
+            .handle_request(command(&["SWAPDB", "0", "1"]))
+            .await?;
+        assert_eq!(response, ok());
+        let response = other.handle_request(command(&["GET", "config"])).await?;
+        assert_eq!(response, Value::BulkString("staged".to_string()));
+        let response = handler.handle_request(command(&["GET", "config"])).await?;
+        assert_eq!(response, Value::BulkString("live".to_string()));
+
+        let response = handler.handle_request(command(&["FLUSHDB"])).await?;
+        assert_eq!(response, ok());
+        let response = handler.handle_request(command(&["DBSIZE"])).await?;
+        assert_eq!(response, int(0));
+        let response = other.handle_request(command(&["DBSIZE"])).await?;
+        assert_eq!(response, int(2));
+        let response = other
+            .handle_request(command(&["FLUSHALL", "ASYNC"]))
+            .await?;
+        assert_eq!(response, ok());
+        let response = other.handle_request(command(&["DBSIZE"])).await?;
+        assert_eq!(response, int(0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_move_and_copy_commands() -> Result<()> {
+        let databases = Databases::new(2, 25, 0.25);
+        let mut handler = Handler::new(databases, None);
+        handler
+            .handle_request(command(&["SET", "session", "a", "ex", "100"]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&["COPY", "session", "backup", "DB", "1"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["MOVE", "session", "1"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["MOVE", "session", "0"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = handler
+            .handle_request(command(&["MOVE", "session", "2"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        handler.handle_request(command(&["SELECT", "1"])).await?;
+        let response = handler.handle_request(command(&["TTL", "session"])).await?;
+        assert_eq!(response, int(100));
+        let response = handler.handle_request(command(&["GET", "backup"])).await?;
+        assert_eq!(response, Value::BulkString("a".to_string()));
+        Ok(())
+    }
+}
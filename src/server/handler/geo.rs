@@ -0,0 +1,385 @@
+use crate::cache::geo::{validate, Location, Origin, Search, Shape, Unit};
+use crate::cache::sorted_set::{AddOptions, AddOutcome};
+use crate::resp::value::Value;
+use crate::server::handler::{
+    bulk_strings, integer, parse_count, parse_float, syntax_error, Handler,
+};
+use anyhow::{Error, Result};
+
+fn coordinate_value(value: f64) -> Value {
+    Value::BulkString(value.to_string())
+}
+
+fn distance_value(meters: f64, unit: Unit) -> Value {
+    Value::BulkString(format!("{:.4}", meters / unit.meters()))
+}
+
+/// Parse `GEOADD key [NX|XX] [CH] longitude latitude member [...]`.
+fn parse_geoadd(args: &[String]) -> Result<(AddOptions, Vec<Location>)> {
+    let mut options = AddOptions::default();
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].to_ascii_lowercase().as_str() {
+            "nx" => options.nx = true,
+            "xx" => options.xx = true,
+            "ch" => options.ch = true,
+            _ => break,
+        }
+        i += 1;
+    }
+
+    let points = &args[i..];
+    if points.is_empty() || !points.len().is_multiple_of(3) {
+        return Err(syntax_error());
+    }
+    let points = points
+        .chunks(3)
+        .map(|point| {
+            let lon = parse_float(&point[0])?;
+            let lat = parse_float(&point[1])?;
+            Ok((lon, lat, point[2].clone()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((options, points))
+}
+
+/// The reply options of GEOSEARCH.
+#[derive(Default)]
+struct With {
+    coord: bool,
+    dist: bool,
+    hash: bool,
+}
+
+/// Parse `GEOSEARCH key FROMMEMBER member|FROMLONLAT longitude latitude
+/// BYRADIUS radius unit|BYBOX width height unit [ASC|DESC] [COUNT count [ANY]]
+/// [WITHCOORD] [WITHDIST] [WITHHASH]`.
+fn parse_geosearch(args: &[String]) -> Result<(Search, Unit, With)> {
+    let mut origin = None;
+    let mut shape = None;
+    let mut unit = Unit::Meters;
+    let mut ascending = None;
+    let mut count = None;
+    let mut any = false;
+    let mut with = With::default();
+
+    let mut i = 1;
+    while i < args.len() {
+        let arg = |offset: usize| args.get(i + offset).ok_or_else(syntax_error);
+        match args[i].to_ascii_lowercase().as_str() {
+            "frommember" if origin.is_none() => {
+                origin = Some(Origin::Member(arg(1)?.clone()));
+                i += 2;
+            }
+            "fromlonlat" if origin.is_none() => {
+                let (lon, lat) = (parse_float(arg(1)?)?, parse_float(arg(2)?)?);
+                validate(lon, lat)?;
+                origin = Some(Origin::Point(lon, lat));
+                i += 3;
+            }
+            "byradius" if shape.is_none() => {
+                let radius = parse_float(arg(1)?)?;
+                unit = Unit::parse(arg(2)?)?;
+                if radius < 0.0 {
+                    return Err(Error::msg("radius cannot be negative"));
+                }
+                shape = Some(Shape::Radius(radius * unit.meters()));
+                i += 3;
+            }
+            "bybox" if shape.is_none() => {
+                let (width, height) = (parse_float(arg(1)?)?, parse_float(arg(2)?)?);
+                unit = Unit::parse(arg(3)?)?;
+                if width < 0.0 || height < 0.0 {
+                    return Err(Error::msg("height or width cannot be negative"));
+                }
+                shape = Some(Shape::Box {
+                    width: width * unit.meters(),
+                    height: height * unit.meters(),
+                });
+                i += 4;
+            }
+            "asc" => {
+                ascending = Some(true);
+                i += 1;
+            }
+            "desc" => {
+                ascending = Some(false);
+                i += 1;
+            }
+            "count" => {
+                let parsed = parse_count(arg(1)?)?;
+                if parsed == 0 {
+                    return Err(Error::msg("COUNT must be > 0"));
+                }
+                count = Some(parsed);
+                i += 2;
+                if args
+                    .get(i)
+                    .map(|arg| arg.eq_ignore_ascii_case("any"))
+                    .unwrap_or(false)
+                {
+                    any = true;
+                    i += 1;
+                }
+            }
+            "withcoord" => {
+                with.coord = true;
+                i += 1;
+            }
+            "withdist" => {
+                with.dist = true;
+                i += 1;
+            }
+            "withhash" => {
+                with.hash = true;
+                i += 1;
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+
+    let (origin, shape) = match (origin, shape) {
+        (Some(origin), Some(shape)) => (origin, shape),
+        (None, _) => {
+            return Err(Error::msg(
+                "exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH",
+            ))
+        }
+        (_, None) => {
+            return Err(Error::msg(
+                "exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH",
+            ))
+        }
+    };
+    let search = Search {
+        origin,
+        shape,
+        ascending,
+        count,
+        any,
+    };
+    Ok((search, unit, with))
+}
+
+impl Handler {
+    pub(super) async fn handle_geoadd(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 4 => args,
+            _ => {
+                return Value::Error(
+                    "GEOADD requires a key and longitude latitude member triplets".to_string(),
+                )
+            }
+        };
+        let (options, points) = match parse_geoadd(&args) {
+            Ok(parsed) => parsed,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .geoadd(args[0].clone(), options, points)
+            .await
+        {
+            Ok(outcomes) => integer(
+                outcomes
+                    .iter()
+                    .filter(|outcome| match outcome {
+                        AddOutcome::Added(_) => true,
+                        AddOutcome::Updated(_) => options.ch,
+                        _ => false,
+                    })
+                    .count(),
+            ),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_geopos(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("GEOPOS requires a key".to_string()),
+        };
+
+        let members = args[1..].to_vec();
+        match self.client_store.geopos(args[0].clone(), members).await {
+            Ok(positions) => Value::Array(
+                positions
+                    .into_iter()
+                    .map(|position| match position {
+                        Some((lon, lat)) => {
+                            Value::Array(vec![coordinate_value(lon), coordinate_value(lat)])
+                        }
+                        None => Value::Null,
+                    })
+                    .collect(),
+            ),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_geodist(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 3 || args.len() == 4 => args,
+            _ => return Value::Error("GEODIST requires a key and two members".to_string()),
+        };
+        let unit = match args.get(3).map(|unit| Unit::parse(unit)) {
+            Some(Ok(unit)) => unit,
+            Some(Err(e)) => return Value::Error(e.to_string()),
+            None => Unit::Meters,
+        };
+
+        match self
+            .client_store
+            .geodist(args[0].clone(), args[1].clone(), args[2].clone())
+            .await
+        {
+            Ok(Some(meters)) => distance_value(meters, unit),
+            Ok(None) => Value::Null,
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_geosearch(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("GEOSEARCH requires a key".to_string()),
+        };
+        let (search, unit, with) = match parse_geosearch(&args) {
+            Ok(parsed) => parsed,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        let found = match self.client_store.geosearch(args[0].clone(), search).await {
+            Ok(found) => found,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        let items = found
+            .into_iter()
+            .map(|found| {
+                if !(with.coord || with.dist || with.hash) {
+                    return Value::BulkString(found.member);
+                }
+                let mut item = vec![Value::BulkString(found.member)];
+                if with.dist {
+                    item.push(distance_value(found.distance, unit));
+                }
+                if with.hash {
+                    item.push(integer(found.hash));
+                }
+                if with.coord {
+                    item.push(Value::Array(vec![
+                        coordinate_value(found.lon),
+                        coordinate_value(found.lat),
+                    ]));
+                }
+                Value::Array(item)
+            })
+            .collect();
+        Value::Array(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    async fn sicily() -> Result<Handler> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        let response = handler
+            .handle_request(command(&[
+                "GEOADD",
+                "Sicily",
+                "13.361389",
+                "38.115556",
+                "Palermo",
+                "15.087269",
+                "37.502669",
+                "Catania",
+            ]))
+            .await?;
+        assert_eq!(response, int(2));
+        Ok(handler)
+    }
+
+    #[tokio::test]
+    async fn test_geoadd_geodist_command() -> Result<()> {
+        let mut handler = sicily().await?;
+
+        let response = handler
+            .handle_request(command(&["GEODIST", "Sicily", "Palermo", "Catania", "KM"]))
+            .await?;
+        assert_eq!(response, bulk("166.2742"));
+        let response = handler
+            .handle_request(command(&["GEODIST", "Sicily", "Palermo", "Rome"]))
+            .await?;
+        assert_eq!(response, Value::Null);
+
+        let response = handler
+            .handle_request(command(&["GEOADD", "Sicily", "200", "0", "Nowhere"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&["GEOPOS", "Sicily", "Rome"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![Value::Null]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_geosearch_command() -> Result<()> {
+        let mut handler = sicily().await?;
+
+        let response = handler
+            .handle_request(command(&[
+                "GEOSEARCH",
+                "Sicily",
+                "FROMLONLAT",
+                "15",
+                "37",
+                "BYRADIUS",
+                "200",
+                "km",
+                "ASC",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![bulk("Catania"), bulk("Palermo")])
+        );
+
+        let response = handler
+            .handle_request(command(&[
+                "GEOSEARCH",
+                "Sicily",
+                "FROMMEMBER",
+                "Palermo",
+                "BYBOX",
+                "400",
+                "400",
+                "km",
+                "DESC",
+                "COUNT",
+                "1",
+                "WITHDIST",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![Value::Array(vec![bulk("Catania"), bulk("166.2742")])])
+        );
+
+        let response = handler
+            .handle_request(command(&["GEOSEARCH", "Sicily", "BYRADIUS", "1", "m"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        Ok(())
+    }
+}
@@ -0,0 +1,345 @@
+use crate::cache::graph::{Direction, Edge, Properties, Traversal};
+use crate::resp::value::Value;
+use crate::server::handler::{
+    bulk_strings, integer, parse_count, parse_float, syntax_error, Handler,
+};
+use anyhow::Result;
+
+fn parse_properties(args: &[String]) -> Result<Properties> {
+    if !args.len().is_multiple_of(2) {
+        return Err(syntax_error());
+    }
+    Ok(args
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect())
+}
+
+/// Parse `[WEIGHT weight] [field value ...]`.
+fn parse_edge(args: &[String]) -> Result<Edge> {
+    match args {
+        [option, weight, properties @ ..] if option.eq_ignore_ascii_case("weight") => Ok(Edge {
+            weight: parse_float(weight)?,
+            properties: parse_properties(properties)?,
+        }),
+        _ => Ok(Edge {
+            weight: 1.0,
+            properties: parse_properties(args)?,
+        }),
+    }
+}
+
+/// Parse `[BFS|DFS] [DEPTH depth] [DIRECTION OUT|IN|BOTH]`.
+fn parse_traversal(args: &[String]) -> Result<(Traversal, usize, Direction)> {
+    let mut traversal = Traversal::BreadthFirst;
+    let mut depth = usize::MAX;
+    let mut direction = Direction::Out;
+    let mut rest = args;
+    while let Some(option) = rest.first() {
+        match option.to_ascii_lowercase().as_str() {
+            "bfs" => traversal = Traversal::BreadthFirst,
+            "dfs" => traversal = Traversal::DepthFirst,
+            "depth" if rest.len() >= 2 => {
+                depth = parse_count(&rest[1])?;
+                rest = &rest[1..];
+            }
+            "direction" if rest.len() >= 2 => {
+                direction = Direction::parse(&rest[1])?;
+                rest = &rest[1..];
+            }
+            _ => return Err(syntax_error()),
+        }
+        rest = &rest[1..];
+    }
+    Ok((traversal, depth, direction))
+}
+
+fn properties_value(properties: Properties) -> Value {
+    Value::Array(
+        properties
+            .into_iter()
+            .flat_map(|(field, value)| [Value::BulkString(field), Value::BulkString(value)])
+            .collect(),
+    )
+}
+
+impl Handler {
+    pub(super) async fn handle_graph_addnode(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("GRAPH.ADDNODE requires a key and a node".to_string()),
+        };
+        let properties = match parse_properties(&args[2..]) {
+            Ok(properties) => properties,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .graph_addnode(args[0].clone(), args[1].clone(), properties)
+            .await
+        {
+            Ok(added) => integer(added as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_graph_addedge(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => return Value::Error("GRAPH.ADDEDGE requires a key and two nodes".to_string()),
+        };
+        let edge = match parse_edge(&args[3..]) {
+            Ok(edge) => edge,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .graph_addedge(args[0].clone(), args[1].clone(), args[2].clone(), edge)
+            .await
+        {
+            Ok(added) => integer(added as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_graph_delnode(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => return Value::Error("GRAPH.DELNODE requires a key and a node".to_string()),
+        };
+
+        match self
+            .client_store
+            .graph_delnode(args[0].clone(), args[1].clone())
+            .await
+        {
+            Ok(removed) => integer(removed as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_graph_deledge(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 3 => args,
+            _ => return Value::Error("GRAPH.DELEDGE requires a key and two nodes".to_string()),
+        };
+
+        match self
+            .client_store
+            .graph_deledge(args[0].clone(), args[1].clone(), args[2].clone())
+            .await
+        {
+            Ok(removed) => integer(removed as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_graph_getnode(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => return Value::Error("GRAPH.GETNODE requires a key and a node".to_string()),
+        };
+
+        match self
+            .client_store
+            .graph_getnode(args[0].clone(), args[1].clone())
+            .await
+        {
+            Ok(Some(properties)) => properties_value(properties),
+            Ok(None) => Value::Null,
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_graph_neighbours(&self, args: &[Value]) -> Value {
+        let (args, direction) = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => (args, Ok(Direction::Out)),
+            Some(args) if args.len() == 3 => {
+                let direction = Direction::parse(&args[2]);
+                (args, direction)
+            }
+            _ => return Value::Error("GRAPH.NEIGHBOURS requires a key and a node".to_string()),
+        };
+        let direction = match direction {
+            Ok(direction) => direction,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .graph_neighbours(args[0].clone(), args[1].clone(), direction)
+            .await
+        {
+            Ok(neighbours) => Value::Array(neighbours.into_iter().map(Value::BulkString).collect()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_graph_traverse(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("GRAPH.TRAVERSE requires a key and a node".to_string()),
+        };
+        let (traversal, depth, direction) = match parse_traversal(&args[2..]) {
+            Ok(options) => options,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .graph_traverse(
+                args[0].clone(),
+                args[1].clone(),
+                traversal,
+                depth,
+                direction,
+            )
+            .await
+        {
+            Ok(visited) => Value::Array(
+                visited
+                    .into_iter()
+                    .map(|(node, distance)| {
+                        Value::Array(vec![Value::BulkString(node), integer(distance)])
+                    })
+                    .collect(),
+            ),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_graph_shortestpath(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 3 || args.len() == 5 => args,
+            _ => {
+                return Value::Error("GRAPH.SHORTESTPATH requires a key and two nodes".to_string())
+            }
+        };
+        let direction = match &args[3..] {
+            [] => Ok(Direction::Out),
+            [option, direction] if option.eq_ignore_ascii_case("direction") => {
+                Direction::parse(direction)
+            }
+            _ => Err(syntax_error()),
+        };
+        let direction = match direction {
+            Ok(direction) => direction,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .graph_shortest_path(args[0].clone(), args[1].clone(), args[2].clone(), direction)
+            .await
+        {
+            Ok(Some((cost, path))) => Value::Array(vec![
+                Value::BulkString(cost.to_string()),
+                Value::Array(path.into_iter().map(Value::BulkString).collect()),
+            ]),
+            Ok(None) => Value::Null,
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_graph_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["GRAPH.ADDNODE", "org", "ceo", "name", "Ada"]))
+            .await?;
+        assert_eq!(response, int(1));
+        for (from, to, weight) in [
+            ("ceo", "cto", "1"),
+            ("cto", "dev", "2"),
+            ("ceo", "dev", "5"),
+        ] {
+            let response = handler
+                .handle_request(command(&[
+                    "GRAPH.ADDEDGE",
+                    "org",
+                    from,
+                    to,
+                    "WEIGHT",
+                    weight,
+                ]))
+                .await?;
+            assert_eq!(response, int(1));
+        }
+        let response = handler
+            .handle_request(command(&["GRAPH.ADDEDGE", "org", "a", "b", "WEIGHT", "-1"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&["GRAPH.GETNODE", "org", "ceo"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("name"), bulk("Ada")]));
+        let response = handler
+            .handle_request(command(&["GRAPH.NEIGHBOURS", "org", "dev", "IN"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("ceo"), bulk("cto")]));
+
+        let response = handler
+            .handle_request(command(&[
+                "GRAPH.TRAVERSE",
+                "org",
+                "dev",
+                "DFS",
+                "DEPTH",
+                "1",
+                "DIRECTION",
+                "IN",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![
+                Value::Array(vec![bulk("dev"), int(0)]),
+                Value::Array(vec![bulk("ceo"), int(1)]),
+                Value::Array(vec![bulk("cto"), int(1)]),
+            ])
+        );
+
+        let response = handler
+            .handle_request(command(&["GRAPH.SHORTESTPATH", "org", "ceo", "dev"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![
+                bulk("3"),
+                Value::Array(vec![bulk("ceo"), bulk("cto"), bulk("dev")]),
+            ])
+        );
+        let response = handler
+            .handle_request(command(&["GRAPH.SHORTESTPATH", "org", "dev", "ceo"]))
+            .await?;
+        assert_eq!(response, Value::Null);
+
+        let response = handler
+            .handle_request(command(&["GRAPH.DELEDGE", "org", "ceo", "dev"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["GRAPH.DELNODE", "org", "cto"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["GRAPH.NEIGHBOURS", "org", "ceo"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![]));
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use crate::resp::value::Value;
+use crate::server::handler::{
+    bulk_strings, expire_reply, integer, parse_deadline, parse_members, persist_reply, ttl_reply,
+    Handler,
+};
+
+fn optional_value(value: Option<String>) -> Value {
+    value.map(Value::BulkString).unwrap_or(Value::Null)
+}
+
+impl Handler {
+    pub(super) async fn handle_hset(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 && args.len() % 2 == 1 => args,
+            _ => return Value::Error("HSET requires a key and field value pairs".to_string()),
+        };
+
+        let fields = args[1..]
+            .chunks(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+        match self.client_store.hset(args[0].clone(), fields).await {
+            Ok(added) => integer(added),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    /// Handle HMGET, or HGET when `single` is set.
+    pub(super) async fn handle_hmget(&self, args: &[Value], single: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if single && args.len() == 2 => args,
+            Some(args) if !single && args.len() >= 2 => args,
+            _ if single => return Value::Error("HGET requires a key and a field".to_string()),
+            _ => return Value::Error("HMGET requires a key and fields".to_string()),
+        };
+
+        let fields = args[1..].to_vec();
+        match self.client_store.hmget(args[0].clone(), fields).await {
+            Ok(mut values) if single => optional_value(values.remove(0)),
+            Ok(values) => Value::Array(values.into_iter().map(optional_value).collect()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_hdel(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("HDEL requires a key and fields".to_string()),
+        };
+
+        let fields = args[1..].to_vec();
+        match self.client_store.hdel(args[0].clone(), fields).await {
+            Ok(removed) => integer(removed),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_hgetall(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("HGETALL requires a key".to_string()),
+        };
+
+        match self.client_store.hgetall(args[0].clone()).await {
+            Ok(fields) => Value::Array(
+                fields
+                    .into_iter()
+                    .flat_map(|(field, value)| [Value::BulkString(field), Value::BulkString(value)])
+                    .collect(),
+            ),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_hlen(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("HLEN requires a key".to_string()),
+        };
+
+        match self.client_store.hlen(args[0].clone()).await {
+            Ok(len) => integer(len),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    /// Handle HEXPIRE, or HPEXPIRE when `millis` is set.
+    pub(super) async fn handle_hexpire(&self, args: &[Value], millis: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 5 => args,
+            _ => {
+                return Value::Error(
+                    "HEXPIRE and HPEXPIRE require a key, a timeout and fields".to_string(),
+                )
+            }
+        };
+        let deadline = match parse_deadline(&args[1], millis) {
+            Ok(deadline) => deadline,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        let fields = match parse_members(&args[2..], "FIELDS") {
+            Ok(fields) => fields,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .hexpire(args[0].clone(), fields, deadline)
+            .await
+        {
+            Ok(expired) => expire_reply(expired),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    /// Handle HTTL, or HPTTL when `millis` is set.
+    pub(super) async fn handle_httl(&self, args: &[Value], millis: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 4 => args,
+            _ => return Value::Error("HTTL and HPTTL require a key and fields".to_string()),
+        };
+        let fields = match parse_members(&args[1..], "FIELDS") {
+            Ok(fields) => fields,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.httl(args[0].clone(), fields).await {
+            Ok(remaining) => ttl_reply(remaining, millis),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_hpersist(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 4 => args,
+            _ => return Value::Error("HPERSIST requires a key and fields".to_string()),
+        };
+        let fields = match parse_members(&args[1..], "FIELDS") {
+            Ok(fields) => fields,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.hpersist(args[0].clone(), fields).await {
+            Ok(persisted) => persist_reply(persisted),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_hash_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["HSET", "user", "name", "Ada", "lang", "en"]))
+            .await?;
+        assert_eq!(response, int(2));
+        let response = handler
+            .handle_request(command(&["HSET", "user", "name"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&["HGET", "user", "name"]))
+            .await?;
+        assert_eq!(response, bulk("Ada"));
+        let response = handler
+            .handle_request(command(&["HMGET", "user", "lang", "missing"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("en"), Value::Null]));
+        let response = handler
+            .handle_request(command(&["HGETALL", "user"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![bulk("lang"), bulk("en"), bulk("name"), bulk("Ada")])
+        );
+
+        let response = handler
+            .handle_request(command(&["HDEL", "user", "lang", "missing"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler.handle_request(command(&["HLEN", "user"])).await?;
+        assert_eq!(response, int(1));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_field_expiry_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        handler
+            .handle_request(command(&[
+                "HSET", "sessions", "phone", "t1", "laptop", "t2",
+            ]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&[
+                "HEXPIRE", "sessions", "100", "FIELDS", "2", "phone", "tablet",
+            ]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(1), int(-2)]));
+        let response = handler
+            .handle_request(command(&[
+                "HEXPIRE", "sessions", "100", "FIELDS", "2", "phone",
+            ]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = handler
+            .handle_request(command(&[
+                "HEXPIRE",
+                "sessions",
+                "9223372036854775807",
+                "FIELDS",
+                "1",
+                "laptop",
+            ]))
+            .await?;
+        assert_eq!(response, Value::Error("invalid expire time".to_string()));
+
+        let response = handler
+            .handle_request(command(&[
+                "HTTL", "sessions", "FIELDS", "2", "phone", "laptop",
+            ]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(100), int(-1)]));
+        let response = handler
+            .handle_request(command(&["HPERSIST", "sessions", "FIELDS", "1", "phone"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(1)]));
+
+        let response = handler
+            .handle_request(command(&[
+                "HPEXPIRE", "sessions", "0", "FIELDS", "1", "laptop",
+            ]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(2)]));
+        let response = handler
+            .handle_request(command(&["HGETALL", "sessions"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("phone"), bulk("t1")]));
+        Ok(())
+    }
+}
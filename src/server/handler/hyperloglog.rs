@@ -0,0 +1,81 @@
+use crate::resp::value::Value;
+use crate::server::handler::{bulk_strings, integer, Handler};
+
+impl Handler {
+    pub(super) async fn handle_pfadd(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("PFADD requires a key".to_string()),
+        };
+
+        let elements = args[1..].to_vec();
+        match self.client_store.pfadd(args[0].clone(), elements).await {
+            Ok(changed) => integer(changed as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_pfcount(&self, args: &[Value]) -> Value {
+        let keys = match bulk_strings(args) {
+            Some(keys) if !keys.is_empty() => keys,
+            _ => return Value::Error("PFCOUNT requires keys".to_string()),
+        };
+
+        match self.client_store.pfcount(keys).await {
+            Ok(count) => integer(count),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_pfmerge(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("PFMERGE requires a destination".to_string()),
+        };
+
+        let keys = args[1..].to_vec();
+        match self.client_store.pfmerge(args[0].clone(), keys).await {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_pf_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["PFADD", "visitors", "alice", "bob", "carol"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["PFADD", "visitors", "bob"]))
+            .await?;
+        assert_eq!(response, int(0));
+        handler
+            .handle_request(command(&["PFADD", "other", "dave", "alice"]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&["PFCOUNT", "visitors"]))
+            .await?;
+        assert_eq!(response, int(3));
+        let response = handler
+            .handle_request(command(&["PFMERGE", "all", "visitors", "other"]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        let response = handler.handle_request(command(&["PFCOUNT", "all"])).await?;
+        assert_eq!(response, int(4));
+        Ok(())
+    }
+}
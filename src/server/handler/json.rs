@@ -0,0 +1,310 @@
+use crate::cache::json::{JsonPath, SetCondition};
+use crate::resp::value::Value;
+use crate::server::handler::{bulk_strings, integer, syntax_error, Handler};
+use anyhow::{Error, Result};
+use serde_json::Value as Json;
+
+fn parse_json(s: &str) -> Result<Json> {
+    serde_json::from_str(s).map_err(|e| Error::msg(format!("invalid JSON: {}", e)))
+}
+
+/// Parse the optional path following the key, defaulting to the root.
+fn parse_optional_path(args: &[String]) -> Result<JsonPath> {
+    match args {
+        [] => Ok(JsonPath::root()),
+        [path] => JsonPath::parse(path),
+        _ => Err(syntax_error()),
+    }
+}
+
+fn missing_path(path: &str) -> Value {
+    Value::Error(format!("Path '{}' does not exist", path))
+}
+
+impl Handler {
+    pub(super) async fn handle_json_set(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 3 || args.len() == 4 => args,
+            _ => return Value::Error("JSON.SET requires a key, a path and a value".to_string()),
+        };
+        let condition = match args.get(3).map(|s| s.to_ascii_lowercase()) {
+            None => None,
+            Some(option) if option == "nx" => Some(SetCondition::Nx),
+            Some(option) if option == "xx" => Some(SetCondition::Xx),
+            Some(_) => return Value::Error(syntax_error().to_string()),
+        };
+        let (path, value) = match (JsonPath::parse(&args[1]), parse_json(&args[2])) {
+            (Ok(path), Ok(value)) => (path, value),
+            (Err(e), _) | (_, Err(e)) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .json_set(args[0].clone(), path, value, condition)
+            .await
+        {
+            Ok(true) => Value::SimpleString("OK".to_string()),
+            Ok(false) => Value::Null,
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_json_get(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("JSON.GET requires a key".to_string()),
+        };
+        let texts = match &args[1..] {
+            [] => vec![".".to_string()],
+            paths => paths.to_vec(),
+        };
+        let paths = match texts
+            .iter()
+            .map(|path| JsonPath::parse(path))
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(paths) => paths,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        let legacy = paths.iter().all(JsonPath::is_legacy);
+
+        let values = match self.client_store.json_get(args[0].clone(), paths).await {
+            Ok(Some(values)) => values,
+            Ok(None) => return Value::Null,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        // legacy paths reply with their first match, JSONPath with all of them
+        let mut replies = serde_json::Map::new();
+        for (text, mut matches) in texts.into_iter().zip(values) {
+            let reply = if legacy {
+                if matches.is_empty() {
+                    return missing_path(&text);
+                }
+                matches.swap_remove(0)
+            } else {
+                Json::Array(matches)
+            };
+            replies.insert(text, reply);
+        }
+        let reply = if replies.len() == 1 {
+            replies.into_iter().next().unwrap().1
+        } else {
+            Json::Object(replies)
+        };
+        Value::BulkString(reply.to_string())
+    }
+
+    pub(super) async fn handle_json_del(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("JSON.DEL requires a key".to_string()),
+        };
+        let path = match parse_optional_path(&args[1..]) {
+            Ok(path) => path,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.json_del(args[0].clone(), path).await {
+            Ok(deleted) => integer(deleted),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_json_numincrby(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 3 => args,
+            _ => {
+                return Value::Error(
+                    "JSON.NUMINCRBY requires a key, a path and a number".to_string(),
+                )
+            }
+        };
+        let by = match parse_json(&args[2]) {
+            Ok(Json::Number(by)) => by,
+            Ok(_) => return Value::Error("value is not a number".to_string()),
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        let path = match JsonPath::parse(&args[1]) {
+            Ok(path) => path,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        let legacy = path.is_legacy();
+
+        match self
+            .client_store
+            .json_numincrby(args[0].clone(), path, by)
+            .await
+        {
+            Ok(results) if legacy => match results.into_iter().next() {
+                Some(Some(number)) => Value::BulkString(number.to_string()),
+                Some(None) => Value::Error(format!("Path '{}' is not a number", args[1])),
+                None => missing_path(&args[1]),
+            },
+            Ok(results) => {
+                let results = results
+                    .into_iter()
+                    .map(|number| number.map(Json::Number).unwrap_or(Json::Null))
+                    .collect::<Vec<_>>();
+                Value::BulkString(Json::Array(results).to_string())
+            }
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_json_arrappend(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => {
+                return Value::Error("JSON.ARRAPPEND requires a key, a path and values".to_string())
+            }
+        };
+        let values = match args[2..]
+            .iter()
+            .map(|value| parse_json(value))
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(values) => values,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        let path = match JsonPath::parse(&args[1]) {
+            Ok(path) => path,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        let legacy = path.is_legacy();
+
+        match self
+            .client_store
+            .json_arrappend(args[0].clone(), path, values)
+            .await
+        {
+            Ok(lengths) if legacy => match lengths.into_iter().next() {
+                Some(Some(length)) => integer(length),
+                Some(None) => Value::Error(format!("Path '{}' is not an array", args[1])),
+                None => missing_path(&args[1]),
+            },
+            Ok(lengths) => Value::Array(
+                lengths
+                    .into_iter()
+                    .map(|length| length.map(integer).unwrap_or(Value::Null))
+                    .collect(),
+            ),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_json_type(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("JSON.TYPE requires a key".to_string()),
+        };
+        let path = match parse_optional_path(&args[1..]) {
+            Ok(path) => path,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        let legacy = path.is_legacy();
+
+        match self.client_store.json_type(args[0].clone(), path).await {
+            Ok(Some(types)) if legacy => types
+                .first()
+                .map(|name| Value::SimpleString(name.to_string()))
+                .unwrap_or(Value::Null),
+            Ok(Some(types)) => Value::Array(
+                types
+                    .into_iter()
+                    .map(|name| Value::BulkString(name.to_string()))
+                    .collect(),
+            ),
+            Ok(None) => Value::Null,
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_json_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&[
+                "JSON.SET",
+                "user",
+                "$",
+                r#"{"name":"Ada","visits":1,"tags":["admin"]}"#,
+            ]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        let response = handler
+            .handle_request(command(&["JSON.SET", "user", "$.name", r#""Grace""#, "NX"]))
+            .await?;
+        assert_eq!(response, Value::Null);
+        let response = handler
+            .handle_request(command(&["JSON.SET", "user", "$.name", "{bad"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&["JSON.NUMINCRBY", "user", "$.visits", "2"]))
+            .await?;
+        assert_eq!(response, bulk("[3]"));
+        let response = handler
+            .handle_request(command(&["JSON.NUMINCRBY", "user", ".visits", "0.5"]))
+            .await?;
+        assert_eq!(response, bulk("3.5"));
+        let response = handler
+            .handle_request(command(&["JSON.ARRAPPEND", "user", "$.tags", r#""dev""#]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(2)]));
+
+        let response = handler
+            .handle_request(command(&["JSON.GET", "user", "$.tags[-1]"]))
+            .await?;
+        assert_eq!(response, bulk(r#"["dev"]"#));
+        let response = handler
+            .handle_request(command(&["JSON.GET", "user", ".name"]))
+            .await?;
+        assert_eq!(response, bulk(r#""Ada""#));
+        let response = handler
+            .handle_request(command(&["JSON.GET", "user", ".name", ".visits"]))
+            .await?;
+        assert_eq!(response, bulk(r#"{".name":"Ada",".visits":3.5}"#));
+
+        let response = handler
+            .handle_request(command(&["JSON.TYPE", "user", "$.*"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![bulk("string"), bulk("number"), bulk("array")])
+        );
+        let response = handler
+            .handle_request(command(&["JSON.TYPE", "user"]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("object".to_string()));
+
+        let response = handler
+            .handle_request(command(&["JSON.DEL", "user", "$.tags"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["JSON.GET", "user"]))
+            .await?;
+        assert_eq!(response, bulk(r#"{"name":"Ada","visits":3.5}"#));
+        let response = handler
+            .handle_request(command(&["JSON.DEL", "user"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["JSON.GET", "user"]))
+            .await?;
+        assert_eq!(response, Value::Null);
+        Ok(())
+    }
+}
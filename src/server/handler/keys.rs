@@ -0,0 +1,159 @@
+use crate::resp::value::Value;
+use crate::server::handler::{bulk_strings, integer, syntax_error, Handler};
+
+impl Handler {
+    /// Handle RENAME, or RENAMENX when `replace` is unset.
+    pub(super) async fn handle_rename(&self, args: &[Value], replace: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => return Value::Error("RENAME and RENAMENX require two keys".to_string()),
+        };
+
+        match self
+            .client_store
+            .rename(args[0].clone(), args[1].clone(), replace)
+            .await
+        {
+            Ok(_) if replace => Value::SimpleString("OK".to_string()),
+            Ok(renamed) => integer(renamed as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    /// Handle `COPY source destination [DB db] [REPLACE]`.
+    pub(super) async fn handle_copy(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("COPY requires a source and a destination".to_string()),
+        };
+        let mut target = self.client_store.clone();
+        let mut replace = false;
+        let mut rest = &args[2..];
+        while let Some(option) = rest.first() {
+            match option.to_ascii_lowercase().as_str() {
+                "replace" => replace = true,
+                "db" if rest.len() >= 2 => {
+                    target = match self.parse_database(&rest[1]) {
+                        Ok(db) => self.databases.get(db).unwrap(),
+                        Err(e) => return Value::Error(e.to_string()),
+                    };
+                    rest = &rest[1..];
+                }
+                _ => return Value::Error(syntax_error().to_string()),
+            }
+            rest = &rest[1..];
+        }
+
+        match self
+            .client_store
+            .copy(args[0].clone(), &target, args[1].clone(), replace)
+            .await
+        {
+            Ok(copied) => integer(copied as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_move(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => return Value::Error("MOVE requires a key and a database".to_string()),
+        };
+        let target = match self.parse_database(&args[1]) {
+            Ok(db) => self.databases.get(db).unwrap(),
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.move_to(args[0].clone(), &target).await {
+            Ok(moved) => integer(moved as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_touch(&self, args: &[Value]) -> Value {
+        let keys = match bulk_strings(args) {
+            Some(keys) if !keys.is_empty() => keys,
+            _ => return Value::Error("TOUCH requires keys".to_string()),
+        };
+
+        integer(self.client_store.touch(keys).await)
+    }
+
+    pub(super) async fn handle_unlink(&self, args: &[Value]) -> Value {
+        let keys = match bulk_strings(args) {
+            Some(keys) if !keys.is_empty() => keys,
+            _ => return Value::Error("UNLINK requires keys".to_string()),
+        };
+
+        integer(self.client_store.unlink(keys).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_key_management_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        handler
+            .handle_request(command(&["SET", "config:next", "v2", "ex", "100"]))
+            .await?;
+        handler
+            .handle_request(command(&["SET", "config", "v1"]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&["RENAMENX", "config:next", "config"]))
+            .await?;
+        assert_eq!(response, int(0));
+        let response = handler
+            .handle_request(command(&["RENAME", "config:next", "config"]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        let response = handler.handle_request(command(&["GET", "config"])).await?;
+        assert_eq!(response, Value::BulkString("v2".to_string()));
+        let response = handler.handle_request(command(&["TTL", "config"])).await?;
+        assert_eq!(response, int(100));
+        let response = handler
+            .handle_request(command(&["RENAME", "config:next", "config"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&["COPY", "config", "backup"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["COPY", "config", "backup"]))
+            .await?;
+        assert_eq!(response, int(0));
+        let response = handler
+            .handle_request(command(&["COPY", "config", "backup", "REPLACE"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["MOVE", "config", "1"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&["TOUCH", "config", "backup", "missing"]))
+            .await?;
+        assert_eq!(response, int(2));
+        let response = handler
+            .handle_request(command(&["UNLINK", "config", "backup", "missing"]))
+            .await?;
+        assert_eq!(response, int(2));
+        let response = handler
+            .handle_request(command(&["EXISTS", "config"]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("false".to_string()));
+        Ok(())
+    }
+}
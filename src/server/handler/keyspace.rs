@@ -0,0 +1,291 @@
+use crate::cache::expiry::Expiry;
+use crate::cache::keyspace::ScanOptions;
+use crate::resp::value::Value;
+use crate::server::handler::{
+    bulk_strings, integer, parse_count, parse_ttl, syntax_error, Handler,
+};
+use anyhow::Result;
+
+/// Parse `cursor [MATCH pattern] [COUNT count] [TYPE type]`, the cursor being parsed apart.
+fn parse_scan(args: &[String]) -> Result<ScanOptions> {
+    let mut options = ScanOptions::default();
+    let mut rest = &args[1..];
+    while let [option, value, tail @ ..] = rest {
+        match option.to_ascii_lowercase().as_str() {
+            "match" => options.pattern = Some(value.clone()),
+            "count" => options.count = parse_count(value)?,
+            "type" => options.type_name = Some(value.clone()),
+            _ => return Err(syntax_error()),
+        }
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        return Err(syntax_error());
+    }
+    Ok(options)
+}
+
+/// Parse `[REV] [LIMIT count]`, returning whether to go in reverse and the limit.
+fn parse_range(args: &[String]) -> Result<(bool, Option<usize>)> {
+    let mut reverse = false;
+    let mut limit = None;
+    let mut rest = args;
+    while let Some(option) = rest.first() {
+        match option.to_ascii_lowercase().as_str() {
+            "rev" => reverse = true,
+            "limit" if rest.len() >= 2 => {
+                limit = Some(parse_count(&rest[1])?);
+                rest = &rest[1..];
+            }
+            _ => return Err(syntax_error()),
+        }
+        rest = &rest[1..];
+    }
+    Ok((reverse, limit))
+}
+
+impl Handler {
+    pub(super) async fn handle_scan(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("SCAN requires a cursor".to_string()),
+        };
+        let cursor = match args[0].parse::<u64>() {
+            Ok(cursor) => cursor,
+            Err(_) => return Value::Error("invalid cursor".to_string()),
+        };
+        let options = match parse_scan(&args) {
+            Ok(options) => options,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.scan(cursor, options).await {
+            Ok((cursor, keys)) => Value::Array(vec![
+                Value::BulkString(cursor.to_string()),
+                Value::Array(keys.into_iter().map(Value::BulkString).collect()),
+            ]),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_keys(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("KEYS requires a pattern".to_string()),
+        };
+
+        let keys = self.client_store.keys(args[0].clone()).await;
+        Value::Array(keys.into_iter().map(Value::BulkString).collect())
+    }
+
+    pub(super) async fn handle_krange(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("KRANGE requires a start and an end key".to_string()),
+        };
+        let (reverse, limit) = match parse_range(&args[2..]) {
+            Ok(options) => options,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        // `+` stands for the end of the keyspace
+        let end = (args[1] != "+").then(|| args[1].clone());
+        let entries = self
+            .client_store
+            .range(args[0].clone(), end, reverse, limit)
+            .await;
+        Value::Array(
+            entries
+                .into_iter()
+                .flat_map(|(key, value)| {
+                    [
+                        Value::BulkString(key),
+                        value.map_or(Value::Null, Value::from),
+                    ]
+                })
+                .collect(),
+        )
+    }
+
+    pub(super) async fn handle_prefixcount(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("PREFIXCOUNT requires a prefix".to_string()),
+        };
+
+        integer(self.client_store.prefix_count(args[0].clone()).await)
+    }
+
+    pub(super) async fn handle_prefixdel(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("PREFIXDEL requires a prefix".to_string()),
+        };
+
+        integer(self.client_store.prefix_delete(args[0].clone()).await)
+    }
+
+    /// Handle PREFIXEXPIRE, or PREFIXPEXPIRE when `millis` is set.
+    pub(super) async fn handle_prefixexpire(&self, args: &[Value], millis: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => {
+                return Value::Error(
+                    "PREFIXEXPIRE and PREFIXPEXPIRE require a prefix and a timeout".to_string(),
+                )
+            }
+        };
+        let expiry = match parse_ttl(&args[1], millis).and_then(Expiry::after) {
+            Ok(expiry) => expiry,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        let expired = self
+            .client_store
+            .prefix_expire(args[0].clone(), expiry)
+            .await;
+        integer(expired)
+    }
+
+    pub(super) async fn handle_type(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("TYPE requires a key".to_string()),
+        };
+
+        let type_name = self.client_store.key_type(args[0].clone()).await;
+        Value::SimpleString(type_name.to_string())
+    }
+
+    pub(super) async fn handle_randomkey(&self, args: &[Value]) -> Value {
+        if !args.is_empty() {
+            return Value::Error("RANDOMKEY takes no arguments".to_string());
+        }
+
+        match self.client_store.random_key().await {
+            Some(key) => Value::BulkString(key),
+            None => Value::Null,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_keyspace_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        for key in ["user:1", "user:2", "session:1"] {
+            handler
+                .handle_request(command(&["SET", key, "value"]))
+                .await?;
+        }
+        handler
+            .handle_request(command(&["HSET", "user:3", "name", "Ada"]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&["SCAN", "0", "MATCH", "user:*", "COUNT", "2"]))
+            .await?;
+        let Value::Array(reply) = response else {
+            panic!("SCAN replies with an array");
+        };
+        assert_ne!(reply[0], bulk("0"));
+        assert_eq!(reply[1], Value::Array(vec![bulk("user:1")]));
+        let Value::BulkString(cursor) = &reply[0] else {
+            panic!("the cursor is a bulk string");
+        };
+        assert!(cursor.parse::<u64>().is_ok());
+        let response = handler
+            .handle_request(command(&[
+                "SCAN", cursor, "MATCH", "user:*", "TYPE", "hash",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![bulk("0"), Value::Array(vec![bulk("user:3")])])
+        );
+        let response = handler
+            .handle_request(command(&["SCAN", "0", "COUNT"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = handler.handle_request(command(&["SCAN", "1abc"])).await?;
+        assert_eq!(response, Value::Error("invalid cursor".to_string()));
+
+        let response = handler
+            .handle_request(command(&["KEYS", "user:[12]"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("user:1"), bulk("user:2")]));
+        let response = handler.handle_request(command(&["TYPE", "user:3"])).await?;
+        assert_eq!(response, Value::SimpleString("hash".to_string()));
+        let response = handler.handle_request(command(&["RANDOMKEY"])).await?;
+        assert!(matches!(response, Value::BulkString(_)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_range_and_prefix_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        for key in ["events:16:a", "events:16:b", "events:17:a"] {
+            handler.handle_request(command(&["SET", key, "1"])).await?;
+        }
+        handler
+            .handle_request(command(&["HSET", "events:17:meta", "n", "1"]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&["KRANGE", "events:16", "events:17"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![
+                bulk("events:16:a"),
+                bulk("1"),
+                bulk("events:16:b"),
+                bulk("1")
+            ])
+        );
+        let response = handler
+            .handle_request(command(&["KRANGE", "events:", "+", "REV", "LIMIT", "1"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![bulk("events:17:meta"), Value::Null])
+        );
+
+        let response = handler
+            .handle_request(command(&["PREFIXCOUNT", "events:"]))
+            .await?;
+        assert_eq!(response, int(4));
+        let response = handler
+            .handle_request(command(&["PREFIXEXPIRE", "events:17:", "100"]))
+            .await?;
+        assert_eq!(response, int(2));
+        let response = handler
+            .handle_request(command(&[
+                "PREFIXEXPIRE",
+                "events:17:",
+                "9223372036854775807",
+            ]))
+            .await?;
+        assert_eq!(response, Value::Error("invalid expire time".to_string()));
+        let response = handler
+            .handle_request(command(&["TTL", "events:17:meta"]))
+            .await?;
+        assert_eq!(response, int(100));
+        let response = handler
+            .handle_request(command(&["PREFIXDEL", "events:16:"]))
+            .await?;
+        assert_eq!(response, int(2));
+        let response = handler
+            .handle_request(command(&["PREFIXCOUNT", "events:"]))
+            .await?;
+        assert_eq!(response, int(2));
+        Ok(())
+    }
+}
@@ -0,0 +1,280 @@
+use crate::cache::list::End;
+use crate::resp::value::Value;
+use crate::server::handler::{
+    bulk_strings, integer, parse_count, parse_integer, parse_timeout, Handler,
+};
+
+fn strings_value(values: Vec<String>) -> Value {
+    Value::Array(values.into_iter().map(Value::BulkString).collect())
+}
+
+impl Handler {
+    pub(super) async fn handle_push(&self, args: &[Value], end: End, create: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("LPUSH and RPUSH require a key and values".to_string()),
+        };
+
+        let values = args[1..].to_vec();
+        match self
+            .client_store
+            .push(args[0].clone(), end, values, create)
+            .await
+        {
+            Ok(len) => integer(len),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_pop(&self, args: &[Value], end: End) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 || args.len() == 2 => args,
+            _ => return Value::Error("LPOP and RPOP require a key".to_string()),
+        };
+        let count = match args.get(1).map(|count| parse_count(count)) {
+            Some(Ok(count)) => Some(count),
+            Some(Err(e)) => return Value::Error(e.to_string()),
+            None => None,
+        };
+
+        let popped = self
+            .client_store
+            .pop(args[0].clone(), end, count.unwrap_or(1))
+            .await;
+        match (popped, count) {
+            // without a count, a single value is returned instead of an array
+            (Ok(Some(mut values)), None) => Value::BulkString(values.remove(0)),
+            (Ok(Some(values)), Some(_)) => strings_value(values),
+            (Ok(None), _) => Value::Null,
+            (Err(e), _) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_llen(&self, args: &[Value]) -> Value {
+        if let Some(Value::BulkString(key)) = args.first() {
+            match self.client_store.llen(key.clone()).await {
+                Ok(len) => integer(len),
+                Err(e) => Value::Error(e.to_string()),
+            }
+        } else {
+            Value::Error("LLEN requires one argument".to_string())
+        }
+    }
+
+    pub(super) async fn handle_lrange(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 3 => args,
+            _ => return Value::Error("LRANGE requires a key, a start and a stop".to_string()),
+        };
+        let (start, stop) = match (parse_integer(&args[1]), parse_integer(&args[2])) {
+            (Ok(start), Ok(stop)) => (start, stop),
+            (Err(e), _) | (_, Err(e)) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.lrange(args[0].clone(), start, stop).await {
+            Ok(values) => strings_value(values),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_lmove(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 4 => args,
+            _ => {
+                return Value::Error(
+                    "LMOVE requires a source, a destination and two directions".to_string(),
+                )
+            }
+        };
+        let (from, to) = match (End::parse(&args[2]), End::parse(&args[3])) {
+            (Ok(from), Ok(to)) => (from, to),
+            (Err(e), _) | (_, Err(e)) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .lmove(args[0].clone(), args[1].clone(), from, to)
+            .await
+        {
+            Ok(Some(value)) => Value::BulkString(value),
+            Ok(None) => Value::Null,
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_blocking_pop(&self, args: &[Value], end: End) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("BLPOP and BRPOP require keys and a timeout".to_string()),
+        };
+        let (keys, timeout) = args.split_at(args.len() - 1);
+        let timeout = match parse_timeout(&timeout[0]) {
+            Ok(timeout) => timeout,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        // the first non empty list in argument order is served
+        let store = &self.client_store;
+        let popped = store
+            .block_on(keys, timeout, || async move {
+                for key in keys {
+                    if let Some(mut values) = store.pop(key.clone(), end, 1).await? {
+                        return Ok(Some((key.clone(), values.remove(0))));
+                    }
+                }
+                Ok(None)
+            })
+            .await;
+
+        match popped {
+            Ok(Some((key, value))) => {
+                Value::Array(vec![Value::BulkString(key), Value::BulkString(value)])
+            }
+            Ok(None) => Value::Null,
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_blmove(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 5 => args,
+            _ => {
+                return Value::Error(
+                    "BLMOVE requires a source, a destination, two directions and a timeout"
+                        .to_string(),
+                )
+            }
+        };
+        let (from, to) = match (End::parse(&args[2]), End::parse(&args[3])) {
+            (Ok(from), Ok(to)) => (from, to),
+            (Err(e), _) | (_, Err(e)) => return Value::Error(e.to_string()),
+        };
+        let timeout = match parse_timeout(&args[4]) {
+            Ok(timeout) => timeout,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        let store = &self.client_store;
+        let (source, destination) = (&args[0], &args[1]);
+        let moved = store
+            .block_on(&args[..1], timeout, || {
+                store.lmove(source.clone(), destination.clone(), from, to)
+            })
+            .await;
+
+        match moved {
+            Ok(Some(value)) => Value::BulkString(value),
+            Ok(None) => Value::Null,
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_list_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["RPUSH", "list", "a", "b", "c"]))
+            .await?;
+        assert_eq!(response, int(3));
+        let response = handler
+            .handle_request(command(&["LPUSHX", "missing", "a"]))
+            .await?;
+        assert_eq!(response, int(0));
+
+        let response = handler.handle_request(command(&["LPOP", "list"])).await?;
+        assert_eq!(response, bulk("a"));
+        let response = handler
+            .handle_request(command(&["RPOP", "list", "1"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("c")]));
+
+        let response = handler
+            .handle_request(command(&["LMOVE", "list", "other", "LEFT", "RIGHT"]))
+            .await?;
+        assert_eq!(response, bulk("b"));
+        let response = handler.handle_request(command(&["LLEN", "list"])).await?;
+        assert_eq!(response, int(0));
+        let response = handler
+            .handle_request(command(&["LRANGE", "other", "0", "-1"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("b")]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blpop_command() -> Result<()> {
+        let cache = Arc::new(Cache::default());
+        let mut handler = Handler::new(cache.clone(), None);
+
+        let response = handler
+            .handle_request(command(&["BLPOP", "a", "b", "0.05"]))
+            .await?;
+        assert_eq!(response, Value::Null);
+
+        let response = handler
+            .handle_request(command(&["BLPOP", "a", "-1"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        for timeout in ["1e300", "1e19"] {
+            let response = handler
+                .handle_request(command(&["BLPOP", "a", timeout]))
+                .await?;
+            assert_eq!(
+                response,
+                Value::Error("timeout is out of range".to_string())
+            );
+        }
+
+        let blocked = tokio::spawn(async move {
+            let mut handler = Handler::new(cache, None);
+            handler
+                .handle_request(command(&["BRPOP", "a", "b", "0"]))
+                .await
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        handler
+            .handle_request(command(&["RPUSH", "b", "x", "y"]))
+            .await?;
+
+        let response = blocked.await??;
+        assert_eq!(response, Value::Array(vec![bulk("b"), bulk("y")]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blmove_command() -> Result<()> {
+        let cache = Arc::new(Cache::default());
+        let mut handler = Handler::new(cache.clone(), None);
+
+        let blocked = tokio::spawn(async move {
+            let mut handler = Handler::new(cache, None);
+            handler
+                .handle_request(command(&["BLMOVE", "jobs", "taken", "RIGHT", "LEFT", "1"]))
+                .await
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        handler
+            .handle_request(command(&["LPUSH", "jobs", "job"]))
+            .await?;
+
+        let response = blocked.await??;
+        assert_eq!(response, bulk("job"));
+        let response = handler
+            .handle_request(command(&["LRANGE", "taken", "0", "-1"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("job")]));
+        Ok(())
+    }
+}
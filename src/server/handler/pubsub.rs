@@ -0,0 +1,273 @@
+use crate::cache::pubsub::Message;
+use crate::resp::value::Value;
+use crate::server::handler::{bulk_strings, integer, Handler};
+
+/// Encode a message pushed to a subscriber, as `message` or `pmessage` when received through
+/// a pattern.
+pub(super) fn message_value(message: Message) -> Value {
+    let mut value = match message.pattern {
+        Some(pattern) => vec![
+            Value::BulkString("pmessage".to_string()),
+            Value::BulkString(pattern),
+        ],
+        None => vec![Value::BulkString("message".to_string())],
+    };
+    value.push(Value::BulkString(message.channel));
+    value.push(Value::BulkString(message.payload));
+    Value::Array(value)
+}
+
+/// Encode the confirmation of a subscription change, with the number of subscriptions left.
+fn confirmation(kind: &str, channel: Option<String>, count: usize) -> Value {
+    Value::Array(vec![
+        Value::BulkString(kind.to_string()),
+        channel.map_or(Value::Null, Value::BulkString),
+        integer(count),
+    ])
+}
+
+impl Handler {
+    /// Reply with the first of `replies`, the others being written right after it.
+    fn reply_many(&mut self, mut replies: Vec<Value>) -> Value {
+        let first = replies.remove(0);
+        self.replies.extend(replies);
+        first
+    }
+
+    /// Handle SUBSCRIBE, or PSUBSCRIBE when `pattern` is set, which put the client in
+    /// subscriber mode.
+    pub(super) async fn handle_subscribe(&mut self, args: &[Value], pattern: bool) -> Value {
+        let channels = match bulk_strings(args) {
+            Some(channels) if !channels.is_empty() => channels,
+            _ => return Value::Error("SUBSCRIBE and PSUBSCRIBE require channels".to_string()),
+        };
+
+        let subscriber = self
+            .subscriber
+            .get_or_insert_with(|| self.pubsub.subscriber());
+        let replies = channels
+            .into_iter()
+            .map(|channel| match pattern {
+                true => {
+                    let count = subscriber.psubscribe(channel.clone());
+                    confirmation("psubscribe", Some(channel), count)
+                }
+                false => {
+                    let count = subscriber.subscribe(channel.clone());
+                    confirmation("subscribe", Some(channel), count)
+                }
+            })
+            .collect();
+        self.reply_many(replies)
+    }
+
+    /// Handle UNSUBSCRIBE, or PUNSUBSCRIBE when `pattern` is set, from every channel if none is
+    /// given. The client leaves subscriber mode once it has no subscription left.
+    pub(super) async fn handle_unsubscribe(&mut self, args: &[Value], pattern: bool) -> Value {
+        let channels = match bulk_strings(args) {
+            Some(channels) => channels,
+            None => return Value::Error("UNSUBSCRIBE and PUNSUBSCRIBE take channels".to_string()),
+        };
+        let kind = match pattern {
+            true => "punsubscribe",
+            false => "unsubscribe",
+        };
+
+        let subscriber = match self.subscriber.as_mut() {
+            Some(subscriber) => subscriber,
+            None if channels.is_empty() => return confirmation(kind, None, 0),
+            None => {
+                let replies = channels
+                    .into_iter()
+                    .map(|channel| confirmation(kind, Some(channel), 0))
+                    .collect();
+                return self.reply_many(replies);
+            }
+        };
+        let channels = match (channels.is_empty(), pattern) {
+            (true, true) => subscriber.patterns(),
+            (true, false) => subscriber.channels(),
+            (false, _) => channels,
+        };
+        if channels.is_empty() {
+            return confirmation(kind, None, subscriber.count());
+        }
+
+        let replies = channels
+            .into_iter()
+            .map(|channel| {
+                let count = match pattern {
+                    true => subscriber.punsubscribe(&channel),
+                    false => subscriber.unsubscribe(&channel),
+                };
+                confirmation(kind, Some(channel), count)
+            })
+            .collect();
+        if subscriber.count() == 0 {
+            self.subscriber = None;
+        }
+        self.reply_many(replies)
+    }
+
+    pub(super) async fn handle_publish(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => return Value::Error("PUBLISH requires a channel and a message".to_string()),
+        };
+
+        integer(self.pubsub.publish(&args[0], &args[1]))
+    }
+
+    /// Handle `PUBSUB CHANNELS [pattern]`, `PUBSUB NUMSUB [channel ...]` and `PUBSUB NUMPAT`.
+    pub(super) async fn handle_pubsub(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("PUBSUB requires a subcommand".to_string()),
+        };
+
+        match args[0].to_ascii_lowercase().as_str() {
+            "channels" if args.len() <= 2 => {
+                let channels = self.pubsub.channels(args.get(1).map(String::as_str));
+                Value::Array(channels.into_iter().map(Value::BulkString).collect())
+            }
+            "numsub" => {
+                let channels = &args[1..];
+                let counts = self.pubsub.numsub(channels);
+                Value::Array(
+                    channels
+                        .iter()
+                        .zip(counts)
+                        .flat_map(|(channel, count)| {
+                            [Value::BulkString(channel.clone()), integer(count)]
+                        })
+                        .collect(),
+                )
+            }
+            "numpat" if args.len() == 1 => integer(self.pubsub.numpat()),
+            _ => Value::Error(format!(
+                "unknown or malformed PUBSUB subcommand {}",
+                args[0]
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::notifications::Notifications;
+    use crate::cache::pubsub::PubSub;
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::pubsub::message_value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_pubsub_commands() -> Result<()> {
+        let notifications = Notifications::new(PubSub::default());
+        let cache = Arc::new(Cache::default());
+        let mut subscriber =
+            Handler::new(cache.clone(), None).with_notifications(notifications.clone());
+        let mut publisher = Handler::new(cache, None).with_notifications(notifications);
+
+        let response = subscriber
+            .handle_request(command(&[
+                "SUBSCRIBE",
+                "invalidate:users",
+                "invalidate:orders",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![bulk("subscribe"), bulk("invalidate:users"), int(1)])
+        );
+        assert_eq!(
+            subscriber.replies.drain(..).collect::<Vec<_>>(),
+            vec![Value::Array(vec![
+                bulk("subscribe"),
+                bulk("invalidate:orders"),
+                int(2)
+            ])]
+        );
+        subscriber
+            .handle_request(command(&["PSUBSCRIBE", "invalidate:*"]))
+            .await?;
+        let response = subscriber.handle_request(command(&["GET", "key"])).await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = subscriber.handle_request(command(&["PING"])).await?;
+        assert_eq!(response, Value::Array(vec![bulk("pong"), bulk("")]));
+
+        let response = publisher
+            .handle_request(command(&["PUBLISH", "invalidate:users", "42"]))
+            .await?;
+        assert_eq!(response, int(2));
+        let message = subscriber
+            .subscriber
+            .as_mut()
+            .unwrap()
+            .recv()
+            .await
+            .unwrap();
+        assert_eq!(
+            message_value(message),
+            Value::Array(vec![bulk("message"), bulk("invalidate:users"), bulk("42")])
+        );
+        let message = subscriber
+            .subscriber
+            .as_mut()
+            .unwrap()
+            .recv()
+            .await
+            .unwrap();
+        assert_eq!(
+            message_value(message),
+            Value::Array(vec![
+                bulk("pmessage"),
+                bulk("invalidate:*"),
+                bulk("invalidate:users"),
+                bulk("42")
+            ])
+        );
+
+        let response = publisher
+            .handle_request(command(&["PUBSUB", "CHANNELS", "*:users"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("invalidate:users")]));
+        let response = publisher
+            .handle_request(command(&["PUBSUB", "NUMSUB", "invalidate:orders", "none"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![
+                bulk("invalidate:orders"),
+                int(1),
+                bulk("none"),
+                int(0)
+            ])
+        );
+        let response = publisher
+            .handle_request(command(&["PUBSUB", "NUMPAT"]))
+            .await?;
+        assert_eq!(response, int(1));
+
+        subscriber.handle_request(command(&["UNSUBSCRIBE"])).await?;
+        subscriber.replies.clear();
+        let response = subscriber
+            .handle_request(command(&["PUNSUBSCRIBE"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![bulk("punsubscribe"), bulk("invalidate:*"), int(0)])
+        );
+        // out of subscriber mode
+        let response = subscriber.handle_request(command(&["PING"])).await?;
+        assert_eq!(response, Value::SimpleString("PONG".to_string()));
+        let response = publisher
+            .handle_request(command(&["PUBLISH", "invalidate:users", "43"]))
+            .await?;
+        assert_eq!(response, int(0));
+        Ok(())
+    }
+}
@@ -0,0 +1,461 @@
+use crate::resp::value::Value;
+use crate::server::handler::{bulk_strings, integer, parse_count, Command, Handler};
+use rhai::{Array, Blob, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use std::any::TypeId;
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+
+/// The most arguments `redis_call` and `redis_pcall` take, arrays among them being flattened.
+const MAX_ARGUMENTS: usize = 16;
+
+/// A command called by a script, with the channel its reply is sent back on.
+type Call = (Vec<Value>, oneshot::Sender<Value>);
+
+/// Convert a reply to a script value: integers to integers, strings to strings, nulls to `()`,
+/// arrays to arrays and errors to `#{ err: message }` maps. Bytes that aren't UTF-8 become
+/// blobs.
+fn to_dynamic(value: Value) -> Dynamic {
+    match value {
+        Value::Null => Dynamic::UNIT,
+        Value::Integer(n) => n.parse::<i64>().map_or(Dynamic::from(n), Dynamic::from),
+        Value::SimpleString(s) | Value::BulkString(s) => Dynamic::from(s),
+        Value::BulkBytes(bytes) => match String::from_utf8(bytes) {
+            Ok(s) => Dynamic::from(s),
+            Err(e) => Dynamic::from_blob(e.into_bytes()),
+        },
+        Value::Error(e) => {
+            let mut map = Map::new();
+            map.insert("err".into(), Dynamic::from(e));
+            Dynamic::from_map(map)
+        }
+        Value::Array(values) => Dynamic::from_array(values.into_iter().map(to_dynamic).collect()),
+    }
+}
+
+/// Convert the value a script returns to a reply. Like Redis does with Lua, `true` becomes 1,
+/// `false` and `()` become null, and `#{ err: message }` and `#{ ok: status }` maps become
+/// errors and statuses. Other maps are flattened to arrays of keys and values.
+fn from_dynamic(value: Dynamic) -> Value {
+    if value.is_unit() {
+        return Value::Null;
+    }
+    if let Ok(b) = value.as_bool() {
+        return match b {
+            true => integer(1),
+            false => Value::Null,
+        };
+    }
+    if let Ok(n) = value.as_int() {
+        return integer(n);
+    }
+    if value.is_blob() {
+        return Value::BulkBytes(value.cast::<Blob>());
+    }
+    if value.is_array() {
+        let array = value.cast::<Array>();
+        return Value::Array(array.into_iter().map(from_dynamic).collect());
+    }
+    if value.is_map() {
+        let map = value.cast::<Map>();
+        if let Some(e) = map.get("err") {
+            return Value::Error(e.to_string());
+        }
+        if let Some(status) = map.get("ok") {
+            return Value::SimpleString(status.to_string());
+        }
+        return Value::Array(
+            map.into_iter()
+                .flat_map(|(key, value)| [Value::BulkString(key.into()), from_dynamic(value)])
+                .collect(),
+        );
+    }
+    Value::BulkString(value.to_string())
+}
+
+/// Convert an argument of `redis_call` to a bulk string, blobs being passed as their bytes.
+fn to_argument(arg: &Dynamic) -> Value {
+    match arg.read_lock::<Blob>() {
+        Some(blob) => Value::from(blob.clone()),
+        None => Value::BulkString(arg.to_string()),
+    }
+}
+
+/// Register `redis_call` and `redis_pcall`, sending the commands of the script to `calls`,
+/// `call` being a keyword of Rhai. `redis_call` raises the errors commands reply with,
+/// `redis_pcall` returns them as `#{ err: message }` maps.
+fn register_calls(engine: &mut Engine, calls: mpsc::UnboundedSender<Call>) {
+    for arity in 1..=MAX_ARGUMENTS {
+        for (name, protected) in [("redis_call", false), ("redis_pcall", true)] {
+            let calls = calls.clone();
+            let types = vec![TypeId::of::<Dynamic>(); arity];
+            engine.register_raw_fn(name, types, move |_, args| {
+                let command = args
+                    .iter()
+                    .flat_map(|arg| match arg.read_lock::<Array>() {
+                        Some(array) => array.iter().map(to_argument).collect(),
+                        None => vec![to_argument(arg)],
+                    })
+                    .collect();
+                let (reply, response) = oneshot::channel();
+                calls
+                    .send((command, reply))
+                    .map_err(|_| "the connection is closed")?;
+                match response.blocking_recv() {
+                    Ok(Value::Error(e)) if !protected => Err(e.into()),
+                    Ok(value) => Ok(to_dynamic(value)),
+                    Err(_) => Err("the connection is closed".into()),
+                }
+            });
+        }
+    }
+}
+
+/// Describe why a script failed, passing on the errors it raised as they are.
+fn script_error(error: EvalAltResult) -> String {
+    match error {
+        EvalAltResult::ErrorRuntime(value, _) => value.to_string(),
+        EvalAltResult::ErrorTerminated(..) => "script exceeded its time limit".to_string(),
+        EvalAltResult::ErrorTooManyOperations(..) => {
+            "script exceeded its operation limit".to_string()
+        }
+        error => format!("Error running script: {}", error),
+    }
+}
+
+impl Handler {
+    /// Handle `EVAL script numkeys [key ...] [arg ...]`, or EVALSHA naming a loaded script
+    /// when `sha` is set.
+    pub(super) async fn handle_eval(&mut self, args: &[Value], sha: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => {
+                return Value::Error(
+                    "EVAL and EVALSHA require a script and a number of keys".to_string(),
+                )
+            }
+        };
+        let ast = match sha {
+            true => match self.scripts.get(&args[0]) {
+                Some(ast) => ast,
+                None => {
+                    return Value::Error(
+                        "NOSCRIPT No matching script. Please use EVAL.".to_string(),
+                    )
+                }
+            },
+            false => match self.scripts.load(&args[0]) {
+                Ok((_, ast)) => ast,
+                Err(e) => return Value::Error(e.to_string()),
+            },
+        };
+        let keys = match parse_count(&args[1]) {
+            Ok(keys) if keys <= args.len() - 2 => keys,
+            Ok(_) => {
+                return Value::Error(
+                    "Number of keys can't be greater than number of args".to_string(),
+                )
+            }
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        let (keys, argv) = args[2..].split_at(keys);
+
+        self.run_script(ast, keys.to_vec(), argv.to_vec()).await
+    }
+
+    /// Run the script `ast` with the `KEYS` and `ARGV` constants, holding the selected
+    /// database exclusively. The script runs on a blocking thread, while the commands it calls
+    /// are run here one by one.
+    async fn run_script(&mut self, ast: Arc<AST>, keys: Vec<String>, argv: Vec<String>) -> Value {
+        let store = self.client_store.clone();
+        let _gate = store.enter_exclusive().await;
+
+        // the timeout starts with the engine, so only once the database is ours
+        let (sender, mut calls) = mpsc::unbounded_channel();
+        let mut engine = self.scripts.engine();
+        register_calls(&mut engine, sender);
+        let script = tokio::task::spawn_blocking(move || {
+            let mut scope = Scope::new();
+            scope.push_constant(
+                "KEYS",
+                keys.into_iter().map(Dynamic::from).collect::<Array>(),
+            );
+            scope.push_constant(
+                "ARGV",
+                argv.into_iter().map(Dynamic::from).collect::<Array>(),
+            );
+            engine.eval_ast_with_scope::<Dynamic>(&mut scope, &ast)
+        });
+
+        // the calls end once the engine is dropped with the script done
+        while let Some((command, reply)) = calls.recv().await {
+            _ = reply.send(self.call_from_script(command).await);
+        }
+        match script.await {
+            Ok(Ok(value)) => from_dynamic(value),
+            Ok(Err(e)) => Value::Error(script_error(*e)),
+            Err(e) => Value::Error(format!("Error running script: {}", e)),
+        }
+    }
+
+    /// Run a command called by a script, through the dispatcher of client commands.
+    async fn call_from_script(&mut self, command: Vec<Value>) -> Value {
+        let (name, args) = match command.split_first() {
+            Some((Value::BulkString(name), args)) => (name.clone(), args),
+            _ => {
+                return Value::Error(
+                    "Please specify at least one argument for redis_call()".to_string(),
+                )
+            }
+        };
+        let command: Command = name.to_ascii_lowercase().as_str().into();
+        match command {
+            Command::Uninitialized => {
+                return Value::Error(format!("Unknown command '{}' called from script", name))
+            }
+            Command::Eval
+            | Command::EvalSha
+            | Command::Script
+            | Command::Subscribe
+            | Command::PSubscribe
+            | Command::Unsubscribe
+            | Command::PUnsubscribe => {
+                return Value::Error(format!("Command '{}' is not allowed from scripts", name))
+            }
+            _ if command.is_transaction_control() || command.is_blocking() => {
+                return Value::Error(format!("Command '{}' is not allowed from scripts", name))
+            }
+            _ => {}
+        }
+
+        let response = self.dispatch(command, &name, args).await;
+        // a command like SELECT may change the database of the next ones
+        match self.databases.get(self.db) {
+            Ok(store) => self.client_store = store,
+            Err(e) => return Value::Error(e.to_string()),
+        }
+        response
+    }
+
+    /// Handle `SCRIPT LOAD script`, `SCRIPT EXISTS sha [sha ...]` and `SCRIPT FLUSH`. Scripts
+    /// are the server's, which clients bound to a tenant can't flush.
+    pub(super) async fn handle_script(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("SCRIPT requires a subcommand".to_string()),
+        };
+
+        match (args[0].to_ascii_lowercase().as_str(), &args[1..]) {
+            ("load", [source]) => match self.scripts.load(source) {
+                Ok((sha, _)) => Value::BulkString(sha),
+                Err(e) => Value::Error(e.to_string()),
+            },
+            ("exists", shas) if !shas.is_empty() => Value::Array(
+                self.scripts
+                    .exists(shas)
+                    .into_iter()
+                    .map(|exists| integer(exists as u8))
+                    .collect(),
+            ),
+            ("flush", _) if self.tenant.is_some() => {
+                Value::Error("NOPERM clients bound to a tenant can't flush the scripts".to_string())
+            }
+            ("flush", []) => {
+                self.scripts.flush();
+                Value::SimpleString("OK".to_string())
+            }
+            ("flush", [mode])
+                if mode.eq_ignore_ascii_case("async") || mode.eq_ignore_ascii_case("sync") =>
+            {
+                self.scripts.flush();
+                Value::SimpleString("OK".to_string())
+            }
+            _ => Value::Error(format!(
+                "unknown or malformed SCRIPT subcommand {}",
+                args[0]
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::scripts::Scripts;
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Let at most ARGV[0] requests through per window of ARGV[1] seconds.
+    const RATE_LIMITER: &str = r#"
+        let count = redis_call("GET", KEYS[0]);
+        if count == () {
+            redis_call("SET", KEYS[0], 1, "ex", ARGV[1]);
+            return true;
+        }
+        let count = parse_int(count);
+        if count >= parse_int(ARGV[0]) {
+            return false;
+        }
+        // SET clears the expiry, which ends the window
+        redis_call("SET", KEYS[0], count + 1, "ex", redis_call("TTL", KEYS[0]));
+        true
+    "#;
+
+    #[tokio::test]
+    async fn test_eval() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        for expected in [int(1), int(1), Value::Null] {
+            let response = handler
+                .handle_request(command(&[
+                    "EVAL",
+                    RATE_LIMITER,
+                    "1",
+                    "rate:user",
+                    "2",
+                    "60",
+                ]))
+                .await?;
+            assert_eq!(response, expected);
+        }
+        let response = handler
+            .handle_request(command(&["TTL", "rate:user"]))
+            .await?;
+        assert!(matches!(response, Value::Integer(ttl) if ttl == "59" || ttl == "60"));
+
+        // values convert both ways
+        let response = handler
+            .handle_request(command(&[
+                "EVAL",
+                r#"[KEYS, ARGV.len(), redis_call("SADD", "set", ARGV), #{ ok: "done" }]"#,
+                "1",
+                "key",
+                "a",
+                "b",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![
+                Value::Array(vec![bulk("key")]),
+                int(2),
+                int(2),
+                Value::SimpleString("done".to_string()),
+            ])
+        );
+
+        // redis_call raises errors, redis_pcall returns them
+        let response = handler
+            .handle_request(command(&[
+                "EVAL",
+                r#"redis_call("SADD", "rate:user", "a")"#,
+                "0",
+            ]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = handler
+            .handle_request(command(&[
+                "EVAL",
+                r#"let reply = redis_pcall("SADD", "rate:user", "a"); "err" in reply"#,
+                "0",
+            ]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["EVAL", r#"redis_call("BLPOP", "list", 0)"#, "0"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = handler
+            .handle_request(command(&["EVAL", "1", "2", "key"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        // blobs are passed as their bytes
+        let response = handler
+            .handle_request(command(&[
+                "EVAL",
+                r#"redis_call("SET", "raw", blob(2, 0xff)); redis_call("GET", "raw")"#,
+                "0",
+            ]))
+            .await?;
+        assert_eq!(response, Value::BulkBytes(vec![0xff, 0xff]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_script_cache() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["SCRIPT", "LOAD", "ARGV[0]"]))
+            .await?;
+        let Value::BulkString(sha) = response else {
+            panic!("SCRIPT LOAD replies with the digest");
+        };
+        let response = handler
+            .handle_request(command(&["EVALSHA", &sha, "0", "hello"]))
+            .await?;
+        assert_eq!(response, bulk("hello"));
+        let response = handler
+            .handle_request(command(&["SCRIPT", "EXISTS", &sha, "missing"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(1), int(0)]));
+
+        handler
+            .handle_request(command(&["SCRIPT", "FLUSH"]))
+            .await?;
+        let response = handler
+            .handle_request(command(&["EVALSHA", &sha, "0", "hello"]))
+            .await?;
+        let Value::Error(error) = response else {
+            panic!("flushed scripts are gone");
+        };
+        assert!(error.starts_with("NOSCRIPT"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_script_limits() -> Result<()> {
+        let cache = Arc::new(Cache::default());
+        let scripts = Scripts::new(10_000, Duration::from_secs(5));
+        let mut handler = Handler::new(cache.clone(), None).with_scripts(scripts);
+        let response = handler
+            .handle_request(command(&[
+                "EVAL",
+                r#"redis_call("SET", "a", "1"); loop {}"#,
+                "0",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Error("script exceeded its operation limit".to_string())
+        );
+        // writes made until then are kept
+        assert_eq!(cache.get("a".to_string()).await, Some("1".to_string()));
+
+        let scripts = Scripts::new(0, Duration::from_millis(20));
+        let mut handler = Handler::new(cache.clone(), None).with_scripts(scripts);
+        let response = handler
+            .handle_request(command(&["EVAL", "loop {}", "0"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Error("script exceeded its time limit".to_string())
+        );
+
+        // waiting for the database doesn't count against the time limit
+        let gate = cache.enter_exclusive().await;
+        let (response, _) = tokio::join!(
+            handler.handle_request(command(&["EVAL", r#"redis_call("GET", "a")"#, "0"])),
+            async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                drop(gate);
+            }
+        );
+        assert_eq!(response?, bulk("1"));
+        Ok(())
+    }
+}
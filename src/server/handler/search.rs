@@ -0,0 +1,353 @@
+use crate::cache::search::{FieldType, IndexOptions, Query, SearchIndex, SearchOptions, Source};
+use crate::cache::text::{Analyzer, DEFAULT_STOPWORDS};
+use crate::resp::value::Value;
+use crate::server::handler::{bulk_strings, integer, parse_count, syntax_error, Handler};
+use anyhow::{Error, Result};
+
+/// Parse `[ON HASH|STRING] [PREFIX count prefix ...] [STOPWORDS count word ...] [NOSTEM]
+/// SCHEMA field type [field type ...]`, the schema of strings defaulting to `value TEXT`.
+fn parse_create(args: &[String]) -> Result<SearchIndex> {
+    let mut options = IndexOptions::default();
+    let mut stopwords = None;
+    let mut stemming = true;
+    let mut i = 0;
+    let fields = loop {
+        match args.get(i).map(|arg| arg.to_ascii_lowercase()).as_deref() {
+            Some("on") => {
+                let source = args.get(i + 1).ok_or_else(syntax_error)?;
+                options.source = match source.to_ascii_lowercase().as_str() {
+                    "hash" => Source::Hash,
+                    "string" => Source::String,
+                    _ => return Err(syntax_error()),
+                };
+                i += 2;
+            }
+            Some("prefix") => {
+                let count = parse_count(args.get(i + 1).ok_or_else(syntax_error)?)?;
+                options.prefixes = args
+                    .get(i + 2..i + 2 + count)
+                    .ok_or_else(syntax_error)?
+                    .to_vec();
+                i += 2 + count;
+            }
+            Some("stopwords") => {
+                let count = parse_count(args.get(i + 1).ok_or_else(syntax_error)?)?;
+                let words = args.get(i + 2..i + 2 + count).ok_or_else(syntax_error)?;
+                stopwords = Some(words.to_vec());
+                i += 2 + count;
+            }
+            Some("nostem") => {
+                stemming = false;
+                i += 1;
+            }
+            Some("schema") => break &args[i + 1..],
+            None if options.source == Source::String => break &[][..],
+            _ => return Err(syntax_error()),
+        }
+    };
+    let stopwords = stopwords.unwrap_or_else(|| {
+        DEFAULT_STOPWORDS
+            .iter()
+            .map(|word| word.to_string())
+            .collect()
+    });
+    options.analyzer = Analyzer::new(stopwords, stemming);
+
+    let schema = match fields {
+        [] if options.source == Source::String => vec![("value".to_string(), FieldType::Text)],
+        _ if fields.is_empty() || !fields.len().is_multiple_of(2) => {
+            return Err(Error::msg("SCHEMA requires field type pairs"))
+        }
+        _ => fields
+            .chunks(2)
+            .map(|pair| Ok((pair[0].clone(), FieldType::parse(&pair[1])?)))
+            .collect::<Result<Vec<_>>>()?,
+    };
+    Ok(SearchIndex::new(options, schema))
+}
+
+/// What FT.SEARCH replies with for every match besides its key.
+#[derive(Clone, Copy, Debug, Default)]
+struct Reply {
+    scores: bool,
+    no_content: bool,
+}
+
+/// Parse `[NOCONTENT] [WITHSCORES] [SORTBY field [ASC|DESC]] [LIMIT offset count]
+/// [HIGHLIGHT [TAGS open close]]`.
+fn parse_search(args: &[String]) -> Result<(SearchOptions, Reply)> {
+    let mut options = SearchOptions::default();
+    let mut reply = Reply::default();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].to_ascii_lowercase().as_str() {
+            "nocontent" => {
+                reply.no_content = true;
+                i += 1;
+            }
+            "withscores" => {
+                reply.scores = true;
+                i += 1;
+            }
+            "highlight" => match args.get(i + 1) {
+                Some(tags) if tags.eq_ignore_ascii_case("tags") => {
+                    let (Some(open), Some(close)) = (args.get(i + 2), args.get(i + 3)) else {
+                        return Err(syntax_error());
+                    };
+                    options.highlight = Some((open.clone(), close.clone()));
+                    i += 4;
+                }
+                _ => {
+                    options.highlight = Some(("<b>".to_string(), "</b>".to_string()));
+                    i += 1;
+                }
+            },
+            "sortby" => {
+                let field = args.get(i + 1).ok_or_else(syntax_error)?.clone();
+                let order = args.get(i + 2).map(|order| order.to_ascii_lowercase());
+                let (descending, used) = match order.as_deref() {
+                    Some("asc") => (false, 3),
+                    Some("desc") => (true, 3),
+                    _ => (false, 2),
+                };
+                options.sort_by = Some((field, descending));
+                i += used;
+            }
+            "limit" => {
+                let (Some(offset), Some(limit)) = (args.get(i + 1), args.get(i + 2)) else {
+                    return Err(syntax_error());
+                };
+                options.offset = parse_count(offset)?;
+                options.limit = parse_count(limit)?;
+                i += 3;
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+    Ok((options, reply))
+}
+
+impl Handler {
+    pub(super) async fn handle_ft_create(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => return Value::Error("FT.CREATE requires an index and a schema".to_string()),
+        };
+        let index = match parse_create(&args[1..]) {
+            Ok(index) => index,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.ft_create(args[0].clone(), index).await {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_ft_search(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("FT.SEARCH requires an index and a query".to_string()),
+        };
+        let (query, (options, reply)) = match (Query::parse(&args[1]), parse_search(&args[2..])) {
+            (Ok(query), Ok(options)) => (query, options),
+            (Err(e), _) | (_, Err(e)) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .ft_search(args[0].clone(), query, options)
+            .await
+        {
+            Ok((total, page)) => {
+                let mut values = vec![integer(total)];
+                for (key, score, fields) in page {
+                    values.push(Value::BulkString(key));
+                    if reply.scores {
+                        values.push(Value::BulkString(score.to_string()));
+                    }
+                    if !reply.no_content {
+                        values.push(Value::Array(
+                            fields
+                                .into_iter()
+                                .flat_map(|(field, value)| {
+                                    [Value::BulkString(field), Value::BulkString(value)]
+                                })
+                                .collect(),
+                        ));
+                    }
+                }
+                Value::Array(values)
+            }
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_ft_dropindex(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("FT.DROPINDEX requires an index".to_string()),
+        };
+
+        match self.client_store.ft_dropindex(args[0].clone()).await {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_ft_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&[
+                "FT.CREATE",
+                "sessions",
+                "ON",
+                "HASH",
+                "PREFIX",
+                "1",
+                "session:",
+                "SCHEMA",
+                "tenant",
+                "TAG",
+                "started",
+                "NUMERIC",
+            ]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        for (key, tenant, started) in [
+            ("session:1", "x", "300"),
+            ("session:2", "x", "100"),
+            ("session:3", "y", "200"),
+        ] {
+            handler
+                .handle_request(command(&[
+                    "HSET", key, "tenant", tenant, "started", started,
+                ]))
+                .await?;
+        }
+
+        let response = handler
+            .handle_request(command(&[
+                "FT.SEARCH",
+                "sessions",
+                "@tenant:{x}",
+                "SORTBY",
+                "started",
+                "DESC",
+                "LIMIT",
+                "0",
+                "1",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![
+                int(2),
+                bulk("session:1"),
+                Value::Array(vec![
+                    bulk("started"),
+                    bulk("300"),
+                    bulk("tenant"),
+                    bulk("x"),
+                ]),
+            ])
+        );
+        let response = handler
+            .handle_request(command(&[
+                "FT.SEARCH",
+                "sessions",
+                "@started:[150 +inf]",
+                "NOCONTENT",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![int(2), bulk("session:1"), bulk("session:3")])
+        );
+        let response = handler
+            .handle_request(command(&["FT.SEARCH", "sessions", "@missing:{x}"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&["FT.DROPINDEX", "sessions"]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ft_full_text() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        handler
+            .handle_request(command(&["SET", "doc:1", "Rust caches, cached in Rust"]))
+            .await?;
+        handler
+            .handle_request(command(&["SET", "doc:2", "The cache of the edge"]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&[
+                "FT.CREATE",
+                "docs",
+                "ON",
+                "STRING",
+                "PREFIX",
+                "1",
+                "doc:",
+                "STOPWORDS",
+                "1",
+                "of",
+            ]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+
+        let response = handler
+            .handle_request(command(&[
+                "FT.SEARCH",
+                "docs",
+                "\"the edge\"",
+                "HIGHLIGHT",
+                "TAGS",
+                "*",
+                "*",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![
+                int(1),
+                bulk("doc:2"),
+                Value::Array(vec![bulk("value"), bulk("*The* cache of *the* *edge*")]),
+            ])
+        );
+
+        let response = handler
+            .handle_request(command(&["FT.SEARCH", "docs", "caching", "WITHSCORES"]))
+            .await?;
+        let Value::Array(values) = response else {
+            panic!("expected an array, got {:?}", response);
+        };
+        assert_eq!(values[..2], [int(2), bulk("doc:1")]);
+        assert!(matches!(&values[2], Value::BulkString(score) if score.parse::<f64>()? > 0.0));
+        assert_eq!(values[4], bulk("doc:2"));
+
+        let response = handler
+            .handle_request(command(&["FT.CREATE", "other", "SCHEMA", "title"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        Ok(())
+    }
+}
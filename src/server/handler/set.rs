@@ -0,0 +1,192 @@
+use crate::resp::value::Value;
+use crate::server::handler::{
+    bulk_strings, expire_reply, integer, parse_deadline, parse_members, persist_reply, ttl_reply,
+    Handler,
+};
+
+impl Handler {
+    pub(super) async fn handle_sadd(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("SADD requires a key and members".to_string()),
+        };
+
+        let members = args[1..].to_vec();
+        match self.client_store.sadd(args[0].clone(), members).await {
+            Ok(added) => integer(added),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_srem(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("SREM requires a key and members".to_string()),
+        };
+
+        let members = args[1..].to_vec();
+        match self.client_store.srem(args[0].clone(), members).await {
+            Ok(removed) => integer(removed),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_smembers(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("SMEMBERS requires a key".to_string()),
+        };
+
+        match self.client_store.smembers(args[0].clone()).await {
+            Ok(members) => Value::Array(members.into_iter().map(Value::BulkString).collect()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_sismember(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => return Value::Error("SISMEMBER requires a key and a member".to_string()),
+        };
+
+        match self
+            .client_store
+            .sismember(args[0].clone(), args[1].clone())
+            .await
+        {
+            Ok(found) => integer(found as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_scard(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("SCARD requires a key".to_string()),
+        };
+
+        match self.client_store.scard(args[0].clone()).await {
+            Ok(len) => integer(len),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    /// Handle SEXPIRE, or SPEXPIRE when `millis` is set.
+    pub(super) async fn handle_sexpire(&self, args: &[Value], millis: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 5 => args,
+            _ => {
+                return Value::Error(
+                    "SEXPIRE and SPEXPIRE require a key, a timeout and members".to_string(),
+                )
+            }
+        };
+        let deadline = match parse_deadline(&args[1], millis) {
+            Ok(deadline) => deadline,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        let members = match parse_members(&args[2..], "MEMBERS") {
+            Ok(members) => members,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .sexpire(args[0].clone(), members, deadline)
+            .await
+        {
+            Ok(expired) => expire_reply(expired),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    /// Handle STTL, or SPTTL when `millis` is set.
+    pub(super) async fn handle_sttl(&self, args: &[Value], millis: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 4 => args,
+            _ => return Value::Error("STTL and SPTTL require a key and members".to_string()),
+        };
+        let members = match parse_members(&args[1..], "MEMBERS") {
+            Ok(members) => members,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.sttl(args[0].clone(), members).await {
+            Ok(remaining) => ttl_reply(remaining, millis),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_spersist(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 4 => args,
+            _ => return Value::Error("SPERSIST requires a key and members".to_string()),
+        };
+        let members = match parse_members(&args[1..], "MEMBERS") {
+            Ok(members) => members,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.spersist(args[0].clone(), members).await {
+            Ok(persisted) => persist_reply(persisted),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_set_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["SADD", "online", "bob", "ada", "bob"]))
+            .await?;
+        assert_eq!(response, int(2));
+        let response = handler
+            .handle_request(command(&["SMEMBERS", "online"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("ada"), bulk("bob")]));
+        let response = handler
+            .handle_request(command(&["SISMEMBER", "online", "eve"]))
+            .await?;
+        assert_eq!(response, int(0));
+
+        let response = handler
+            .handle_request(command(&[
+                "SPEXPIRE", "online", "50000", "MEMBERS", "2", "ada", "eve",
+            ]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(1), int(-2)]));
+        let response = handler
+            .handle_request(command(&["STTL", "online", "MEMBERS", "2", "ada", "bob"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(50), int(-1)]));
+        let response = handler
+            .handle_request(command(&["SPERSIST", "online", "MEMBERS", "1", "bob"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(-1)]));
+        let response = handler
+            .handle_request(command(&["SEXPIRE", "online", "0", "FIELDS", "1", "ada"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&["SREM", "online", "ada"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["SCARD", "online"]))
+            .await?;
+        assert_eq!(response, int(1));
+        Ok(())
+    }
+}
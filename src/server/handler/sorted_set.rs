@@ -0,0 +1,641 @@
+use crate::cache::sorted_set::{
+    parse_score, AddOptions, AddOutcome, Aggregate, LexBound, LexRange, Limit, RangeBy, ScoreBound,
+    ScoreRange,
+};
+use crate::resp::value::Value;
+use crate::server::handler::{
+    bulk_strings, integer, parse_count, parse_integer, parse_timeout, syntax_error, Handler,
+};
+use anyhow::{Error, Result};
+
+fn score_value(score: f64) -> Value {
+    Value::BulkString(score.to_string())
+}
+
+/// Flatten members into `[member, score, ...]`, or just members when scores aren't wanted.
+fn members_value(members: Vec<(String, f64)>, with_scores: bool) -> Value {
+    let mut values = Vec::with_capacity(members.len() * 2);
+    for (member, score) in members {
+        values.push(Value::BulkString(member));
+        if with_scores {
+            values.push(score_value(score));
+        }
+    }
+    Value::Array(values)
+}
+
+/// Parse `ZADD key [NX|XX] [GT|LT] [CH] [INCR] score member [score member ...]`.
+fn parse_zadd(args: &[String]) -> Result<(AddOptions, Vec<(f64, String)>)> {
+    let mut options = AddOptions::default();
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].to_ascii_lowercase().as_str() {
+            "nx" => options.nx = true,
+            "xx" => options.xx = true,
+            "gt" => options.gt = true,
+            "lt" => options.lt = true,
+            "ch" => options.ch = true,
+            "incr" => options.incr = true,
+            _ => break,
+        }
+        i += 1;
+    }
+
+    let pairs = &args[i..];
+    if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+        return Err(syntax_error());
+    }
+    let pairs = pairs
+        .chunks(2)
+        .map(|pair| Ok((parse_score(&pair[0])?, pair[1].clone())))
+        .collect::<Result<Vec<_>>>()?;
+    Ok((options, pairs))
+}
+
+/// Parse `ZRANGE key start stop [BYSCORE|BYLEX] [REV] [LIMIT offset count] [WITHSCORES]`.
+fn parse_zrange(args: &[String]) -> Result<(RangeBy, bool, Limit, bool)> {
+    let mut by_score = false;
+    let mut by_lex = false;
+    let mut reverse = false;
+    let mut limit = None;
+    let mut with_scores = false;
+
+    let mut i = 3;
+    while i < args.len() {
+        match args[i].to_ascii_lowercase().as_str() {
+            "byscore" => by_score = true,
+            "bylex" => by_lex = true,
+            "rev" => reverse = true,
+            "withscores" => with_scores = true,
+            "limit" if i + 2 < args.len() => {
+                let offset = parse_integer(&args[i + 1])?;
+                let count = parse_integer(&args[i + 2])?;
+                limit = Some(Limit {
+                    offset: offset.max(0) as usize,
+                    count: if count < 0 {
+                        None
+                    } else {
+                        Some(count as usize)
+                    },
+                });
+                i += 2;
+            }
+            _ => return Err(syntax_error()),
+        }
+        i += 1;
+    }
+
+    if by_score && by_lex {
+        return Err(syntax_error());
+    }
+    if limit.is_some() && !by_score && !by_lex {
+        return Err(Error::msg(
+            "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX",
+        ));
+    }
+    if with_scores && by_lex {
+        return Err(Error::msg(
+            "syntax error, WITHSCORES not supported in combination with BYLEX",
+        ));
+    }
+
+    // in reverse the first bound is the upper one
+    let (min, max) = if reverse && (by_score || by_lex) {
+        (&args[2], &args[1])
+    } else {
+        (&args[1], &args[2])
+    };
+    let by = if by_score {
+        RangeBy::Score(ScoreRange::new(
+            ScoreBound::parse(min)?,
+            ScoreBound::parse(max)?,
+        ))
+    } else if by_lex {
+        RangeBy::Lex(LexRange::new(LexBound::parse(min)?, LexBound::parse(max)?))
+    } else {
+        RangeBy::Rank(parse_integer(min)?, parse_integer(max)?)
+    };
+    Ok((by, reverse, limit.unwrap_or_default(), with_scores))
+}
+
+/// Parse `numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM|MIN|MAX]`.
+fn parse_zstore(args: &[String]) -> Result<(Vec<String>, Option<Vec<f64>>, Aggregate)> {
+    let count = parse_integer(&args[0])?;
+    if count <= 0 {
+        return Err(Error::msg("at least 1 input key is needed"));
+    }
+    let count = count as usize;
+    if args.len() < count + 1 {
+        return Err(syntax_error());
+    }
+    let keys = args[1..=count].to_vec();
+
+    let mut weights = None;
+    let mut aggregate = Aggregate::Sum;
+    let mut i = count + 1;
+    while i < args.len() {
+        match args[i].to_ascii_lowercase().as_str() {
+            "weights" if i + count < args.len() => {
+                let parsed = args[i + 1..=i + count]
+                    .iter()
+                    .map(|weight| {
+                        parse_score(weight).map_err(|_| Error::msg("weight value is not a float"))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                weights = Some(parsed);
+                i += count;
+            }
+            "aggregate" if i + 1 < args.len() => {
+                aggregate = Aggregate::parse(&args[i + 1])?;
+                i += 1;
+            }
+            _ => return Err(syntax_error()),
+        }
+        i += 1;
+    }
+    Ok((keys, weights, aggregate))
+}
+
+impl Handler {
+    pub(super) async fn handle_zadd(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => return Value::Error("ZADD requires a key and score member pairs".to_string()),
+        };
+        let (options, pairs) = match parse_zadd(&args) {
+            Ok(parsed) => parsed,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .zadd(args[0].clone(), options, pairs)
+            .await
+        {
+            Ok(outcomes) if options.incr => match outcomes[0].score() {
+                Some(score) => score_value(score),
+                None => Value::Null,
+            },
+            Ok(outcomes) => integer(
+                outcomes
+                    .iter()
+                    .filter(|outcome| match outcome {
+                        AddOutcome::Added(_) => true,
+                        AddOutcome::Updated(_) => options.ch,
+                        _ => false,
+                    })
+                    .count(),
+            ),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_zcard(&self, args: &[Value]) -> Value {
+        if let Some(Value::BulkString(key)) = args.first() {
+            match self.client_store.zcard(key.clone()).await {
+                Ok(len) => integer(len),
+                Err(e) => Value::Error(e.to_string()),
+            }
+        } else {
+            Value::Error("ZCARD requires one argument".to_string())
+        }
+    }
+
+    pub(super) async fn handle_zscore(&self, args: &[Value]) -> Value {
+        if let (Some(Value::BulkString(key)), Some(Value::BulkString(member))) =
+            (args.first(), args.get(1))
+        {
+            match self.client_store.zscore(key.clone(), member.clone()).await {
+                Ok(Some(score)) => score_value(score),
+                Ok(None) => Value::Null,
+                Err(e) => Value::Error(e.to_string()),
+            }
+        } else {
+            Value::Error("ZSCORE requires two arguments".to_string())
+        }
+    }
+
+    pub(super) async fn handle_zrank(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 || args.len() == 3 => args,
+            _ => return Value::Error("ZRANK requires a key and a member".to_string()),
+        };
+        let with_score = match args.get(2) {
+            Some(option) if option.eq_ignore_ascii_case("withscore") => true,
+            Some(_) => return Value::Error(syntax_error().to_string()),
+            None => false,
+        };
+
+        match self
+            .client_store
+            .zrank(args[0].clone(), args[1].clone(), false)
+            .await
+        {
+            Ok(Some((rank, score))) if with_score => {
+                Value::Array(vec![integer(rank), score_value(score)])
+            }
+            Ok(Some((rank, _))) => integer(rank),
+            Ok(None) => Value::Null,
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_zrange(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => return Value::Error("ZRANGE requires a key, a start and a stop".to_string()),
+        };
+        let (by, reverse, limit, with_scores) = match parse_zrange(&args) {
+            Ok(parsed) => parsed,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .zrange(args[0].clone(), by, reverse, limit)
+            .await
+        {
+            Ok(members) => members_value(members, with_scores),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_zrem(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("ZREM requires a key and members".to_string()),
+        };
+
+        match self
+            .client_store
+            .zrem(args[0].clone(), args[1..].to_vec())
+            .await
+        {
+            Ok(removed) => integer(removed),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_zremrangebyscore(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 3 => args,
+            _ => return Value::Error("ZREMRANGEBYSCORE requires three arguments".to_string()),
+        };
+        let range = match (ScoreBound::parse(&args[1]), ScoreBound::parse(&args[2])) {
+            (Ok(min), Ok(max)) => ScoreRange::new(min, max),
+            (Err(e), _) | (_, Err(e)) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .zremrangebyscore(args[0].clone(), range)
+            .await
+        {
+            Ok(removed) => integer(removed),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_zpop(&self, args: &[Value], max: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 || args.len() == 2 => args,
+            _ => return Value::Error("ZPOPMIN and ZPOPMAX require a key".to_string()),
+        };
+        let count = match args.get(1).map(|count| parse_count(count)) {
+            Some(Ok(count)) => count,
+            Some(Err(e)) => return Value::Error(e.to_string()),
+            None => 1,
+        };
+
+        match self.client_store.zpop(args[0].clone(), count, max).await {
+            Ok(members) => members_value(members, true),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_bzpop(&self, args: &[Value], max: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => {
+                return Value::Error("BZPOPMIN and BZPOPMAX require keys and a timeout".to_string())
+            }
+        };
+        let (keys, timeout) = args.split_at(args.len() - 1);
+        let timeout = match parse_timeout(&timeout[0]) {
+            Ok(timeout) => timeout,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        // the first non empty sorted set in argument order is served
+        let store = &self.client_store;
+        let popped = store
+            .block_on(keys, timeout, || async move {
+                for key in keys {
+                    let mut members = store.zpop(key.clone(), 1, max).await?;
+                    if let Some((member, score)) = members.pop() {
+                        return Ok(Some((key.clone(), member, score)));
+                    }
+                }
+                Ok(None)
+            })
+            .await;
+
+        match popped {
+            Ok(Some((key, member, score))) => Value::Array(vec![
+                Value::BulkString(key),
+                Value::BulkString(member),
+                score_value(score),
+            ]),
+            Ok(None) => Value::Null,
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_zstore(&self, args: &[Value], intersect: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => {
+                return Value::Error(
+                    "ZUNIONSTORE and ZINTERSTORE require a destination, numkeys and keys"
+                        .to_string(),
+                )
+            }
+        };
+        let (keys, weights, aggregate) = match parse_zstore(&args[1..]) {
+            Ok(parsed) => parsed,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        let destination = args[0].clone();
+        let result = if intersect {
+            self.client_store
+                .zinterstore(destination, keys, weights, aggregate)
+                .await
+        } else {
+            self.client_store
+                .zunionstore(destination, keys, weights, aggregate)
+                .await
+        };
+        match result {
+            Ok(len) => integer(len),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_bzpopmin_command() -> Result<()> {
+        let cache = Arc::new(Cache::default());
+        let mut handler = Handler::new(cache.clone(), None);
+
+        let response = handler
+            .handle_request(command(&["BZPOPMIN", "board", "0.01"]))
+            .await?;
+        assert_eq!(response, Value::Null);
+
+        let blocked = tokio::spawn(async move {
+            let mut handler = Handler::new(cache, None);
+            handler
+                .handle_request(command(&["BZPOPMIN", "board", "0"]))
+                .await
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        handler
+            .handle_request(command(&["ZADD", "board", "2", "b", "1", "a"]))
+            .await?;
+
+        let response = blocked.await??;
+        assert_eq!(
+            response,
+            Value::Array(vec![bulk("board"), bulk("a"), bulk("1")])
+        );
+        let response = handler.handle_request(command(&["ZCARD", "board"])).await?;
+        assert_eq!(response, int(1));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zadd_command() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["ZADD", "board", "1", "a", "2", "b"]))
+            .await?;
+        assert_eq!(response, int(2));
+
+        let response = handler
+            .handle_request(command(&["ZADD", "board", "CH", "5", "a", "3", "c"]))
+            .await?;
+        assert_eq!(response, int(2));
+
+        let response = handler
+            .handle_request(command(&["ZADD", "board", "INCR", "2.5", "a"]))
+            .await?;
+        assert_eq!(response, bulk("7.5"));
+
+        let response = handler
+            .handle_request(command(&["ZADD", "board", "NX", "INCR", "1", "a"]))
+            .await?;
+        assert_eq!(response, Value::Null);
+
+        let response = handler
+            .handle_request(command(&["ZADD", "board", "NX", "XX", "1", "a"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&["ZADD", "board", "1"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zrange_command() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        handler
+            .handle_request(command(&["ZADD", "board", "1", "a", "2", "b", "3", "c"]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&["ZRANGE", "board", "0", "-1", "WITHSCORES"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![
+                bulk("a"),
+                bulk("1"),
+                bulk("b"),
+                bulk("2"),
+                bulk("c"),
+                bulk("3")
+            ])
+        );
+
+        let response = handler
+            .handle_request(command(&[
+                "ZRANGE", "board", "+inf", "(1", "BYSCORE", "REV", "LIMIT", "0", "1",
+            ]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("c")]));
+
+        let response = handler
+            .handle_request(command(&["ZRANGE", "board", "[b", "+", "BYLEX"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("b"), bulk("c")]));
+
+        let response = handler
+            .handle_request(command(&["ZRANGE", "board", "0", "1", "LIMIT", "0", "1"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zrank_and_zscore_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        handler
+            .handle_request(command(&["ZADD", "board", "10", "a", "20", "b"]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&["ZRANK", "board", "b"]))
+            .await?;
+        assert_eq!(response, int(1));
+
+        let response = handler
+            .handle_request(command(&["ZRANK", "board", "b", "WITHSCORE"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(1), bulk("20")]));
+
+        let response = handler
+            .handle_request(command(&["ZRANK", "board", "missing"]))
+            .await?;
+        assert_eq!(response, Value::Null);
+
+        let response = handler
+            .handle_request(command(&["ZSCORE", "board", "a"]))
+            .await?;
+        assert_eq!(response, bulk("10"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zrem_and_zremrangebyscore_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        handler
+            .handle_request(command(&["ZADD", "board", "1", "a", "2", "b", "3", "c"]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&["ZREM", "board", "a", "missing"]))
+            .await?;
+        assert_eq!(response, int(1));
+
+        let response = handler
+            .handle_request(command(&["ZREMRANGEBYSCORE", "board", "-inf", "(3"]))
+            .await?;
+        assert_eq!(response, int(1));
+
+        let response = handler.handle_request(command(&["ZCARD", "board"])).await?;
+        assert_eq!(response, int(1));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zpop_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        handler
+            .handle_request(command(&["ZADD", "queue", "1", "a", "2", "b", "3", "c"]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&["ZPOPMIN", "queue"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("a"), bulk("1")]));
+
+        let response = handler
+            .handle_request(command(&["ZPOPMAX", "queue", "5"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![bulk("c"), bulk("3"), bulk("b"), bulk("2")])
+        );
+
+        let response = handler
+            .handle_request(command(&["ZPOPMAX", "queue"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zstore_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        handler
+            .handle_request(command(&["ZADD", "a", "1", "x", "2", "y"]))
+            .await?;
+        handler
+            .handle_request(command(&["ZADD", "b", "3", "y", "4", "z"]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&[
+                "ZUNIONSTORE",
+                "out",
+                "2",
+                "a",
+                "b",
+                "WEIGHTS",
+                "1",
+                "10",
+                "AGGREGATE",
+                "MIN",
+            ]))
+            .await?;
+        assert_eq!(response, int(3));
+        let response = handler
+            .handle_request(command(&["ZSCORE", "out", "y"]))
+            .await?;
+        assert_eq!(response, bulk("2"));
+
+        let response = handler
+            .handle_request(command(&["ZINTERSTORE", "out", "2", "a", "b"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["ZSCORE", "out", "y"]))
+            .await?;
+        assert_eq!(response, bulk("5"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sorted_set_wrong_type() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        handler
+            .handle_request(command(&["SET", "key", "value"]))
+            .await?;
+        let response = handler
+            .handle_request(command(&["ZADD", "key", "1", "a"]))
+            .await?;
+        assert!(matches!(response, Value::Error(e) if e.starts_with("WRONGTYPE")));
+
+        handler
+            .handle_request(command(&["ZADD", "board", "1", "a"]))
+            .await?;
+        let response = handler.handle_request(command(&["GET", "board"])).await?;
+        assert!(matches!(response, Value::Error(e) if e.starts_with("WRONGTYPE")));
+        Ok(())
+    }
+}
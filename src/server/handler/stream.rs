@@ -0,0 +1,864 @@
+use crate::cache::stream::{
+    now_millis, ClaimOptions, Fields, GroupEntries, GroupRead, IdSpec, PendingQuery, ReadFrom,
+    StreamId, Trim, TrimStrategy,
+};
+use crate::resp::value::Value;
+use crate::server::handler::{
+    bulk_strings, integer, parse_count, parse_integer, syntax_error, Handler,
+};
+use anyhow::{Error, Result};
+
+fn id_value(id: StreamId) -> Value {
+    Value::BulkString(id.to_string())
+}
+
+fn fields_value(fields: Fields) -> Value {
+    let mut values = Vec::with_capacity(fields.len() * 2);
+    for (field, value) in fields {
+        values.push(Value::BulkString(field));
+        values.push(Value::BulkString(value));
+    }
+    Value::Array(values)
+}
+
+/// Encode an entry as `[id, [field, value, ...]]`, with a null in place of the fields of an
+/// entry that no longer exists.
+fn entry_value(id: StreamId, fields: Option<Fields>) -> Value {
+    Value::Array(vec![
+        id_value(id),
+        fields.map(fields_value).unwrap_or(Value::Null),
+    ])
+}
+
+fn entries_value(entries: Vec<(StreamId, Fields)>) -> Value {
+    Value::Array(
+        entries
+            .into_iter()
+            .map(|(id, fields)| entry_value(id, Some(fields)))
+            .collect(),
+    )
+}
+
+/// Encode the reply of XREAD and XREADGROUP, `[[key, entries], ...]` or null when nothing was
+/// read.
+fn streams_value(streams: Vec<(String, GroupEntries)>) -> Value {
+    if streams.is_empty() {
+        return Value::Null;
+    }
+    Value::Array(
+        streams
+            .into_iter()
+            .map(|(key, entries)| {
+                let entries = entries
+                    .into_iter()
+                    .map(|(id, fields)| entry_value(id, fields))
+                    .collect();
+                Value::Array(vec![Value::BulkString(key), Value::Array(entries)])
+            })
+            .collect(),
+    )
+}
+
+/// Parse `MAXLEN|MINID [=|~] threshold [LIMIT count]` starting at `args[*i]`, advancing `i`
+/// past it. Returns `None` if `args[*i]` doesn't start a trimming clause.
+fn parse_trim(args: &[String], i: &mut usize) -> Result<Option<Trim>> {
+    let by_len = match args.get(*i).map(|arg| arg.to_ascii_lowercase()) {
+        Some(arg) if arg == "maxlen" => true,
+        Some(arg) if arg == "minid" => false,
+        _ => return Ok(None),
+    };
+    *i += 1;
+
+    let mut approximate = false;
+    match args.get(*i).map(|arg| arg.as_str()) {
+        Some("~") => {
+            approximate = true;
+            *i += 1;
+        }
+        Some("=") => *i += 1,
+        _ => {}
+    }
+
+    let threshold = args.get(*i).ok_or_else(syntax_error)?;
+    let strategy = if by_len {
+        TrimStrategy::MaxLen(parse_count(threshold)?)
+    } else {
+        TrimStrategy::MinId(StreamId::parse(threshold, 0)?)
+    };
+    *i += 1;
+
+    let mut limit = None;
+    if let Some(arg) = args.get(*i) {
+        if arg.eq_ignore_ascii_case("limit") {
+            if !approximate {
+                return Err(Error::msg(
+                    "syntax error, LIMIT cannot be used without the special ~ option",
+                ));
+            }
+            let count = args.get(*i + 1).ok_or_else(syntax_error)?;
+            limit = Some(parse_count(count)?).filter(|count| *count > 0);
+            *i += 2;
+        }
+    }
+    Ok(Some(Trim { strategy, limit }))
+}
+
+/// Parse `STREAMS key [key ...] id [id ...]` starting at `args[i]`.
+fn parse_streams(args: &[String], i: usize) -> Result<Vec<(String, String)>> {
+    match args.get(i) {
+        Some(arg) if arg.eq_ignore_ascii_case("streams") => {}
+        _ => return Err(syntax_error()),
+    }
+    let rest = &args[i + 1..];
+    if rest.is_empty() || !rest.len().is_multiple_of(2) {
+        return Err(Error::msg(
+            "Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified.",
+        ));
+    }
+    let (keys, ids) = rest.split_at(rest.len() / 2);
+    Ok(keys.iter().cloned().zip(ids.iter().cloned()).collect())
+}
+
+fn parse_millis(s: &str) -> Result<u64> {
+    s.parse::<u64>()
+        .map_err(|_| Error::msg("Invalid min-idle-time argument for XCLAIM"))
+}
+
+impl Handler {
+    pub(super) async fn handle_xadd(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 4 => args,
+            _ => {
+                return Value::Error("XADD requires a key, an id and field value pairs".to_string())
+            }
+        };
+
+        let mut i = 1;
+        let mut create = true;
+        if args[i].eq_ignore_ascii_case("nomkstream") {
+            create = false;
+            i += 1;
+        }
+        let trim = match parse_trim(&args, &mut i) {
+            Ok(trim) => trim,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        let id = match args.get(i).map(|id| IdSpec::parse(id)) {
+            Some(Ok(id)) => id,
+            Some(Err(e)) => return Value::Error(e.to_string()),
+            None => return Value::Error(syntax_error().to_string()),
+        };
+        let pairs = &args[i + 1..];
+        if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+            return Value::Error("wrong number of arguments for 'xadd' command".to_string());
+        }
+        let fields = pairs
+            .chunks(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+
+        match self
+            .client_store
+            .xadd(args[0].clone(), id, fields, trim, create)
+            .await
+        {
+            Ok(Some(id)) => id_value(id),
+            Ok(None) => Value::Null,
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_xlen(&self, args: &[Value]) -> Value {
+        if let Some(Value::BulkString(key)) = args.first() {
+            match self.client_store.xlen(key.clone()).await {
+                Ok(len) => integer(len),
+                Err(e) => Value::Error(e.to_string()),
+            }
+        } else {
+            Value::Error("XLEN requires one argument".to_string())
+        }
+    }
+
+    pub(super) async fn handle_xrange(&self, args: &[Value], reverse: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 3 || args.len() == 5 => args,
+            _ => {
+                return Value::Error(
+                    "XRANGE and XREVRANGE require a key, a start and an end".to_string(),
+                )
+            }
+        };
+
+        // XREVRANGE takes the end first
+        let (start, end) = if reverse {
+            (&args[2], &args[1])
+        } else {
+            (&args[1], &args[2])
+        };
+        let (start, end) = match (StreamId::parse_start(start), StreamId::parse_end(end)) {
+            (Ok(start), Ok(end)) => (start, end),
+            (Err(e), _) | (_, Err(e)) => return Value::Error(e.to_string()),
+        };
+        let count = match args.get(3) {
+            Some(option) if option.eq_ignore_ascii_case("count") => match parse_count(&args[4]) {
+                Ok(count) => Some(count),
+                Err(e) => return Value::Error(e.to_string()),
+            },
+            Some(_) => return Value::Error(syntax_error().to_string()),
+            None => None,
+        };
+
+        match self
+            .client_store
+            .xrange(args[0].clone(), start, end, count, reverse)
+            .await
+        {
+            Ok(entries) => entries_value(entries),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_xtrim(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => return Value::Error("XTRIM requires a key and a trimming strategy".to_string()),
+        };
+
+        let mut i = 1;
+        let trim = match parse_trim(&args, &mut i) {
+            Ok(Some(trim)) if i == args.len() => trim,
+            Ok(_) => return Value::Error(syntax_error().to_string()),
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.xtrim(args[0].clone(), trim).await {
+            Ok(evicted) => integer(evicted),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_xread(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => return Value::Error("XREAD requires STREAMS, keys and ids".to_string()),
+        };
+
+        let mut i = 0;
+        let mut count = None;
+        if args[i].eq_ignore_ascii_case("count") {
+            match args.get(i + 1).map(|count| parse_count(count)) {
+                Some(Ok(parsed)) => count = Some(parsed),
+                Some(Err(e)) => return Value::Error(e.to_string()),
+                None => return Value::Error(syntax_error().to_string()),
+            }
+            i += 2;
+        }
+        let streams = match parse_streams(&args, i).and_then(|streams| {
+            streams
+                .into_iter()
+                .map(|(key, id)| Ok((key, ReadFrom::parse(&id)?)))
+                .collect::<Result<Vec<_>>>()
+        }) {
+            Ok(streams) => streams,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.xread(streams, count).await {
+            Ok(streams) => streams_value(
+                streams
+                    .into_iter()
+                    .map(|(key, entries)| {
+                        let entries = entries
+                            .into_iter()
+                            .map(|(id, fields)| (id, Some(fields)))
+                            .collect();
+                        (key, entries)
+                    })
+                    .collect(),
+            ),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_xgroup(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => {
+                return Value::Error("XGROUP requires a subcommand, a key and a group".to_string())
+            }
+        };
+        let (key, group) = (args[1].clone(), args[2].clone());
+
+        match args[0].to_ascii_lowercase().as_str() {
+            "create" if args.len() == 4 || args.len() == 5 => {
+                let create = match args.get(4) {
+                    Some(option) if option.eq_ignore_ascii_case("mkstream") => true,
+                    Some(_) => return Value::Error(syntax_error().to_string()),
+                    None => false,
+                };
+                let from = match ReadFrom::parse(&args[3]) {
+                    Ok(from) => from,
+                    Err(e) => return Value::Error(e.to_string()),
+                };
+                match self
+                    .client_store
+                    .xgroup_create(key, group, from, create)
+                    .await
+                {
+                    Ok(()) => Value::SimpleString("OK".to_string()),
+                    Err(e) => Value::Error(e.to_string()),
+                }
+            }
+            "setid" if args.len() == 4 => {
+                let from = match ReadFrom::parse(&args[3]) {
+                    Ok(from) => from,
+                    Err(e) => return Value::Error(e.to_string()),
+                };
+                match self.client_store.xgroup_setid(key, group, from).await {
+                    Ok(()) => Value::SimpleString("OK".to_string()),
+                    Err(e) => Value::Error(e.to_string()),
+                }
+            }
+            "destroy" if args.len() == 3 => {
+                match self.client_store.xgroup_destroy(key, group).await {
+                    Ok(destroyed) => integer(destroyed as u8),
+                    Err(e) => Value::Error(e.to_string()),
+                }
+            }
+            "createconsumer" if args.len() == 4 => {
+                let consumer = args[3].clone();
+                match self
+                    .client_store
+                    .xgroup_createconsumer(key, group, consumer)
+                    .await
+                {
+                    Ok(created) => integer(created as u8),
+                    Err(e) => Value::Error(e.to_string()),
+                }
+            }
+            "delconsumer" if args.len() == 4 => {
+                let consumer = args[3].clone();
+                match self
+                    .client_store
+                    .xgroup_delconsumer(key, group, consumer)
+                    .await
+                {
+                    Ok(pending) => integer(pending),
+                    Err(e) => Value::Error(e.to_string()),
+                }
+            }
+            _ => Value::Error(format!("unknown XGROUP subcommand '{}'", args[0])),
+        }
+    }
+
+    pub(super) async fn handle_xreadgroup(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 6 && args[0].eq_ignore_ascii_case("group") => args,
+            _ => {
+                return Value::Error(
+                    "XREADGROUP requires GROUP, a group, a consumer, STREAMS, keys and ids"
+                        .to_string(),
+                )
+            }
+        };
+        let (group, consumer) = (args[1].clone(), args[2].clone());
+
+        let mut i = 3;
+        let mut count = None;
+        let mut no_ack = false;
+        loop {
+            match args.get(i).map(|arg| arg.to_ascii_lowercase()) {
+                Some(arg) if arg == "count" => {
+                    match args.get(i + 1).map(|count| parse_count(count)) {
+                        Some(Ok(parsed)) => count = Some(parsed),
+                        Some(Err(e)) => return Value::Error(e.to_string()),
+                        None => return Value::Error(syntax_error().to_string()),
+                    }
+                    i += 2;
+                }
+                Some(arg) if arg == "noack" => {
+                    no_ack = true;
+                    i += 1;
+                }
+                _ => break,
+            }
+        }
+        let streams = match parse_streams(&args, i).and_then(|streams| {
+            streams
+                .into_iter()
+                .map(|(key, id)| Ok((key, GroupRead::parse(&id)?)))
+                .collect::<Result<Vec<_>>>()
+        }) {
+            Ok(streams) => streams,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .xreadgroup(group, consumer, streams, count, no_ack)
+            .await
+        {
+            Ok(streams) => streams_value(streams),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_xack(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => return Value::Error("XACK requires a key, a group and ids".to_string()),
+        };
+        let ids = match args[2..]
+            .iter()
+            .map(|id| StreamId::parse(id, 0))
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(ids) => ids,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .xack(args[0].clone(), args[1].clone(), ids)
+            .await
+        {
+            Ok(acked) => integer(acked),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_xpending(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("XPENDING requires a key and a group".to_string()),
+        };
+        let (key, group) = (args[0].clone(), args[1].clone());
+
+        if args.len() == 2 {
+            return match self.client_store.xpending_summary(key, group).await {
+                Ok(summary) if summary.count == 0 => {
+                    Value::Array(vec![integer(0), Value::Null, Value::Null, Value::Null])
+                }
+                Ok(summary) => Value::Array(vec![
+                    integer(summary.count),
+                    summary.first.map(id_value).unwrap_or(Value::Null),
+                    summary.last.map(id_value).unwrap_or(Value::Null),
+                    Value::Array(
+                        summary
+                            .consumers
+                            .into_iter()
+                            .map(|(consumer, count)| {
+                                Value::Array(vec![
+                                    Value::BulkString(consumer),
+                                    Value::BulkString(count.to_string()),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ]),
+                Err(e) => Value::Error(e.to_string()),
+            };
+        }
+
+        let mut i = 2;
+        let mut min_idle = None;
+        if args[i].eq_ignore_ascii_case("idle") {
+            match args.get(i + 1).map(|idle| parse_integer(idle)) {
+                Some(Ok(idle)) => min_idle = Some(idle.max(0) as u64),
+                Some(Err(e)) => return Value::Error(e.to_string()),
+                None => return Value::Error(syntax_error().to_string()),
+            }
+            i += 2;
+        }
+        let rest = &args[i..];
+        if rest.len() != 3 && rest.len() != 4 {
+            return Value::Error(syntax_error().to_string());
+        }
+        let range = StreamId::parse_start(&rest[0])
+            .and_then(|start| Ok((start, StreamId::parse_end(&rest[1])?)));
+        let (start, end) = match range {
+            Ok(range) => range,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        let count = match parse_integer(&rest[2]) {
+            Ok(count) => count.max(0) as usize,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        let query = PendingQuery {
+            start,
+            end,
+            count,
+            consumer: rest.get(3).cloned(),
+            min_idle,
+        };
+
+        match self.client_store.xpending(key, group, query).await {
+            Ok(pending) => Value::Array(
+                pending
+                    .into_iter()
+                    .map(|(id, entry)| {
+                        let idle = now_millis().saturating_sub(entry.delivered);
+                        Value::Array(vec![
+                            id_value(id),
+                            Value::BulkString(entry.consumer),
+                            integer(idle),
+                            integer(entry.deliveries),
+                        ])
+                    })
+                    .collect(),
+            ),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_xclaim(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 5 => args,
+            _ => {
+                return Value::Error(
+                    "XCLAIM requires a key, a group, a consumer, a min-idle-time and ids"
+                        .to_string(),
+                )
+            }
+        };
+        let min_idle = match parse_millis(&args[3]) {
+            Ok(min_idle) => min_idle,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        let mut i = 4;
+        let mut ids = Vec::new();
+        while let Some(Ok(id)) = args.get(i).map(|id| StreamId::parse(id, 0)) {
+            ids.push(id);
+            i += 1;
+        }
+        if ids.is_empty() {
+            return Value::Error(
+                "Invalid stream ID specified as stream command argument".to_string(),
+            );
+        }
+
+        let mut options = ClaimOptions::default();
+        while i < args.len() {
+            let option = args[i].to_ascii_lowercase();
+            let parse_next = |name: &str| -> Result<u64> {
+                let value = args.get(i + 1).ok_or_else(syntax_error)?;
+                value
+                    .parse::<u64>()
+                    .map_err(|_| Error::msg(format!("Invalid {} option argument for XCLAIM", name)))
+            };
+            let parsed = match option.as_str() {
+                "force" => {
+                    options.force = true;
+                    Ok(1)
+                }
+                "justid" => {
+                    options.just_id = true;
+                    Ok(1)
+                }
+                "idle" => parse_next("IDLE").map(|idle| {
+                    options.idle = Some(idle);
+                    2
+                }),
+                "time" => parse_next("TIME").map(|time| {
+                    options.time = Some(time);
+                    2
+                }),
+                "retrycount" => parse_next("RETRYCOUNT").map(|count| {
+                    options.retry_count = Some(count);
+                    2
+                }),
+                _ => Err(Error::msg(format!(
+                    "Unrecognized XCLAIM option '{}'",
+                    args[i]
+                ))),
+            };
+            match parsed {
+                Ok(consumed) => i += consumed,
+                Err(e) => return Value::Error(e.to_string()),
+            }
+        }
+
+        let just_id = options.just_id;
+        match self
+            .client_store
+            .xclaim(
+                args[0].clone(),
+                args[1].clone(),
+                args[2].clone(),
+                min_idle,
+                ids,
+                options,
+            )
+            .await
+        {
+            Ok(claimed) if just_id => {
+                Value::Array(claimed.into_iter().map(|(id, _)| id_value(id)).collect())
+            }
+            Ok(claimed) => entries_value(claimed),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    fn entry(id: &str, fields: &[&str]) -> Value {
+        Value::Array(vec![
+            bulk(id),
+            Value::Array(fields.iter().map(|f| bulk(f)).collect()),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_xadd_and_xrange_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["XADD", "s", "1-1", "a", "1"]))
+            .await?;
+        assert_eq!(response, bulk("1-1"));
+        let response = handler
+            .handle_request(command(&["XADD", "s", "1-*", "b", "2"]))
+            .await?;
+        assert_eq!(response, bulk("1-2"));
+        let response = handler
+            .handle_request(command(&["XADD", "s", "1-1", "c", "3"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = handler
+            .handle_request(command(&["XADD", "missing", "NOMKSTREAM", "*", "a", "1"]))
+            .await?;
+        assert_eq!(response, Value::Null);
+
+        let response = handler
+            .handle_request(command(&["XRANGE", "s", "-", "+"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![entry("1-1", &["a", "1"]), entry("1-2", &["b", "2"])])
+        );
+        let response = handler
+            .handle_request(command(&["XREVRANGE", "s", "+", "-", "COUNT", "1"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![entry("1-2", &["b", "2"])]));
+
+        let response = handler.handle_request(command(&["XLEN", "s"])).await?;
+        assert_eq!(response, int(2));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_xtrim_command() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        for id in ["1", "2", "3", "4"] {
+            handler
+                .handle_request(command(&["XADD", "s", id, "f", "v"]))
+                .await?;
+        }
+
+        let response = handler
+            .handle_request(command(&["XTRIM", "s", "MAXLEN", "=", "3"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["XTRIM", "s", "MINID", "3"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["XTRIM", "s", "MAXLEN", "0", "LIMIT", "1"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        handler
+            .handle_request(command(&["XADD", "s", "MAXLEN", "2", "5", "f", "v"]))
+            .await?;
+        let response = handler
+            .handle_request(command(&["XRANGE", "s", "-", "+"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![entry("4-0", &["f", "v"]), entry("5-0", &["f", "v"])])
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_xread_command() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        handler
+            .handle_request(command(&["XADD", "a", "1", "f", "v"]))
+            .await?;
+        handler
+            .handle_request(command(&["XADD", "b", "2", "f", "v"]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&[
+                "XREAD", "COUNT", "5", "STREAMS", "a", "b", "0", "2",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![Value::Array(vec![
+                bulk("a"),
+                Value::Array(vec![entry("1-0", &["f", "v"])])
+            ])])
+        );
+
+        let response = handler
+            .handle_request(command(&["XREAD", "STREAMS", "a", "$"]))
+            .await?;
+        assert_eq!(response, Value::Null);
+
+        let response = handler
+            .handle_request(command(&["XREAD", "STREAMS", "a", "b", "0"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_consumer_group_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        let response = handler
+            .handle_request(command(&["XGROUP", "CREATE", "s", "g", "$", "MKSTREAM"]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        let response = handler
+            .handle_request(command(&["XGROUP", "CREATE", "s", "g", "$"]))
+            .await?;
+        assert!(matches!(response, Value::Error(e) if e.starts_with("BUSYGROUP")));
+
+        handler
+            .handle_request(command(&["XADD", "s", "1", "job", "a"]))
+            .await?;
+        handler
+            .handle_request(command(&["XADD", "s", "2", "job", "b"]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&[
+                "XREADGROUP",
+                "GROUP",
+                "g",
+                "alice",
+                "COUNT",
+                "1",
+                "STREAMS",
+                "s",
+                ">",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![Value::Array(vec![
+                bulk("s"),
+                Value::Array(vec![entry("1-0", &["job", "a"])])
+            ])])
+        );
+
+        let response = handler
+            .handle_request(command(&["XPENDING", "s", "g"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![
+                int(1),
+                bulk("1-0"),
+                bulk("1-0"),
+                Value::Array(vec![Value::Array(vec![bulk("alice"), bulk("1")])])
+            ])
+        );
+
+        let response = handler
+            .handle_request(command(&["XCLAIM", "s", "g", "bob", "0", "1-0", "JUSTID"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("1-0")]));
+
+        let response = handler
+            .handle_request(command(&["XPENDING", "s", "g", "-", "+", "10", "bob"]))
+            .await?;
+        match response {
+            Value::Array(entries) => {
+                assert_eq!(entries.len(), 1);
+                match &entries[0] {
+                    Value::Array(fields) => {
+                        assert_eq!(fields[0], bulk("1-0"));
+                        assert_eq!(fields[1], bulk("bob"));
+                        assert_eq!(fields[3], int(1));
+                    }
+                    other => panic!("unexpected pending entry {:?}", other),
+                }
+            }
+            other => panic!("unexpected reply {:?}", other),
+        }
+
+        let response = handler
+            .handle_request(command(&["XACK", "s", "g", "1-0", "2-0"]))
+            .await?;
+        assert_eq!(response, int(1));
+
+        let response = handler
+            .handle_request(command(&[
+                "XREADGROUP",
+                "GROUP",
+                "nope",
+                "c",
+                "STREAMS",
+                "s",
+                ">",
+            ]))
+            .await?;
+        assert!(matches!(response, Value::Error(e) if e.starts_with("NOGROUP")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_xgroup_consumer_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+        handler
+            .handle_request(command(&["XADD", "s", "1", "job", "a"]))
+            .await?;
+        handler
+            .handle_request(command(&["XGROUP", "CREATE", "s", "g", "0"]))
+            .await?;
+
+        let response = handler
+            .handle_request(command(&["XGROUP", "CREATECONSUMER", "s", "g", "alice"]))
+            .await?;
+        assert_eq!(response, int(1));
+        handler
+            .handle_request(command(&[
+                "XREADGROUP",
+                "GROUP",
+                "g",
+                "alice",
+                "STREAMS",
+                "s",
+                ">",
+            ]))
+            .await?;
+        let response = handler
+            .handle_request(command(&["XGROUP", "DELCONSUMER", "s", "g", "alice"]))
+            .await?;
+        assert_eq!(response, int(1));
+
+        let response = handler
+            .handle_request(command(&["XGROUP", "SETID", "s", "g", "0"]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        let response = handler
+            .handle_request(command(&["XGROUP", "DESTROY", "s", "g"]))
+            .await?;
+        assert_eq!(response, int(1));
+        Ok(())
+    }
+}
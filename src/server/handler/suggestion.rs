@@ -0,0 +1,205 @@
+use crate::cache::suggestion::Suggestion;
+use crate::resp::value::Value;
+use crate::server::handler::{
+    bulk_strings, integer, parse_count, parse_float, syntax_error, Handler,
+};
+use anyhow::Result;
+
+/// Parse `string score [INCR] [PAYLOAD payload]`, returning whether to increment the score.
+fn parse_suggestion(args: &[String]) -> Result<(Suggestion, bool)> {
+    let mut suggestion = Suggestion {
+        string: args[0].clone(),
+        score: parse_float(&args[1])?,
+        payload: None,
+    };
+    let mut increment = false;
+    let mut rest = &args[2..];
+    while let Some(option) = rest.first() {
+        match option.to_ascii_lowercase().as_str() {
+            "incr" => {
+                increment = true;
+                rest = &rest[1..];
+            }
+            "payload" if rest.len() >= 2 => {
+                suggestion.payload = Some(rest[1].clone());
+                rest = &rest[2..];
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+    Ok((suggestion, increment))
+}
+
+/// What FT.SUGGET looks up and replies with for every suggestion besides its string.
+#[derive(Clone, Copy, Debug)]
+struct Lookup {
+    fuzzy: bool,
+    scores: bool,
+    payloads: bool,
+    max: usize,
+}
+
+/// Parse `[FUZZY] [WITHSCORES] [WITHPAYLOADS] [MAX max]`.
+fn parse_lookup(args: &[String]) -> Result<Lookup> {
+    let mut lookup = Lookup {
+        fuzzy: false,
+        scores: false,
+        payloads: false,
+        max: 5,
+    };
+    let mut rest = args;
+    while let Some(option) = rest.first() {
+        match option.to_ascii_lowercase().as_str() {
+            "fuzzy" => lookup.fuzzy = true,
+            "withscores" => lookup.scores = true,
+            "withpayloads" => lookup.payloads = true,
+            "max" if rest.len() >= 2 => {
+                lookup.max = parse_count(&rest[1])?;
+                rest = &rest[1..];
+            }
+            _ => return Err(syntax_error()),
+        }
+        rest = &rest[1..];
+    }
+    Ok(lookup)
+}
+
+impl Handler {
+    pub(super) async fn handle_ft_sugadd(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => return Value::Error("FT.SUGADD requires a key, a string and a score".to_string()),
+        };
+        let (suggestion, increment) = match parse_suggestion(&args[1..]) {
+            Ok(parsed) => parsed,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .sugadd(args[0].clone(), suggestion, increment)
+            .await
+        {
+            Ok(len) => integer(len),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_ft_sugget(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("FT.SUGGET requires a key and a prefix".to_string()),
+        };
+        let lookup = match parse_lookup(&args[2..]) {
+            Ok(lookup) => lookup,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .sugget(args[0].clone(), args[1].clone(), lookup.fuzzy, lookup.max)
+            .await
+        {
+            Ok(suggestions) => {
+                let mut values = Vec::new();
+                for suggestion in suggestions {
+                    values.push(Value::BulkString(suggestion.string));
+                    if lookup.scores {
+                        values.push(Value::BulkString(suggestion.score.to_string()));
+                    }
+                    if lookup.payloads {
+                        values.push(suggestion.payload.map_or(Value::Null, Value::BulkString));
+                    }
+                }
+                Value::Array(values)
+            }
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_ft_sugdel(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => return Value::Error("FT.SUGDEL requires a key and a string".to_string()),
+        };
+
+        match self
+            .client_store
+            .sugdel(args[0].clone(), args[1].clone())
+            .await
+        {
+            Ok(removed) => integer(removed as u8),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_ft_suglen(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("FT.SUGLEN requires a key".to_string()),
+        };
+
+        match self.client_store.suglen(args[0].clone()).await {
+            Ok(len) => integer(len),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_suggestion_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["FT.SUGADD", "ac", "hello", "2", "PAYLOAD", "p1"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["FT.SUGADD", "ac", "help", "1", "INCR"]))
+            .await?;
+        assert_eq!(response, int(2));
+        let response = handler
+            .handle_request(command(&["FT.SUGADD", "ac", "jelly", "x"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&[
+                "FT.SUGGET",
+                "ac",
+                "hel",
+                "WITHSCORES",
+                "WITHPAYLOADS",
+                "MAX",
+                "1",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![bulk("hello"), bulk("1.2"), bulk("p1")])
+        );
+        let response = handler
+            .handle_request(command(&["FT.SUGGET", "ac", "jelp", "FUZZY"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("help")]));
+
+        let response = handler
+            .handle_request(command(&["FT.SUGDEL", "ac", "HELLO"]))
+            .await?;
+        assert_eq!(response, int(1));
+        let response = handler
+            .handle_request(command(&["FT.SUGLEN", "ac"]))
+            .await?;
+        assert_eq!(response, int(1));
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use crate::cache::tdigest::{TDigest, DEFAULT_COMPRESSION};
+use crate::resp::value::Value;
+use crate::server::handler::{bulk_strings, parse_float, parse_keys, syntax_error, Handler};
+use anyhow::{Error, Result};
+
+/// Format an estimate the way Redis formats doubles.
+fn float_value(value: f64) -> Value {
+    let formatted = if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        value.to_string()
+    };
+    Value::BulkString(formatted)
+}
+
+fn parse_compression(s: &str) -> Result<f64> {
+    let compression = parse_float(s)?;
+    if compression < 1.0 {
+        return Err(Error::msg("compression must be at least 1"));
+    }
+    Ok(compression)
+}
+
+/// Parse `numkeys source [source ...] [COMPRESSION compression] [OVERRIDE]`.
+fn parse_merge(args: &[String]) -> Result<(Vec<String>, Option<f64>, bool)> {
+    let (sources, rest) = parse_keys(args)?;
+    let mut compression = None;
+    let mut replace = false;
+    let mut i = 0;
+    while i < rest.len() {
+        match rest[i].to_ascii_lowercase().as_str() {
+            "compression" => {
+                compression = Some(parse_compression(
+                    rest.get(i + 1).ok_or_else(syntax_error)?,
+                )?);
+                i += 2;
+            }
+            "override" => {
+                replace = true;
+                i += 1;
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+    Ok((sources, compression, replace))
+}
+
+impl Handler {
+    pub(super) async fn handle_tdigest_create(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 || args.len() == 3 => args,
+            _ => return Value::Error("TDIGEST.CREATE requires a key".to_string()),
+        };
+        let compression = match args.get(1..) {
+            Some([option, value]) if option.eq_ignore_ascii_case("compression") => {
+                match parse_compression(value) {
+                    Ok(compression) => compression,
+                    Err(e) => return Value::Error(e.to_string()),
+                }
+            }
+            Some([]) => DEFAULT_COMPRESSION,
+            _ => return Value::Error(syntax_error().to_string()),
+        };
+
+        match self
+            .client_store
+            .tdigest_create(args[0].clone(), compression)
+            .await
+        {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_tdigest_add(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("TDIGEST.ADD requires a key and values".to_string()),
+        };
+        let values = match args[1..]
+            .iter()
+            .map(|value| parse_float(value))
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(values) => values,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.tdigest_add(args[0].clone(), values).await {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    /// Handle TDIGEST.QUANTILE, or TDIGEST.CDF when `cdf` is set.
+    pub(super) async fn handle_tdigest_estimate(&self, args: &[Value], cdf: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ if cdf => return Value::Error("TDIGEST.CDF requires a key and values".to_string()),
+            _ => return Value::Error("TDIGEST.QUANTILE requires a key and quantiles".to_string()),
+        };
+        let inputs = match args[1..]
+            .iter()
+            .map(|input| parse_float(input))
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(inputs) => inputs,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+        if !cdf && inputs.iter().any(|q| !(0.0..=1.0).contains(q)) {
+            return Value::Error("quantiles must be between 0 and 1".to_string());
+        }
+
+        let estimates = self
+            .client_store
+            .tdigest_read(args[0].clone(), |digest| {
+                inputs
+                    .iter()
+                    .map(|&input| {
+                        if cdf {
+                            digest.cdf(input)
+                        } else {
+                            digest.quantile(input)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .await;
+        match estimates {
+            Ok(estimates) => Value::Array(estimates.into_iter().map(float_value).collect()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    /// Handle TDIGEST.MAX, or TDIGEST.MIN when `min` is set.
+    pub(super) async fn handle_tdigest_bound(&self, args: &[Value], min: bool) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("TDIGEST.MIN and TDIGEST.MAX require a key".to_string()),
+        };
+
+        let bound = if min { TDigest::min } else { TDigest::max };
+        match self.client_store.tdigest_read(args[0].clone(), bound).await {
+            Ok(value) => float_value(value),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_tdigest_merge(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => {
+                return Value::Error(
+                    "TDIGEST.MERGE requires a destination, numkeys and sources".to_string(),
+                )
+            }
+        };
+        let (sources, compression, replace) = match parse_merge(&args[1..]) {
+            Ok(parsed) => parsed,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .tdigest_merge(args[0].clone(), sources, compression, replace)
+            .await
+        {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_tdigest_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&[
+                "TDIGEST.CREATE",
+                "latency",
+                "COMPRESSION",
+                "200",
+            ]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        let response = handler
+            .handle_request(command(&["TDIGEST.MIN", "latency"]))
+            .await?;
+        assert_eq!(response, bulk("nan"));
+
+        let response = handler
+            .handle_request(command(&[
+                "TDIGEST.ADD",
+                "latency",
+                "1",
+                "2",
+                "3",
+                "4",
+                "5",
+            ]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        let response = handler
+            .handle_request(command(&["TDIGEST.QUANTILE", "latency", "0", "0.5", "1"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![bulk("1"), bulk("3"), bulk("5")])
+        );
+        let response = handler
+            .handle_request(command(&["TDIGEST.CDF", "latency", "0", "10"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("0"), bulk("1")]));
+        let response = handler
+            .handle_request(command(&["TDIGEST.QUANTILE", "latency", "2"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&["TDIGEST.MERGE", "all", "1", "latency"]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        let response = handler
+            .handle_request(command(&["TDIGEST.MAX", "all"]))
+            .await?;
+        assert_eq!(response, bulk("5"));
+        Ok(())
+    }
+}
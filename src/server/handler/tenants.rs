@@ -0,0 +1,286 @@
+use crate::cache::databases::Databases;
+use crate::cache::expiry::Expiry;
+use crate::cache::quota::{Eviction, Quota};
+use crate::cache::Cache;
+use crate::resp::value::Value;
+use crate::server::handler::{bulk_strings, integer, parse_count, syntax_error, Handler};
+use anyhow::{Error, Result};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Parse `[PASSWORD password] [MAXKEYS count] [MAXMEMORY bytes] [DEFAULTTTL seconds]
+/// [EVICTION policy]`, the password only being accepted if `password` is set. The default
+/// time to live must end before the latest instant the clock can hold.
+fn parse_quota(args: &[String], password: bool) -> Result<(Option<String>, Quota)> {
+    let mut secret = None;
+    let mut quota = Quota::default();
+    let mut rest = args;
+    while let [option, value, tail @ ..] = rest {
+        match option.to_ascii_lowercase().as_str() {
+            "password" if password => secret = Some(value.clone()),
+            "maxkeys" => quota.max_keys = Some(parse_count(value)?),
+            "maxmemory" => quota.max_memory = Some(parse_count(value)?),
+            "defaultttl" => {
+                let ttl = Duration::from_secs(parse_count(value)? as u64);
+                Expiry::after(ttl)?;
+                quota.default_ttl = Some(ttl);
+            }
+            "eviction" => quota.eviction = Eviction::parse(value)?,
+            _ => return Err(syntax_error()),
+        }
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        return Err(syntax_error());
+    }
+    Ok((secret, quota))
+}
+
+fn optional(value: Option<usize>) -> Value {
+    value.map_or(Value::Null, integer)
+}
+
+impl Handler {
+    /// Bind the client to the tenant `name`, whose keyspace becomes its only database.
+    fn bind(&mut self, name: String, cache: Arc<Cache>) {
+        log::debug!("binding a client to tenant {}", name);
+        self.databases = Databases::from(cache);
+        self.db = 0;
+        self.tenant = Some(name);
+    }
+
+    /// Handle `AUTH tenant password`, or `AUTH password` with the admin password to manage the
+    /// tenants.
+    pub(super) async fn handle_auth(&mut self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            Some(args) if args.len() == 1 => {
+                return match self.tenants.authenticate_admin(&args[0]).await {
+                    Ok(()) => {
+                        self.admin = true;
+                        Value::SimpleString("OK".to_string())
+                    }
+                    Err(e) => Value::Error(e.to_string()),
+                };
+            }
+            _ => return Value::Error("AUTH requires a tenant and a password".to_string()),
+        };
+
+        match self.tenants.authenticate(&args[0], &args[1]).await {
+            Ok(cache) => {
+                self.bind(args[0].clone(), cache);
+                Value::SimpleString("OK".to_string())
+            }
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    /// Handle the TENANT subcommands, which only clients authenticated with the admin password
+    /// and not bound to a tenant can run.
+    pub(super) async fn handle_tenant(&mut self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("TENANT requires a subcommand".to_string()),
+        };
+        if !self.admin {
+            return Value::Error(
+                "NOPERM authenticate with the admin password to manage tenants".to_string(),
+            );
+        }
+        if self.tenant.is_some() {
+            return Value::Error(
+                "NOPERM clients bound to a tenant can't manage tenants".to_string(),
+            );
+        }
+
+        let result = match (args[0].to_ascii_lowercase().as_str(), args.get(1)) {
+            ("create", Some(name)) => match parse_quota(&args[2..], true) {
+                Ok((password, quota)) => self.tenants.create(name.clone(), password, quota).await,
+                Err(e) => Err(e),
+            },
+            ("quota", Some(name)) => match parse_quota(&args[2..], false) {
+                Ok((_, quota)) => self.tenants.set_quota(name, quota).await,
+                Err(e) => Err(e),
+            },
+            ("delete", Some(name)) if args.len() == 2 => self.tenants.delete(name).await,
+            ("use", Some(name)) if args.len() == 2 => match self.tenants.bind(name).await {
+                Ok(cache) => {
+                    self.bind(name.clone(), cache);
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            },
+            ("info", Some(name)) if args.len() == 2 => {
+                return match self.tenants.info(name).await {
+                    Ok(info) => Value::Array(vec![
+                        Value::BulkString("keys".to_string()),
+                        integer(info.keys),
+                        Value::BulkString("memory".to_string()),
+                        integer(info.memory),
+                        Value::BulkString("maxkeys".to_string()),
+                        optional(info.quota.max_keys),
+                        Value::BulkString("maxmemory".to_string()),
+                        optional(info.quota.max_memory),
+                        Value::BulkString("defaultttl".to_string()),
+                        optional(info.quota.default_ttl.map(|ttl| ttl.as_secs() as usize)),
+                        Value::BulkString("eviction".to_string()),
+                        Value::BulkString(info.quota.eviction.name().to_string()),
+                    ]),
+                    Err(e) => Value::Error(e.to_string()),
+                }
+            }
+            ("list", None) => {
+                let names = self.tenants.names().await;
+                return Value::Array(names.into_iter().map(Value::BulkString).collect());
+            }
+            _ => Err(Error::msg(format!(
+                "unknown or malformed TENANT subcommand {}",
+                args[0]
+            ))),
+        };
+
+        match result {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::tenants::Tenants;
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int, ok};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_tenant_commands() -> Result<()> {
+        let tenants = Tenants::default().with_admin_password(Some("root".to_string()));
+        let cache = Arc::new(Cache::default());
+        let mut admin = Handler::new(cache.clone(), None).with_tenants(tenants.clone());
+        let mut client = Handler::new(cache, None).with_tenants(tenants);
+        let response = admin.handle_request(command(&["AUTH", "root"])).await?;
+        assert_eq!(response, ok());
+
+        let response = admin
+            .handle_request(command(&[
+                "TENANT", "CREATE", "team-a", "PASSWORD", "secret", "MAXKEYS", "2",
+            ]))
+            .await?;
+        assert_eq!(response, ok());
+        let response = admin
+            .handle_request(command(&["TENANT", "CREATE", "team-b", "DEFAULTTTL", "60"]))
+            .await?;
+        assert_eq!(response, ok());
+        let response = admin
+            .handle_request(command(&[
+                "TENANT",
+                "CREATE",
+                "team-c",
+                "DEFAULTTTL",
+                "18446744073709551615",
+            ]))
+            .await?;
+        assert_eq!(response, Value::Error("invalid expire time".to_string()));
+        let response = admin.handle_request(command(&["TENANT", "LIST"])).await?;
+        assert_eq!(response, Value::Array(vec![bulk("team-a"), bulk("team-b")]));
+        admin
+            .handle_request(command(&["SET", "shared", "value"]))
+            .await?;
+
+        let response = client
+            .handle_request(command(&["AUTH", "team-a", "wrong"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = client
+            .handle_request(command(&["AUTH", "team-a", "secret"]))
+            .await?;
+        assert_eq!(response, ok());
+        let response = client.handle_request(command(&["GET", "shared"])).await?;
+        assert_eq!(response, Value::Null);
+        for key in ["a", "b"] {
+            let response = client.handle_request(command(&["SET", key, "1"])).await?;
+            assert_eq!(response, ok());
+        }
+        let response = client.handle_request(command(&["SET", "c", "1"])).await?;
+        let Value::Error(error) = response else {
+            panic!("the third key is over the quota");
+        };
+        assert!(error.starts_with("OOM"));
+        let response = client
+            .handle_request(command(&["TENANT", "DELETE", "team-a"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = admin
+            .handle_request(command(&["TENANT", "INFO", "team-a"]))
+            .await?;
+        let Value::Array(info) = response else {
+            panic!("TENANT INFO replies with an array");
+        };
+        assert_eq!(info[1], int(2));
+        assert_eq!(info[5], int(2));
+        assert_eq!(info[7], Value::Null);
+        assert_eq!(info[11], bulk("noeviction"));
+        let response = admin
+            .handle_request(command(&["TENANT", "QUOTA", "team-a", "EVICTION", "lru"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = admin
+            .handle_request(command(&["TENANT", "QUOTA", "team-a", "MAXKEYS", "3"]))
+            .await?;
+        assert_eq!(response, ok());
+        let response = client.handle_request(command(&["SET", "c", "1"])).await?;
+        assert_eq!(response, ok());
+
+        let response = admin
+            .handle_request(command(&["TENANT", "USE", "team-a"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = admin
+            .handle_request(command(&["TENANT", "USE", "team-b"]))
+            .await?;
+        assert_eq!(response, ok());
+        admin.handle_request(command(&["SET", "a", "1"])).await?;
+        let response = admin.handle_request(command(&["TTL", "a"])).await?;
+        assert_eq!(response, int(60));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tenant_management_requires_admin() -> Result<()> {
+        let tenants = Tenants::default().with_admin_password(Some("root".to_string()));
+        let cache = Arc::new(Cache::default());
+        let mut client = Handler::new(cache.clone(), None).with_tenants(tenants);
+
+        for request in [
+            command(&["TENANT", "CREATE", "team-a"]),
+            command(&["TENANT", "LIST"]),
+        ] {
+            let response = client.handle_request(request).await?;
+            let Value::Error(error) = response else {
+                panic!("unauthenticated clients can't manage tenants");
+            };
+            assert!(error.starts_with("NOPERM"));
+        }
+        let response = client.handle_request(command(&["AUTH", "wrong"])).await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = client
+            .handle_request(command(&["TENANT", "CREATE", "team-a"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        // without an admin password nobody can
+        let mut client = Handler::new(cache, None).with_tenants(Tenants::default());
+        let response = client.handle_request(command(&["AUTH", ""])).await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = client
+            .handle_request(command(&["TENANT", "CREATE", "team-a"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+//! Builders of the requests and replies the handler tests exchange.
+
+use crate::resp::value::Value;
+
+/// Build the request a client sends for `args`.
+pub(super) fn command(args: &[&str]) -> Value {
+    Value::Array(
+        args.iter()
+            .map(|arg| Value::BulkString(arg.to_string()))
+            .collect(),
+    )
+}
+
+pub(super) fn bulk(s: &str) -> Value {
+    Value::BulkString(s.to_string())
+}
+
+pub(super) fn int(n: i64) -> Value {
+    Value::Integer(n.to_string())
+}
+
+pub(super) fn ok() -> Value {
+    Value::SimpleString("OK".to_string())
+}
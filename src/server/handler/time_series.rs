@@ -0,0 +1,363 @@
+use crate::cache::stream::now_millis;
+use crate::cache::time_series::{Aggregation, LabelFilter, SeriesOptions};
+use crate::resp::value::Value;
+use crate::server::handler::{
+    bulk_strings, integer, parse_count, parse_float, syntax_error, Handler,
+};
+use anyhow::{Error, Result};
+
+fn parse_timestamp(s: &str) -> Result<u64> {
+    s.parse::<u64>()
+        .map_err(|_| Error::msg("invalid timestamp, must be a positive integer"))
+}
+
+/// Parse the start of a range, `-` being the earliest sample.
+fn parse_from(s: &str) -> Result<u64> {
+    if s == "-" {
+        return Ok(0);
+    }
+    parse_timestamp(s)
+}
+
+/// Parse the end of a range, `+` being the latest sample.
+fn parse_to(s: &str) -> Result<u64> {
+    if s == "+" {
+        return Ok(u64::MAX);
+    }
+    parse_timestamp(s)
+}
+
+/// Parse `aggregator bucket`, the bucket being in milliseconds.
+fn parse_aggregation(aggregator: &str, bucket: &str) -> Result<(Aggregation, u64)> {
+    let aggregation = Aggregation::parse(aggregator)?;
+    match parse_count(bucket)? {
+        0 => Err(Error::msg("bucket duration must be positive")),
+        bucket => Ok((aggregation, bucket as u64)),
+    }
+}
+
+/// Parse `[RETENTION retention] [LABELS label value ...]`.
+fn parse_options(args: &[String]) -> Result<SeriesOptions> {
+    let mut options = SeriesOptions::default();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].to_ascii_lowercase().as_str() {
+            "retention" => {
+                options.retention = parse_count(args.get(i + 1).ok_or_else(syntax_error)?)? as u64;
+                i += 2;
+            }
+            "labels" => {
+                let pairs = &args[i + 1..];
+                if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+                    return Err(syntax_error());
+                }
+                options.labels = pairs
+                    .chunks(2)
+                    .map(|pair| (pair[0].clone(), pair[1].clone()))
+                    .collect();
+                i = args.len();
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+    Ok(options)
+}
+
+fn samples_value(samples: Vec<(u64, f64)>) -> Value {
+    Value::Array(
+        samples
+            .into_iter()
+            .map(|(timestamp, value)| {
+                Value::Array(vec![
+                    integer(timestamp),
+                    Value::BulkString(value.to_string()),
+                ])
+            })
+            .collect(),
+    )
+}
+
+impl Handler {
+    pub(super) async fn handle_ts_create(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("TS.CREATE requires a key".to_string()),
+        };
+        let options = match parse_options(&args[1..]) {
+            Ok(options) => options,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.ts_create(args[0].clone(), options).await {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_ts_add(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => return Value::Error("TS.ADD requires a key, a timestamp and a value".to_string()),
+        };
+        let timestamp = match args[1].as_str() {
+            "*" => Ok(now_millis()),
+            timestamp => parse_timestamp(timestamp),
+        };
+        let parsed = (timestamp, parse_float(&args[2]), parse_options(&args[3..]));
+        let (timestamp, value, options) = match parsed {
+            (Ok(timestamp), Ok(value), Ok(options)) => (timestamp, value, options),
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .ts_add(args[0].clone(), timestamp, value, options)
+            .await
+        {
+            Ok(timestamp) => integer(timestamp),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_ts_range(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 3 || args.len() == 6 => args,
+            _ => return Value::Error("TS.RANGE requires a key, a start and an end".to_string()),
+        };
+        let aggregation = match &args[3..] {
+            [] => Ok(None),
+            [option, aggregator, bucket] if option.eq_ignore_ascii_case("aggregation") => {
+                parse_aggregation(aggregator, bucket).map(Some)
+            }
+            _ => Err(syntax_error()),
+        };
+        let (from, to, aggregation) = match (parse_from(&args[1]), parse_to(&args[2]), aggregation)
+        {
+            (Ok(from), Ok(to), Ok(aggregation)) => (from, to, aggregation),
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .ts_range(args[0].clone(), from, to, aggregation)
+            .await
+        {
+            Ok(samples) => samples_value(samples),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_ts_mrange(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 4 => args,
+            _ => return Value::Error("TS.MRANGE requires a start, an end and filters".to_string()),
+        };
+        let (from, to) = match (parse_from(&args[0]), parse_to(&args[1])) {
+            (Ok(from), Ok(to)) => (from, to),
+            (Err(e), _) | (_, Err(e)) => return Value::Error(e.to_string()),
+        };
+
+        // options come first, the filters take the rest of the arguments
+        let mut with_labels = false;
+        let mut aggregation = None;
+        let mut filters = None;
+        let mut i = 2;
+        while i < args.len() {
+            match args[i].to_ascii_lowercase().as_str() {
+                "withlabels" => {
+                    with_labels = true;
+                    i += 1;
+                }
+                "aggregation" if i + 2 < args.len() => {
+                    match parse_aggregation(&args[i + 1], &args[i + 2]) {
+                        Ok(parsed) => aggregation = Some(parsed),
+                        Err(e) => return Value::Error(e.to_string()),
+                    }
+                    i += 3;
+                }
+                "filter" if i + 1 < args.len() => {
+                    match args[i + 1..]
+                        .iter()
+                        .map(|filter| LabelFilter::parse(filter))
+                        .collect::<Result<Vec<_>>>()
+                    {
+                        Ok(parsed) => filters = Some(parsed),
+                        Err(e) => return Value::Error(e.to_string()),
+                    }
+                    i = args.len();
+                }
+                _ => return Value::Error(syntax_error().to_string()),
+            }
+        }
+        let filters = match filters {
+            Some(filters) => filters,
+            None => return Value::Error("TS.MRANGE requires filters".to_string()),
+        };
+
+        let ranges = self
+            .client_store
+            .ts_mrange(&filters, from, to, aggregation)
+            .await;
+        Value::Array(
+            ranges
+                .into_iter()
+                .map(|(key, labels, samples)| {
+                    let labels = labels
+                        .into_iter()
+                        .filter(|_| with_labels)
+                        .map(|(label, value)| {
+                            Value::Array(vec![Value::BulkString(label), Value::BulkString(value)])
+                        })
+                        .collect();
+                    Value::Array(vec![
+                        Value::BulkString(key),
+                        Value::Array(labels),
+                        samples_value(samples),
+                    ])
+                })
+                .collect(),
+        )
+    }
+
+    pub(super) async fn handle_ts_createrule(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 5 && args[2].eq_ignore_ascii_case("aggregation") => args,
+            _ => {
+                return Value::Error(
+                    "TS.CREATERULE requires a source, a destination and an aggregation".to_string(),
+                )
+            }
+        };
+        let (aggregation, bucket) = match parse_aggregation(&args[3], &args[4]) {
+            Ok(parsed) => parsed,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .ts_create_rule(args[0].clone(), args[1].clone(), aggregation, bucket)
+            .await
+        {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_ts_deleterule(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 2 => args,
+            _ => {
+                return Value::Error(
+                    "TS.DELETERULE requires a source and a destination".to_string(),
+                )
+            }
+        };
+
+        match self
+            .client_store
+            .ts_delete_rule(args[0].clone(), args[1].clone())
+            .await
+        {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    fn sample(timestamp: i64, value: &str) -> Value {
+        Value::Array(vec![int(timestamp), bulk(value)])
+    }
+
+    #[tokio::test]
+    async fn test_ts_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["TS.CREATE", "temp", "LABELS", "room", "kitchen"]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        handler
+            .handle_request(command(&["TS.CREATE", "temp:avg"]))
+            .await?;
+        let response = handler
+            .handle_request(command(&[
+                "TS.CREATERULE",
+                "temp",
+                "temp:avg",
+                "AGGREGATION",
+                "avg",
+                "60",
+            ]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+
+        for (timestamp, value) in [("10", "20"), ("20", "21"), ("70", "23")] {
+            let response = handler
+                .handle_request(command(&["TS.ADD", "temp", timestamp, value]))
+                .await?;
+            assert_eq!(response, int(timestamp.parse()?));
+        }
+        let response = handler
+            .handle_request(command(&["TS.ADD", "temp", "bad", "1"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&[
+                "TS.RANGE",
+                "temp",
+                "-",
+                "+",
+                "AGGREGATION",
+                "max",
+                "60",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![sample(0, "21"), sample(60, "23")])
+        );
+        let response = handler
+            .handle_request(command(&["TS.RANGE", "temp:avg", "-", "+"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![sample(0, "20.5")]));
+
+        let response = handler
+            .handle_request(command(&[
+                "TS.MRANGE",
+                "0",
+                "15",
+                "WITHLABELS",
+                "FILTER",
+                "room=kitchen",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![Value::Array(vec![
+                bulk("temp"),
+                Value::Array(vec![Value::Array(vec![bulk("room"), bulk("kitchen")])]),
+                Value::Array(vec![sample(10, "20")]),
+            ])])
+        );
+
+        let response = handler
+            .handle_request(command(&["TS.DELETERULE", "temp", "temp:avg"]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        let response = handler
+            .handle_request(command(&["TS.DELETERULE", "temp", "temp:avg"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use crate::cache::top_k::{TopK, DEFAULT_DECAY, DEFAULT_DEPTH, DEFAULT_WIDTH};
+use crate::resp::value::Value;
+use crate::server::handler::{
+    bulk_strings, integer, parse_count, parse_float, parse_keys, syntax_error, Handler,
+};
+use anyhow::{Error, Result};
+
+/// Parse `TOPK.RESERVE key topk [width depth decay]`.
+fn parse_reserve(args: &[String]) -> Result<TopK> {
+    let k = parse_count(&args[1])?;
+    let (width, depth, decay) = match &args[2..] {
+        [] => (DEFAULT_WIDTH, DEFAULT_DEPTH, DEFAULT_DECAY),
+        [width, depth, decay] => (
+            parse_count(width)?,
+            parse_count(depth)?,
+            parse_float(decay)?,
+        ),
+        _ => return Err(syntax_error()),
+    };
+    if k == 0 || width == 0 || depth == 0 {
+        return Err(Error::msg("topk, width and depth must be positive"));
+    }
+    if decay <= 0.0 || decay > 1.0 {
+        return Err(Error::msg(
+            "decay must be between 0 exclusive and 1 inclusive",
+        ));
+    }
+    TopK::new(k, width, depth, decay)
+}
+
+fn expelled_value(expelled: Vec<Option<String>>) -> Value {
+    Value::Array(
+        expelled
+            .into_iter()
+            .map(|item| item.map(Value::BulkString).unwrap_or(Value::Null))
+            .collect(),
+    )
+}
+
+impl Handler {
+    pub(super) async fn handle_topk_reserve(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("TOPK.RESERVE requires a key and topk".to_string()),
+        };
+        let top_k = match parse_reserve(&args) {
+            Ok(top_k) => top_k,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.topk_reserve(args[0].clone(), top_k).await {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_topk_add(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("TOPK.ADD requires a key and items".to_string()),
+        };
+
+        let items = args[1..].iter().map(|item| (item.clone(), 1)).collect();
+        match self.client_store.topk_add(args[0].clone(), items).await {
+            Ok(expelled) => expelled_value(expelled),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_topk_incrby(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 && !args.len().is_multiple_of(2) => args,
+            _ => {
+                return Value::Error(
+                    "TOPK.INCRBY requires a key and item increment pairs".to_string(),
+                )
+            }
+        };
+        let items = match args[1..]
+            .chunks(2)
+            .map(|pair| Ok((pair[0].clone(), parse_count(&pair[1])? as u64)))
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(items) => items,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.topk_add(args[0].clone(), items).await {
+            Ok(expelled) => expelled_value(expelled),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_topk_query(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 2 => args,
+            _ => return Value::Error("TOPK.QUERY requires a key and items".to_string()),
+        };
+
+        let items = args[1..].to_vec();
+        match self.client_store.topk_query(args[0].clone(), items).await {
+            Ok(found) => Value::Array(
+                found
+                    .into_iter()
+                    .map(|found| integer(found as u8))
+                    .collect(),
+            ),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_topk_list(&self, args: &[Value]) -> Value {
+        let (args, with_count) = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => (args, false),
+            Some(args) if args.len() == 2 && args[1].eq_ignore_ascii_case("withcount") => {
+                (args, true)
+            }
+            _ => return Value::Error("TOPK.LIST requires a key".to_string()),
+        };
+
+        match self.client_store.topk_list(args[0].clone()).await {
+            Ok(list) => Value::Array(
+                list.into_iter()
+                    .flat_map(|(item, count)| {
+                        let count = with_count.then(|| integer(count));
+                        std::iter::once(Value::BulkString(item)).chain(count)
+                    })
+                    .collect(),
+            ),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_topk_merge(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => {
+                return Value::Error(
+                    "TOPK.MERGE requires a destination, numkeys and sources".to_string(),
+                )
+            }
+        };
+        let sources = match parse_keys(&args[1..]) {
+            Ok((sources, [])) => sources,
+            Ok(_) => return Value::Error(syntax_error().to_string()),
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.topk_merge(args[0].clone(), sources).await {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command, int};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_topk_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["TOPK.RESERVE", "pages", "2"]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        // sizes too large are refused rather than allocated
+        for args in [
+            ["1099511627776", "8", "7", "0.9"],
+            ["10", "4294967296", "4294967296", "0.9"],
+            ["10", "16777216", "64", "0.9"],
+        ] {
+            let response = handler
+                .handle_request(command(&[&["TOPK.RESERVE", "huge"], &args[..]].concat()))
+                .await?;
+            assert!(matches!(response, Value::Error(_)));
+        }
+        let response = handler
+            .handle_request(command(&["TOPK.ADD", "pages", "home", "home", "about"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![Value::Null; 3]));
+        let response = handler
+            .handle_request(command(&["TOPK.INCRBY", "pages", "blog", "5"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("about")]));
+
+        let response = handler
+            .handle_request(command(&["TOPK.LIST", "pages", "WITHCOUNT"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![bulk("blog"), int(5), bulk("home"), int(2)])
+        );
+        let response = handler
+            .handle_request(command(&["TOPK.QUERY", "pages", "home", "about"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![int(1), int(0)]));
+
+        handler
+            .handle_request(command(&["TOPK.RESERVE", "all", "2"]))
+            .await?;
+        let response = handler
+            .handle_request(command(&["TOPK.MERGE", "all", "1", "pages"]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        let response = handler
+            .handle_request(command(&["TOPK.LIST", "all"]))
+            .await?;
+        assert_eq!(response, Value::Array(vec![bulk("blog"), bulk("home")]));
+        Ok(())
+    }
+}
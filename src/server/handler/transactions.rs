@@ -0,0 +1,416 @@
+use crate::cache::Cache;
+use crate::resp::value::Value;
+use crate::server::handler::{bulk_strings, Command, Handler};
+use anyhow::Result;
+use std::sync::Arc;
+
+/// The commands queued by a client since MULTI.
+#[derive(Debug, Default)]
+pub(super) struct Transaction {
+    /// Each command with the name the client gave it and its arguments.
+    commands: Vec<(Command, String, Vec<Value>)>,
+    /// Set once a command couldn't be queued, discarding the transaction on EXEC.
+    aborted: bool,
+}
+
+/// A key watched by a client, with the version it had then, how many times it was removed
+/// before and the epoch of its keyspace.
+#[derive(Debug)]
+pub(super) struct Watch {
+    cache: Arc<Cache>,
+    key: String,
+    version: Option<u64>,
+    removals: u64,
+    epoch: u64,
+}
+
+impl Watch {
+    fn new(cache: Arc<Cache>, key: String) -> Self {
+        cache.watch(&key);
+        Watch {
+            epoch: cache.epoch(),
+            version: None,
+            removals: 0,
+            cache,
+            key,
+        }
+    }
+
+    /// Retrieve whether the key changed since it was watched.
+    async fn changed(&self) -> bool {
+        // a flush or swap of the database changes every key, existing or not
+        self.cache.epoch() != self.epoch
+            || self.cache.version(&self.key).await != self.version
+            || self.cache.removals(&self.key) != self.removals
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.cache.unwatch(&self.key);
+    }
+}
+
+impl Handler {
+    /// Queue `command` in the transaction, unless it can't run in one which aborts it.
+    pub(super) fn queue(&mut self, command: Command, name: String, args: Vec<Value>) -> Value {
+        let transaction = self.transaction.as_mut().unwrap();
+        let error = match command {
+            Command::Uninitialized => format!("unknown command '{}'", name),
+            Command::Eval
+            | Command::EvalSha
+            | Command::Subscribe
+            | Command::PSubscribe
+            | Command::Unsubscribe
+            | Command::PUnsubscribe => {
+                format!("Command '{}' is not allowed in a transaction", name)
+            }
+            _ if command.is_blocking() => {
+                format!("Command '{}' is not allowed in a transaction", name)
+            }
+            _ => {
+                transaction.commands.push((command, name, args));
+                return Value::SimpleString("QUEUED".to_string());
+            }
+        };
+        transaction.aborted = true;
+        Value::Error(error)
+    }
+
+    pub(super) async fn handle_multi(&mut self, args: &[Value]) -> Value {
+        if !args.is_empty() {
+            return Value::Error("MULTI takes no arguments".to_string());
+        }
+        if self.transaction.is_some() {
+            return Value::Error("MULTI calls can not be nested".to_string());
+        }
+
+        self.transaction = Some(Transaction::default());
+        Value::SimpleString("OK".to_string())
+    }
+
+    /// Handle EXEC, running the queued commands with every database held exclusively, unless a
+    /// watched key changed since it was watched. Holding only the selected one wouldn't do, as
+    /// SELECT, MOVE, COPY with DB, SWAPDB and FLUSHALL reach the others.
+    pub(super) async fn handle_exec(&mut self, args: &[Value]) -> Result<Value> {
+        if !args.is_empty() {
+            return Ok(Value::Error("EXEC takes no arguments".to_string()));
+        }
+        let transaction = match self.transaction.take() {
+            Some(transaction) => transaction,
+            None => return Ok(Value::Error("EXEC without MULTI".to_string())),
+        };
+        let watched = std::mem::take(&mut self.watched);
+        if transaction.aborted {
+            return Ok(Value::Error(
+                "EXECABORT Transaction discarded because of previous errors.".to_string(),
+            ));
+        }
+
+        // taken in an order SWAPDB doesn't change, so that two transactions don't each hold
+        // what the other waits for
+        let mut stores = self.databases.all();
+        stores.sort_by_key(Arc::as_ptr);
+        let mut gates = Vec::with_capacity(stores.len());
+        for store in &stores {
+            gates.push(store.enter_exclusive().await);
+        }
+        for watch in &watched {
+            if watch.changed().await {
+                log::debug!("aborting a transaction as key {} changed", watch.key);
+                return Ok(Value::Null);
+            }
+        }
+
+        let mut responses = Vec::with_capacity(transaction.commands.len());
+        for (command, name, args) in transaction.commands {
+            responses.push(self.dispatch(command, &name, &args).await);
+            // a command like SELECT may change the database of the next ones
+            self.client_store = self.databases.get(self.db)?;
+        }
+        Ok(Value::Array(responses))
+    }
+
+    pub(super) async fn handle_discard(&mut self, args: &[Value]) -> Value {
+        if !args.is_empty() {
+            return Value::Error("DISCARD takes no arguments".to_string());
+        }
+        if self.transaction.take().is_none() {
+            return Value::Error("DISCARD without MULTI".to_string());
+        }
+
+        self.watched.clear();
+        Value::SimpleString("OK".to_string())
+    }
+
+    /// Handle WATCH, aborting the next transaction if any of the keys is written, removed or
+    /// expires before EXEC, or if its database is flushed or swapped.
+    pub(super) async fn handle_watch(&mut self, args: &[Value]) -> Value {
+        let keys = match bulk_strings(args) {
+            Some(keys) if !keys.is_empty() => keys,
+            _ => return Value::Error("WATCH requires keys".to_string()),
+        };
+        if self.transaction.is_some() {
+            return Value::Error("WATCH inside MULTI is not allowed".to_string());
+        }
+
+        for key in keys {
+            // counted from before the version is taken, which may find the key expired
+            let mut watch = Watch::new(self.client_store.clone(), key);
+            watch.version = self.client_store.version(&watch.key).await;
+            watch.removals = self.client_store.removals(&watch.key);
+            self.watched.push(watch);
+        }
+        Value::SimpleString("OK".to_string())
+    }
+
+    pub(super) async fn handle_unwatch(&mut self, args: &[Value]) -> Value {
+        if !args.is_empty() {
+            return Value::Error("UNWATCH takes no arguments".to_string());
+        }
+
+        self.watched.clear();
+        Value::SimpleString("OK".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::databases::Databases;
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{command, int, ok};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn queued() -> Value {
+        Value::SimpleString("QUEUED".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_multi_exec() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler.handle_request(command(&["MULTI"])).await?;
+        assert_eq!(response, ok());
+        let response = handler.handle_request(command(&["MULTI"])).await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = handler
+            .handle_request(command(&["SET", "stock", "10"]))
+            .await?;
+        assert_eq!(response, queued());
+        let response = handler
+            .handle_request(command(&["SADD", "reserved", "order:1"]))
+            .await?;
+        assert_eq!(response, queued());
+        let response = handler.handle_request(command(&["EXEC"])).await?;
+        assert_eq!(response, Value::Array(vec![ok(), int(1)]));
+        let response = handler.handle_request(command(&["EXEC"])).await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        // commands that can't be queued discard the transaction
+        handler.handle_request(command(&["MULTI"])).await?;
+        handler
+            .handle_request(command(&["SET", "stock", "9"]))
+            .await?;
+        let response = handler
+            .handle_request(command(&["BLPOP", "list", "0"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = handler.handle_request(command(&["EXEC"])).await?;
+        let Value::Error(error) = response else {
+            panic!("the transaction is discarded");
+        };
+        assert!(error.starts_with("EXECABORT"));
+
+        handler.handle_request(command(&["MULTI"])).await?;
+        handler
+            .handle_request(command(&["SET", "stock", "9"]))
+            .await?;
+        let response = handler.handle_request(command(&["DISCARD"])).await?;
+        assert_eq!(response, ok());
+        let response = handler.handle_request(command(&["GET", "stock"])).await?;
+        assert_eq!(response, Value::BulkString("10".to_string()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watch() -> Result<()> {
+        let cache = Arc::new(Cache::default());
+        let mut client = Handler::new(cache.clone(), None);
+        let mut other = Handler::new(cache, None);
+        client
+            .handle_request(command(&["SET", "stock", "10"]))
+            .await?;
+
+        // an untouched key lets the transaction run
+        client.handle_request(command(&["WATCH", "stock"])).await?;
+        client.handle_request(command(&["MULTI"])).await?;
+        client
+            .handle_request(command(&["SET", "stock", "9"]))
+            .await?;
+        let response = client.handle_request(command(&["EXEC"])).await?;
+        assert_eq!(response, Value::Array(vec![ok()]));
+
+        // a write by another client aborts it
+        client.handle_request(command(&["WATCH", "stock"])).await?;
+        other
+            .handle_request(command(&["SET", "stock", "5"]))
+            .await?;
+        client.handle_request(command(&["MULTI"])).await?;
+        client
+            .handle_request(command(&["SET", "stock", "8"]))
+            .await?;
+        let response = client.handle_request(command(&["EXEC"])).await?;
+        assert_eq!(response, Value::Null);
+        let response = client.handle_request(command(&["GET", "stock"])).await?;
+        assert_eq!(response, Value::BulkString("5".to_string()));
+
+        // and so does an expiry
+        other
+            .handle_request(command(&["SET", "hold", "1", "px", "10"]))
+            .await?;
+        client.handle_request(command(&["WATCH", "hold"])).await?;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        client.handle_request(command(&["MULTI"])).await?;
+        client
+            .handle_request(command(&["SET", "stock", "4"]))
+            .await?;
+        let response = client.handle_request(command(&["EXEC"])).await?;
+        assert_eq!(response, Value::Null);
+
+        // and so does a key created then removed, though it's missing both times
+        client.handle_request(command(&["WATCH", "order"])).await?;
+        other
+            .handle_request(command(&["SET", "order", "1"]))
+            .await?;
+        other.handle_request(command(&["DEL", "order"])).await?;
+        client.handle_request(command(&["MULTI"])).await?;
+        client
+            .handle_request(command(&["SET", "stock", "4"]))
+            .await?;
+        let response = client.handle_request(command(&["EXEC"])).await?;
+        assert_eq!(response, Value::Null);
+
+        // or created then expired
+        client.handle_request(command(&["WATCH", "order"])).await?;
+        other
+            .handle_request(command(&["SET", "order", "1", "px", "10"]))
+            .await?;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        client.handle_request(command(&["MULTI"])).await?;
+        client
+            .handle_request(command(&["SET", "stock", "4"]))
+            .await?;
+        let response = client.handle_request(command(&["EXEC"])).await?;
+        assert_eq!(response, Value::Null);
+
+        // watches don't outlive UNWATCH
+        client.handle_request(command(&["WATCH", "stock"])).await?;
+        other
+            .handle_request(command(&["SET", "stock", "3"]))
+            .await?;
+        client.handle_request(command(&["UNWATCH"])).await?;
+        client.handle_request(command(&["MULTI"])).await?;
+        let response = client.handle_request(command(&["WATCH", "stock"])).await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = client.handle_request(command(&["EXEC"])).await?;
+        assert_eq!(response, Value::Array(vec![]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watch_survives_sweep() -> Result<()> {
+        let cache = Arc::new(Cache::default());
+        let mut client = Handler::new(cache.clone(), None);
+        client
+            .handle_request(command(&[
+                "TS.ADD",
+                "temperature",
+                "*",
+                "21",
+                "RETENTION",
+                "60000",
+            ]))
+            .await?;
+
+        // a sweep that trims nothing from the series doesn't count as a write
+        client
+            .handle_request(command(&["WATCH", "temperature"]))
+            .await?;
+        cache.sweep().await;
+        client.handle_request(command(&["MULTI"])).await?;
+        client
+            .handle_request(command(&["SET", "stock", "1"]))
+            .await?;
+        let response = client.handle_request(command(&["EXEC"])).await?;
+        assert_eq!(response, Value::Array(vec![ok()]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watch_across_swap_and_flush() -> Result<()> {
+        let databases = Databases::new(2, 25, 0.25);
+        let mut client = Handler::new(databases.clone(), None);
+        let mut other = Handler::new(databases, None);
+        client
+            .handle_request(command(&["SET", "stock", "10"]))
+            .await?;
+
+        // swapping the database replaces the watched key
+        client.handle_request(command(&["WATCH", "stock"])).await?;
+        other.handle_request(command(&["SWAPDB", "0", "1"])).await?;
+        client.handle_request(command(&["MULTI"])).await?;
+        client
+            .handle_request(command(&["SET", "stock", "9"]))
+            .await?;
+        let response = client.handle_request(command(&["EXEC"])).await?;
+        assert_eq!(response, Value::Null);
+
+        // and so does flushing it, even for a key that was missing
+        client
+            .handle_request(command(&["WATCH", "missing"]))
+            .await?;
+        other.handle_request(command(&["FLUSHDB"])).await?;
+        client.handle_request(command(&["MULTI"])).await?;
+        client
+            .handle_request(command(&["SET", "stock", "9"]))
+            .await?;
+        let response = client.handle_request(command(&["EXEC"])).await?;
+        assert_eq!(response, Value::Null);
+
+        client.handle_request(command(&["WATCH", "stock"])).await?;
+        client.handle_request(command(&["MULTI"])).await?;
+        client
+            .handle_request(command(&["SET", "stock", "9"]))
+            .await?;
+        let response = client.handle_request(command(&["EXEC"])).await?;
+        assert_eq!(response, Value::Array(vec![ok()]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_exec_holds_every_database() -> Result<()> {
+        let databases = Databases::new(2, 25, 0.25);
+        let mut client = Handler::new(databases.clone(), None);
+        client.handle_request(command(&["MULTI"])).await?;
+        client.handle_request(command(&["SELECT", "1"])).await?;
+        client
+            .handle_request(command(&["SET", "stock", "10"]))
+            .await?;
+
+        // a command running on the other database holds the transaction back
+        let other = databases.get(1)?;
+        let gate = other.enter().await;
+        let exec = tokio::spawn(async move { client.handle_request(command(&["EXEC"])).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!exec.is_finished());
+
+        drop(gate);
+        assert_eq!(exec.await??, Value::Array(vec![ok(), ok()]));
+        assert_eq!(other.get("stock".to_string()).await, Some("10".to_string()));
+        Ok(())
+    }
+}
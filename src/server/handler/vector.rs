@@ -0,0 +1,301 @@
+use crate::cache::vector::{Embedding, HnswOptions, Metric, VectorIndex};
+use crate::resp::value::Value;
+use crate::server::handler::{bulk_strings, parse_count, parse_float, syntax_error, Handler};
+use anyhow::{Error, Result};
+
+/// Parse the leading components of a vector, returning them and the arguments following.
+fn parse_vector(args: &[String]) -> (Vec<f32>, &[String]) {
+    let length = args
+        .iter()
+        .take_while(|arg| parse_float(arg).is_ok())
+        .count();
+    let vector = args[..length]
+        .iter()
+        .map(|arg| parse_float(arg).unwrap() as f32)
+        .collect();
+    (vector, &args[length..])
+}
+
+fn parse_positive(s: &str) -> Result<usize> {
+    match parse_count(s)? {
+        0 => Err(Error::msg("value must be positive")),
+        n => Ok(n),
+    }
+}
+
+/// Parse `DIM dim METRIC metric [ALGORITHM FLAT|HNSW] [M m] [EF_CONSTRUCTION ef]
+/// [EF_RUNTIME ef]`.
+fn parse_index(args: &[String]) -> Result<VectorIndex> {
+    let mut dimension = None;
+    let mut metric = None;
+    let mut hnsw = false;
+    let mut options = HnswOptions::default();
+    for pair in args.chunks(2) {
+        let [option, value] = pair else {
+            return Err(syntax_error());
+        };
+        match option.to_ascii_lowercase().as_str() {
+            "dim" => dimension = Some(parse_positive(value)?),
+            "metric" => metric = Some(Metric::parse(value)?),
+            "algorithm" if value.eq_ignore_ascii_case("flat") => hnsw = false,
+            "algorithm" if value.eq_ignore_ascii_case("hnsw") => hnsw = true,
+            "m" => options.m = parse_positive(value)?,
+            "ef_construction" => options.ef_construction = parse_positive(value)?,
+            "ef_runtime" => options.ef_runtime = parse_positive(value)?,
+            _ => return Err(syntax_error()),
+        }
+    }
+    let (dimension, metric) = match (dimension, metric) {
+        (Some(dimension), Some(metric)) => (dimension, metric),
+        _ => return Err(Error::msg("DIM and METRIC are required")),
+    };
+    if hnsw {
+        Ok(VectorIndex::hnsw(dimension, metric, options))
+    } else {
+        Ok(VectorIndex::flat(dimension, metric))
+    }
+}
+
+/// Parse `index component [component ...] [META field value ...]`.
+fn parse_embedding(args: &[String]) -> Result<Embedding> {
+    let (vector, rest) = parse_vector(&args[1..]);
+    let metadata = match rest {
+        [] => Vec::new(),
+        [meta, pairs @ ..]
+            if meta.eq_ignore_ascii_case("meta")
+                && !pairs.is_empty()
+                && pairs.len().is_multiple_of(2) =>
+        {
+            pairs
+                .chunks(2)
+                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                .collect()
+        }
+        _ => return Err(syntax_error()),
+    };
+    Ok(Embedding {
+        index: args[0].clone(),
+        vector,
+        metadata,
+    })
+}
+
+/// Parse `[PREFIX prefix] [EF ef]`.
+fn parse_search(args: &[String]) -> Result<(Option<String>, Option<usize>)> {
+    let mut prefix = None;
+    let mut ef = None;
+    for pair in args.chunks(2) {
+        let [option, value] = pair else {
+            return Err(syntax_error());
+        };
+        match option.to_ascii_lowercase().as_str() {
+            "prefix" => prefix = Some(value.clone()),
+            "ef" => ef = Some(parse_positive(value)?),
+            _ => return Err(syntax_error()),
+        }
+    }
+    Ok((prefix, ef))
+}
+
+fn metadata_value(metadata: Vec<(String, String)>) -> Value {
+    Value::Array(
+        metadata
+            .into_iter()
+            .flat_map(|(field, value)| [Value::BulkString(field), Value::BulkString(value)])
+            .collect(),
+    )
+}
+
+impl Handler {
+    pub(super) async fn handle_vec_create(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if !args.is_empty() => args,
+            _ => return Value::Error("VEC.CREATE requires an index".to_string()),
+        };
+        let index = match parse_index(&args[1..]) {
+            Ok(index) => index,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.vec_create(args[0].clone(), index).await {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_vec_set(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => return Value::Error("VEC.SET requires a key, an index and a vector".to_string()),
+        };
+        let embedding = match parse_embedding(&args[1..]) {
+            Ok(embedding) => embedding,
+            Err(e) => return Value::Error(e.to_string()),
+        };
+
+        match self.client_store.vec_set(args[0].clone(), embedding).await {
+            Ok(()) => Value::SimpleString("OK".to_string()),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_vec_get(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() == 1 => args,
+            _ => return Value::Error("VEC.GET requires a key".to_string()),
+        };
+
+        match self.client_store.vec_get(args[0].clone()).await {
+            Ok(Some(embedding)) => Value::Array(vec![
+                Value::BulkString(embedding.index),
+                Value::Array(
+                    embedding
+                        .vector
+                        .into_iter()
+                        .map(|component| Value::BulkString(component.to_string()))
+                        .collect(),
+                ),
+                metadata_value(embedding.metadata),
+            ]),
+            Ok(None) => Value::Null,
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+
+    pub(super) async fn handle_vec_search(&self, args: &[Value]) -> Value {
+        let args = match bulk_strings(args) {
+            Some(args) if args.len() >= 3 => args,
+            _ => {
+                return Value::Error(
+                    "VEC.SEARCH requires an index, a count and a vector".to_string(),
+                )
+            }
+        };
+        let (query, rest) = parse_vector(&args[2..]);
+        let (k, (prefix, ef)) = match (parse_positive(&args[1]), parse_search(rest)) {
+            (Ok(k), Ok(options)) => (k, options),
+            (Err(e), _) | (_, Err(e)) => return Value::Error(e.to_string()),
+        };
+
+        match self
+            .client_store
+            .vec_search(args[0].clone(), query, k, ef, prefix)
+            .await
+        {
+            Ok(neighbours) => Value::Array(
+                neighbours
+                    .into_iter()
+                    .map(|(key, distance, metadata)| {
+                        Value::Array(vec![
+                            Value::BulkString(key),
+                            Value::BulkString(distance.to_string()),
+                            metadata_value(metadata),
+                        ])
+                    })
+                    .collect(),
+            ),
+            Err(e) => Value::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+    use crate::resp::value::Value;
+    use crate::server::handler::test_helpers::{bulk, command};
+    use crate::server::handler::Handler;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_vec_commands() -> Result<()> {
+        let mut handler = Handler::new(Arc::new(Cache::default()), None);
+
+        let response = handler
+            .handle_request(command(&["VEC.CREATE", "docs", "DIM", "2"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+        let response = handler
+            .handle_request(command(&[
+                "VEC.CREATE",
+                "docs",
+                "DIM",
+                "2",
+                "METRIC",
+                "L2",
+                "ALGORITHM",
+                "HNSW",
+                "M",
+                "8",
+            ]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+
+        let response = handler
+            .handle_request(command(&[
+                "VEC.SET", "doc:1", "docs", "0", "0", "META", "title", "origin",
+            ]))
+            .await?;
+        assert_eq!(response, Value::SimpleString("OK".to_string()));
+        handler
+            .handle_request(command(&["VEC.SET", "doc:2", "docs", "3", "4"]))
+            .await?;
+        let response = handler
+            .handle_request(command(&["VEC.SET", "doc:3", "docs", "1"]))
+            .await?;
+        assert!(matches!(response, Value::Error(_)));
+
+        let response = handler
+            .handle_request(command(&["VEC.GET", "doc:1"]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![
+                bulk("docs"),
+                Value::Array(vec![bulk("0"), bulk("0")]),
+                Value::Array(vec![bulk("title"), bulk("origin")]),
+            ])
+        );
+
+        let response = handler
+            .handle_request(command(&[
+                "VEC.SEARCH",
+                "docs",
+                "1",
+                "3",
+                "4.5",
+                "EF",
+                "20",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![Value::Array(vec![
+                bulk("doc:2"),
+                bulk("0.5"),
+                Value::Array(vec![]),
+            ])])
+        );
+        let response = handler
+            .handle_request(command(&[
+                "VEC.SEARCH",
+                "docs",
+                "5",
+                "3",
+                "4",
+                "PREFIX",
+                "doc:1",
+            ]))
+            .await?;
+        assert_eq!(
+            response,
+            Value::Array(vec![Value::Array(vec![
+                bulk("doc:1"),
+                bulk("5"),
+                Value::Array(vec![bulk("title"), bulk("origin")]),
+            ])])
+        );
+        Ok(())
+    }
+}
@@ -1,26 +1,40 @@
 mod connection;
 mod handler;
-pub mod shutdown;
 
-use crate::cache::Cache;
+use crate::cache::databases::Databases;
+use crate::cache::notifications::Notifications;
+use crate::cache::scripts::Scripts;
+use crate::cache::tenants::Tenants;
 use crate::server::{connection::Connection, handler::Handler};
 use anyhow::Result;
 use std::str;
-use std::sync::Arc;
 use tokio::net::TcpListener;
 
 #[derive(Debug)]
 pub struct Server<'a> {
     socket_addr: &'a str,
-    main_cache: Arc<Cache>,
+    databases: Databases,
+    tenants: Tenants,
+    notifications: Notifications,
+    scripts: Scripts,
     listener: TcpListener,
 }
 
 impl<'a> Server<'a> {
-    pub fn new(socket_addr: &'a str, main_cache: Arc<Cache>, listener: TcpListener) -> Self {
+    pub fn new(
+        socket_addr: &'a str,
+        databases: Databases,
+        tenants: Tenants,
+        notifications: Notifications,
+        scripts: Scripts,
+        listener: TcpListener,
+    ) -> Self {
         Server {
             socket_addr,
-            main_cache,
+            databases,
+            tenants,
+            notifications,
+            scripts,
             listener,
         }
     }
@@ -33,8 +47,11 @@ impl<'a> Server<'a> {
 
             match incoming {
                 Ok((s, _)) => {
-                    let client_cache = self.main_cache.clone();
-                    let mut handler = Handler::new(client_cache, Some(Connection::new(s)));
+                    let databases = self.databases.clone();
+                    let mut handler = Handler::new(databases, Some(Connection::new(s)))
+                        .with_tenants(self.tenants.clone())
+                        .with_notifications(self.notifications.clone())
+                        .with_scripts(self.scripts.clone());
                     tokio::spawn(async move {
                         handler.handle_connection().await;
                     });